@@ -0,0 +1,149 @@
+//! Error type of the xlsx and ods read and write, and of the formula calculation
+use std::error;
+use std::fmt;
+use std::io;
+use std::string::FromUtf8Error;
+
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    #[cfg(any(feature = "ods", feature = "xlsx"))]
+    Zip(zip::result::ZipError),
+    /// The XML of a part is broken at `position` (byte offset in the part).
+    #[cfg(any(feature = "ods", feature = "xlsx"))]
+    Xml {
+        part: String,
+        position: usize,
+        source: quick_xml::Error,
+    },
+    Utf8(FromUtf8Error),
+    /// The XML is well-formed but its content can not be understood,
+    /// e.g. a cell value which is not a number.
+    Malformed {
+        part: String,
+        position: usize,
+        sheet: Option<String>,
+        cell: Option<String>,
+        message: String,
+    },
+    /// The document uses a format or a feature which this library can not handle.
+    Unsupported(String),
+    /// A formula can not be parsed.
+    Formula {
+        sheet: String,
+        cell: String,
+        message: String,
+    },
+    /// Formulas depend on each other in a loop; `cell` is one of them.
+    CircularReference {
+        sheet: String,
+        cell: String,
+    },
+}
+
+impl Error {
+    #[cfg(any(feature = "ods", feature = "xlsx"))]
+    pub(crate) fn xml(part: &str, position: usize, source: quick_xml::Error) -> Error {
+        Error::Xml {
+            part: part.to_string(),
+            position,
+            source,
+        }
+    }
+
+    #[cfg(any(feature = "ods", feature = "xlsx"))]
+    pub(crate) fn malformed<S: Into<String>>(part: &str, position: usize, message: S) -> Error {
+        Error::Malformed {
+            part: part.to_string(),
+            position,
+            sheet: None,
+            cell: None,
+            message: message.into(),
+        }
+    }
+
+    #[cfg(any(feature = "ods", feature = "xlsx"))]
+    pub(crate) fn in_cell(self, sheet_name: &str, cell_ref: String) -> Error {
+        match self {
+            Error::Malformed { part, position, message, .. } => Error::Malformed {
+                part,
+                position,
+                sheet: Some(sheet_name.to_string()),
+                cell: Some(cell_ref),
+                message,
+            },
+            other => other,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io(err) => write!(f, "I/O error: {}", err),
+            #[cfg(any(feature = "ods", feature = "xlsx"))]
+            Error::Zip(err) => write!(f, "zip error: {}", err),
+            #[cfg(any(feature = "ods", feature = "xlsx"))]
+            Error::Xml { part, position, source } => {
+                write!(f, "XML error in {} at byte {}: {}", part, position, source)
+            },
+            Error::Utf8(err) => write!(f, "encoding error: {}", err),
+            Error::Malformed { part, position, sheet, cell, message } => {
+                write!(f, "malformed {} at byte {}", part, position)?;
+                if let Some(sheet) = sheet {
+                    write!(f, ", sheet {}", sheet)?;
+                }
+                if let Some(cell) = cell {
+                    write!(f, ", cell {}", cell)?;
+                }
+                write!(f, ": {}", message)
+            },
+            Error::Unsupported(message) => write!(f, "unsupported: {}", message),
+            Error::Formula { sheet, cell, message } => {
+                write!(f, "formula error in sheet {}, cell {}: {}", sheet, cell, message)
+            },
+            Error::CircularReference { sheet, cell } => {
+                write!(f, "circular reference in sheet {}, cell {}", sheet, cell)
+            },
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Error::Io(err) => Some(err),
+            #[cfg(any(feature = "ods", feature = "xlsx"))]
+            Error::Zip(err) => Some(err),
+            // quick_xml::Error does not implement std::error::Error itself
+            #[cfg(any(feature = "ods", feature = "xlsx"))]
+            Error::Xml { source, .. } => match source {
+                quick_xml::Error::Io(err) => Some(err),
+                quick_xml::Error::Utf8(err) => Some(err),
+                _ => None,
+            },
+            Error::Utf8(err) => Some(err),
+            Error::Malformed { .. } | Error::Unsupported(_) => None,
+            Error::Formula { .. } | Error::CircularReference { .. } => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Error {
+        Error::Io(err)
+    }
+}
+
+#[cfg(any(feature = "ods", feature = "xlsx"))]
+impl From<zip::result::ZipError> for Error {
+    fn from(err: zip::result::ZipError) -> Error {
+        Error::Zip(err)
+    }
+}
+
+impl From<FromUtf8Error> for Error {
+    fn from(err: FromUtf8Error) -> Error {
+        Error::Utf8(err)
+    }
+}
@@ -1,96 +1,159 @@
 extern crate quick_xml;
-extern crate tempdir;
-extern crate walkdir;
 extern crate zip;
 
 use self::quick_xml::events::attributes::Attribute;
 use self::quick_xml::events::{BytesEnd, BytesStart, BytesText, Event};
-use self::quick_xml::Writer;
-use self::tempdir::TempDir;
-use self::walkdir::WalkDir;
+use self::quick_xml::{Reader, Writer};
+use self::zip::read::ZipFile;
 use self::zip::write::FileOptions;
+use self::zip::{ZipArchive, ZipWriter};
+use super::{Book, Error, column_and_row_to_index};
 use std::borrow::Cow;
-use std::fs;
-use std::fs::File;
+use std::ffi::OsStr;
 use std::io;
-use std::io::{Cursor, Read, Write};
+use std::io::{BufReader, Cursor, Read, Seek, Write};
 use std::path::Path;
 use std::string::FromUtf8Error;
 
-pub fn write_to_file(path: &Path, dir: &TempDir) -> Result<(), io::Error> {
-    let file = File::create(&path)?;
-    let mut zip = zip::ZipWriter::new(file);
-    let options = FileOptions::default()
-        .compression_method(zip::CompressionMethod::Deflated)
-        .unix_permissions(0o644);
-    let walkdir = WalkDir::new(dir.path());
-    let it = walkdir.into_iter();
-
-    for dent in it.filter_map(|e| e.ok()) {
-        let path = dent.path();
-        let name = path
-            .strip_prefix(Path::new(dir.path()))
-            .unwrap()
-            .to_str()
-            .unwrap();
-
-        if path.is_file() {
-            //println!("adding {:?} as {:?} ...", path, name);
-            zip.start_file(name, options)?;
-            let mut f = File::open(path)?;
-            let mut buffer = Vec::new();
-            f.read_to_end(&mut buffer)?;
-            zip.write_all(&*buffer)?;
-        }
-        /*
-         else {
-            let mut dir_name = String::from(name);
-            dir_name.push('/');
-            let _ = zip.add_directory(dir_name.as_str(), FileOptions::default());
+pub const ODS_MIMETYPE: &str = "application/vnd.oasis.opendocument.spreadsheet";
+
+/// Spreadsheet file formats this library knows.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Kind {
+    Xlsx,
+    Ods,
+}
+
+impl Kind {
+    pub fn from_path(path: &Path) -> Option<Kind> {
+        let extension = path.extension().and_then(OsStr::to_str)?;
+        match extension.to_ascii_lowercase().as_str() {
+            "xlsx" => Some(Kind::Xlsx),
+            "ods" => Some(Kind::Ods),
+            _ => None,
         }
-        */
     }
+}
 
-    zip.finish()?;
-    Ok(())
+fn has_part<R: Read + Seek>(zip: &mut ZipArchive<R>, path: &str) -> bool {
+    zip.by_name(path).is_ok()
 }
 
-pub fn unzip(zip_file: &File, dir: &TempDir) -> Result<(), zip::result::ZipError> {
-    let mut zip = zip::ZipArchive::new(zip_file)?;
-    for i in 0..zip.len() {
-        let mut file = zip.by_index(i)?;
-        let path = dir.path().join(file.name());
-        if let Some(parent) = path.parent() {
-            fs::create_dir_all(parent)?
-        }
-        if (&*file.name()).ends_with("/") {
-            fs::create_dir_all(path)?
-        } else {
-            let mut archived_file = File::create(path)?;
-            let _ = io::copy(&mut file, &mut archived_file);
+// The ods mimetype entry is authoritative. Otherwise look at the parts each
+// format needs, preferring the one the file extension suggests.
+fn detect<R: Read + Seek>(zip: &mut ZipArchive<R>, hint: Option<Kind>) -> Result<Kind, Error> {
+    if let Ok(mut file) = zip.by_name("mimetype") {
+        let mut mimetype = String::new();
+        file.read_to_string(&mut mimetype)?;
+        let mimetype = mimetype.trim();
+        if mimetype == ODS_MIMETYPE {
+            return Ok(Kind::Ods);
         }
+        return Err(Error::Unsupported(format!("mimetype {}", mimetype)));
+    }
+    let is_xlsx = has_part(zip, "[Content_Types].xml") && has_part(zip, "xl/workbook.xml");
+    let is_ods = has_part(zip, "content.xml") && has_part(zip, "styles.xml");
+    match (is_xlsx, is_ods) {
+        (true, true) => Ok(hint.unwrap_or(Kind::Xlsx)),
+        (true, false) => Ok(Kind::Xlsx),
+        (false, true) => Ok(Kind::Ods),
+        (false, false) => Err(Error::Unsupported(String::from("neither a xlsx nor an ods file"))),
+    }
+}
+
+pub fn read_book<R: Read + Seek>(reader: R, hint: Option<Kind>) -> Result<Book, Error> {
+    let mut zip = match ZipArchive::new(reader) {
+        Ok(zip) => zip,
+        Err(zip::result::ZipError::InvalidArchive(_)) => {
+            return Err(Error::Unsupported(String::from("not a zip archive")));
+        },
+        Err(err) => return Err(Error::Zip(err)),
+    };
+    match detect(&mut zip, hint)? {
+        Kind::Xlsx => read_xlsx(&mut zip),
+        Kind::Ods => read_ods(&mut zip),
+    }
+}
+
+#[cfg(feature = "xlsx")]
+fn read_xlsx<R: Read + Seek>(zip: &mut ZipArchive<R>) -> Result<Book, Error> {
+    crate::xlsx::read_archive(zip)
+}
+
+#[cfg(not(feature = "xlsx"))]
+fn read_xlsx<R: Read + Seek>(_zip: &mut ZipArchive<R>) -> Result<Book, Error> {
+    Err(Error::Unsupported(String::from("xlsx feature is not enabled")))
+}
+
+#[cfg(feature = "ods")]
+fn read_ods<R: Read + Seek>(zip: &mut ZipArchive<R>) -> Result<Book, Error> {
+    crate::ods::read_archive(zip)
+}
+
+#[cfg(not(feature = "ods"))]
+fn read_ods<R: Read + Seek>(_zip: &mut ZipArchive<R>) -> Result<Book, Error> {
+    Err(Error::Unsupported(String::from("ods feature is not enabled")))
+}
+
+pub fn write_book(book: &Book, path: &Path) -> Result<(), Error> {
+    match Kind::from_path(path) {
+        Some(Kind::Xlsx) => write_xlsx(book, path),
+        Some(Kind::Ods) => write_ods(book, path),
+        None => Err(Error::Unsupported(format!("file extension of {}", path.display()))),
     }
-    Ok(())
 }
 
-pub fn make_static_file(
-    temp_dir: &TempDir,
+#[cfg(feature = "xlsx")]
+fn write_xlsx(book: &Book, path: &Path) -> Result<(), Error> {
+    crate::xlsx::write(book, path)
+}
+
+#[cfg(not(feature = "xlsx"))]
+fn write_xlsx(_book: &Book, _path: &Path) -> Result<(), Error> {
+    Err(Error::Unsupported(String::from("xlsx feature is not enabled")))
+}
+
+#[cfg(feature = "ods")]
+fn write_ods(book: &Book, path: &Path) -> Result<(), Error> {
+    crate::ods::write(book, path)
+}
+
+#[cfg(not(feature = "ods"))]
+fn write_ods(_book: &Book, _path: &Path) -> Result<(), Error> {
+    Err(Error::Unsupported(String::from("ods feature is not enabled")))
+}
+
+fn file_options() -> FileOptions {
+    FileOptions::default()
+        .compression_method(zip::CompressionMethod::Deflated)
+        .unix_permissions(0o644)
+}
+
+pub fn open_part<'a, R: Read + Seek>(
+    zip: &'a mut ZipArchive<R>,
+    path: &str,
+) -> Result<Reader<BufReader<ZipFile<'a>>>, zip::result::ZipError> {
+    Ok(Reader::from_reader(BufReader::new(zip.by_name(path)?)))
+}
+
+// The mimetype entry has to be the first one and must not be compressed.
+#[cfg(feature = "ods")]
+pub fn make_mimetype_file<W: Write + Seek>(
+    zip: &mut ZipWriter<W>,
+    mimetype: &str,
+) -> Result<(), io::Error> {
+    let options = FileOptions::default().compression_method(zip::CompressionMethod::Stored);
+    zip.start_file("mimetype", options)?;
+    zip.write_all(mimetype.as_bytes())
+}
+
+pub fn make_static_file<W: Write + Seek>(
+    zip: &mut ZipWriter<W>,
     path: &str,
     data: &str,
-    dir: Option<&str>,
 ) -> Result<(), io::Error> {
-    match dir {
-        Some(dir) => {
-            let dir_path = temp_dir.path().join(dir);
-            fs::create_dir_all(dir_path)?;
-        }
-        None => {}
-    }
-    let file_path = temp_dir.path().join(path);
-    let mut f = File::create(file_path)?;
-    f.write_all(data.as_bytes())?;
-    f.sync_all()?;
-    Ok(())
+    zip.start_file(path, file_options())?;
+    zip.write_all(data.as_bytes())
 }
 
 pub fn write_start_tag<'a, S>(
@@ -127,35 +190,78 @@ where
     let _ = writer.write_event(Event::Text(BytesText::from_plain_str(&data.into())));
 }
 
-pub fn make_file_from_writer(
+pub fn make_file_from_writer<W: Write + Seek>(
     path: &str,
-    temp_dir: &TempDir,
+    zip: &mut ZipWriter<W>,
     writer: Writer<Cursor<Vec<u8>>>,
-    dir: Option<&str>,
 ) -> Result<(), io::Error> {
-    match dir {
-        Some(dir) => {
-            let dir_path = temp_dir.path().join(dir);
-            fs::create_dir_all(dir_path)?;
-        }
-        None => {}
-    }
-    let file_path = temp_dir.path().join(path);
-    let mut f = File::create(file_path)?;
-    f.write_all(writer.into_inner().get_ref())?;
-    f.sync_all()?;
-    Ok(())
+    zip.start_file(path, file_options())?;
+    zip.write_all(writer.into_inner().get_ref())
 }
 
+/// Attribute value with the character references resolved.
 pub fn get_attribute_value(attr: &Attribute) -> Result<String, FromUtf8Error> {
     let value = (&attr.value).clone().into_owned();
-    String::from_utf8(value)
+    String::from_utf8(value).map(|value| condvert_character_reference(&value))
 }
 
 pub fn condvert_character_reference(src: &str) -> String {
-    src.replace("&amp;", "&")
-        .replace("&lt;", "<")
-        .replace("&gt;", ">")
-        .replace("&quot;", "\"")
-        .replace("&apos;", "'")
+    let mut result = String::with_capacity(src.len());
+    let mut rest = src;
+    while let Some(start) = rest.find('&') {
+        result.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let end = match rest.find(';') {
+            Some(end) => end,
+            None => break,
+        };
+        let character = match &rest[1..end] {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            name if name.starts_with("#x") => u32::from_str_radix(&name[2..], 16).ok().and_then(char::from_u32),
+            name if name.starts_with('#') => name[1..].parse::<u32>().ok().and_then(char::from_u32),
+            _ => None,
+        };
+        match character {
+            Some(character) => {
+                result.push(character);
+                rest = &rest[end + 1..];
+            },
+            None => {
+                result.push('&');
+                rest = &rest[1..];
+            },
+        }
+    }
+    result.push_str(rest);
+    result
+}
+
+// a sheet name as it is written in a reference, quoted unless it is only letters, digits and underscores
+pub fn quote_sheet_name(name: &str) -> String {
+    if !name.is_empty() && name.chars().all(|c| c.is_alphanumeric() || c == '_') {
+        name.to_string()
+    } else {
+        format!("'{}'", name.replace('\'', "''"))
+    }
+}
+
+// Sheet1!A1, 'My sheet'!$B$2:$C$3 or the same with the separator of ods, as the sheet and its top left cell
+pub fn parse_sheet_reference(reference: &str, separator: char) -> Option<(String, usize, usize)> {
+    let quote_end = reference.rfind('\'').map_or(0, |index| index + 1);
+    let reference = match reference[quote_end..].find(':') {
+        Some(index) => &reference[..quote_end + index],
+        None => reference,
+    };
+    let (sheet, cell) = reference.rsplit_once(separator)?;
+    let sheet = sheet.trim_start_matches('$');
+    let sheet = match sheet.strip_prefix('\'').and_then(|sheet| sheet.strip_suffix('\'')) {
+        Some(quoted) => quoted.replace("''", "'"),
+        None => sheet.to_string(),
+    };
+    let (column, row) = column_and_row_to_index(cell.replace('$', ""))?;
+    Some((sheet, row, column))
 }
@@ -1,263 +1,858 @@
 //! Excel Base Format
+//!
+//! A format code such as `[Red]#,##0.00;"none"` is parsed into up to four
+//! [`Section`]s of [`Item`]s. The parsed format renders values the way Excel
+//! shows them and `to_string()` writes it back as a format code.
+//!
+//! ```
+//! use spsheet::format::{Color, Format, Item, Placeholder};
+//! let format = Format::new("#,##0.00;[RED]-0");
+//! let sections = format.get_sections();
+//! assert_eq!(2, sections.len());
+//! assert_eq!(Some(Color::Red), sections[1].get_color());
+//! assert_eq!(&Item::Digit(Placeholder::Hash), &sections[0].get_items()[0]);
+//! assert_eq!("#,##0.00;[Red]-0", format.to_string());
+//! ```
 use std::borrow::Cow;
-use super::nom::{ branch::alt, 
+use std::fmt;
+use std::sync::{Arc, OnceLock, RwLock};
+use super::nom::{ branch::alt,
     bytes::complete::{
         tag,
         tag_no_case,
         take,
     take_until,
     take_while1},
-    combinator::{ complete, 
-        map, 
-        value },
-        multi::{ many0, many_m_n },
+    character::complete::{ anychar, one_of },
+    combinator::{ map,
+        value,
+        verify },
+        multi::many0,
     IResult,
-sequence::{ delimited } };
+sequence::{ delimited, preceded } };
 use chrono::prelude::*;
 use nom::Parser;
 use super::era_jp;
+use super::DateSystem;
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct Format {
     content: String,
+    sections: Vec<Section>,
+}
+
+/// One of the `;` separated parts of a format: positive, negative, zero and text
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Section {
+    condition: Option<Condition>,
+    color: Option<Color>,
+    items: Vec<Item>,
+}
+
+/// Condition such as `[<100]` deciding which section shows a number
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Condition {
+    Eq(f64),
+    Ne(f64),
+    Lt(f64),
+    Le(f64),
+    Gt(f64),
+    Ge(f64),
+}
+
+/// Font color of a section, `[Red]` or `[Color10]`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Color {
+    Black,
+    Blue,
+    Cyan,
+    Green,
+    Magenta,
+    Red,
+    White,
+    Yellow,
+    /// `[ColorN]`, the Nth (1 to 56) color of the palette
+    Indexed(u8),
+}
+
+/// `[$￥-411]`: currency symbol and locale of a section
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LocaleTag {
+    currency: String,
+    locale: String,
+}
+
+/// Month and day names and number separators of a language
+///
+/// A section with a locale tag such as `[$-407]` or `[$-de-DE]` renders with
+/// the locale of that tag, others use `en-US`. The table has English,
+/// Japanese, German, French, Spanish and Chinese, and `register` adds more.
+///
+/// ```
+/// use spsheet::Cell;
+/// use spsheet::format::{Format, Locale};
+/// Locale::new("nl-NL", 0x413)
+///     .with_separators(',', '.')
+///     .with_month_names(
+///         ["januari", "februari", "maart", "april", "mei", "juni",
+///          "juli", "augustus", "september", "oktober", "november", "december"],
+///         ["jan", "feb", "mrt", "apr", "mei", "jun", "jul", "aug", "sep", "okt", "nov", "dec"])
+///     .register();
+/// assert_eq!("1.234,50", Format::new("[$-413]#,##0.00").get_formated_number(1234.5));
+/// assert_eq!(Some(String::from("1 maart")), Cell::date("2018-03-01", "[$-nl-NL]d mmmm").unwrap().get_formated_value());
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Locale {
+    name: String,
+    lcid: u32,
+    decimal_separator: char,
+    grouping_separator: char,
+    month_names: Vec<String>,
+    month_abbreviations: Vec<String>,
+    day_names: Vec<String>,
+    day_abbreviations: Vec<String>,
+}
+
+/// Digit placeholder
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Placeholder {
+    /// `0` shows a zero when there is no digit
+    Zero,
+    /// `#` shows nothing when there is no digit
+    Hash,
+    /// `?` shows a space when there is no digit
+    Question,
+}
+
+/// Piece of a section
+#[derive(Debug, Clone, PartialEq)]
+pub enum Item {
+    Digit(Placeholder),
+    /// `.`
+    DecimalPoint,
+    /// `,`, a thousands separator or a scale of 1000 after the last digit
+    Thousands,
+    /// `%`
+    Percent,
+    /// `E+` or `E-`
+    Exponent { plus: bool },
+    /// `/` of a fraction or a date
+    Slash,
+    General,
+    /// `@`, the text value
+    Text,
+    /// Quoted, escaped or plain text shown as is
+    Literal(String),
+    /// `_x`, a space as wide as x
+    Skip(char),
+    /// `*x`, x repeated to fill the cell
+    Fill(char),
+    Locale(LocaleTag),
+    /// `yy` or `yyyy`
+    Year { long: bool },
+    /// `m` to `mmmmm`
+    Month(usize),
+    /// `d` to `dddd`
+    Day(usize),
+    Hour { long: bool },
+    Minute { long: bool },
+    Second { long: bool },
+    /// `e` or `ee`, year of the japanese era
+    EraYear { long: bool },
+    /// `g` to `ggg`, name of the japanese era
+    Era(usize),
+    /// `aaa` or `aaaa`, day of the week in the language of the locale, japanese without a tag
+    Weekday { long: bool },
+    /// `[h]`, hours elapsed beyond 24
+    ElapsedHour { long: bool },
+    /// `[m]`, minutes elapsed beyond 60
+    ElapsedMinute { long: bool },
+    /// `[s]`, seconds elapsed beyond 60
+    ElapsedSecond { long: bool },
+    /// `AM/PM` or `A/P` as written, which turns the hours into a 12-hour clock
+    AmPm { am: String, pm: String },
+    /// `.0` to `.000` after the seconds
+    SecondFraction(usize),
 }
 
 impl Format {
-    pub fn new<'a, S>(content: S) -> Format 
+    pub fn new<'a, S>(content: S) -> Format
          where S: Into<Cow<'a, str>>
     {
+        let content = content.into().into_owned();
         Format {
-            content: content.into().into_owned(),
+            sections: sections(&content),
+            content,
         }
     }
 
-    fn is_ymdhm<'a, S>(value: S) -> bool 
-        where S: Into<Cow<'a, str>>
-    {
-        match ymdhms(value.into().into_owned().as_str()) {
-            IResult::Ok((_, _)) => true,
-            _ => false
-        }
+    pub fn get_content(&self) -> &String {
+        &self.content
     }
 
-    fn is_numeric_ary<'a, S>(value: S) -> bool 
-    where S: Into<Cow<'a, str>>
-    {
-        match numeric_ary(value.into().into_owned().as_str()) {
-            IResult::Ok((_, _)) => true,
-            _ => false
+    pub fn get_sections(&self) -> &Vec<Section> {
+        &self.sections
+    }
+
+    /// True when the format shows a date or time rather than a number, e.g. `yyyy/mm/dd`.
+    pub fn is_date_format(&self) -> bool {
+        let items = || self.sections.iter().flat_map(|section| section.items.iter());
+        items().any(Item::is_date) && !items().any(|item| matches!(item, Item::Digit(_) | Item::General))
+    }
+
+    /// True for a date format with only hours, minutes, seconds and AM/PM, e.g. `hh:mm`.
+    pub fn is_time_format(&self) -> bool {
+        self.is_date_format() && self.sections.iter()
+            .flat_map(|section| section.items.iter())
+            .filter(|item| item.is_date())
+            .all(Item::is_time)
+    }
+
+    /// True for a time format counting elapsed time, e.g. `[h]:mm:ss`.
+    pub fn is_duration_format(&self) -> bool {
+        self.is_time_format() && self.sections.iter()
+            .flat_map(|section| section.items.iter())
+            .any(|item| matches!(item, Item::ElapsedHour { .. } | Item::ElapsedMinute { .. } | Item::ElapsedSecond { .. }))
+    }
+
+    /// ISO 4217 code of the currency the format shows, from a tag such as
+    /// `[$€-407]` or `[$USD]`, or a plain `$`, `€`, `£` or `¥` sign.
+    ///
+    /// ```
+    /// use spsheet::format::Format;
+    /// assert_eq!(Some(String::from("EUR")), Format::new("#,##0.00 [$€-407]").get_currency_code());
+    /// assert_eq!(Some(String::from("CHF")), Format::new("[$CHF] #,##0.00").get_currency_code());
+    /// assert_eq!(Some(String::from("USD")), Format::new("$#,##0_);($#,##0)").get_currency_code());
+    /// assert_eq!(None, Format::new("[$-409]#,##0").get_currency_code());
+    /// ```
+    pub fn get_currency_code(&self) -> Option<String> {
+        let symbol = |text: &str| match text.trim() {
+            "$" => Some("USD"),
+            "€" => Some("EUR"),
+            "£" => Some("GBP"),
+            "¥" | "￥" | "円" => Some("JPY"),
+            _ => None,
+        };
+        let items = &self.sections[0].items;
+        let tagged = items.iter().find_map(|item| match item {
+            Item::Locale(tag) if tag.currency.len() == 3 && tag.currency.chars().all(|c| c.is_ascii_uppercase()) =>
+                Some(tag.currency.clone()),
+            Item::Locale(tag) => symbol(&tag.currency).map(String::from),
+            _ => None,
+        });
+        tagged.or_else(|| items.iter().find_map(|item| match item {
+            Item::Literal(text) => symbol(text).map(String::from),
+            _ => None,
+        }))
+    }
+
+    /// Render a date with the first section, a number format shows its serial
+    /// value and elapsed time (`[h]`) counts from the start of `system`.
+    pub fn get_formated_date(&self, dt: &NaiveDateTime, system: DateSystem) -> Option<String> {
+        if !self.is_date_format() {
+            return Some(self.get_formated_number(system.date_to_serial(dt)));
         }
+        let section = &self.sections[0];
+        let twelve_hour = section.items.iter().any(|item| matches!(item, Item::AmPm { .. }));
+        // aaa is a japanese token, it shows japanese names unless a tag says otherwise
+        let names = section.find_locale().unwrap_or_else(Locale::default_locale);
+        let weekdays = section.find_locale()
+            .unwrap_or_else(|| Locale::find("ja-JP").unwrap_or_else(Locale::default_locale));
+        Some(section.items.iter().map(|item| item.format_date(dt, system, twelve_hour, &names, &weekdays)).collect())
     }
 
-    pub fn get_content(&self) -> &String {
-        &self.content
+    /// Render a number the way Excel displays it with this format.
+    ///
+    /// The format may have up to four `;` separated sections for positive,
+    /// negative, zero and text values, or sections chosen by conditions such
+    /// as `[>=1000]`. An empty format is `General`.
+    pub fn get_formated_number(&self, value: f64) -> String {
+        self.get_formated_number_with_color(value).0
     }
 
-    pub fn get_date_formats(&self) -> Option<Vec<&str>> {
-        match ymdhms(self.content.as_str()) {
-            IResult::Ok((_, output)) => {
-                let mut result = vec![];
-                for i in output {
-                    for j in i {
-                        result.push(j);
-                    }
-                }
-                Some(result)
-            },
-            _ => None
+    /// Render a number together with the color of the section that shows it.
+    ///
+    /// ```
+    /// use spsheet::format::{Color, Format};
+    /// let format = Format::new("[Red][<0]#,##0;[Blue]#,##0");
+    /// assert_eq!((String::from("1,234"), Some(Color::Red)), format.get_formated_number_with_color(-1234.0));
+    /// assert_eq!((String::from("56"), Some(Color::Blue)), format.get_formated_number_with_color(56.0));
+    /// ```
+    pub fn get_formated_number_with_color(&self, value: f64) -> (String, Option<Color>) {
+        // infinity and NaN have no digits, Excel can only hold them as an error
+        if !value.is_finite() {
+            return (String::from("#NUM!"), None);
         }
+        let (section, minus) = self.number_section(value);
+        let locale = section.find_locale().unwrap_or_else(Locale::default_locale);
+        let text = format_section(&section.items, value.abs(), &locale);
+        (if minus { format!("-{}", text) } else { text }, section.color)
     }
 
-    pub fn get_formated_date(&self, dt: &DateTime<Utc>) -> Option<String> {
-        match ymdhms(self.content.as_str()) {
-            IResult::Ok((_, output)) => {
-                let mut format = String::from("");
-                let era_year = era_jp::get_year(dt);
-                for item_ary in output {
-                    for item in item_ary {
-                        match item {
-                            "{{era1}}" => {
-                                format = format!("{}{}", format, era_year);
-                            },
-                            "{{era2}}" => {
-                                format = format!("{}{:>02}", format, era_year);
-                            },
-                            "{{gengou1}}" => {
-                                format.push_str(era_jp::get_abbreviation_name(&dt));
-                            },
-                            "{{gengou2}}" => {
-                                format.push_str(era_jp::get_short_name(&dt));
-                            },
-                            "{{gengou3}}" => {
-                                format.push_str(era_jp::get_name(&dt));
-                            },
-                            _ => {
-                                format.push_str(item);
-                            }
-                        }
-                    }
-                }
-                Some(dt.format(format.as_str()).to_string())
-            },
-            _ => None
+    // the section showing a number, and whether it needs a minus sign
+    fn number_section(&self, value: f64) -> (&Section, bool) {
+        let sections = &self.sections;
+        let numeric = &sections[..sections.len().min(3)];
+        if numeric.iter().all(|section| section.condition.is_none()) {
+            return match sections.len() {
+                1 => (&sections[0], value < 0.0),
+                _ if value < 0.0 => (&sections[1], false),
+                2 => (&sections[0], false),
+                _ if value == 0.0 => (&sections[2], false),
+                _ => (&sections[0], false),
+            };
         }
+        // conditions are tried in order, the first section without one takes the rest
+        let section = numeric.iter()
+            .find(|section| section.condition.is_some_and(|condition| condition.matches(value)))
+            .or_else(|| numeric.iter().find(|section| section.condition.is_none()))
+            .unwrap_or(&numeric[numeric.len() - 1]);
+        // a section only for negative numbers, such as [<0], drops the sign
+        let negative_only = matches!(section.condition, Some(Condition::Lt(limit)) | Some(Condition::Le(limit)) if limit <= 0.0);
+        (section, value < 0.0 && !negative_only)
+    }
+
+    /// Render a text value, only the text section (the fourth one, or a
+    /// single section with `@`) changes it.
+    pub fn get_formated_text(&self, text: &str) -> String {
+        self.get_formated_text_with_color(text).0
     }
-}
 
-fn year4(input: &str) -> IResult<&str, &str> {
-    value("%Y", tag_no_case("yyyy")).parse(input)
+    /// Render a text value together with the color of the text section.
+    pub fn get_formated_text_with_color(&self, text: &str) -> (String, Option<Color>) {
+        let section = match self.sections.len() {
+            1 if self.sections[0].items.contains(&Item::Text) => &self.sections[0],
+            4 => &self.sections[3],
+            _ => return (text.to_string(), None),
+        };
+        (section.items.iter().map(|item| match item {
+            Item::Text => text.to_string(),
+            item => item.literal(),
+        }).collect(), section.color)
+    }
 }
 
-fn year2(input: &str) -> IResult<&str, &str> {
-    value("%Y", tag_no_case("yy")).parse(input)
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, section) in self.sections.iter().enumerate() {
+            if i > 0 {
+                write!(f, ";")?;
+            }
+            write!(f, "{}", section)?;
+        }
+        Ok(())
+    }
 }
 
-fn era1(input: &str) -> IResult<&str, &str> {
-    value("{{era1}}", tag_no_case("e")).parse(input)
+impl Section {
+    pub fn get_condition(&self) -> Option<Condition> {
+        self.condition
+    }
+
+    pub fn get_color(&self) -> Option<Color> {
+        self.color
+    }
+
+    pub fn get_items(&self) -> &Vec<Item> {
+        &self.items
+    }
+
+    /// The first locale tag of the section
+    pub fn get_locale(&self) -> Option<&LocaleTag> {
+        self.items.iter().find_map(|item| match item {
+            Item::Locale(tag) => Some(tag),
+            _ => None,
+        })
+    }
+
+    // the first tag naming a locale of the table
+    fn find_locale(&self) -> Option<Arc<Locale>> {
+        self.items.iter().find_map(|item| match item {
+            Item::Locale(tag) if !tag.locale.is_empty() => Locale::find(&tag.locale),
+            _ => None,
+        })
+    }
 }
 
-fn era2(input: &str) -> IResult<&str, &str> {
-    value("{{era2}}", tag_no_case("ee")).parse(input)
+impl fmt::Display for Section {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(color) = self.color {
+            write!(f, "[{}]", color)?;
+        }
+        if let Some(condition) = self.condition {
+            write!(f, "[{}]", condition)?;
+        }
+        for (i, item) in self.items.iter().enumerate() {
+            // the digits of a fixed denominator such as ?/8 stay bare, an escaped one is text
+            let denominator = fixed_digit(item).is_some() && self.items[..i].iter().rev()
+                .find(|item| fixed_digit(item).is_none() && **item != Item::Digit(Placeholder::Zero)) == Some(&Item::Slash);
+            match item {
+                Item::Literal(text) if denominator => write!(f, "{}", text)?,
+                item => write!(f, "{}", item)?,
+            }
+        }
+        Ok(())
+    }
 }
 
-fn gengou1(input: &str) -> IResult<&str, &str> {
-    value("{{gengou1}}", tag_no_case("g")).parse(input)
+impl Condition {
+    pub fn matches(&self, value: f64) -> bool {
+        match *self {
+            Condition::Eq(limit) => value == limit,
+            Condition::Ne(limit) => value != limit,
+            Condition::Lt(limit) => value < limit,
+            Condition::Le(limit) => value <= limit,
+            Condition::Gt(limit) => value > limit,
+            Condition::Ge(limit) => value >= limit,
+        }
+    }
 }
 
-fn gengou2(input: &str) -> IResult<&str, &str> {
-    value("{{gengou2}}", tag_no_case("gg")).parse(input)
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Condition::Eq(value) => write!(f, "={}", value),
+            Condition::Ne(value) => write!(f, "<>{}", value),
+            Condition::Lt(value) => write!(f, "<{}", value),
+            Condition::Le(value) => write!(f, "<={}", value),
+            Condition::Gt(value) => write!(f, ">{}", value),
+            Condition::Ge(value) => write!(f, ">={}", value),
+        }
+    }
 }
 
-fn gengou3(input: &str) -> IResult<&str, &str> {
-    value("{{gengou3}}", tag_no_case("ggg")).parse(input)
+// the default palette of [Color1] to [Color56]
+const PALETTE: [u32; 56] = [
+    0x000000, 0xFFFFFF, 0xFF0000, 0x00FF00, 0x0000FF, 0xFFFF00, 0xFF00FF, 0x00FFFF,
+    0x800000, 0x008000, 0x000080, 0x808000, 0x800080, 0x008080, 0xC0C0C0, 0x808080,
+    0x9999FF, 0x993366, 0xFFFFCC, 0xCCFFFF, 0x660066, 0xFF8080, 0x0066CC, 0xCCCCFF,
+    0x000080, 0xFF00FF, 0xFFFF00, 0x00FFFF, 0x800080, 0x800000, 0x008080, 0x0000FF,
+    0x00CCFF, 0xCCFFFF, 0xCCFFCC, 0xFFFF99, 0x99CCFF, 0xFF99CC, 0xCC99FF, 0xFFCC99,
+    0x3366FF, 0x33CCCC, 0x99CC00, 0xFFCC00, 0xFF9900, 0xFF6600, 0x666699, 0x969696,
+    0x003366, 0x339966, 0x003300, 0x333300, 0x993300, 0x993366, 0x333399, 0x333333,
+];
+
+impl Color {
+    /// RGB value such as `0xFF0000` for red
+    pub fn get_rgb(&self) -> u32 {
+        match *self {
+            Color::Black => 0x000000,
+            Color::Blue => 0x0000FF,
+            Color::Cyan => 0x00FFFF,
+            Color::Green => 0x00FF00,
+            Color::Magenta => 0xFF00FF,
+            Color::Red => 0xFF0000,
+            Color::White => 0xFFFFFF,
+            Color::Yellow => 0xFFFF00,
+            Color::Indexed(index) => PALETTE[(index as usize).clamp(1, 56) - 1],
+        }
+    }
 }
 
-fn year(input: &str) -> IResult<&str, &str> {
-    alt((
-        complete(year4),
-        complete(year2),
-        complete(era2),
-        complete(era1),
-        complete(gengou3),
-        complete(gengou2),
-        complete(gengou1)
-    )).parse(input)
+impl fmt::Display for Color {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Color::Black => write!(f, "Black"),
+            Color::Blue => write!(f, "Blue"),
+            Color::Cyan => write!(f, "Cyan"),
+            Color::Green => write!(f, "Green"),
+            Color::Magenta => write!(f, "Magenta"),
+            Color::Red => write!(f, "Red"),
+            Color::White => write!(f, "White"),
+            Color::Yellow => write!(f, "Yellow"),
+            Color::Indexed(index) => write!(f, "Color{}", index),
+        }
+    }
 }
 
-fn month1(input: &str) -> IResult<&str, &str> {
-    value("%-m", tag_no_case("m")).parse(input)
+impl LocaleTag {
+    /// Currency symbol, empty for a locale only tag such as `[$-409]`
+    pub fn get_currency(&self) -> &String {
+        &self.currency
+    }
+
+    /// Locale after the `-`, e.g. `411` or `ja-JP`
+    pub fn get_locale(&self) -> &String {
+        &self.locale
+    }
 }
 
-fn month2(input: &str) -> IResult<&str, &str> {
-    value("%m", tag_no_case("mm")).parse(input)
+impl fmt::Display for LocaleTag {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.locale.is_empty() {
+            write!(f, "[${}]", self.currency)
+        } else {
+            write!(f, "[${}-{}]", self.currency, self.locale)
+        }
+    }
 }
 
-fn month3(input: &str) -> IResult<&str, &str> {
-    value("%b", tag_no_case("mmm")).parse(input)
+fn names(names: &[&str]) -> Vec<String> {
+    names.iter().map(|name| name.to_string()).collect()
 }
 
-fn month4(input: &str) -> IResult<&str, &str> {
-    value("%B", tag_no_case("mmmm")).parse(input)
+// the locales every table starts with
+fn builtin_locales() -> Vec<Arc<Locale>> {
+    vec![
+        Locale::new("en-US", 0x409),
+        Locale::new("en-GB", 0x809),
+        Locale::new("ja-JP", 0x411)
+            .with_month_names(
+                ["1月", "2月", "3月", "4月", "5月", "6月", "7月", "8月", "9月", "10月", "11月", "12月"],
+                ["1月", "2月", "3月", "4月", "5月", "6月", "7月", "8月", "9月", "10月", "11月", "12月"])
+            .with_day_names(
+                ["日曜日", "月曜日", "火曜日", "水曜日", "木曜日", "金曜日", "土曜日"],
+                ["日", "月", "火", "水", "木", "金", "土"]),
+        Locale::new("de-DE", 0x407)
+            .with_separators(',', '.')
+            .with_month_names(
+                ["Januar", "Februar", "März", "April", "Mai", "Juni",
+                 "Juli", "August", "September", "Oktober", "November", "Dezember"],
+                ["Jan", "Feb", "Mär", "Apr", "Mai", "Jun", "Jul", "Aug", "Sep", "Okt", "Nov", "Dez"])
+            .with_day_names(
+                ["Sonntag", "Montag", "Dienstag", "Mittwoch", "Donnerstag", "Freitag", "Samstag"],
+                ["So", "Mo", "Di", "Mi", "Do", "Fr", "Sa"]),
+        Locale::new("fr-FR", 0x40C)
+            .with_separators(',', '\u{a0}')
+            .with_month_names(
+                ["janvier", "février", "mars", "avril", "mai", "juin",
+                 "juillet", "août", "septembre", "octobre", "novembre", "décembre"],
+                ["janv.", "févr.", "mars", "avr.", "mai", "juin",
+                 "juil.", "août", "sept.", "oct.", "nov.", "déc."])
+            .with_day_names(
+                ["dimanche", "lundi", "mardi", "mercredi", "jeudi", "vendredi", "samedi"],
+                ["dim.", "lun.", "mar.", "mer.", "jeu.", "ven.", "sam."]),
+        Locale::new("es-ES", 0xC0A)
+            .with_separators(',', '.')
+            .with_month_names(
+                ["enero", "febrero", "marzo", "abril", "mayo", "junio",
+                 "julio", "agosto", "septiembre", "octubre", "noviembre", "diciembre"],
+                ["ene", "feb", "mar", "abr", "may", "jun", "jul", "ago", "sep", "oct", "nov", "dic"])
+            .with_day_names(
+                ["domingo", "lunes", "martes", "miércoles", "jueves", "viernes", "sábado"],
+                ["dom", "lun", "mar", "mié", "jue", "vie", "sáb"]),
+        Locale::new("zh-CN", 0x804)
+            .with_month_names(
+                ["一月", "二月", "三月", "四月", "五月", "六月",
+                 "七月", "八月", "九月", "十月", "十一月", "十二月"],
+                ["1月", "2月", "3月", "4月", "5月", "6月", "7月", "8月", "9月", "10月", "11月", "12月"])
+            .with_day_names(
+                ["星期日", "星期一", "星期二", "星期三", "星期四", "星期五", "星期六"],
+                ["周日", "周一", "周二", "周三", "周四", "周五", "周六"]),
+    ].into_iter().map(Arc::new).collect()
 }
 
-fn month5(input: &str) -> IResult<&str, &str> {
-    value("{{month5}}", tag_no_case("mmmmm")).parse(input)
+fn locale_table() -> &'static RwLock<Vec<Arc<Locale>>> {
+    static TABLE: OnceLock<RwLock<Vec<Arc<Locale>>>> = OnceLock::new();
+    TABLE.get_or_init(|| RwLock::new(builtin_locales()))
 }
 
-fn month(input: &str) -> IResult<&str, &str> {
-    alt((
-        complete(month5),
-        complete(month4),
-        complete(month3),
-        complete(month2),
-        complete(month1)
-    )).parse(input)
+impl Locale {
+    /// A locale with English names and `.` `,` separators, e.g. `Locale::new("nl-NL", 0x413)`
+    pub fn new<'a, S>(name: S, lcid: u32) -> Locale
+        where S: Into<Cow<'a, str>>
+    {
+        Locale {
+            name: name.into().into_owned(),
+            lcid,
+            decimal_separator: '.',
+            grouping_separator: ',',
+            month_names: names(&["January", "February", "March", "April", "May", "June",
+                "July", "August", "September", "October", "November", "December"]),
+            month_abbreviations: names(&["Jan", "Feb", "Mar", "Apr", "May", "Jun",
+                "Jul", "Aug", "Sep", "Oct", "Nov", "Dec"]),
+            day_names: names(&["Sunday", "Monday", "Tuesday", "Wednesday", "Thursday", "Friday", "Saturday"]),
+            day_abbreviations: names(&["Sun", "Mon", "Tue", "Wed", "Thu", "Fri", "Sat"]),
+        }
+    }
+
+    pub fn with_separators(mut self, decimal: char, grouping: char) -> Locale {
+        self.decimal_separator = decimal;
+        self.grouping_separator = grouping;
+        self
+    }
+
+    /// Names for `mmmm` and `mmm`, January first
+    pub fn with_month_names(mut self, names: [&str; 12], abbreviations: [&str; 12]) -> Locale {
+        self.month_names = self::names(&names);
+        self.month_abbreviations = self::names(&abbreviations);
+        self
+    }
+
+    /// Names for `dddd`/`aaaa` and `ddd`/`aaa`, Sunday first
+    pub fn with_day_names(mut self, names: [&str; 7], abbreviations: [&str; 7]) -> Locale {
+        self.day_names = self::names(&names);
+        self.day_abbreviations = self::names(&abbreviations);
+        self
+    }
+
+    /// Language tag such as `ja-JP`
+    pub fn get_name(&self) -> &String {
+        &self.name
+    }
+
+    /// Windows locale id such as `0x411`, written as `[$-411]` in a format
+    pub fn get_lcid(&self) -> u32 {
+        self.lcid
+    }
+
+    pub fn get_decimal_separator(&self) -> char {
+        self.decimal_separator
+    }
+
+    pub fn get_grouping_separator(&self) -> char {
+        self.grouping_separator
+    }
+
+    pub fn get_month_names(&self) -> &Vec<String> {
+        &self.month_names
+    }
+
+    pub fn get_month_abbreviations(&self) -> &Vec<String> {
+        &self.month_abbreviations
+    }
+
+    pub fn get_day_names(&self) -> &Vec<String> {
+        &self.day_names
+    }
+
+    pub fn get_day_abbreviations(&self) -> &Vec<String> {
+        &self.day_abbreviations
+    }
+
+    /// Add the locale to the table, replacing one with the same name or id
+    pub fn register(self) {
+        let mut table = locale_table().write().unwrap_or_else(|e| e.into_inner());
+        table.retain(|locale| locale.name != self.name && locale.lcid != self.lcid);
+        table.push(Arc::new(self));
+    }
+
+    /// Look up the locale of a tag: a name such as `ja-JP` (`ja-JP-x-gannen`
+    /// and `ja` match too) or a hexadecimal id such as `411` or `0411`.
+    /// The calendar and numeral bytes of an id such as `1010409` are ignored.
+    pub fn find(tag: &str) -> Option<Arc<Locale>> {
+        let table = locale_table().read().unwrap_or_else(|e| e.into_inner());
+        let tag = tag.to_ascii_lowercase();
+        let by_name = table.iter().find(|locale| {
+            let name = locale.name.to_ascii_lowercase();
+            tag == name || tag.starts_with(&format!("{}-", name))
+        }).or_else(|| table.iter().find(|locale| {
+            locale.name.to_ascii_lowercase().split('-').next() == Some(tag.as_str())
+        }));
+        by_name.or_else(|| {
+            let lcid = u32::from_str_radix(&tag, 16).ok()? & 0xFFFF;
+            table.iter().find(|locale| locale.lcid == lcid)
+        }).cloned()
+    }
+
+    // en-US, or what replaced it in the table
+    fn default_locale() -> Arc<Locale> {
+        Locale::find("en-US").unwrap_or_else(|| Arc::new(Locale::new("en-US", 0x409)))
+    }
 }
 
-fn day1(input: &str) -> IResult<&str, &str> {
-    value("%-d", tag_no_case("d")).parse(input)
+impl Item {
+    /// True for the year, month, day, time and era items
+    pub fn is_date(&self) -> bool {
+        matches!(self, Item::Year { .. } | Item::Month(_) | Item::Day(_) | Item::Hour { .. }
+            | Item::Minute { .. } | Item::Second { .. } | Item::EraYear { .. } | Item::Era(_)
+            | Item::Weekday { .. } | Item::ElapsedHour { .. } | Item::ElapsedMinute { .. }
+            | Item::ElapsedSecond { .. } | Item::AmPm { .. } | Item::SecondFraction(_))
+    }
+
+    /// True for the items of a time: hours, minutes, seconds and AM/PM
+    pub fn is_time(&self) -> bool {
+        matches!(self, Item::Hour { .. } | Item::Minute { .. } | Item::Second { .. }
+            | Item::ElapsedHour { .. } | Item::ElapsedMinute { .. } | Item::ElapsedSecond { .. }
+            | Item::AmPm { .. } | Item::SecondFraction(_))
+    }
+
+    // what a number section shows for the item besides the digits
+    pub(crate) fn literal(&self) -> String {
+        match self {
+            Item::DecimalPoint => String::from("."),
+            Item::Percent => String::from("%"),
+            Item::Slash => String::from("/"),
+            Item::Literal(text) => text.clone(),
+            Item::Skip(_) => String::from(" "),
+            Item::Locale(tag) => tag.currency.clone(),
+            _ => String::new(),
+        }
+    }
+
+    // names are used for months and days, weekdays for aaa
+    fn format_date(&self, dt: &NaiveDateTime, system: DateSystem, twelve_hour: bool, names: &Locale, weekdays: &Locale) -> String {
+        let two_digits = |value: i64, long: bool| if long { format!("{:02}", value) } else { value.to_string() };
+        // elapsed time counts from the start of the serial dates
+        let elapsed_seconds = || (system.date_to_serial(dt) * 86400.0).floor() as i64;
+        // era_jp only takes zoned dates, the era does not depend on the zone
+        let era_dt = dt.and_utc();
+        match *self {
+            Item::Year { long: true } => format!("{:04}", dt.year()),
+            Item::Year { long: false } => format!("{:02}", dt.year().rem_euclid(100)),
+            Item::Month(1) => dt.month().to_string(),
+            Item::Month(2) => format!("{:02}", dt.month()),
+            Item::Month(3) => names.month_abbreviations[dt.month0() as usize].clone(),
+            Item::Month(4) => names.month_names[dt.month0() as usize].clone(),
+            Item::Month(_) => names.month_names[dt.month0() as usize].chars().take(1).collect(),
+            Item::Day(1) => dt.day().to_string(),
+            Item::Day(2) => format!("{:02}", dt.day()),
+            Item::Day(3) => names.day_abbreviations[dt.weekday().num_days_from_sunday() as usize].clone(),
+            Item::Day(_) => names.day_names[dt.weekday().num_days_from_sunday() as usize].clone(),
+            Item::Hour { long } if twelve_hour => two_digits(((dt.hour() + 11) % 12 + 1).into(), long),
+            Item::Hour { long } => two_digits(dt.hour().into(), long),
+            Item::Minute { long } => two_digits(dt.minute().into(), long),
+            Item::Second { long } => two_digits(dt.second().into(), long),
+            Item::ElapsedHour { long } => two_digits(elapsed_seconds() / 3600, long),
+            Item::ElapsedMinute { long } => two_digits(elapsed_seconds() / 60, long),
+            Item::ElapsedSecond { long } => two_digits(elapsed_seconds(), long),
+            Item::AmPm { ref am, ref pm } => if dt.hour() < 12 { am.clone() } else { pm.clone() },
+            Item::SecondFraction(places) => {
+                let scale = 10u32.pow(9 - places as u32);
+                let fraction = ((dt.nanosecond() % 1_000_000_000) + scale / 2) / scale;
+                format!(".{:0width$}", fraction.min(10u32.pow(places as u32) - 1), width = places)
+            },
+            Item::EraYear { long: true } => format!("{:02}", era_jp::get_year(&era_dt)),
+            Item::EraYear { long: false } => era_jp::get_year(&era_dt).to_string(),
+            Item::Era(1) => era_jp::get_abbreviation_name(&era_dt).to_string(),
+            Item::Era(2) => era_jp::get_short_name(&era_dt).to_string(),
+            Item::Era(_) => era_jp::get_name(&era_dt).to_string(),
+            Item::Weekday { long } => {
+                let day = dt.weekday().num_days_from_sunday() as usize;
+                if long { weekdays.day_names[day].clone() } else { weekdays.day_abbreviations[day].clone() }
+            },
+            // a comma in a date is shown as is
+            Item::Thousands => String::from(","),
+            ref item => item.literal(),
+        }
+    }
 }
 
-fn day2(input: &str) -> IResult<&str, &str> {
-    value("%d", tag_no_case("dd")).parse(input)
+impl fmt::Display for Item {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let repeat = |c: &str, count: usize| c.repeat(count);
+        match self {
+            Item::Digit(Placeholder::Zero) => write!(f, "0"),
+            Item::Digit(Placeholder::Hash) => write!(f, "#"),
+            Item::Digit(Placeholder::Question) => write!(f, "?"),
+            Item::DecimalPoint => write!(f, "."),
+            Item::Thousands => write!(f, ","),
+            Item::Percent => write!(f, "%"),
+            Item::Exponent { plus } => write!(f, "E{}", if *plus { "+" } else { "-" }),
+            Item::Slash => write!(f, "/"),
+            Item::General => write!(f, "General"),
+            Item::Text => write!(f, "@"),
+            Item::Literal(text) => {
+                let mut chars = text.chars();
+                match (chars.next(), chars.next()) {
+                    (None, _) => Ok(()),
+                    // digits and placeholders such as 0 # ? . , % E / @ are escaped
+                    (Some(c), None) if "$-+():!^&'~{}<>= ".contains(c) => write!(f, "{}", c),
+                    (Some(c), None) => write!(f, "\\{}", c),
+                    _ if text.contains('"') => text.chars().try_for_each(|c| write!(f, "\\{}", c)),
+                    _ => write!(f, "\"{}\"", text),
+                }
+            },
+            Item::Skip(c) => write!(f, "_{}", c),
+            Item::Fill(c) => write!(f, "*{}", c),
+            Item::Locale(tag) => write!(f, "{}", tag),
+            Item::Year { long } => write!(f, "{}", if *long { "yyyy" } else { "yy" }),
+            Item::Month(count) => write!(f, "{}", repeat("m", *count)),
+            Item::Day(count) => write!(f, "{}", repeat("d", *count)),
+            Item::Hour { long } => write!(f, "{}", if *long { "hh" } else { "h" }),
+            Item::Minute { long } => write!(f, "{}", if *long { "mm" } else { "m" }),
+            Item::Second { long } => write!(f, "{}", if *long { "ss" } else { "s" }),
+            Item::EraYear { long } => write!(f, "{}", if *long { "ee" } else { "e" }),
+            Item::Era(count) => write!(f, "{}", repeat("g", *count)),
+            Item::Weekday { long } => write!(f, "{}", if *long { "aaaa" } else { "aaa" }),
+            Item::ElapsedHour { long } => write!(f, "[{}]", if *long { "hh" } else { "h" }),
+            Item::ElapsedMinute { long } => write!(f, "[{}]", if *long { "mm" } else { "m" }),
+            Item::ElapsedSecond { long } => write!(f, "[{}]", if *long { "ss" } else { "s" }),
+            Item::AmPm { am, pm } => write!(f, "{}/{}", am, pm),
+            Item::SecondFraction(places) => write!(f, ".{}", repeat("0", *places)),
+        }
+    }
 }
 
-fn dow3(input: &str) -> IResult<&str, &str> {
-    value("%a", tag_no_case("ddd")).parse(input)
+// what a format code is made of before the sections are put together
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Item(Item),
+    Color(Color),
+    Condition(Condition),
+    /// `[h]` and other bracket codes which are not supported
+    Unknown,
+    Separator,
 }
 
-fn dow4(input: &str) -> IResult<&str, &str> {
-    value("%A", tag_no_case("dddd")).parse(input)
+fn letters(letter: char) -> impl Fn(&str) -> IResult<&str, usize> {
+    move |input: &str| {
+        map(take_while1(|c: char| c.eq_ignore_ascii_case(&letter)), |x: &str| x.chars().count()).parse(input)
+    }
 }
 
-fn youbi3(input: &str) -> IResult<&str, &str> {
-    value("{{youbi3}}", tag_no_case("aaa")).parse(input)
+fn year(input: &str) -> IResult<&str, Item> {
+    map(letters('y'), |count| Item::Year { long: count > 2 }).parse(input)
 }
 
-fn youbi4(input: &str) -> IResult<&str, &str> {
-    value("{{youbi4}}", tag_no_case("aaaa")).parse(input)
+// m is turned into a minute next to an hour or a second, see resolve_minutes
+fn month(input: &str) -> IResult<&str, Item> {
+    map(letters('m'), |count| Item::Month(count.min(5))).parse(input)
 }
 
-fn day(input: &str) -> IResult<&str, &str> {
-    alt((
-        complete(youbi4),
-        complete(youbi3),
-        complete(dow4),
-        complete(dow3),
-        complete(day2),
-        complete(day1)
-    )).parse(input)
+fn day(input: &str) -> IResult<&str, Item> {
+    map(letters('d'), |count| Item::Day(count.min(4))).parse(input)
 }
 
-fn hour1(input: &str) -> IResult<&str, &str> {
-    value("%-H", tag_no_case("h")).parse(input)
+fn hour(input: &str) -> IResult<&str, Item> {
+    map(letters('h'), |count| Item::Hour { long: count > 1 }).parse(input)
 }
 
-fn hour2(input: &str) -> IResult<&str, &str> {
-    value("%H", tag_no_case("hh")).parse(input)
+fn second(input: &str) -> IResult<&str, Item> {
+    map(letters('s'), |count| Item::Second { long: count > 1 }).parse(input)
 }
 
-fn hour(input: &str) -> IResult<&str, &str> {
-    alt((
-        complete(hour2),
-        complete(hour1)
-    )).parse(input)
+fn era_year(input: &str) -> IResult<&str, Item> {
+    map(letters('e'), |count| Item::EraYear { long: count > 1 }).parse(input)
 }
 
-fn minute1(input: &str) -> IResult<&str, &str> {
-    value("%-M", tag_no_case("m")).parse(input)
+fn era(input: &str) -> IResult<&str, Item> {
+    map(letters('g'), |count| Item::Era(count.min(3))).parse(input)
 }
 
-fn minute2(input: &str) -> IResult<&str, &str> {
-    value("%M", tag_no_case("mm")).parse(input)
+fn weekday(input: &str) -> IResult<&str, Item> {
+    map(verify(letters('a'), |count| *count >= 3), |count| Item::Weekday { long: count > 3 }).parse(input)
 }
 
-fn minute(input: &str) -> IResult<&str, &str> {
+// the case is kept as written: am/pm shows "am"
+fn am_pm(input: &str) -> IResult<&str, Item> {
     alt((
-        complete(minute2),
-        complete(minute1)
+        map(tag_no_case("am/pm"), |x: &str| Item::AmPm { am: x[..2].to_string(), pm: x[3..].to_string() }),
+        map(tag_no_case("a/p"), |x: &str| Item::AmPm { am: x[..1].to_string(), pm: x[2..].to_string() }),
     )).parse(input)
 }
 
-fn second1(input: &str) -> IResult<&str, &str> {
-    value("%-S", tag_no_case("s")).parse(input)
+fn date_item(input: &str) -> IResult<&str, Item> {
+    alt((year, month, day, hour, second, era_year, era, weekday)).parse(input)
 }
 
-fn second2(input: &str) -> IResult<&str, &str> {
-    value("%S", tag_no_case("ss")).parse(input)
-}
-
-fn second(input: &str) -> IResult<&str, &str> {
+fn digit(input: &str) -> IResult<&str, Item> {
     alt((
-        complete(second2),
-        complete(second1)
+        value(Item::Digit(Placeholder::Zero), tag("0")),
+        value(Item::Digit(Placeholder::Hash), tag("#")),
+        value(Item::Digit(Placeholder::Question), tag("?")),
     )).parse(input)
 }
 
-fn special_word(input: &str) -> IResult<&str, &str> {
+fn number_item(input: &str) -> IResult<&str, Item> {
     alt((
-        map(tag("/"), |x| x),
-        map(tag(":"), |x| x)
+        digit,
+        value(Item::DecimalPoint, tag(".")),
+        value(Item::Thousands, tag(",")),
+        value(Item::Percent, tag("%")),
+        value(Item::Slash, tag("/")),
+        value(Item::Text, tag("@")),
+        map((one_of("Ee"), one_of("+-")), |(_, sign)| Item::Exponent { plus: sign == '+' }),
     )).parse(input)
 }
 
@@ -269,96 +864,469 @@ fn quoted_word(input: &str) -> IResult<&str, &str> {
     delimited(tag("\""), take_until("\""), tag("\"")).parse(input)
 }
 
-fn word(input: &str) -> IResult<&str, &str> {
+fn literal_item(input: &str) -> IResult<&str, Item> {
     alt((
-        complete(quoted_word),
-        complete(escaped_word),
-        complete(special_word)
+        map(quoted_word, |x| Item::Literal(x.to_string())),
+        map(escaped_word, |x| Item::Literal(x.to_string())),
+        map(preceded(tag("_"), anychar), Item::Skip),
+        map(preceded(tag("*"), anychar), Item::Fill),
     )).parse(input)
 }
 
-fn hm(input: &str) -> IResult<&str, Vec<&str>> {
-    map((hour, word, minute), |(h, w, m)| vec![h, w, m]).parse(input)
+fn color(name: &str) -> Option<Color> {
+    let color = match name.to_lowercase().as_str() {
+        "black" | "黒" => Color::Black,
+        "blue" | "青" => Color::Blue,
+        "cyan" | "水" => Color::Cyan,
+        "green" | "緑" => Color::Green,
+        "magenta" | "紫" => Color::Magenta,
+        "red" | "赤" => Color::Red,
+        "white" | "白" => Color::White,
+        "yellow" | "黄" => Color::Yellow,
+        name => {
+            let index = name.strip_prefix("color")?.parse::<u8>().ok()?;
+            if !(1..=56).contains(&index) {
+                return None;
+            }
+            Color::Indexed(index)
+        },
+    };
+    Some(color)
 }
 
-fn ms(input: &str) -> IResult<&str, Vec<&str>> {
-    map((minute, word, second), |(m, w, s)| vec![m, w, s]).parse(input)
+fn condition(content: &str) -> Option<Condition> {
+    let (make, rest): (fn(f64) -> Condition, &str) = if let Some(rest) = content.strip_prefix("<=") {
+        (Condition::Le, rest)
+    } else if let Some(rest) = content.strip_prefix(">=") {
+        (Condition::Ge, rest)
+    } else if let Some(rest) = content.strip_prefix("<>") {
+        (Condition::Ne, rest)
+    } else if let Some(rest) = content.strip_prefix('<') {
+        (Condition::Lt, rest)
+    } else if let Some(rest) = content.strip_prefix('>') {
+        (Condition::Gt, rest)
+    } else if let Some(rest) = content.strip_prefix('=') {
+        (Condition::Eq, rest)
+    } else {
+        return None;
+    };
+    rest.trim().parse::<f64>().ok().map(make)
 }
 
-fn ymdhms(input: &str) -> IResult<&str, Vec<Vec<&str>>> {
-    many0(alt((
-        hm,
-        ms,
-        map(second, |x| vec![x]),
-        map(hour, |x| vec![x]),
-        map(year, |x| vec![x]),
-        map(month, |x| vec![x]),
-        map(day, |x| vec![x]),
-        map(word, |x| vec![x]),
-    ))).parse(input)
+// [h], [mm], [ss]
+fn elapsed(content: &str) -> Option<Item> {
+    let first = content.chars().next()?.to_ascii_lowercase();
+    if !content.chars().all(|c| c.eq_ignore_ascii_case(&first)) {
+        return None;
+    }
+    let long = content.len() > 1;
+    match first {
+        'h' => Some(Item::ElapsedHour { long }),
+        'm' => Some(Item::ElapsedMinute { long }),
+        's' => Some(Item::ElapsedSecond { long }),
+        _ => None,
+    }
 }
 
-fn currency_jp(input: &str) -> IResult<&str, &str> {
-    value("{currency_jp}", tag("[$￥-411]")).parse(input)
+fn bracket_token(content: &str) -> Token {
+    if let Some(rest) = content.strip_prefix('$') {
+        let (currency, locale) = match rest.find('-') {
+            Some(position) => (&rest[..position], &rest[position + 1..]),
+            None => (rest, ""),
+        };
+        return Token::Item(Item::Locale(LocaleTag {
+            currency: currency.to_string(),
+            locale: locale.to_string(),
+        }));
+    }
+    if let Some(condition) = condition(content) {
+        return Token::Condition(condition);
+    }
+    if let Some(item) = elapsed(content) {
+        return Token::Item(item);
+    }
+    match color(content) {
+        Some(color) => Token::Color(color),
+        None => Token::Unknown,
+    }
 }
 
-fn red(input: &str) -> IResult<&str, &str> {
+fn token(input: &str) -> IResult<&str, Token> {
     alt((
-        value("{{red}}", tag("[赤]")),
-        value("{{red}}", tag("[RED]"))
+        value(Token::Item(Item::General), tag_no_case("general")),
+        map(number_item, Token::Item),
+        map(am_pm, Token::Item),
+        map(date_item, Token::Item),
+        map(literal_item, Token::Item),
+        map(delimited(tag("["), take_until("]"), tag("]")), bracket_token),
+        value(Token::Separator, tag(";")),
+        map(anychar, |c| Token::Item(Item::Literal(c.to_string()))),
     )).parse(input)
 }
 
-fn black(input: &str) -> IResult<&str, &str> {
-    alt((
-        value("{{balack}}", tag("[黒]")),
-        value("{{balack}}", tag("[BLACK]"))
-    )).parse(input)
+// m after an hour or before a second is a minute: h:mm, mm:ss
+fn resolve_minutes(items: &mut [Item]) {
+    let dates: Vec<usize> = (0..items.len()).filter(|i| items[*i].is_date()).collect();
+    for (n, i) in dates.iter().enumerate() {
+        let count = match items[*i] {
+            Item::Month(count) if count <= 2 => count,
+            _ => continue,
+        };
+        let after_hour = n > 0 && matches!(items[dates[n - 1]], Item::Hour { .. } | Item::ElapsedHour { .. });
+        let before_second = dates.get(n + 1).is_some_and(|j| matches!(items[*j], Item::Second { .. }));
+        if after_hour || before_second {
+            items[*i] = Item::Minute { long: count == 2 };
+        }
+    }
 }
 
-fn color(input: &str) -> IResult<&str, &str> {
-    alt((
-        red,
-        black
-    )).parse(input)
+// .000 after the seconds is a fraction of a second, not a decimal point
+fn resolve_second_fractions(items: &mut Vec<Item>) {
+    let mut i = 1;
+    while i < items.len() {
+        if items[i] == Item::DecimalPoint && matches!(items[i - 1], Item::Second { .. } | Item::ElapsedSecond { .. }) {
+            let places = items[i + 1..].iter().take_while(|item| **item == Item::Digit(Placeholder::Zero)).count();
+            if places > 0 {
+                items.splice(i..i + 1 + places, [Item::SecondFraction(places.min(3))]);
+            }
+        }
+        i += 1;
+    }
+}
+
+fn sections(content: &str) -> Vec<Section> {
+    let tokens = match many0(token).parse(content) {
+        IResult::Ok((_, tokens)) => tokens,
+        _ => vec![],
+    };
+    let mut sections = vec![Section::default()];
+    for token in tokens {
+        let section = sections.last_mut().unwrap();
+        match token {
+            Token::Item(item) => section.items.push(item),
+            Token::Color(color) => section.color = Some(color),
+            Token::Condition(condition) => section.condition = Some(condition),
+            Token::Unknown => (),
+            Token::Separator => sections.push(Section::default()),
+        }
+    }
+    for section in sections.iter_mut() {
+        resolve_minutes(&mut section.items);
+        resolve_second_fractions(&mut section.items);
+    }
+    if sections[0].items.is_empty() {
+        sections[0].items.push(Item::General);
+    }
+    sections
 }
 
-fn number(input: &str) -> IResult<&str, &str> {
-    take_while1(|c: char| c.is_ascii()).parse(input)
+fn is_digit(item: &Item) -> bool {
+    matches!(item, Item::Digit(_))
 }
 
-fn numeric(input: &str) -> IResult<&str, Vec<&str>> {
-    map((color, many0(
-        alt((
-            word,
-            currency_jp
-        ))
-    ), number, many0(
-        alt((
-            word,
-            currency_jp
-        ))
-    )), |(color, word1, number, word2)| {
-        let mut res = vec![];
+fn format_section(items: &[Item], value: f64, locale: &Locale) -> String {
+    if items.contains(&Item::General)
+        || (items.contains(&Item::Text) && !items.iter().any(is_digit)) {
+        return items.iter().map(|item| match item {
+            Item::General | Item::Text => general(value).replace('.', &locale.decimal_separator.to_string()),
+            item => item.literal(),
+        }).collect();
+    }
+    if !items.iter().any(is_digit) {
+        return items.iter().map(Item::literal).collect();
+    }
+    let mut value = value;
+    for _ in items.iter().filter(|item| **item == Item::Percent) {
+        value *= 100.0;
+    }
+    // a comma between digit placeholders groups thousands,
+    // one after the last of them scales the number by 1000
+    let mut grouping = false;
+    for (i, item) in items.iter().enumerate() {
+        if *item != Item::Thousands || items[..i].contains(&Item::DecimalPoint) {
+            continue;
+        }
+        let before = items[..i].iter().any(is_digit);
+        let after = items[i + 1..].iter()
+            .take_while(|item| !matches!(item, Item::DecimalPoint | Item::Exponent { .. } | Item::Slash))
+            .any(is_digit);
+        if before && after {
+            grouping = true;
+        } else if before {
+            value /= 1000.0;
+        }
+    }
+    if let Some(position) = items.iter().position(|item| matches!(item, Item::Exponent { .. })) {
+        format_scientific(items, position, value, locale.decimal_separator)
+    } else if let Some(position) = items.iter().position(|item| *item == Item::Slash)
+        .filter(|position| items[..*position].iter().any(is_digit)) {
+        format_fraction(items, position, value)
+    } else {
+        format_fixed(items, value, locale.decimal_separator, grouping.then_some(locale.grouping_separator))
+    }
+}
 
-        res.push(color);
+fn format_fixed(items: &[Item], value: f64, decimal: char, grouping: Option<char>) -> String {
+    match items.iter().position(|item| *item == Item::DecimalPoint) {
+        Some(point) => {
+            let places = items[point + 1..].iter().filter(|item| is_digit(item)).count();
+            let (integer, fraction) = round_digits(value, places);
+            format!("{}{}{}", format_integer(&items[..point], &integer, grouping), decimal,
+                format_decimals(&items[point + 1..], &fraction))
+        },
+        None => format_integer(items, &round_digits(value, 0).0, grouping),
+    }
+}
 
-        for item in word1 {
-            res.push(item);
+// place the digits right to left, the leftmost placeholder takes all that remain
+fn format_integer(items: &[Item], digits: &str, grouping: Option<char>) -> String {
+    let places = items.iter().filter(|item| is_digit(item)).count();
+    let digits: Vec<char> = digits.chars().collect();
+    if places == 0 {
+        let text: String = items.iter().map(Item::literal).collect();
+        return format!("{}{}", digits.iter().collect::<String>(), text);
+    }
+    let mut remaining = digits.len();
+    let mut position = 0;
+    let mut seen = 0;
+    let mut reversed: Vec<char> = vec![];
+    let mut push_digit = |reversed: &mut Vec<char>, c: char| {
+        if let Some(separator) = grouping.filter(|_| position > 0 && position % 3 == 0) {
+            reversed.push(separator);
         }
+        reversed.push(c);
+        position += 1;
+    };
+    for item in items.iter().rev() {
+        match item {
+            Item::Digit(placeholder) => {
+                seen += 1;
+                let count = if seen == places { remaining } else { remaining.min(1) };
+                if count == 0 {
+                    match placeholder {
+                        Placeholder::Zero => push_digit(&mut reversed, '0'),
+                        Placeholder::Question => reversed.push(' '),
+                        Placeholder::Hash => (),
+                    }
+                }
+                for _ in 0..count {
+                    remaining -= 1;
+                    push_digit(&mut reversed, digits[remaining]);
+                }
+            },
+            item => reversed.extend(item.literal().chars().rev()),
+        }
+    }
+    reversed.iter().rev().collect()
+}
+
+// trailing zeros are dropped for # and blanked for ?
+fn format_decimals(items: &[Item], digits: &str) -> String {
+    let digits: Vec<char> = digits.chars().collect();
+    let last = digits.iter().rposition(|c| *c != '0');
+    let mut index = 0;
+    let mut result = String::new();
+    for item in items {
+        match item {
+            Item::Digit(placeholder) => {
+                if last.is_some_and(|last| index <= last) {
+                    result.push(digits[index]);
+                } else {
+                    match placeholder {
+                        Placeholder::Zero => result.push('0'),
+                        Placeholder::Question => result.push(' '),
+                        Placeholder::Hash => (),
+                    }
+                }
+                index += 1;
+            },
+            item => result.push_str(&item.literal()),
+        }
+    }
+    result
+}
 
-        res.push(number);
+fn format_scientific(items: &[Item], position: usize, value: f64, decimal: char) -> String {
+    let mantissa_items = &items[..position];
+    let point = mantissa_items.iter().position(|item| *item == Item::DecimalPoint)
+        .unwrap_or(mantissa_items.len());
+    let integer_places = mantissa_items[..point].iter().filter(|item| is_digit(item)).count().max(1) as i32;
+    let decimal_places = mantissa_items[point..].iter().filter(|item| is_digit(item)).count();
+    // ##0.0E+0 keeps the exponent a multiple of 3, 00.0E+0 shows two integer digits
+    let step = if integer_places > 1 && mantissa_items[..point].contains(&Item::Digit(Placeholder::Hash)) {
+        integer_places
+    } else {
+        1
+    };
+    let align = |exponent: i32| if step > 1 {
+        exponent.div_euclid(step) * step
+    } else {
+        exponent - integer_places + 1
+    };
+    let mut exponent = if value == 0.0 { 0 } else { align(decimal_exponent(value)) };
+    let width = if step > 1 { step } else { integer_places } as usize;
+    if value != 0.0 && round_digits(value / 10f64.powi(exponent), decimal_places).0.len() > width {
+        exponent = align(decimal_exponent(value) + 1);
+    }
+    let sign = match items[position] {
+        Item::Exponent { .. } if exponent < 0 => "-",
+        Item::Exponent { plus: true } => "+",
+        _ => "",
+    };
+    format!("{}E{}{}",
+        format_fixed(mantissa_items, value / 10f64.powi(exponent), decimal, None),
+        sign,
+        format_integer(&items[position + 1..], &exponent.abs().to_string(), None))
+}
 
-        for item in word2 {
-            res.push(item);
+fn fixed_digit(item: &Item) -> Option<char> {
+    match item {
+        Item::Literal(text) if text.len() == 1 && text.chars().all(|c| c.is_ascii_digit()) => text.chars().next(),
+        _ => None,
+    }
+}
+
+fn format_fraction(items: &[Item], slash: usize, value: f64) -> String {
+    let numerator_start = items[..slash].iter().rposition(|item| !is_digit(item)).map_or(0, |i| i + 1);
+    let denominator_end = items[slash + 1..].iter()
+        .position(|item| !is_digit(item) && fixed_digit(item).is_none())
+        .map_or(items.len(), |i| slash + 1 + i);
+    let integer_items = &items[..numerator_start];
+    let numerator_items = &items[numerator_start..slash];
+    let denominator_items = &items[slash + 1..denominator_end];
+    let mixed = integer_items.iter().any(is_digit);
+    let (mut whole, part) = if mixed { (value.trunc(), value.fract()) } else { (0.0, value) };
+    // a denominator starting with 1-9 is fixed, the zeros of ?/10 are no placeholders
+    let fixed = denominator_items.first().and_then(fixed_digit)
+        .and_then(|_| denominator_items.iter().map(|item| match item {
+            Item::Digit(Placeholder::Zero) => Some('0'),
+            item => fixed_digit(item),
+        }).collect::<Option<String>>())
+        .and_then(|text| text.parse::<u64>().ok())
+        .filter(|denominator| *denominator > 0);
+    // Excel tries denominators of up to five digits
+    let (mut numerator, denominator) = match fixed {
+        Some(denominator) => ((part * denominator as f64).round() as u64, denominator),
+        None => best_fraction(part, 10u64.pow(denominator_items.len().min(5) as u32) - 1),
+    };
+    if mixed && numerator == denominator {
+        whole += 1.0;
+        numerator = 0;
+    }
+    let mut result = String::new();
+    if mixed {
+        let digits = if whole == 0.0 && numerator != 0 { String::new() } else { round_digits(whole, 0).0 };
+        let digits = if digits.is_empty() && numerator == 0 { String::from("0") } else { digits };
+        result.push_str(&format_integer(integer_items, &digits, None));
+    }
+    if mixed && numerator == 0 {
+        // a whole number leaves the fraction blank
+        let width = numerator_items.len() + 1 + denominator_items.len();
+        result.push_str(&" ".repeat(width));
+    } else {
+        result.push_str(&format_integer(numerator_items, &numerator.to_string(), None));
+        result.push('/');
+        if fixed.is_some() {
+            result.push_str(&denominator.to_string());
+        } else {
+            let text = denominator.to_string();
+            let blanks = denominator_items.iter().skip(text.len())
+                .filter(|item| **item == Item::Digit(Placeholder::Question)).count();
+            result.push_str(&text);
+            result.push_str(&" ".repeat(blanks));
         }
+    }
+    for item in &items[denominator_end..] {
+        result.push_str(&item.literal());
+    }
+    result
+}
 
-        res
-    }).parse(input)
+// closest fraction with a denominator up to max_denominator, the smallest one on a tie
+fn best_fraction(value: f64, max_denominator: u64) -> (u64, u64) {
+    let mut best = ((value.round()) as u64, 1);
+    let mut best_error = (value - value.round()).abs();
+    for denominator in 2..=max_denominator.max(1) {
+        let numerator = (value * denominator as f64).round();
+        let error = (value - numerator / denominator as f64).abs();
+        if error < best_error - 1e-12 {
+            best = (numerator as u64, denominator);
+            best_error = error;
+        }
+    }
+    best
 }
 
-fn numeric_ary(input: &str) -> IResult<&str, Vec<Vec<&str>>> {
-    many_m_n(1, 4, map(
-        (tag(";"), numeric), 
-        |(_, res)| res)).parse(input)
-}
\ No newline at end of file
+fn decimal_exponent(value: f64) -> i32 {
+    let text = format!("{:.14e}", value);
+    text[text.find('e').unwrap() + 1..].parse().unwrap_or(0)
+}
+
+/// Integer and decimal digits of a non-negative number rounded half away
+/// from zero, after cutting it to the 15 significant digits Excel keeps.
+/// The integer digits are empty for zero.
+fn round_digits(value: f64, decimals: usize) -> (String, String) {
+    let text = format!("{:.14e}", value);
+    let (mantissa, exponent) = text.split_at(text.find('e').unwrap());
+    let exponent: i64 = exponent[1..].parse().unwrap_or(0);
+    let mut digits: Vec<u8> = mantissa.bytes().filter(|c| c.is_ascii_digit()).map(|c| c - b'0').collect();
+    // make the point sit after `point` digits, with at least one integer digit
+    let mut point = exponent + 1;
+    if point < 1 {
+        let mut zeros = vec![0; (1 - point) as usize];
+        zeros.extend(digits);
+        digits = zeros;
+        point = 1;
+    }
+    let point = point as usize;
+    let keep = point + decimals;
+    if digits.len() < keep + 1 {
+        digits.resize(keep + 1, 0);
+    }
+    let round_up = digits[keep] >= 5;
+    digits.truncate(keep);
+    let mut point = point;
+    if round_up {
+        let mut i = keep;
+        loop {
+            if i == 0 {
+                digits.insert(0, 1);
+                point += 1;
+                break;
+            }
+            i -= 1;
+            if digits[i] == 9 {
+                digits[i] = 0;
+            } else {
+                digits[i] += 1;
+                break;
+            }
+        }
+    }
+    let to_text = |digits: &[u8]| digits.iter().map(|d| (b'0' + d) as char).collect::<String>();
+    let integer = to_text(&digits[..point]);
+    (integer.trim_start_matches('0').to_string(), to_text(&digits[point..]))
+}
+
+// General shows up to 11 characters, switching to 0.#####E+00 when that loses the number
+fn general(value: f64) -> String {
+    if value == 0.0 {
+        return String::from("0");
+    }
+    let exponent = decimal_exponent(value);
+    if exponent < 11 {
+        let decimals = if exponent < 0 { 9 } else { (9 - exponent).max(0) as usize };
+        let (integer, fraction) = round_digits(value, decimals);
+        let fraction = fraction.trim_end_matches('0');
+        let integer = if integer.is_empty() { "0" } else { integer.as_str() };
+        let text = if fraction.is_empty() { integer.to_string() } else { format!("{}.{}", integer, fraction) };
+        if exponent > -5 || text.parse::<f64>().is_ok_and(|shown| shown == value) {
+            return text;
+        }
+    }
+    let (integer, fraction) = round_digits(value / 10f64.powi(exponent), 5);
+    let (integer, exponent) = if integer.len() > 1 { (String::from("1"), exponent + 1) } else { (integer, exponent) };
+    let fraction = fraction.trim_end_matches('0');
+    format!("{}{}E{}{:02}", integer, if fraction.is_empty() { String::new() } else { format!(".{}", fraction) },
+        if exponent < 0 { "-" } else { "+" }, exponent.abs())
+}
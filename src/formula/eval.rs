@@ -0,0 +1,403 @@
+//! Dependency graph over a book and evaluation of its formulas
+use super::functions::{self, duration_to_serial, serial_to_duration, serial_to_time, time_to_serial};
+use super::parser::{parse, BinaryOp, Expr};
+use super::syntax::{CellRef, Reference, Span};
+use crate::{Book, DateSystem, Error, ErrorKind, Value};
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet, VecDeque};
+
+/// Sheet index, row index and column index of a cell.
+type Key = (usize, usize, usize);
+
+/// Value of an expression while a formula is calculated.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Val {
+    Number(f64),
+    Text(String),
+    Bool(bool),
+    Error(ErrorKind),
+    Empty,
+}
+
+/// Function argument: references keep their shape so that SUM can skip text
+/// in a range and VLOOKUP can look at its columns.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Arg {
+    Value(Val),
+    Range(Vec<Vec<Val>>),
+}
+
+impl Val {
+    pub(crate) fn to_number(&self) -> Result<f64, ErrorKind> {
+        match self {
+            Val::Number(n) => Ok(*n),
+            Val::Bool(b) => Ok(if *b { 1.0 } else { 0.0 }),
+            Val::Empty => Ok(0.0),
+            // "inf" and "NaN" parse, but are no numbers to a spreadsheet
+            Val::Text(text) => text.trim().parse::<f64>().ok().filter(|n| n.is_finite()).ok_or(ErrorKind::Value),
+            Val::Error(kind) => Err(*kind),
+        }
+    }
+
+    pub(crate) fn to_text(&self) -> Result<String, ErrorKind> {
+        match self {
+            Val::Number(n) => Ok(number_to_text(*n)),
+            Val::Bool(b) => Ok(String::from(if *b { "TRUE" } else { "FALSE" })),
+            Val::Empty => Ok(String::new()),
+            Val::Text(text) => Ok(text.clone()),
+            Val::Error(kind) => Err(*kind),
+        }
+    }
+
+    pub(crate) fn to_bool(&self) -> Result<bool, ErrorKind> {
+        match self {
+            Val::Number(n) => Ok(*n != 0.0),
+            Val::Bool(b) => Ok(*b),
+            Val::Empty => Ok(false),
+            Val::Text(text) if text.eq_ignore_ascii_case("TRUE") => Ok(true),
+            Val::Text(text) if text.eq_ignore_ascii_case("FALSE") => Ok(false),
+            Val::Text(_) => Err(ErrorKind::Value),
+            Val::Error(kind) => Err(*kind),
+        }
+    }
+}
+
+impl Arg {
+    /// Single value; a reference to one cell gives that cell's value.
+    pub(crate) fn value(self) -> Val {
+        match self {
+            Arg::Value(val) => val,
+            Arg::Range(mut rows) => {
+                if rows.len() == 1 && rows[0].len() == 1 {
+                    rows.remove(0).remove(0)
+                } else {
+                    Val::Error(ErrorKind::Value)
+                }
+            },
+        }
+    }
+}
+
+// numbers are shown with up to 15 significant digits, as Excel's General format
+pub(crate) fn number_to_text(n: f64) -> String {
+    if n == 0.0 {
+        return String::from("0");
+    }
+    let digits = 14 - n.abs().log10().floor() as i32;
+    let text = if digits > 0 {
+        format!("{:.*}", digits.min(30) as usize, n)
+    } else {
+        let scale = 10f64.powi(-digits);
+        format!("{}", (n / scale).round() * scale)
+    };
+    if text.contains('.') {
+        text.trim_end_matches('0').trim_end_matches('.').to_string()
+    } else {
+        text
+    }
+}
+
+/// Excel's ordering: numbers < text < booleans, text ignores case.
+pub(crate) fn compare(left: &Val, right: &Val) -> Result<Ordering, ErrorKind> {
+    fn rank(val: &Val) -> u8 {
+        match val {
+            Val::Number(_) | Val::Empty => 0,
+            Val::Text(_) => 1,
+            _ => 2,
+        }
+    }
+    // an empty cell is 0, "" or FALSE depending on what it is compared with
+    let (left, right) = match (left, right) {
+        (Val::Error(kind), _) | (_, Val::Error(kind)) => return Err(*kind),
+        (Val::Empty, Val::Text(_)) => (Val::Text(String::new()), right.clone()),
+        (Val::Text(_), Val::Empty) => (left.clone(), Val::Text(String::new())),
+        (Val::Empty, Val::Bool(_)) => (Val::Bool(false), right.clone()),
+        (Val::Bool(_), Val::Empty) => (left.clone(), Val::Bool(false)),
+        _ => (left.clone(), right.clone()),
+    };
+    Ok(match (&left, &right) {
+        (Val::Text(l), Val::Text(r)) => l.to_lowercase().cmp(&r.to_lowercase()),
+        (Val::Bool(l), Val::Bool(r)) => l.cmp(r),
+        _ if rank(&left) == 0 && rank(&right) == 0 => {
+            left.to_number()?.partial_cmp(&right.to_number()?).unwrap_or(Ordering::Equal)
+        },
+        _ => rank(&left).cmp(&rank(&right)),
+    })
+}
+
+struct Evaluator<'a> {
+    book: &'a Book,
+    sheets: &'a HashMap<String, usize>,
+    computed: &'a HashMap<Key, Val>,
+    sheet: usize,
+    date_system: DateSystem,
+}
+
+impl<'a> Evaluator<'a> {
+    fn cell(&self, key: Key) -> Val {
+        if let Some(val) = self.computed.get(&key) {
+            return val.clone();
+        }
+        match self.book.sheets[key.0].get_cell(key.1, key.2).map(|cell| cell.get_value()) {
+            None => Val::Empty,
+            Some(Value::Str(text)) => Val::Text(text.clone()),
+            Some(Value::Float(n)) | Some(Value::Currency(n, _)) | Some(Value::Percentage(n)) => Val::Number(*n),
+            Some(Value::Bool(b)) => Val::Bool(*b),
+            Some(Value::Error(kind)) => Val::Error(*kind),
+            Some(Value::Date(dt)) => Val::Number(self.date_system.date_to_serial(dt)),
+            Some(Value::Time(time)) => Val::Number(time_to_serial(time)),
+            Some(Value::Duration(duration)) => Val::Number(duration_to_serial(duration)),
+        }
+    }
+
+    fn range(&self, reference: &Reference) -> Result<Vec<Vec<Val>>, ErrorKind> {
+        let sheet = resolve_sheet(self.sheets, reference, self.sheet).ok_or(ErrorKind::Ref)?;
+        let (top, left, mut bottom, mut right) = bounds(reference);
+        // whole columns and rows end with the used part of the sheet
+        if reference.span != Span::Cells {
+            let (last_row, last_column) = self.book.sheets[sheet].get_max_index().unwrap_or((0, 0));
+            bottom = bottom.min(last_row);
+            right = right.min(last_column);
+        }
+        Ok((top..=bottom).map(|row| {
+            (left..=right).map(|column| self.cell((sheet, row, column))).collect()
+        }).collect())
+    }
+
+    fn arg(&self, expr: &Expr) -> Arg {
+        match expr {
+            Expr::Ref(reference) => match self.range(reference) {
+                Ok(rows) => Arg::Range(rows),
+                Err(kind) => Arg::Value(Val::Error(kind)),
+            },
+            other => Arg::Value(self.eval(other)),
+        }
+    }
+
+    fn eval(&self, expr: &Expr) -> Val {
+        match expr {
+            Expr::Number(n) => Val::Number(*n),
+            Expr::Text(text) => Val::Text(text.clone()),
+            Expr::Bool(b) => Val::Bool(*b),
+            Expr::Error(kind) => Val::Error(*kind),
+            Expr::Missing => Val::Empty,
+            Expr::Ref(_) => self.arg(expr).value(),
+            Expr::Negate(inner) => match self.eval(inner).to_number() {
+                Ok(n) => Val::Number(-n),
+                Err(kind) => Val::Error(kind),
+            },
+            Expr::Percent(inner) => match self.eval(inner).to_number() {
+                Ok(n) => Val::Number(n / 100.0),
+                Err(kind) => Val::Error(kind),
+            },
+            Expr::Binary(op, left, right) => {
+                let left = self.eval(left);
+                let right = self.eval(right);
+                binary(*op, &left, &right).unwrap_or_else(Val::Error)
+            },
+            Expr::Call(name, args) => self.call(name.trim_start_matches("_XLFN."), args),
+        }
+    }
+
+    // IF and IFERROR only evaluate the branch they return
+    fn call(&self, name: &str, args: &[Expr]) -> Val {
+        match name {
+            "IF" => {
+                if args.is_empty() || args.len() > 3 {
+                    return Val::Error(ErrorKind::Value);
+                }
+                match self.eval(&args[0]).to_bool() {
+                    Ok(true) => args.get(1).map(|e| self.eval(e)).unwrap_or(Val::Bool(true)),
+                    Ok(false) => args.get(2).map(|e| self.eval(e)).unwrap_or(Val::Bool(false)),
+                    Err(kind) => Val::Error(kind),
+                }
+            },
+            "IFERROR" => {
+                if args.len() != 2 {
+                    return Val::Error(ErrorKind::Value);
+                }
+                match self.eval(&args[0]) {
+                    Val::Error(_) => self.eval(&args[1]),
+                    val => val,
+                }
+            },
+            _ => functions::call(name, args.iter().map(|arg| self.arg(arg)).collect(), self.date_system),
+        }
+    }
+}
+
+fn binary(op: BinaryOp, left: &Val, right: &Val) -> Result<Val, ErrorKind> {
+    let result = match op {
+        BinaryOp::Concat => return Ok(Val::Text(left.to_text()? + &right.to_text()?)),
+        BinaryOp::Eq => return Ok(Val::Bool(compare(left, right)? == Ordering::Equal)),
+        BinaryOp::Ne => return Ok(Val::Bool(compare(left, right)? != Ordering::Equal)),
+        BinaryOp::Lt => return Ok(Val::Bool(compare(left, right)? == Ordering::Less)),
+        BinaryOp::Le => return Ok(Val::Bool(compare(left, right)? != Ordering::Greater)),
+        BinaryOp::Gt => return Ok(Val::Bool(compare(left, right)? == Ordering::Greater)),
+        BinaryOp::Ge => return Ok(Val::Bool(compare(left, right)? != Ordering::Less)),
+        BinaryOp::Add => left.to_number()? + right.to_number()?,
+        BinaryOp::Sub => left.to_number()? - right.to_number()?,
+        BinaryOp::Mul => left.to_number()? * right.to_number()?,
+        BinaryOp::Div => {
+            let divisor = right.to_number()?;
+            let dividend = left.to_number()?;
+            if divisor == 0.0 {
+                return Err(ErrorKind::Div0);
+            }
+            dividend / divisor
+        },
+        BinaryOp::Pow => left.to_number()?.powf(right.to_number()?),
+    };
+    functions::number(result)
+}
+
+fn resolve_sheet(sheets: &HashMap<String, usize>, reference: &Reference, current: usize) -> Option<usize> {
+    match reference.sheet {
+        Some(ref name) => sheets.get(&name.to_lowercase()).cloned(),
+        None => Some(current),
+    }
+}
+
+// (top, left, bottom, right) of a reference, in either corner order
+fn bounds(reference: &Reference) -> (usize, usize, usize, usize) {
+    let end = reference.end.as_ref().unwrap_or(&reference.start);
+    (reference.start.row.min(end.row), reference.start.column.min(end.column),
+     reference.start.row.max(end.row), reference.start.column.max(end.column))
+}
+
+fn collect_references<'e>(expr: &'e Expr, references: &mut Vec<&'e Reference>) {
+    match expr {
+        Expr::Ref(reference) => references.push(reference),
+        Expr::Negate(inner) | Expr::Percent(inner) => collect_references(inner, references),
+        Expr::Binary(_, left, right) => {
+            collect_references(left, references);
+            collect_references(right, references);
+        },
+        Expr::Call(_, args) => {
+            for arg in args {
+                collect_references(arg, references);
+            }
+        },
+        _ => (),
+    }
+}
+
+fn cell_name(book: &Book, key: Key) -> (String, String) {
+    let cell = CellRef { column: key.2, row: key.1, column_absolute: false, row_absolute: false };
+    (book.sheets[key.0].get_name().clone(), cell.to_a1())
+}
+
+// formula results keep the kind of the cell: a date stays a date
+fn to_value(val: Val, previous: &Value, date_system: DateSystem) -> Value {
+    match val {
+        Val::Number(n) => match previous {
+            Value::Date(_) => date_system.serial_to_date(n).map(Value::Date).unwrap_or(Value::Error(ErrorKind::Num)),
+            Value::Currency(_, code) => Value::Currency(n, code.clone()),
+            Value::Percentage(_) => Value::Percentage(n),
+            // a time past midnight becomes a duration
+            Value::Time(_) => serial_to_time(n).map(Value::Time).unwrap_or_else(|| Value::Duration(serial_to_duration(n))),
+            Value::Duration(_) => Value::Duration(serial_to_duration(n)),
+            _ => Value::Float(n),
+        },
+        Val::Text(text) => Value::Str(text),
+        Val::Bool(b) => Value::Bool(b),
+        Val::Error(kind) => Value::Error(kind),
+        Val::Empty => Value::Float(0.0),
+    }
+}
+
+/// Calculate every formula of the book in dependency order and store the
+/// results as the cached values. Nothing is changed when an error is returned.
+pub(crate) fn recalculate(book: &mut Book) -> Result<(), Error> {
+    let sheets: HashMap<String, usize> = book.sheets.iter().enumerate()
+        .map(|(index, sheet)| (sheet.get_name().to_lowercase(), index))
+        .collect();
+
+    let mut formulas: Vec<(Key, Expr)> = vec![];
+    for (sheet_index, sheet) in book.sheets.iter().enumerate() {
+        for (row, cells) in &sheet.rows {
+            for (column, cell) in cells {
+                if let Some(formula) = cell.get_formula() {
+                    let key = (sheet_index, *row, *column);
+                    let expr = parse(formula).map_err(|message| {
+                        let (sheet, cell) = cell_name(book, key);
+                        Error::Formula { sheet, cell, message }
+                    })?;
+                    formulas.push((key, expr));
+                }
+            }
+        }
+    }
+    formulas.sort_by_key(|(key, _)| *key);
+    let index: HashMap<Key, usize> = formulas.iter().enumerate().map(|(i, (key, _))| (*key, i)).collect();
+
+    // edges from each formula to the formulas it reads
+    let mut depends_on: Vec<HashSet<usize>> = vec![HashSet::new(); formulas.len()];
+    for (i, (key, expr)) in formulas.iter().enumerate() {
+        let mut references = vec![];
+        collect_references(expr, &mut references);
+        for reference in references {
+            let sheet = match resolve_sheet(&sheets, reference, key.0) {
+                Some(sheet) => sheet,
+                None => continue,
+            };
+            let (top, left, bottom, right) = bounds(reference);
+            if reference.end.is_none() {
+                if let Some(j) = index.get(&(sheet, top, left)) {
+                    depends_on[i].insert(*j);
+                }
+                continue;
+            }
+            for (j, (other, _)) in formulas.iter().enumerate() {
+                if other.0 == sheet && (top..=bottom).contains(&other.1) && (left..=right).contains(&other.2) {
+                    depends_on[i].insert(j);
+                }
+            }
+        }
+    }
+
+    // Kahn's algorithm; what is left over is in or behind a cycle
+    let mut waiting: Vec<usize> = depends_on.iter().map(|deps| deps.len()).collect();
+    let mut dependents: Vec<Vec<usize>> = vec![vec![]; formulas.len()];
+    for (i, deps) in depends_on.iter().enumerate() {
+        for j in deps {
+            dependents[*j].push(i);
+        }
+    }
+    let mut queue: VecDeque<usize> = (0..formulas.len()).filter(|i| waiting[*i] == 0).collect();
+    let mut order = vec![];
+    while let Some(i) = queue.pop_front() {
+        order.push(i);
+        for j in &dependents[i] {
+            waiting[*j] -= 1;
+            if waiting[*j] == 0 {
+                queue.push_back(*j);
+            }
+        }
+    }
+    if order.len() < formulas.len() {
+        // walk the unresolved dependencies until a formula repeats
+        let mut current = (0..formulas.len()).find(|i| waiting[*i] > 0).unwrap();
+        let mut seen = HashSet::new();
+        while seen.insert(current) {
+            current = *depends_on[current].iter().filter(|j| waiting[**j] > 0).min().unwrap();
+        }
+        let (sheet, cell) = cell_name(book, formulas[current].0);
+        return Err(Error::CircularReference { sheet, cell });
+    }
+
+    let date_system = book.get_date_system();
+    let mut computed: HashMap<Key, Val> = HashMap::new();
+    for i in order {
+        let (key, ref expr) = formulas[i];
+        let val = Evaluator { book, sheets: &sheets, computed: &computed, sheet: key.0, date_system }.eval(expr);
+        computed.insert(key, val);
+    }
+
+    for ((sheet, row, column), val) in computed {
+        if let Some(cell) = book.sheets[sheet].rows.get_mut(&row).and_then(|cells| cells.get_mut(&column)) {
+            cell.value = to_value(val, &cell.value, date_system);
+        }
+    }
+    Ok(())
+}
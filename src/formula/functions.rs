@@ -0,0 +1,500 @@
+//! Built-in spreadsheet functions
+use super::eval::{compare, number_to_text, Arg, Val};
+use crate::format::Format;
+use crate::{DateSystem, ErrorKind};
+use chrono::prelude::*;
+use chrono::Duration;
+use std::cmp::Ordering;
+
+type Result<T> = std::result::Result<T, ErrorKind>;
+
+/// Call the function `name` (upper case) with evaluated arguments, dates are
+/// serial numbers of `system`. Unknown functions give #NAME? as in Excel.
+pub(crate) fn call(name: &str, args: Vec<Arg>, system: DateSystem) -> Val {
+    let result = match name {
+        "SUM" => numbers(&args).and_then(|ns| number(ns.iter().sum())),
+        "PRODUCT" => numbers(&args).and_then(|ns| number(ns.iter().product())),
+        "AVERAGE" => numbers(&args).and_then(|ns| {
+            if ns.is_empty() {
+                Err(ErrorKind::Div0)
+            } else {
+                number(ns.iter().sum::<f64>() / ns.len() as f64)
+            }
+        }),
+        "MIN" => numbers(&args).map(|ns| extreme(&ns, f64::min)),
+        "MAX" => numbers(&args).map(|ns| extreme(&ns, f64::max)),
+        "COUNT" => Ok(count(&args,
+            |val| matches!(val, Val::Number(_)),
+            |val| *val != Val::Empty && val.to_number().is_ok())),
+        "COUNTA" => Ok(count(&args, |val| *val != Val::Empty, |_| true)),
+        "COUNTBLANK" => Ok(count(&args, |val| *val == Val::Empty || *val == Val::Text(String::new()), |_| false)),
+        "SUMIF" => sum_if(args),
+        "COUNTIF" => count_if(args),
+        "AND" => logical(&args, true),
+        "OR" => logical(&args, false),
+        "NOT" => arity(&args, 1, 1).and_then(|_| Ok(Val::Bool(!scalar(&args, 0).to_bool()?))),
+        "TRUE" => arity(&args, 0, 0).map(|_| Val::Bool(true)),
+        "FALSE" => arity(&args, 0, 0).map(|_| Val::Bool(false)),
+        "ROUND" => round(&args, f64::round),
+        "ROUNDUP" => round(&args, |n| n.signum() * n.abs().ceil()),
+        "ROUNDDOWN" => round(&args, f64::trunc),
+        "INT" => arity(&args, 1, 1).and_then(|_| number(num(&args, 0)?.floor())),
+        "ABS" => arity(&args, 1, 1).and_then(|_| number(num(&args, 0)?.abs())),
+        "SQRT" => arity(&args, 1, 1).and_then(|_| number(num(&args, 0)?.sqrt())),
+        "POWER" => arity(&args, 2, 2).and_then(|_| number(num(&args, 0)?.powf(num(&args, 1)?))),
+        "MOD" => arity(&args, 2, 2).and_then(|_| {
+            let (n, d) = (num(&args, 0)?, num(&args, 1)?);
+            if d == 0.0 {
+                Err(ErrorKind::Div0)
+            } else {
+                number(n - d * (n / d).floor())
+            }
+        }),
+        "VLOOKUP" => lookup(args, true),
+        "HLOOKUP" => lookup(args, false),
+        "INDEX" => index(args),
+        "MATCH" => match_position(args),
+        "DATE" => date(&args, system),
+        "YEAR" => date_part(&args, system, |dt| dt.year() as f64),
+        "MONTH" => date_part(&args, system, |dt| dt.month() as f64),
+        "DAY" => date_part(&args, system, |dt| dt.day() as f64),
+        "TODAY" => arity(&args, 0, 0).map(|_| Val::Number(system.date_to_serial(&Local::now().naive_local()).floor())),
+        "NOW" => arity(&args, 0, 0).map(|_| Val::Number(system.date_to_serial(&Local::now().naive_local()))),
+        "TEXT" => arity(&args, 2, 2).and_then(|_| Ok(Val::Text(text_format(finite(num(&args, 0)?)?, &text(&args, 1)?, system)))),
+        "VALUE" => arity(&args, 1, 1).and_then(|_| number(num(&args, 0)?)),
+        "LEN" => arity(&args, 1, 1).and_then(|_| Ok(Val::Number(text(&args, 0)?.chars().count() as f64))),
+        "LEFT" => substring(&args, |text, n| text.chars().take(n).collect()),
+        "RIGHT" => substring(&args, |text, n| {
+            let skip = text.chars().count().saturating_sub(n);
+            text.chars().skip(skip).collect()
+        }),
+        "MID" => arity(&args, 3, 3).and_then(|_| {
+            let (start, length) = (num(&args, 1)?.trunc(), num(&args, 2)?.trunc());
+            if start < 1.0 || length < 0.0 {
+                return Err(ErrorKind::Value);
+            }
+            Ok(Val::Text(text(&args, 0)?.chars().skip(start as usize - 1).take(length as usize).collect()))
+        }),
+        "UPPER" => arity(&args, 1, 1).and_then(|_| Ok(Val::Text(text(&args, 0)?.to_uppercase()))),
+        "LOWER" => arity(&args, 1, 1).and_then(|_| Ok(Val::Text(text(&args, 0)?.to_lowercase()))),
+        "TRIM" => arity(&args, 1, 1).and_then(|_| {
+            Ok(Val::Text(text(&args, 0)?.split(' ').filter(|word| !word.is_empty()).collect::<Vec<_>>().join(" ")))
+        }),
+        "CONCATENATE" | "CONCAT" => concat(&args),
+        "ISBLANK" => arity(&args, 1, 1).map(|_| Val::Bool(scalar(&args, 0) == Val::Empty)),
+        "ISERROR" => arity(&args, 1, 1).map(|_| Val::Bool(matches!(scalar(&args, 0), Val::Error(_)))),
+        "ISNA" => arity(&args, 1, 1).map(|_| Val::Bool(scalar(&args, 0) == Val::Error(ErrorKind::NA))),
+        "ISNUMBER" => arity(&args, 1, 1).map(|_| Val::Bool(matches!(scalar(&args, 0), Val::Number(_)))),
+        "ISTEXT" => arity(&args, 1, 1).map(|_| Val::Bool(matches!(scalar(&args, 0), Val::Text(_)))),
+        "NA" => arity(&args, 0, 0).and(Err(ErrorKind::NA)),
+        _ => Err(ErrorKind::Name),
+    };
+    result.unwrap_or_else(Val::Error)
+}
+
+/// A finite number, or #NUM! for overflows and the like.
+pub(crate) fn number(n: f64) -> Result<Val> {
+    finite(n).map(Val::Number)
+}
+
+fn finite(n: f64) -> Result<f64> {
+    if n.is_finite() {
+        Ok(n)
+    } else {
+        Err(ErrorKind::Num)
+    }
+}
+
+// a time of day is the fraction of the day, 0.5 is noon
+pub(crate) fn time_to_serial(time: &NaiveTime) -> f64 {
+    duration_to_serial(&(*time - NaiveTime::MIN))
+}
+
+pub(crate) fn serial_to_time(serial: f64) -> Option<NaiveTime> {
+    if !(0.0..1.0).contains(&serial) {
+        return None;
+    }
+    Some(NaiveTime::MIN + serial_to_duration(serial))
+}
+
+// a duration counts in days, 1.5 is 36 hours
+pub(crate) fn duration_to_serial(duration: &Duration) -> f64 {
+    duration.num_milliseconds() as f64 / 86_400_000.0
+}
+
+pub(crate) fn serial_to_duration(serial: f64) -> Duration {
+    Duration::milliseconds((serial * 86_400_000.0).round() as i64)
+}
+
+fn arity(args: &[Arg], min: usize, max: usize) -> Result<()> {
+    if args.len() < min || args.len() > max {
+        Err(ErrorKind::Value)
+    } else {
+        Ok(())
+    }
+}
+
+fn scalar(args: &[Arg], index: usize) -> Val {
+    args.get(index).cloned().map(Arg::value).unwrap_or(Val::Empty)
+}
+
+fn num(args: &[Arg], index: usize) -> Result<f64> {
+    scalar(args, index).to_number()
+}
+
+fn text(args: &[Arg], index: usize) -> Result<String> {
+    scalar(args, index).to_text()
+}
+
+// numbers in ranges count, text and booleans there are skipped;
+// values given directly are converted
+fn numbers(args: &[Arg]) -> Result<Vec<f64>> {
+    let mut result = vec![];
+    for arg in args {
+        match arg {
+            Arg::Range(rows) => {
+                for val in rows.iter().flatten() {
+                    match val {
+                        Val::Number(n) => result.push(*n),
+                        Val::Error(kind) => return Err(*kind),
+                        _ => (),
+                    }
+                }
+            },
+            Arg::Value(val) => result.push(val.to_number()?),
+        }
+    }
+    Ok(result)
+}
+
+// `in_range` decides for the cells of references, `direct` for values given directly
+fn count<F, G>(args: &[Arg], in_range: F, direct: G) -> Val
+    where F: Fn(&Val) -> bool, G: Fn(&Val) -> bool
+{
+    let mut result = 0;
+    for arg in args {
+        match arg {
+            Arg::Range(rows) => result += rows.iter().flatten().filter(|val| in_range(val)).count(),
+            Arg::Value(val) if direct(val) => result += 1,
+            Arg::Value(_) => (),
+        }
+    }
+    Val::Number(result as f64)
+}
+
+// MIN / MAX of no numbers is 0
+fn extreme<F: Fn(f64, f64) -> f64>(numbers: &[f64], f: F) -> Val {
+    Val::Number(numbers.iter().cloned().reduce(f).unwrap_or(0.0))
+}
+
+fn logical(args: &[Arg], all: bool) -> Result<Val> {
+    let mut values = vec![];
+    for arg in args {
+        match arg {
+            Arg::Range(rows) => {
+                for val in rows.iter().flatten() {
+                    match val {
+                        Val::Number(_) | Val::Bool(_) => values.push(val.to_bool()?),
+                        Val::Error(kind) => return Err(*kind),
+                        _ => (),
+                    }
+                }
+            },
+            Arg::Value(val) => values.push(val.to_bool()?),
+        }
+    }
+    if values.is_empty() {
+        return Err(ErrorKind::Value);
+    }
+    Ok(Val::Bool(if all { values.iter().all(|b| *b) } else { values.iter().any(|b| *b) }))
+}
+
+fn round<F: Fn(f64) -> f64>(args: &[Arg], f: F) -> Result<Val> {
+    arity(args, 2, 2)?;
+    let (n, digits) = (num(args, 0)?, num(args, 1)?.trunc() as i32);
+    let factor = 10f64.powi(digits);
+    // drop the binary noise first so that ROUND(2.675, 2) is 2.68
+    let scaled = format!("{:.14e}", n * factor).parse::<f64>().unwrap_or(n * factor);
+    number(f(scaled) / factor)
+}
+
+fn range(arg: Option<Arg>) -> Result<Vec<Vec<Val>>> {
+    match arg {
+        Some(Arg::Range(rows)) => Ok(rows),
+        Some(Arg::Value(Val::Error(kind))) => Err(kind),
+        _ => Err(ErrorKind::Value),
+    }
+}
+
+// `?` and `*` wildcards, `~` escapes them
+fn wildcard_match(pattern: &[char], text: &[char]) -> bool {
+    match pattern.split_first() {
+        None => text.is_empty(),
+        Some(('*', rest)) => (0..=text.len()).any(|i| wildcard_match(rest, &text[i..])),
+        Some(('?', rest)) => !text.is_empty() && wildcard_match(rest, &text[1..]),
+        Some(('~', rest)) if !rest.is_empty() => {
+            !text.is_empty() && text[0] == rest[0] && wildcard_match(&rest[1..], &text[1..])
+        },
+        Some((c, rest)) => !text.is_empty() && text[0] == *c && wildcard_match(rest, &text[1..]),
+    }
+}
+
+fn text_matches(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.to_lowercase().chars().collect();
+    let text: Vec<char> = text.to_lowercase().chars().collect();
+    wildcard_match(&pattern, &text)
+}
+
+// equal in the sense of an exact lookup, text may use wildcards
+fn lookup_equal(lookup: &Val, val: &Val) -> bool {
+    match (lookup, val) {
+        (Val::Text(pattern), Val::Text(text)) => text_matches(pattern, text),
+        (Val::Number(_), Val::Number(_)) | (Val::Bool(_), Val::Bool(_)) => {
+            compare(lookup, val) == Ok(Ordering::Equal)
+        },
+        _ => false,
+    }
+}
+
+fn same_kind(left: &Val, right: &Val) -> bool {
+    std::mem::discriminant(left) == std::mem::discriminant(right)
+}
+
+// position of the last value <= lookup in ascending data (or >= in descending)
+fn approximate_position(lookup: &Val, values: &[Val], descending: bool) -> Option<usize> {
+    let mut found = None;
+    for (i, val) in values.iter().enumerate() {
+        if !same_kind(lookup, val) {
+            continue;
+        }
+        match (compare(val, lookup), descending) {
+            (Ok(Ordering::Greater), false) | (Ok(Ordering::Less), true) => break,
+            (Ok(_), _) => found = Some(i),
+            (Err(_), _) => (),
+        }
+    }
+    found
+}
+
+// VLOOKUP / HLOOKUP
+fn lookup(args: Vec<Arg>, vertical: bool) -> Result<Val> {
+    arity(&args, 3, 4)?;
+    let lookup = scalar(&args, 0);
+    if let Val::Error(kind) = lookup {
+        return Err(kind);
+    }
+    let index = num(&args, 2)?.trunc();
+    let approximate = match args.get(3) {
+        Some(_) => scalar(&args, 3).to_bool()?,
+        None => true,
+    };
+    let mut rows = range(args.into_iter().nth(1))?;
+    if !vertical {
+        rows = transpose(rows);
+    }
+    let width = rows.first().map(|row| row.len()).unwrap_or(0);
+    if index < 1.0 {
+        return Err(ErrorKind::Value);
+    }
+    if index as usize > width {
+        return Err(ErrorKind::Ref);
+    }
+    let keys: Vec<Val> = rows.iter().map(|row| row[0].clone()).collect();
+    let position = if approximate {
+        approximate_position(&lookup, &keys, false)
+    } else {
+        keys.iter().position(|key| lookup_equal(&lookup, key))
+    };
+    match position {
+        Some(row) => Ok(rows[row][index as usize - 1].clone()),
+        None => Err(ErrorKind::NA),
+    }
+}
+
+fn transpose(rows: Vec<Vec<Val>>) -> Vec<Vec<Val>> {
+    let width = rows.first().map(|row| row.len()).unwrap_or(0);
+    (0..width).map(|column| rows.iter().map(|row| row[column].clone()).collect()).collect()
+}
+
+fn index(args: Vec<Arg>) -> Result<Val> {
+    arity(&args, 2, 3)?;
+    let row = num(&args, 1)?.trunc();
+    let column = match args.get(2) {
+        Some(_) => Some(num(&args, 2)?.trunc()),
+        None => None,
+    };
+    let rows = range(args.into_iter().next())?;
+    // a single row can be indexed by the column alone
+    let (row, column) = match column {
+        None if rows.len() == 1 => (1.0, row),
+        None => (row, 1.0),
+        Some(column) => (row, column),
+    };
+    if row < 1.0 || column < 1.0 {
+        return Err(ErrorKind::Value);
+    }
+    rows.get(row as usize - 1)
+        .and_then(|cells| cells.get(column as usize - 1))
+        .cloned()
+        .ok_or(ErrorKind::Ref)
+}
+
+fn match_position(args: Vec<Arg>) -> Result<Val> {
+    arity(&args, 2, 3)?;
+    let lookup = scalar(&args, 0);
+    if let Val::Error(kind) = lookup {
+        return Err(kind);
+    }
+    let match_type = match args.get(2) {
+        Some(_) => num(&args, 2)?,
+        None => 1.0,
+    };
+    let rows = range(args.into_iter().nth(1))?;
+    let values: Vec<Val> = if rows.len() == 1 {
+        rows.into_iter().next().unwrap()
+    } else if rows.iter().all(|row| row.len() == 1) {
+        rows.into_iter().map(|mut row| row.remove(0)).collect()
+    } else {
+        return Err(ErrorKind::NA);
+    };
+    let position = if match_type == 0.0 {
+        values.iter().position(|val| lookup_equal(&lookup, val))
+    } else {
+        approximate_position(&lookup, &values, match_type < 0.0)
+    };
+    position.map(|i| Val::Number((i + 1) as f64)).ok_or(ErrorKind::NA)
+}
+
+// conditions of SUMIF/COUNTIF such as ">=10", "<>x" or "a*"
+fn criteria_matches(criteria: &Val, val: &Val) -> bool {
+    let criteria = match criteria {
+        Val::Text(text) => text.clone(),
+        Val::Empty => String::new(),
+        other => return compare(other, val) == Ok(Ordering::Equal) && same_kind(other, val),
+    };
+    let (op, operand) = ["<=", ">=", "<>", "<", ">", "="].iter()
+        .find(|op| criteria.starts_with(*op))
+        .map(|op| (*op, &criteria[op.len()..]))
+        .unwrap_or(("=", criteria.as_str()));
+    let operand = match operand.trim().parse::<f64>() {
+        Ok(n) => Val::Number(n),
+        Err(_) => Val::Text(operand.to_string()),
+    };
+    match (op, &operand, val) {
+        ("=", Val::Text(pattern), Val::Text(text)) => text_matches(pattern, text),
+        ("=", Val::Text(pattern), Val::Empty) => pattern.is_empty(),
+        ("<>", Val::Text(pattern), Val::Text(text)) => !text_matches(pattern, text),
+        ("<>", Val::Text(pattern), _) => !pattern.is_empty() || *val != Val::Empty,
+        _ if !same_kind(&operand, val) => op == "<>",
+        _ => match compare(val, &operand) {
+            Ok(ordering) => match op {
+                "<" => ordering == Ordering::Less,
+                "<=" => ordering != Ordering::Greater,
+                ">" => ordering == Ordering::Greater,
+                ">=" => ordering != Ordering::Less,
+                "<>" => ordering != Ordering::Equal,
+                _ => ordering == Ordering::Equal,
+            },
+            Err(_) => false,
+        },
+    }
+}
+
+fn count_if(args: Vec<Arg>) -> Result<Val> {
+    arity(&args, 2, 2)?;
+    let criteria = scalar(&args, 1);
+    let rows = range(args.into_iter().next())?;
+    Ok(Val::Number(rows.iter().flatten().filter(|val| criteria_matches(&criteria, val)).count() as f64))
+}
+
+fn sum_if(args: Vec<Arg>) -> Result<Val> {
+    arity(&args, 2, 3)?;
+    let criteria = scalar(&args, 1);
+    let mut args = args.into_iter();
+    let rows = range(args.next())?;
+    let sum_rows = match args.nth(1) {
+        Some(arg) => range(Some(arg))?,
+        None => rows.clone(),
+    };
+    let mut sum = 0.0;
+    for (r, row) in rows.iter().enumerate() {
+        for (c, val) in row.iter().enumerate() {
+            if criteria_matches(&criteria, val) {
+                match sum_rows.get(r).and_then(|row| row.get(c)) {
+                    Some(Val::Number(n)) => sum += n,
+                    Some(Val::Error(kind)) => return Err(*kind),
+                    _ => (),
+                }
+            }
+        }
+    }
+    number(sum)
+}
+
+fn date(args: &[Arg], system: DateSystem) -> Result<Val> {
+    arity(args, 3, 3)?;
+    let (year, month, day) = (num(args, 0)?.trunc(), num(args, 1)?.trunc(), num(args, 2)?.trunc());
+    // beyond these the result is out of 1900..9999 anyway, and the
+    // arithmetic below would overflow
+    if !(0.0..10000.0).contains(&year) || month.abs() > 120_000.0 || day.abs() > 3_700_000.0 {
+        return Err(ErrorKind::Num);
+    }
+    let (year, month, day) = (year as i64, month as i64, day as i64);
+    // years before 1900 are counted from 1900, months and days may overflow
+    let year = if (0..1900).contains(&year) { year + 1900 } else { year };
+    let months = year * 12 + month - 1;
+    let first = NaiveDate::from_ymd_opt(months.div_euclid(12) as i32, months.rem_euclid(12) as u32 + 1, 1)
+        .ok_or(ErrorKind::Num)?
+        .and_time(NaiveTime::MIN);
+    let dt = first.checked_add_signed(Duration::try_days(day - 1).ok_or(ErrorKind::Num)?).ok_or(ErrorKind::Num)?;
+    let serial = system.date_to_serial(&dt);
+    if serial < 0.0 {
+        return Err(ErrorKind::Num);
+    }
+    number(serial)
+}
+
+fn date_part<F: Fn(&NaiveDateTime) -> f64>(args: &[Arg], system: DateSystem, f: F) -> Result<Val> {
+    arity(args, 1, 1)?;
+    let dt = system.serial_to_date(num(args, 0)?).ok_or(ErrorKind::Num)?;
+    Ok(Val::Number(f(&dt)))
+}
+
+fn substring<F: Fn(&str, usize) -> String>(args: &[Arg], f: F) -> Result<Val> {
+    arity(args, 1, 2)?;
+    let length = match args.get(1) {
+        Some(_) => num(args, 1)?.trunc(),
+        None => 1.0,
+    };
+    if length < 0.0 {
+        return Err(ErrorKind::Value);
+    }
+    Ok(Val::Text(f(&text(args, 0)?, length as usize)))
+}
+
+fn concat(args: &[Arg]) -> Result<Val> {
+    let mut result = String::new();
+    for arg in args {
+        match arg {
+            Arg::Range(rows) => {
+                for val in rows.iter().flatten() {
+                    result.push_str(&val.to_text()?);
+                }
+            },
+            Arg::Value(val) => result.push_str(&val.to_text()?),
+        }
+    }
+    Ok(Val::Text(result))
+}
+
+// TEXT(): date formats go through the serial date, everything else is a number format
+fn text_format(n: f64, format: &str, system: DateSystem) -> String {
+    let format = Format::new(format);
+    if format.is_date_format() {
+        system.serial_to_date(n)
+            .and_then(|dt| format.get_formated_date(&dt, system))
+            .unwrap_or_else(|| number_to_text(n))
+    } else {
+        format.get_formated_number(n)
+    }
+}
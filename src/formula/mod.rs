@@ -0,0 +1,13 @@
+//! Formulas: Excel (A1) syntax, its OpenFormula translation and evaluation
+mod eval;
+mod functions;
+mod parser;
+mod syntax;
+
+pub(crate) use self::eval::recalculate;
+pub(crate) use self::functions::duration_to_serial;
+// used by the xlsx reader and writer
+#[allow(unused_imports)]
+pub(crate) use self::functions::{serial_to_duration, serial_to_time, time_to_serial};
+#[allow(unused_imports)]
+pub(crate) use self::syntax::*;
@@ -0,0 +1,277 @@
+//! Expression tree of a formula in A1 syntax
+use super::syntax::{tokenize, Reference, Token};
+use crate::ErrorKind;
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Expr {
+    Number(f64),
+    Text(String),
+    Bool(bool),
+    Error(ErrorKind),
+    Ref(Reference),
+    /// Argument left out, e.g. the second one of `IF(A1,,1)`
+    Missing,
+    Negate(Box<Expr>),
+    Percent(Box<Expr>),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+    Call(String, Vec<Expr>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Pow,
+    Concat,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+/// Deepest nesting of parentheses, function calls and signs, Excel's limit
+/// for functions; parsing and evaluation recurse as deep, and a hostile
+/// formula must not overflow the stack.
+const MAX_DEPTH: usize = 64;
+/// Most operators in a formula, a chain such as `1+1+..` nests as deep.
+const MAX_OPERATORS: usize = 2048;
+
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+    depth: usize,
+    operators: usize,
+}
+
+/// Parse a formula such as `=SUM(A1:A3)*2`, the leading `=` is optional.
+pub(crate) fn parse(formula: &str) -> Result<Expr, String> {
+    let formula = formula.strip_prefix('=').unwrap_or(formula);
+    let tokens = tokenize(formula).into_iter()
+        .filter(|token| !matches!(token, Token::Char(c) if c.is_whitespace()))
+        .collect();
+    let mut parser = Parser { tokens, position: 0, depth: 0, operators: 0 };
+    let expr = parser.comparison()?;
+    match parser.peek() {
+        None => Ok(expr),
+        Some(token) => Err(format!("unexpected {:?}", token)),
+    }
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn peek_char(&self) -> Option<char> {
+        match self.peek() {
+            Some(Token::Char(c)) => Some(*c),
+            _ => None,
+        }
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), String> {
+        match self.next() {
+            Some(Token::Char(c)) if c == expected => Ok(()),
+            Some(token) => Err(format!("expected {} but found {:?}", expected, token)),
+            None => Err(format!("expected {} but the formula ends", expected)),
+        }
+    }
+
+    fn enter(&mut self) -> Result<(), String> {
+        self.depth += 1;
+        if self.depth > MAX_DEPTH {
+            return Err(format!("more than {} levels of nesting", MAX_DEPTH));
+        }
+        Ok(())
+    }
+
+    fn operator(&mut self, op: BinaryOp, left: Expr, right: Expr) -> Result<Expr, String> {
+        self.operators += 1;
+        if self.operators > MAX_OPERATORS {
+            return Err(format!("more than {} operators", MAX_OPERATORS));
+        }
+        Ok(Expr::Binary(op, Box::new(left), Box::new(right)))
+    }
+
+    fn comparison(&mut self) -> Result<Expr, String> {
+        self.enter()?;
+        let expr = self.comparison_chain()?;
+        self.depth -= 1;
+        Ok(expr)
+    }
+
+    // = <> < > <= >=
+    fn comparison_chain(&mut self) -> Result<Expr, String> {
+        let mut left = self.concat()?;
+        loop {
+            let op = match self.peek_char() {
+                Some('=') => BinaryOp::Eq,
+                Some('<') => BinaryOp::Lt,
+                Some('>') => BinaryOp::Gt,
+                _ => return Ok(left),
+            };
+            self.position += 1;
+            let op = match (op, self.peek_char()) {
+                (BinaryOp::Lt, Some('=')) => BinaryOp::Le,
+                (BinaryOp::Lt, Some('>')) => BinaryOp::Ne,
+                (BinaryOp::Gt, Some('=')) => BinaryOp::Ge,
+                (op, _) => op,
+            };
+            if !matches!(op, BinaryOp::Eq | BinaryOp::Lt | BinaryOp::Gt) {
+                self.position += 1;
+            }
+            let right = self.concat()?;
+            left = self.operator(op, left, right)?;
+        }
+    }
+
+    fn concat(&mut self) -> Result<Expr, String> {
+        let mut left = self.additive()?;
+        while self.peek_char() == Some('&') {
+            self.position += 1;
+            let right = self.additive()?;
+            left = self.operator(BinaryOp::Concat, left, right)?;
+        }
+        Ok(left)
+    }
+
+    fn additive(&mut self) -> Result<Expr, String> {
+        let mut left = self.multiplicative()?;
+        loop {
+            let op = match self.peek_char() {
+                Some('+') => BinaryOp::Add,
+                Some('-') => BinaryOp::Sub,
+                _ => return Ok(left),
+            };
+            self.position += 1;
+            let right = self.multiplicative()?;
+            left = self.operator(op, left, right)?;
+        }
+    }
+
+    fn multiplicative(&mut self) -> Result<Expr, String> {
+        let mut left = self.power()?;
+        loop {
+            let op = match self.peek_char() {
+                Some('*') => BinaryOp::Mul,
+                Some('/') => BinaryOp::Div,
+                _ => return Ok(left),
+            };
+            self.position += 1;
+            let right = self.power()?;
+            left = self.operator(op, left, right)?;
+        }
+    }
+
+    // ^ is left associative in Excel: 2^3^2 = 64
+    fn power(&mut self) -> Result<Expr, String> {
+        let mut left = self.unary()?;
+        while self.peek_char() == Some('^') {
+            self.position += 1;
+            let right = self.unary()?;
+            left = self.operator(BinaryOp::Pow, left, right)?;
+        }
+        Ok(left)
+    }
+
+    // the sign binds tighter than ^: -2^2 = 4
+    fn unary(&mut self) -> Result<Expr, String> {
+        match self.peek_char() {
+            Some('-') => {
+                self.position += 1;
+                self.enter()?;
+                let expr = Expr::Negate(Box::new(self.unary()?));
+                self.depth -= 1;
+                Ok(expr)
+            },
+            Some('+') => {
+                self.position += 1;
+                self.enter()?;
+                let expr = self.unary()?;
+                self.depth -= 1;
+                Ok(expr)
+            },
+            _ => self.percent(),
+        }
+    }
+
+    fn percent(&mut self) -> Result<Expr, String> {
+        let mut expr = self.primary()?;
+        while self.peek_char() == Some('%') {
+            self.position += 1;
+            self.operators += 1;
+            if self.operators > MAX_OPERATORS {
+                return Err(format!("more than {} operators", MAX_OPERATORS));
+            }
+            expr = Expr::Percent(Box::new(expr));
+        }
+        Ok(expr)
+    }
+
+    fn primary(&mut self) -> Result<Expr, String> {
+        match self.next() {
+            Some(Token::Str(text)) => Ok(Expr::Text(text)),
+            Some(Token::Ref(reference)) => Ok(Expr::Ref(reference)),
+            Some(Token::Word(word)) => Ok(word_to_expr(&word)),
+            Some(Token::Func(name)) => {
+                self.expect('(')?;
+                let args = self.arguments()?;
+                Ok(Expr::Call(name.to_uppercase(), args))
+            },
+            Some(Token::Char('(')) => {
+                let expr = self.comparison()?;
+                self.expect(')')?;
+                Ok(expr)
+            },
+            Some(token) => Err(format!("unexpected {:?}", token)),
+            None => Err(String::from("the formula ends unexpectedly")),
+        }
+    }
+
+    fn arguments(&mut self) -> Result<Vec<Expr>, String> {
+        let mut args = vec![];
+        if self.peek_char() == Some(')') {
+            self.position += 1;
+            return Ok(args);
+        }
+        loop {
+            match self.peek() {
+                Some(Token::ArgSep) => args.push(Expr::Missing),
+                Some(Token::Char(')')) => args.push(Expr::Missing),
+                _ => args.push(self.comparison()?),
+            }
+            match self.next() {
+                Some(Token::ArgSep) => (),
+                Some(Token::Char(')')) => return Ok(args),
+                Some(token) => return Err(format!("unexpected {:?} in the arguments", token)),
+                None => return Err(String::from("missing )")),
+            }
+        }
+    }
+}
+
+fn word_to_expr(word: &str) -> Expr {
+    if word.starts_with(|c: char| c.is_ascii_digit() || c == '.') {
+        if let Ok(number) = word.parse::<f64>() {
+            return Expr::Number(number);
+        }
+    }
+    if word.eq_ignore_ascii_case("TRUE") {
+        return Expr::Bool(true);
+    }
+    if word.eq_ignore_ascii_case("FALSE") {
+        return Expr::Bool(false);
+    }
+    // error literals, and defined names which are not supported
+    Expr::Error(ErrorKind::from_code(&word.to_uppercase()).unwrap_or(ErrorKind::Name))
+}
@@ -0,0 +1,511 @@
+//! Formula text in Excel (A1) syntax and its OpenFormula translation
+use crate::{column_to_index, index_to_column};
+
+/// Largest column and row a reference can point to (XFD1048576).
+const MAX_COLUMN: usize = 16384;
+const MAX_ROW: usize = 1048576;
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct CellRef {
+    pub column: usize,
+    pub row: usize,
+    pub column_absolute: bool,
+    pub row_absolute: bool,
+}
+
+/// What a reference covers; whole columns and rows still carry the first
+/// and last row or column in `start` and `end`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Span {
+    /// A1 or A1:B2
+    Cells,
+    /// A:B
+    Columns,
+    /// 1:2
+    Rows,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Reference {
+    pub sheet: Option<String>,
+    pub span: Span,
+    pub start: CellRef,
+    pub end: Option<CellRef>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Token {
+    /// String literal without the quotes
+    Str(String),
+    Ref(Reference),
+    /// Function name, the `(` follows as a Char
+    Func(String),
+    /// Number, name, boolean or error literal
+    Word(String),
+    /// Argument separator `,` (Excel) / `;` (OpenFormula)
+    ArgSep,
+    /// Array column separator `,` (Excel) / `;` (OpenFormula)
+    ColSep,
+    /// Array row separator `;` (Excel) / `|` (OpenFormula)
+    RowSep,
+    Char(char),
+}
+
+impl CellRef {
+    // A1, $A$1, a1
+    pub(crate) fn parse(text: &str) -> Option<CellRef> {
+        let (column_absolute, rest) = match text.strip_prefix('$') {
+            Some(rest) => (true, rest),
+            None => (false, text),
+        };
+        let letters = rest.chars().take_while(|c| c.is_ascii_alphabetic()).count();
+        if letters == 0 || letters > 3 {
+            return None;
+        }
+        let (column, rest) = rest.split_at(letters);
+        let (row_absolute, digits) = match rest.strip_prefix('$') {
+            Some(rest) => (true, rest),
+            None => (false, rest),
+        };
+        if digits.is_empty() || !digits.chars().all(|c| c.is_ascii_digit()) {
+            return None;
+        }
+        let column = column_to_index(column.to_ascii_uppercase());
+        let row = digits.parse::<usize>().ok()?;
+        if column >= MAX_COLUMN || row == 0 || row > MAX_ROW {
+            return None;
+        }
+        Some(CellRef { column, row: row - 1, column_absolute, row_absolute })
+    }
+
+    // A or $A of a whole column reference
+    fn parse_column(text: &str, row: usize) -> Option<CellRef> {
+        let (column_absolute, letters) = match text.strip_prefix('$') {
+            Some(rest) => (true, rest),
+            None => (false, text),
+        };
+        if letters.is_empty() || letters.len() > 3 || !letters.chars().all(|c| c.is_ascii_alphabetic()) {
+            return None;
+        }
+        let column = column_to_index(letters.to_ascii_uppercase());
+        if column >= MAX_COLUMN {
+            return None;
+        }
+        Some(CellRef { column, row, column_absolute, row_absolute: false })
+    }
+
+    // 1 or $1 of a whole row reference
+    fn parse_row(text: &str, column: usize) -> Option<CellRef> {
+        let (row_absolute, digits) = match text.strip_prefix('$') {
+            Some(rest) => (true, rest),
+            None => (false, text),
+        };
+        if digits.is_empty() || !digits.chars().all(|c| c.is_ascii_digit()) {
+            return None;
+        }
+        let row = digits.parse::<usize>().ok()?;
+        if row == 0 || row > MAX_ROW {
+            return None;
+        }
+        Some(CellRef { column, row: row - 1, column_absolute: false, row_absolute })
+    }
+
+    pub(crate) fn to_a1(&self) -> String {
+        format!("{}{}{}{}",
+            if self.column_absolute { "$" } else { "" },
+            index_to_column(self.column),
+            if self.row_absolute { "$" } else { "" },
+            self.row + 1)
+    }
+
+    #[cfg(any(feature = "ods", feature = "xlsx"))]
+    fn to_text(&self, span: Span) -> String {
+        match span {
+            Span::Cells => self.to_a1(),
+            Span::Columns => format!("{}{}", if self.column_absolute { "$" } else { "" }, index_to_column(self.column)),
+            Span::Rows => format!("{}{}", if self.row_absolute { "$" } else { "" }, self.row + 1),
+        }
+    }
+
+    #[cfg(feature = "xlsx")]
+    fn shift(&self, rows: isize, columns: isize) -> Option<CellRef> {
+        let column = if self.column_absolute { self.column as isize } else { self.column as isize + columns };
+        let row = if self.row_absolute { self.row as isize } else { self.row as isize + rows };
+        if column < 0 || row < 0 || column as usize >= MAX_COLUMN || row as usize >= MAX_ROW {
+            return None;
+        }
+        Some(CellRef { column: column as usize, row: row as usize, ..self.clone() })
+    }
+}
+
+#[cfg(any(feature = "ods", feature = "xlsx"))]
+fn needs_quote(sheet: &str) -> bool {
+    sheet.is_empty()
+        || sheet.starts_with(|c: char| c.is_ascii_digit())
+        || !sheet.chars().all(|c| c.is_alphanumeric() || c == '_')
+        || CellRef::parse(sheet).is_some()
+}
+
+#[cfg(any(feature = "ods", feature = "xlsx"))]
+fn quote_sheet(sheet: &str) -> String {
+    if needs_quote(sheet) {
+        format!("'{}'", sheet.replace('\'', "''"))
+    } else {
+        sheet.to_string()
+    }
+}
+
+// 'It''s' -> (It's, rest after the closing quote)
+fn read_quoted(chars: &[char], start: usize, quote: char) -> Option<(String, usize)> {
+    let mut text = String::new();
+    let mut i = start + 1;
+    while i < chars.len() {
+        if chars[i] == quote {
+            if chars.get(i + 1) == Some(&quote) {
+                text.push(quote);
+                i += 2;
+                continue;
+            }
+            return Some((text, i + 1));
+        }
+        text.push(chars[i]);
+        i += 1;
+    }
+    None
+}
+
+fn is_name_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '.' || c == '$' || c == '\\'
+}
+
+fn read_while<F: Fn(char) -> bool>(chars: &[char], start: usize, f: F) -> usize {
+    let mut i = start;
+    while i < chars.len() && f(chars[i]) {
+        i += 1;
+    }
+    i
+}
+
+// A1:B2, A:B or 1:2 from the text on either side of the colon
+fn parse_range(start: &str, end: &str) -> Option<(Span, CellRef, CellRef)> {
+    if let (Some(start), Some(end)) = (CellRef::parse(start), CellRef::parse(end)) {
+        return Some((Span::Cells, start, end));
+    }
+    if let (Some(start), Some(end)) = (CellRef::parse_column(start, 0), CellRef::parse_column(end, MAX_ROW - 1)) {
+        return Some((Span::Columns, start, end));
+    }
+    let (start, end) = (CellRef::parse_row(start, 0)?, CellRef::parse_row(end, MAX_COLUMN - 1)?);
+    Some((Span::Rows, start, end))
+}
+
+// A1, A1:B2, A:B or 1:2 starting at `start`
+fn read_range(chars: &[char], start: usize, sheet: Option<String>) -> Option<(Reference, usize)> {
+    let end = read_while(chars, start, |c| c.is_ascii_alphanumeric() || c == '$');
+    let first: String = chars[start..end].iter().collect();
+    if chars.get(end) == Some(&':') {
+        let second_end = read_while(chars, end + 1, |c| c.is_ascii_alphanumeric() || c == '$');
+        let second: String = chars[end + 1..second_end].iter().collect();
+        if let Some((span, start, end)) = parse_range(&first, &second) {
+            return Some((Reference { sheet, span, start, end: Some(end) }, second_end));
+        }
+    }
+    let start = CellRef::parse(&first)?;
+    Some((Reference { sheet, span: Span::Cells, start, end: None }, end))
+}
+
+/// Split an Excel formula such as `=SUM(A1:A3,Sheet2!B1)` into tokens.
+pub(crate) fn tokenize(formula: &str) -> Vec<Token> {
+    let chars: Vec<char> = formula.chars().collect();
+    let mut tokens = Vec::new();
+    let mut braces = 0;
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        match c {
+            '"' => {
+                if let Some((text, next)) = read_quoted(&chars, i, '"') {
+                    tokens.push(Token::Str(text));
+                    i = next;
+                    continue;
+                }
+            },
+            '\'' => {
+                if let Some((sheet, next)) = read_quoted(&chars, i, '\'') {
+                    if chars.get(next) == Some(&'!') {
+                        if let Some((reference, next)) = read_range(&chars, next + 1, Some(sheet)) {
+                            tokens.push(Token::Ref(reference));
+                            i = next;
+                            continue;
+                        }
+                    }
+                }
+            },
+            '#' => {
+                let end = read_while(&chars, i + 1, |c| c.is_ascii_alphanumeric() || c == '/' || c == '_');
+                let end = if matches!(chars.get(end), Some('!') | Some('?')) { end + 1 } else { end };
+                tokens.push(Token::Word(chars[i..end].iter().collect()));
+                i = end;
+                continue;
+            },
+            ',' => {
+                tokens.push(if braces > 0 { Token::ColSep } else { Token::ArgSep });
+                i += 1;
+                continue;
+            },
+            ';' if braces > 0 => {
+                tokens.push(Token::RowSep);
+                i += 1;
+                continue;
+            },
+            '{' => braces += 1,
+            '}' => braces -= 1,
+            _ if is_name_char(c) => {
+                let end = read_while(&chars, i, is_name_char);
+                let name: String = chars[i..end].iter().collect();
+                if chars.get(end) == Some(&'!') {
+                    if let Some((reference, next)) = read_range(&chars, end + 1, Some(name.clone())) {
+                        tokens.push(Token::Ref(reference));
+                        i = next;
+                        continue;
+                    }
+                }
+                if chars.get(end) == Some(&'(') {
+                    tokens.push(Token::Func(name));
+                    i = end;
+                    continue;
+                }
+                if let Some((reference, next)) = read_range(&chars, i, None) {
+                    if next >= end {
+                        tokens.push(Token::Ref(reference));
+                        i = next;
+                        continue;
+                    }
+                }
+                // numbers like 1.5E+3 carry the exponent sign
+                let end = if name.ends_with(['E', 'e']) && name.starts_with(|c: char| c.is_ascii_digit())
+                    && matches!(chars.get(end), Some('+') | Some('-')) {
+                    read_while(&chars, end + 1, |c| c.is_ascii_digit())
+                } else {
+                    end
+                };
+                tokens.push(Token::Word(chars[i..end].iter().collect()));
+                i = end;
+                continue;
+            },
+            _ => (),
+        }
+        tokens.push(Token::Char(c));
+        i += 1;
+    }
+    tokens
+}
+
+/// Join tokens back into Excel syntax.
+#[cfg(any(feature = "ods", feature = "xlsx"))]
+pub(crate) fn to_excel(tokens: &[Token]) -> String {
+    let mut result = String::new();
+    for token in tokens {
+        match token {
+            Token::Str(text) => {
+                result.push('"');
+                result.push_str(&text.replace('"', "\"\""));
+                result.push('"');
+            },
+            Token::Ref(reference) => {
+                if let Some(ref sheet) = reference.sheet {
+                    result.push_str(&quote_sheet(sheet));
+                    result.push('!');
+                }
+                result.push_str(&reference.start.to_text(reference.span));
+                if let Some(ref end) = reference.end {
+                    result.push(':');
+                    result.push_str(&end.to_text(reference.span));
+                }
+            },
+            Token::Func(name) | Token::Word(name) => result.push_str(name),
+            Token::ArgSep | Token::ColSep => result.push(','),
+            Token::RowSep => result.push(';'),
+            Token::Char(c) => result.push(*c),
+        }
+    }
+    result
+}
+
+/// Translate an Excel formula into the `of:` form stored in `table:formula`.
+///
+/// `=SUM(A1:A3,Sheet2!B1)` becomes `of:=SUM([.A1:.A3];[$Sheet2.B1])`.
+#[cfg(feature = "ods")]
+pub(crate) fn to_open_formula(formula: &str) -> String {
+    let mut result = String::from("of:");
+    for token in tokenize(formula) {
+        match token {
+            Token::Ref(reference) => {
+                result.push('[');
+                if let Some(ref sheet) = reference.sheet {
+                    result.push('$');
+                    result.push_str(&quote_sheet(sheet));
+                }
+                result.push('.');
+                result.push_str(&reference.start.to_text(reference.span));
+                if let Some(ref end) = reference.end {
+                    result.push_str(":.");
+                    result.push_str(&end.to_text(reference.span));
+                }
+                result.push(']');
+            },
+            Token::ArgSep | Token::ColSep => result.push(';'),
+            Token::RowSep => result.push('|'),
+            other => result.push_str(&to_excel(&[other])),
+        }
+    }
+    result
+}
+
+// .A1 / $Sheet2.A1 / 'My Sheet'.A1, the cell part is left unparsed
+// as it may also be a whole column or row
+#[cfg(feature = "ods")]
+fn parse_open_formula_cell(text: &str) -> Option<(Option<String>, String)> {
+    let text = text.strip_prefix('$').unwrap_or(text);
+    if let Some(cell) = text.strip_prefix('.') {
+        return Some((None, cell.to_string()));
+    }
+    let chars: Vec<char> = text.chars().collect();
+    let (sheet, next) = if chars.first() == Some(&'\'') {
+        read_quoted(&chars, 0, '\'')?
+    } else {
+        let end = read_while(&chars, 0, |c| c != '.');
+        (chars[..end].iter().collect(), end)
+    };
+    if chars.get(next) != Some(&'.') {
+        return None;
+    }
+    Some((Some(sheet), chars[next + 1..].iter().collect()))
+}
+
+// [.A1:.A3] without the brackets
+#[cfg(feature = "ods")]
+fn parse_open_formula_reference(text: &str) -> Option<Reference> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut parts = vec![];
+    let mut current = String::new();
+    let mut i = 0;
+    while i < chars.len() {
+        match chars[i] {
+            '\'' => {
+                let (quoted, next) = read_quoted(&chars, i, '\'')?;
+                current.push('\'');
+                current.push_str(&quoted.replace('\'', "''"));
+                current.push('\'');
+                i = next;
+                continue;
+            },
+            ':' => parts.push(std::mem::take(&mut current)),
+            c => current.push(c),
+        }
+        i += 1;
+    }
+    parts.push(current);
+    match parts.as_slice() {
+        [start] => {
+            let (sheet, start) = parse_open_formula_cell(start)?;
+            Some(Reference { sheet, span: Span::Cells, start: CellRef::parse(&start)?, end: None })
+        },
+        [start, end] => {
+            let (sheet, start) = parse_open_formula_cell(start)?;
+            let (_, end) = parse_open_formula_cell(end)?;
+            let (span, start, end) = parse_range(&start, &end)?;
+            Some(Reference { sheet, span, start, end: Some(end) })
+        },
+        _ => None,
+    }
+}
+
+/// Translate a `table:formula` value back into Excel syntax.
+///
+/// The namespace prefix (`of:`) is dropped; references which can not be
+/// expressed in A1 syntax are kept as they are.
+#[cfg(feature = "ods")]
+pub(crate) fn from_open_formula(formula: &str) -> String {
+    let formula = match formula.find(":=") {
+        Some(index) if formula[..index].chars().all(|c| c.is_ascii_alphabetic()) => &formula[index + 1..],
+        _ => formula,
+    };
+    let chars: Vec<char> = formula.chars().collect();
+    let mut tokens = Vec::new();
+    let mut braces = 0;
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        match c {
+            '"' => {
+                if let Some((text, next)) = read_quoted(&chars, i, '"') {
+                    tokens.push(Token::Str(text));
+                    i = next;
+                    continue;
+                }
+            },
+            '[' => {
+                let mut end = i + 1;
+                while end < chars.len() && chars[end] != ']' {
+                    if chars[end] == '\'' {
+                        end = match read_quoted(&chars, end, '\'') {
+                            Some((_, next)) => next,
+                            None => chars.len(),
+                        };
+                    } else {
+                        end += 1;
+                    }
+                }
+                let inner: String = chars[(i + 1).min(end)..end].iter().collect();
+                let end = (end + 1).min(chars.len());
+                match parse_open_formula_reference(&inner) {
+                    Some(reference) => tokens.push(Token::Ref(reference)),
+                    None => tokens.push(Token::Word(chars[i..end].iter().collect())),
+                }
+                i = end;
+                continue;
+            },
+            ';' => {
+                tokens.push(if braces > 0 { Token::ColSep } else { Token::ArgSep });
+                i += 1;
+                continue;
+            },
+            '|' if braces > 0 => {
+                tokens.push(Token::RowSep);
+                i += 1;
+                continue;
+            },
+            '{' => braces += 1,
+            '}' => braces -= 1,
+            _ => (),
+        }
+        tokens.push(Token::Char(c));
+        i += 1;
+    }
+    to_excel(&tokens)
+}
+
+/// Move the relative references of a formula, as Excel does for the cells
+/// sharing one formula (`<f t="shared">`).
+#[cfg(feature = "xlsx")]
+pub(crate) fn shift(formula: &str, rows: isize, columns: isize) -> String {
+    let tokens: Vec<Token> = tokenize(formula).into_iter().map(|token| match token {
+        Token::Ref(reference) => {
+            // whole columns stay whole columns, whole rows whole rows
+            let (rows, columns) = match reference.span {
+                Span::Cells => (rows, columns),
+                Span::Columns => (0, columns),
+                Span::Rows => (rows, 0),
+            };
+            let start = reference.start.shift(rows, columns);
+            let end = reference.end.as_ref().map(|end| end.shift(rows, columns));
+            match (start, end) {
+                (Some(start), None) => Token::Ref(Reference { start, ..reference }),
+                (Some(start), Some(Some(end))) => Token::Ref(Reference { start, end: Some(end), ..reference }),
+                _ => Token::Word(String::from("#REF!")),
+            }
+        },
+        other => other,
+    }).collect();
+    to_excel(&tokens)
+}
@@ -2,16 +2,23 @@
 extern crate chrono;
 extern crate era_jp;
 
-#[macro_use]
 extern crate nom;
 
 use chrono::prelude::*;
+use chrono::Duration;
 use std::collections::HashMap;
 use std::borrow::Cow;
+use std::fmt;
+
+mod error;
+pub use error::Error;
 
 pub mod format;
 use format::Format;
 
+pub mod style;
+use style::Style;
+
 #[cfg(feature = "ods")]
 pub mod ods;
 
@@ -21,6 +28,34 @@ pub mod xlsx;
 #[cfg(any(feature = "ods", feature = "xlsx"))]
 mod file_common;
 
+mod formula;
+
+#[cfg(any(feature = "ods", feature = "xlsx"))]
+use std::io::{Read, Seek};
+#[cfg(any(feature = "ods", feature = "xlsx"))]
+use std::path::Path;
+#[cfg(any(feature = "ods", feature = "xlsx"))]
+use std::fs::File;
+
+/// Read a xlsx or ods file. The format is told from the contents,
+/// the file extension only breaks a tie.
+#[cfg(any(feature = "ods", feature = "xlsx"))]
+pub fn open(path: &Path) -> Result<Book, Error> {
+    file_common::read_book(File::open(path)?, file_common::Kind::from_path(path))
+}
+
+/// Read a xlsx or ods file from any seekable stream.
+#[cfg(any(feature = "ods", feature = "xlsx"))]
+pub fn open_from<R: Read + Seek>(reader: R) -> Result<Book, Error> {
+    file_common::read_book(reader, None)
+}
+
+/// Write a book as xlsx or ods, chosen by the file extension.
+#[cfg(any(feature = "ods", feature = "xlsx"))]
+pub fn save(book: &Book, path: &Path) -> Result<(), Error> {
+    file_common::write_book(book, path)
+}
+
 /// String index to usize index start with 0
 ///
 /// ```
@@ -39,7 +74,7 @@ pub fn column_to_index<'a, S>(value: S) -> usize
         if i != 0 {
             index = (index + 1) * 26;
         }
-        index = index + alphabet.find(c).unwrap();
+        index += alphabet.find(c).unwrap();
     }
     index
 }
@@ -69,37 +104,107 @@ pub fn index_to_column(index: usize) -> String {
 
 /// Column and row String index to usize index pair
 ///
+/// Returns `None` if the value is not a valid reference.
+///
 /// ```
 /// use spsheet::*;
 /// assert_eq!(Some((701,11)), column_and_row_to_index("ZZ12"));
+/// assert_eq!(None, column_and_row_to_index("A0"));
+/// assert_eq!(None, column_and_row_to_index("#N/A"));
 /// ```
 pub fn column_and_row_to_index<'a, S>(value: S) -> Option<(usize, usize)>
     where S: Into<Cow<'a, str>>
 {
-    let val = String::from(value.into());
-    let digit = "0123456789";
-    let mut index = std::usize::MAX;
-    for i in digit.chars() {
-        match val.find(i) {
-            Some(n) => {
-                if index > n {
-                    index = n;
-                }
-            },
-            None => {}
+    let val = value.into();
+    let index = val.find(|c: char| c.is_ascii_digit())?;
+    let (column, row) = val.split_at(index);
+    if column.is_empty() || !column.chars().all(|c| c.is_ascii_uppercase()) {
+        return None;
+    }
+    match row.parse::<usize>() {
+        Ok(row) if row > 0 => Some((column_to_index(column), row - 1)),
+        _ => None,
+    }
+}
+
+/// Rectangle of cells from its top left to its bottom right cell, both included.
+///
+/// ```
+/// use spsheet::CellRange;
+/// let range = CellRange::parse("C2:A1").unwrap();
+/// assert_eq!(CellRange::new(0, 0, 1, 2), range);
+/// assert_eq!("A1:C2", range.to_string());
+/// assert!(range.contains(1, 2));
+/// assert_eq!(None, CellRange::parse("A1:"));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct CellRange {
+    first_row: usize,
+    first_column: usize,
+    last_row: usize,
+    last_column: usize,
+}
+
+impl CellRange {
+    /// Any two opposite corners make the range
+    pub fn new(row_index: usize, column_index: usize, other_row_index: usize, other_column_index: usize) -> CellRange {
+        CellRange {
+            first_row: row_index.min(other_row_index),
+            first_column: column_index.min(other_column_index),
+            last_row: row_index.max(other_row_index),
+            last_column: column_index.max(other_column_index),
         }
     }
-    if std::usize::MAX == index {
-        None
-    } else {
-        unsafe {
-            Some(
-                (
-                    column_to_index(val.get_unchecked(0..index)), 
-                    val.get_unchecked(index..val.len()).parse::<usize>().unwrap() - 1
-                )
-            )
+
+    /// Parses `A1:C2`, or `A1` for a single cell.
+    pub fn parse<'a, S>(value: S) -> Option<CellRange>
+        where S: Into<Cow<'a, str>>
+    {
+        let value = value.into();
+        let (first, last) = match value.split_once(':') {
+            Some((first, last)) => (first, last),
+            None => (value.as_ref(), value.as_ref()),
+        };
+        let (first_column, first_row) = column_and_row_to_index(first)?;
+        let (last_column, last_row) = column_and_row_to_index(last)?;
+        Some(CellRange::new(first_row, first_column, last_row, last_column))
+    }
+
+    pub fn get_first_row(&self) -> usize {
+        self.first_row
+    }
+
+    pub fn get_first_column(&self) -> usize {
+        self.first_column
+    }
+
+    pub fn get_last_row(&self) -> usize {
+        self.last_row
+    }
+
+    pub fn get_last_column(&self) -> usize {
+        self.last_column
+    }
+
+    pub fn contains(&self, row_index: usize, column_index: usize) -> bool {
+        self.first_row <= row_index && row_index <= self.last_row
+            && self.first_column <= column_index && column_index <= self.last_column
+    }
+
+    pub fn intersects(&self, other: &CellRange) -> bool {
+        self.first_row <= other.last_row && other.first_row <= self.last_row
+            && self.first_column <= other.last_column && other.first_column <= self.last_column
+    }
+}
+
+// A1 for a single cell
+impl fmt::Display for CellRange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}{}", index_to_column(self.first_column), self.first_row + 1)?;
+        if self.first_row != self.last_row || self.first_column != self.last_column {
+            write!(f, ":{}{}", index_to_column(self.last_column), self.last_row + 1)?;
         }
+        Ok(())
     }
 }
 
@@ -111,12 +216,22 @@ pub fn column_and_row_to_index<'a, S>(value: S) -> Option<(usize, usize)>
 #[derive(Debug, Clone, PartialEq)]
 pub struct Book {
     sheets: Vec<Sheet>,
+    date_system: DateSystem,
+    active_sheet: usize,
+}
+
+impl Default for Book {
+    fn default() -> Book {
+        Book::new()
+    }
 }
 
 impl Book {
     pub fn new() -> Book {
         Book {
-            sheets: Vec::new()
+            sheets: Vec::new(),
+            date_system: DateSystem::Date1900,
+            active_sheet: 0,
         }
     }
 
@@ -128,13 +243,54 @@ impl Book {
         &self.sheets[index]
     }
 
+    pub fn get_sheet_mut(&mut self, index: usize) -> &mut Sheet {
+        &mut self.sheets[index]
+    }
+
     pub fn get_sheet_size(&self) -> usize {
-        self.sheets.len() as usize
+        self.sheets.len()
     }
 
     pub fn get_sheet_vec(&self) -> &Vec<Sheet> {
         &self.sheets
     }
+
+    /// Date system the serial numbers of the file count from,
+    /// xlsx from old Mac Excel uses the 1904 one.
+    pub fn get_date_system(&self) -> DateSystem {
+        self.date_system
+    }
+
+    pub fn set_date_system(&mut self, date_system: DateSystem) {
+        self.date_system = date_system;
+    }
+
+    /// Index of the sheet shown when the file is opened
+    pub fn get_active_sheet(&self) -> usize {
+        self.active_sheet
+    }
+
+    pub fn set_active_sheet(&mut self, index: usize) {
+        self.active_sheet = index;
+    }
+
+    /// Calculate every formula again and update the cached values.
+    /// Circular references and formulas which can not be parsed are errors,
+    /// in which case the book is left as it was.
+    ///
+    /// ```
+    /// use spsheet::{Book, Sheet, Cell, Value};
+    /// let mut sheet = Sheet::new("sheet1");
+    /// sheet.add_cell(Cell::float(2.0, ""), 0, 0);
+    /// sheet.add_cell(Cell::formula("=A1*3", Value::Float(0.0)), 0, 1);
+    /// let mut book = Book::new();
+    /// book.add_sheet(sheet);
+    /// book.recalculate().unwrap();
+    /// assert_eq!(&Value::Float(6.0), book.get_sheet(0).get_cell(0, 1).unwrap().get_value());
+    /// ```
+    pub fn recalculate(&mut self) -> Result<(), Error> {
+        formula::recalculate(self)
+    }
 }
 
 /// Sheet has owner of cells.
@@ -146,6 +302,10 @@ impl Book {
 pub struct Sheet {
     name: String,
     rows: HashMap<usize, HashMap<usize, Cell>>,
+    columns: HashMap<usize, Column>,
+    row_settings: HashMap<usize, Row>,
+    merged_ranges: Vec<CellRange>,
+    view: SheetView,
 }
 
 impl Sheet {
@@ -154,7 +314,11 @@ impl Sheet {
     {
         Sheet {
             name: name.into().into_owned(),
-            rows: HashMap::new()
+            rows: HashMap::new(),
+            columns: HashMap::new(),
+            row_settings: HashMap::new(),
+            merged_ranges: Vec::new(),
+            view: SheetView::new(),
         }
     }
 
@@ -180,18 +344,22 @@ impl Sheet {
 
     pub fn get_cell(&self, row_index: usize, column_index: usize) -> Option<&Cell> {
         if let Some(row) = self.rows.get(&row_index) {
-            return row.get(&column_index);
+            row.get(&column_index)
         } else {
-            return None;
+            None
         }
     }
 
+    pub fn get_cell_mut(&mut self, row_index: usize, column_index: usize) -> Option<&mut Cell> {
+        self.rows.get_mut(&row_index).and_then(|row| row.get_mut(&column_index))
+    }
+
     pub fn get_rows(&self) -> &HashMap<usize, HashMap<usize, Cell>> {
         &self.rows
     }
 
     pub fn sorted_access<F>(&self, mut callback: F) 
-        where F : FnMut(usize, usize, &Cell) -> () 
+        where F : FnMut(usize, usize, &Cell)
     {
         let mut row_index_vec: Vec<usize> = Vec::new();
         for (row_index, _) in self.get_rows().iter() {
@@ -212,8 +380,8 @@ impl Sheet {
         }
     }
 
-    pub fn walk_through<F>(&self, mut callback: F) 
-        where F : FnMut(usize, usize, &Cell) -> () 
+    pub fn walk_through<'a, F>(&'a self, mut callback: F)
+        where F : FnMut(usize, usize, &'a Cell)
     {
         for (&row_index, rows) in self.get_rows() {
             for (&col_index, cell) in rows {
@@ -223,7 +391,7 @@ impl Sheet {
     }
 
     pub fn get_max_index(&self) -> Option<(usize, usize)> {
-        if self.get_rows().len() == 0 {
+        if self.get_rows().is_empty() {
             return None;
         }
         let mut max_row_index = 0;
@@ -240,6 +408,410 @@ impl Sheet {
         }
         Some((max_row_index, max_column_index))
     }
+
+    /// Sets the width and flags of a column, a default column is removed.
+    ///
+    /// ```
+    /// let mut sheet = spsheet::Sheet::new("sheet1");
+    /// sheet.set_column(1, spsheet::Column::new().with_width(20.0));
+    /// assert_eq!(Some(20.0), sheet.get_column(1).unwrap().get_width());
+    /// assert_eq!(None, sheet.get_column(0));
+    /// ```
+    pub fn set_column(&mut self, column_index: usize, column: Column) {
+        if column == Column::new() {
+            self.columns.remove(&column_index);
+        } else {
+            self.columns.insert(column_index, column);
+        }
+    }
+
+    pub fn get_column(&self, column_index: usize) -> Option<&Column> {
+        self.columns.get(&column_index)
+    }
+
+    pub fn get_columns(&self) -> &HashMap<usize, Column> {
+        &self.columns
+    }
+
+    /// Sets the height and flags of a row, a default row is removed.
+    pub fn set_row(&mut self, row_index: usize, row: Row) {
+        if row == Row::new() {
+            self.row_settings.remove(&row_index);
+        } else {
+            self.row_settings.insert(row_index, row);
+        }
+    }
+
+    pub fn get_row(&self, row_index: usize) -> Option<&Row> {
+        self.row_settings.get(&row_index)
+    }
+
+    /// The rows which are not of the default height, `get_rows` has the cells.
+    pub fn get_row_settings(&self) -> &HashMap<usize, Row> {
+        &self.row_settings
+    }
+
+    /// Merges the cells of a range into the top left one, the merged ranges it overlaps are undone.
+    /// The other cells keep their values, which are hidden by the merged cell.
+    ///
+    /// ```
+    /// use spsheet::{CellRange, Sheet};
+    /// let mut sheet = Sheet::new("sheet1");
+    /// sheet.merge(CellRange::parse("A1:C1").unwrap());
+    /// sheet.merge(CellRange::parse("C1:C3").unwrap());
+    /// assert_eq!(&vec![CellRange::new(0, 2, 2, 2)], sheet.merged_ranges());
+    /// ```
+    pub fn merge(&mut self, range: CellRange) {
+        self.merged_ranges.retain(|merged| !merged.intersects(&range));
+        // a single cell is not merged with anything
+        if range.first_row == range.last_row && range.first_column == range.last_column {
+            return;
+        }
+        let index = self.merged_ranges.binary_search(&range).unwrap_or_else(|index| index);
+        self.merged_ranges.insert(index, range);
+    }
+
+    /// Undoes the merged ranges which overlap the range
+    pub fn unmerge(&mut self, range: CellRange) {
+        self.merged_ranges.retain(|merged| !merged.intersects(&range));
+    }
+
+    /// Merged ranges in the order of their top left cells
+    pub fn merged_ranges(&self) -> &Vec<CellRange> {
+        &self.merged_ranges
+    }
+
+    /// The merged range which covers a cell
+    pub fn get_merged_range(&self, row_index: usize, column_index: usize) -> Option<&CellRange> {
+        self.merged_ranges.iter().find(|range| range.contains(row_index, column_index))
+    }
+
+    /// How the sheet is shown: panes, zoom, cursor and what is visible
+    pub fn get_view(&self) -> &SheetView {
+        &self.view
+    }
+
+    pub fn set_view(&mut self, view: SheetView) {
+        self.view = view;
+    }
+}
+
+/// Width of a column in characters, as spreadsheet applications show it.
+///
+/// ```
+/// let column = spsheet::Column::new().with_width(8.43).with_hidden(true);
+/// assert!(column.is_hidden());
+/// assert!(!column.is_auto_fit());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Column {
+    width: Option<f64>,
+    hidden: bool,
+    auto_fit: bool,
+}
+
+impl Column {
+    pub fn new() -> Column {
+        Column::default()
+    }
+
+    pub fn with_width(mut self, width: f64) -> Column {
+        self.width = Some(width);
+        self
+    }
+
+    pub fn with_hidden(mut self, hidden: bool) -> Column {
+        self.hidden = hidden;
+        self
+    }
+
+    /// The column is as wide as its contents
+    pub fn with_auto_fit(mut self, auto_fit: bool) -> Column {
+        self.auto_fit = auto_fit;
+        self
+    }
+
+    pub fn get_width(&self) -> Option<f64> {
+        self.width
+    }
+
+    pub fn is_hidden(&self) -> bool {
+        self.hidden
+    }
+
+    pub fn is_auto_fit(&self) -> bool {
+        self.auto_fit
+    }
+}
+
+/// Panes which keep a part of a sheet in place while the rest scrolls.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Pane {
+    /// The rows at the top and the columns at the left
+    Frozen { rows: usize, columns: usize },
+    /// The window is split at a distance from its top left corner in points, each part scrolls
+    Split { x: f64, y: f64 },
+}
+
+/// The view of a sheet.
+///
+/// ```
+/// use spsheet::{Pane, Sheet, SheetView};
+/// let mut sheet = Sheet::new("sheet1");
+/// sheet.set_view(SheetView::new().with_frozen(1, 0).with_zoom(150).with_grid_lines(false));
+/// assert_eq!(Some(Pane::Frozen { rows: 1, columns: 0 }), sheet.get_view().get_pane());
+/// assert_eq!(150, sheet.get_view().get_zoom());
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct SheetView {
+    pane: Option<Pane>,
+    zoom: u16,
+    active_cell: (usize, usize),
+    selection: Option<CellRange>,
+    grid_lines: bool,
+    headers: bool,
+    right_to_left: bool,
+}
+
+impl Default for SheetView {
+    fn default() -> SheetView {
+        SheetView::new()
+    }
+}
+
+impl SheetView {
+    pub fn new() -> SheetView {
+        SheetView {
+            pane: None,
+            zoom: 100,
+            active_cell: (0, 0),
+            selection: None,
+            grid_lines: true,
+            headers: true,
+            right_to_left: false,
+        }
+    }
+
+    /// Freezes the rows at the top and the columns at the left, none of both unfreezes
+    pub fn with_frozen(mut self, rows: usize, columns: usize) -> SheetView {
+        self.pane = if rows == 0 && columns == 0 { None } else { Some(Pane::Frozen { rows, columns }) };
+        self
+    }
+
+    pub fn with_split(mut self, x: f64, y: f64) -> SheetView {
+        self.pane = if x <= 0.0 && y <= 0.0 { None } else { Some(Pane::Split { x: x.max(0.0), y: y.max(0.0) }) };
+        self
+    }
+
+    /// Zoom in percent, from 10 to 400
+    pub fn with_zoom(mut self, zoom: u16) -> SheetView {
+        self.zoom = zoom.clamp(10, 400);
+        self
+    }
+
+    pub fn with_active_cell(mut self, row_index: usize, column_index: usize) -> SheetView {
+        self.active_cell = (row_index, column_index);
+        self
+    }
+
+    /// Cells selected around the active cell, ods files keep only the active cell
+    pub fn with_selection(mut self, range: CellRange) -> SheetView {
+        self.selection = Some(range);
+        self
+    }
+
+    pub fn with_grid_lines(mut self, grid_lines: bool) -> SheetView {
+        self.grid_lines = grid_lines;
+        self
+    }
+
+    /// Shows the column letters and row numbers
+    pub fn with_headers(mut self, headers: bool) -> SheetView {
+        self.headers = headers;
+        self
+    }
+
+    pub fn with_right_to_left(mut self, right_to_left: bool) -> SheetView {
+        self.right_to_left = right_to_left;
+        self
+    }
+
+    pub fn get_pane(&self) -> Option<Pane> {
+        self.pane
+    }
+
+    pub fn get_zoom(&self) -> u16 {
+        self.zoom
+    }
+
+    /// Row and column index of the cursor
+    pub fn get_active_cell(&self) -> (usize, usize) {
+        self.active_cell
+    }
+
+    pub fn get_selection(&self) -> Option<CellRange> {
+        self.selection
+    }
+
+    pub fn shows_grid_lines(&self) -> bool {
+        self.grid_lines
+    }
+
+    pub fn shows_headers(&self) -> bool {
+        self.headers
+    }
+
+    pub fn is_right_to_left(&self) -> bool {
+        self.right_to_left
+    }
+}
+
+/// Height of a row in points.
+///
+/// ```
+/// let row = spsheet::Row::new().with_height(30.0);
+/// assert_eq!(Some(30.0), row.get_height());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Row {
+    height: Option<f64>,
+    hidden: bool,
+    auto_fit: bool,
+}
+
+impl Row {
+    pub fn new() -> Row {
+        Row::default()
+    }
+
+    pub fn with_height(mut self, height: f64) -> Row {
+        self.height = Some(height);
+        self
+    }
+
+    pub fn with_hidden(mut self, hidden: bool) -> Row {
+        self.hidden = hidden;
+        self
+    }
+
+    /// The height follows the contents, the height is what they needed when it was saved
+    pub fn with_auto_fit(mut self, auto_fit: bool) -> Row {
+        self.auto_fit = auto_fit;
+        self
+    }
+
+    pub fn get_height(&self) -> Option<f64> {
+        self.height
+    }
+
+    pub fn is_hidden(&self) -> bool {
+        self.hidden
+    }
+
+    pub fn is_auto_fit(&self) -> bool {
+        self.auto_fit
+    }
+}
+
+/// Note left on a cell by its author, the text may have several lines.
+///
+/// ```
+/// let comment = spsheet::Comment::new("Reviewer", "check this total").with_visible(true);
+/// assert_eq!("Reviewer", comment.get_author());
+/// assert!(comment.is_visible());
+/// ```
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Comment {
+    author: String,
+    text: String,
+    visible: bool,
+}
+
+impl Comment {
+    pub fn new<'a, S>(author: S, text: S) -> Comment
+        where S: Into<Cow<'a, str>>
+    {
+        Comment {
+            author: author.into().into_owned(),
+            text: text.into().into_owned(),
+            visible: false,
+        }
+    }
+
+    /// Shown all the time instead of when the pointer is over the cell
+    pub fn with_visible(mut self, visible: bool) -> Comment {
+        self.visible = visible;
+        self
+    }
+
+    pub fn get_author(&self) -> &String {
+        &self.author
+    }
+
+    pub fn get_text(&self) -> &String {
+        &self.text
+    }
+
+    pub fn is_visible(&self) -> bool {
+        self.visible
+    }
+}
+
+/// Where a hyperlink goes, a URL or a cell of a sheet in the same book.
+#[derive(Debug, Clone, PartialEq)]
+pub enum LinkTarget {
+    Url(String),
+    Cell { sheet: String, row: usize, column: usize },
+}
+
+/// Link from a cell, with the tip shown when the pointer is over it.
+///
+/// ```
+/// use spsheet::{Hyperlink, LinkTarget};
+/// let link = Hyperlink::cell("Summary", 0, 1).with_tooltip("back to the summary");
+/// assert_eq!(&LinkTarget::Cell { sheet: String::from("Summary"), row: 0, column: 1 }, link.get_target());
+/// assert_eq!(None, Hyperlink::url("https://example.com/tickets/42").get_tooltip());
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Hyperlink {
+    target: LinkTarget,
+    tooltip: Option<String>,
+}
+
+impl Hyperlink {
+    pub fn new(target: LinkTarget) -> Hyperlink {
+        Hyperlink {
+            target,
+            tooltip: None,
+        }
+    }
+
+    pub fn url<'a, S>(url: S) -> Hyperlink
+        where S: Into<Cow<'a, str>>
+    {
+        Hyperlink::new(LinkTarget::Url(url.into().into_owned()))
+    }
+
+    pub fn cell<'a, S>(sheet: S, row_index: usize, column_index: usize) -> Hyperlink
+        where S: Into<Cow<'a, str>>
+    {
+        Hyperlink::new(LinkTarget::Cell { sheet: sheet.into().into_owned(), row: row_index, column: column_index })
+    }
+
+    pub fn with_tooltip<'a, S>(mut self, tooltip: S) -> Hyperlink
+        where S: Into<Cow<'a, str>>
+    {
+        self.tooltip = Some(tooltip.into().into_owned());
+        self
+    }
+
+    pub fn get_target(&self) -> &LinkTarget {
+        &self.target
+    }
+
+    pub fn get_tooltip(&self) -> Option<&String> {
+        self.tooltip.as_ref()
+    }
 }
 
 /// Cell has owner of value.
@@ -251,6 +823,10 @@ impl Sheet {
 pub struct Cell {
     value: Value,
     format: Format,
+    formula: Option<String>,
+    style: Style,
+    comment: Option<Comment>,
+    hyperlink: Option<Hyperlink>,
 }
 
 impl Cell {
@@ -258,11 +834,31 @@ impl Cell {
         where S: Into<Cow<'a, str>>
     {
         Cell {
-            value: value,
+            value,
             format: Format::new(content),
+            formula: None,
+            style: Style::new(),
+            comment: None,
+            hyperlink: None,
         }
     }
 
+    /// Formula cell with the value it evaluated to last time.
+    ///
+    /// ```
+    /// use spsheet::{Cell, Value};
+    /// let cell = Cell::formula("=SUM(A1:A3)", Value::Float(6.0));
+    /// assert_eq!(Some(&String::from("=SUM(A1:A3)")), cell.get_formula());
+    /// assert_eq!(&Value::Float(6.0), cell.get_value());
+    /// ```
+    pub fn formula<'a, S>(formula: S, cached_value: Value) -> Cell
+        where S: Into<Cow<'a, str>>
+    {
+        let mut cell = Cell::new(cached_value, "");
+        cell.set_formula(formula);
+        cell
+    }
+
     pub fn str<'a, S>(value: S, format: S) -> Cell 
         where S: Into<Cow<'a, str>>
     {
@@ -281,21 +877,107 @@ impl Cell {
         )
     }
 
-    pub fn date<'a, S>(value: S, format: S) -> Cell 
+    /// Amount of money in the currency with an ISO 4217 code such as `EUR`.
+    ///
+    /// ```
+    /// use spsheet::{Cell, Value};
+    /// let cell = Cell::currency(1234.5, "EUR", "#,##0.00 [$€-407]");
+    /// assert_eq!(&Value::Currency(1234.5, String::from("EUR")), cell.get_value());
+    /// assert_eq!(Some(String::from("1.234,50 €")), cell.get_formated_value());
+    /// ```
+    pub fn currency<'a, S>(value: f64, code: S, format: S) -> Cell
+        where S: Into<Cow<'a, str>>
+    {
+        Cell::new(
+            Value::Currency(value, code.into().into_owned()),
+            format
+        )
+    }
+
+    /// Ratio shown as a percentage, 0.125 is 12.5%. An empty format is `0%`.
+    pub fn percentage<'a, S>(value: f64, format: S) -> Cell
+        where S: Into<Cow<'a, str>>
+    {
+        Cell::new(
+            Value::Percentage(value),
+            default_format(format.into(), "0%")
+        )
+    }
+
+    /// Time of day such as `13:30:00` or `13:30:05.25`. An empty format is `HH:MM:SS`.
+    ///
+    /// ```
+    /// use spsheet::Cell;
+    /// assert_eq!(Some(String::from("1:30 PM")), Cell::time("13:30:00", "h:mm AM/PM").unwrap().get_formated_value());
+    /// assert_eq!(Some(String::from("0.5625")), Cell::time("13:30:00", "0.0000").unwrap().get_formated_value());
+    /// assert!(Cell::time("25:00:00", "").is_err());
+    /// ```
+    pub fn time<'a, S>(value: S, format: S) -> Result<Cell, chrono::ParseError>
+        where S: Into<Cow<'a, str>>
+    {
+        Ok(Cell::new(
+            Value::Time(value.into().parse::<NaiveTime>()?),
+            default_format(format.into(), "HH:MM:SS")
+        ))
+    }
+
+    /// Length of time, which may run over a day. An empty format is `[H]:MM:SS`.
+    ///
+    /// ```
+    /// use spsheet::Cell;
+    /// use chrono::Duration;
+    /// let cell = Cell::duration(Duration::minutes(2 * 24 * 60 + 90), "");
+    /// assert_eq!(Some(String::from("49:30:00")), cell.get_formated_value());
+    /// ```
+    pub fn duration<'a, S>(value: Duration, format: S) -> Cell
+        where S: Into<Cow<'a, str>>
+    {
+        Cell::new(
+            Value::Duration(value),
+            default_format(format.into(), "[H]:MM:SS")
+        )
+    }
+
+    pub fn error<'a, S>(kind: ErrorKind, format: S) -> Cell
+        where S: Into<Cow<'a, str>>
+    {
+        Cell::new(
+            Value::Error(kind),
+            format
+        )
+    }
+
+    pub fn bool<'a, S>(value: bool, format: S) -> Cell
         where S: Into<Cow<'a, str>>
     {
-        let mut str_value = value.into().into_owned();
-        let postfix = match str_value.find('T') {
-            Some(_) => "Z",
-            None => "T00:00:00Z"
-        };
-        str_value.push_str(postfix);
         Cell::new(
-            Value::Date(str_value.parse::<DateTime<Utc>>().unwrap()),
+            Value::Bool(value),
             format
         )
     }
 
+    /// Date such as `2017-12-02`, or date and time such as `2017-12-02T13:30:00`,
+    /// as the wall-clock time a spreadsheet shows.
+    ///
+    /// ```
+    /// use spsheet::{Cell, Value};
+    /// use chrono::NaiveDate;
+    /// let cell = Cell::date("2017-12-02", "YYYY/MM/DD").unwrap();
+    /// assert_eq!(&Value::Date(NaiveDate::from_ymd_opt(2017, 12, 2).unwrap().into()), cell.get_value());
+    /// assert!(Cell::date("2017-12-32", "").is_err());
+    /// ```
+    pub fn date<'a, S>(value: S, format: S) -> Result<Cell, chrono::ParseError>
+        where S: Into<Cow<'a, str>>
+    {
+        let value = value.into();
+        let dt = if value.contains('T') {
+            value.parse::<NaiveDateTime>()?
+        } else {
+            value.parse::<NaiveDate>()?.and_time(NaiveTime::MIN)
+        };
+        Ok(Cell::new(Value::Date(dt), format))
+    }
+
     pub fn get_value(&self) -> &Value {
         &self.value
     }
@@ -304,25 +986,280 @@ impl Cell {
         &self.format
     }
 
+    pub fn get_formula(&self) -> Option<&String> {
+        self.formula.as_ref()
+    }
+
+    pub fn set_formula<'a, S>(&mut self, formula: S)
+        where S: Into<Cow<'a, str>>
+    {
+        self.formula = Some(formula.into().into_owned());
+    }
+
+    pub fn get_style(&self) -> &Style {
+        &self.style
+    }
+
+    pub fn set_style(&mut self, style: Style) {
+        self.style = style;
+    }
+
+    pub fn get_comment(&self) -> Option<&Comment> {
+        self.comment.as_ref()
+    }
+
+    pub fn set_comment(&mut self, comment: Comment) {
+        self.comment = Some(comment);
+    }
+
+    pub fn remove_comment(&mut self) -> Option<Comment> {
+        self.comment.take()
+    }
+
+    pub fn get_hyperlink(&self) -> Option<&Hyperlink> {
+        self.hyperlink.as_ref()
+    }
+
+    pub fn set_hyperlink(&mut self, hyperlink: Hyperlink) {
+        self.hyperlink = Some(hyperlink);
+    }
+
+    pub fn remove_hyperlink(&mut self) -> Option<Hyperlink> {
+        self.hyperlink.take()
+    }
+
+    /// The value as the format shows it in a book of the 1900 date system.
     pub fn get_formated_value(&self) -> Option<String> {
+        self.get_formated_value_in(DateSystem::Date1900)
+    }
+
+    /// The value as the format shows it, serial numbers are dates of `system`.
+    ///
+    /// ```
+    /// use spsheet::{Cell, DateSystem};
+    /// let cell = Cell::float(0.0, "yyyy-mm-dd");
+    /// assert_eq!(Some(String::from("1904-01-01")), cell.get_formated_value_in(DateSystem::Date1904));
+    /// ```
+    pub fn get_formated_value_in(&self, system: DateSystem) -> Option<String> {
         match self.value {
             Value::Date(dt) => {
-                self.format.get_formated_date(&dt)
+                self.format.get_formated_date(&dt, system)
             },
-            _ => None,
+            Value::Float(value) | Value::Currency(value, _) | Value::Percentage(value) => {
+                if self.format.is_date_format() {
+                    system.serial_to_date(value).and_then(|dt| self.format.get_formated_date(&dt, system))
+                } else {
+                    Some(self.format.get_formated_number(value))
+                }
+            },
+            Value::Time(time) => self.get_formated_duration(time - NaiveTime::MIN, system),
+            Value::Duration(duration) => self.get_formated_duration(duration, system),
+            Value::Str(ref value) => Some(self.format.get_formated_text(value)),
+            Value::Error(kind) => Some(kind.to_string()),
+            Value::Bool(true) => Some(String::from("TRUE")),
+            Value::Bool(false) => Some(String::from("FALSE")),
+        }
+    }
+
+    // a time or duration counted from the start of the serial dates
+    fn get_formated_duration(&self, duration: Duration, system: DateSystem) -> Option<String> {
+        if self.format.is_date_format() {
+            system.serial_to_date(0.0).and_then(|start| self.format.get_formated_date(&(start + duration), system))
+        } else {
+            Some(self.format.get_formated_number(formula::duration_to_serial(&duration)))
         }
     }
+
+    /// The formated value with the color its format section asks for, e.g. `[Red]`.
+    ///
+    /// ```
+    /// use spsheet::Cell;
+    /// use spsheet::format::Color;
+    /// let cell = Cell::float(-5.0, "0;[Red]\\(0\\)");
+    /// assert_eq!(Some((String::from("(5)"), Some(Color::Red))), cell.get_formated_value_with_color());
+    /// ```
+    pub fn get_formated_value_with_color(&self) -> Option<(String, Option<format::Color>)> {
+        self.get_formated_value_with_color_in(DateSystem::Date1900)
+    }
+
+    /// The formated value and its color, serial numbers are dates of `system`.
+    pub fn get_formated_value_with_color_in(&self, system: DateSystem) -> Option<(String, Option<format::Color>)> {
+        match self.value {
+            Value::Float(value) | Value::Currency(value, _) | Value::Percentage(value) if !self.format.is_date_format() => {
+                Some(self.format.get_formated_number_with_color(value))
+            },
+            Value::Str(ref value) => Some(self.format.get_formated_text_with_color(value)),
+            _ => self.get_formated_value_in(system).map(|text| (text, None)),
+        }
+    }
+}
+
+// the format of a value type when none is given
+fn default_format<'a>(format: Cow<'a, str>, default: &'a str) -> Cow<'a, str> {
+    if format.is_empty() { Cow::Borrowed(default) } else { format }
 }
 
-/// Value has Str, Float, Data value.
+/// Value has Str, Float, Bool, Error, Data, Currency, Percentage, Time, Duration value.
 #[derive(Debug, Clone, PartialEq)]
 pub enum Value {
     /// String Value
     Str(String),
     /// Float Value
     Float(f64),
-    /// Data Value
-    Date(DateTime<Utc>),
-    /// Currency Value
-    Currency(f64),
-}
\ No newline at end of file
+    /// Bool Value
+    Bool(bool),
+    /// Error Value such as #DIV/0!
+    Error(ErrorKind),
+    /// Date Value, the wall-clock time without a zone
+    Date(NaiveDateTime),
+    /// Currency Value, the amount and an ISO 4217 code such as `EUR`
+    Currency(f64, String),
+    /// Percentage Value, 0.125 is 12.5%
+    Percentage(f64),
+    /// Time of day Value
+    Time(NaiveTime),
+    /// Duration Value, which may be longer than a day
+    Duration(Duration),
+}
+
+impl Value {
+    /// Date of a date and time from any time zone, kept as the wall-clock time there.
+    ///
+    /// ```
+    /// use chrono::{FixedOffset, TimeZone, Utc};
+    /// use spsheet::{Cell, Value};
+    /// let tokyo = FixedOffset::east_opt(9 * 3600).unwrap();
+    /// let dt = tokyo.with_ymd_and_hms(2017, 12, 2, 13, 30, 0).unwrap();
+    /// let value = Value::from_datetime(&dt);
+    /// assert_eq!(Some(String::from("2017/12/02 13:30")), Cell::new(value.clone(), "YYYY/MM/DD\\ HH:MM").get_formated_value());
+    /// assert_eq!(Some(dt), value.to_datetime(&tokyo));
+    /// assert_eq!(Utc.with_ymd_and_hms(2017, 12, 2, 13, 30, 0).single(), value.to_datetime(&Utc));
+    /// ```
+    pub fn from_datetime<Tz: TimeZone>(dt: &DateTime<Tz>) -> Value {
+        Value::Date(dt.naive_local())
+    }
+
+    /// The wall-clock time of a Date value placed in a time zone. `None` for
+    /// other values and for times a daylight saving change skips; of a time
+    /// which occurs twice, the earlier one.
+    pub fn to_datetime<Tz: TimeZone>(&self, tz: &Tz) -> Option<DateTime<Tz>> {
+        match self {
+            Value::Date(dt) => tz.from_local_datetime(dt).earliest(),
+            _ => None,
+        }
+    }
+}
+
+/// Error codes a cell can hold in place of a value.
+///
+/// ```
+/// use spsheet::ErrorKind;
+/// assert_eq!(Some(ErrorKind::Div0), ErrorKind::from_code("#DIV/0!"));
+/// assert_eq!("#N/A", ErrorKind::NA.code());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ErrorKind {
+    /// #NULL!
+    Null,
+    /// #DIV/0!
+    Div0,
+    /// #VALUE!
+    Value,
+    /// #REF!
+    Ref,
+    /// #NAME?
+    Name,
+    /// #NUM!
+    Num,
+    /// #N/A
+    NA,
+    /// #GETTING_DATA
+    GettingData,
+}
+
+impl ErrorKind {
+    pub fn code(&self) -> &'static str {
+        match self {
+            ErrorKind::Null => "#NULL!",
+            ErrorKind::Div0 => "#DIV/0!",
+            ErrorKind::Value => "#VALUE!",
+            ErrorKind::Ref => "#REF!",
+            ErrorKind::Name => "#NAME?",
+            ErrorKind::Num => "#NUM!",
+            ErrorKind::NA => "#N/A",
+            ErrorKind::GettingData => "#GETTING_DATA",
+        }
+    }
+
+    pub fn from_code(code: &str) -> Option<ErrorKind> {
+        match code {
+            "#NULL!" => Some(ErrorKind::Null),
+            "#DIV/0!" => Some(ErrorKind::Div0),
+            "#VALUE!" => Some(ErrorKind::Value),
+            "#REF!" => Some(ErrorKind::Ref),
+            "#NAME?" => Some(ErrorKind::Name),
+            "#NUM!" => Some(ErrorKind::Num),
+            "#N/A" => Some(ErrorKind::NA),
+            "#GETTING_DATA" => Some(ErrorKind::GettingData),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.code())
+    }
+}
+
+/// Epoch of the serial numbers dates are stored as.
+///
+/// ```
+/// use chrono::NaiveDate;
+/// use spsheet::DateSystem;
+/// let dt = NaiveDate::from_ymd_opt(2017, 12, 2).unwrap().and_hms_opt(13, 30, 0).unwrap();
+/// assert_eq!(43071.5625, DateSystem::Date1900.date_to_serial(&dt));
+/// assert_eq!(41609.5625, DateSystem::Date1904.date_to_serial(&dt));
+/// assert_eq!(Some(dt), DateSystem::Date1904.serial_to_date(41609.5625));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DateSystem {
+    /// 1 is 1900-01-01, and 60 the 1900-02-29 Lotus 1-2-3 believed in
+    #[default]
+    Date1900,
+    /// 0 is 1904-01-01
+    Date1904,
+}
+
+impl DateSystem {
+    // the day serial numbers count from, and the serial of 10000-01-01
+    fn epoch(&self) -> (NaiveDateTime, f64) {
+        let (epoch, end) = match self {
+            DateSystem::Date1900 => (NaiveDate::from_ymd_opt(1899, 12, 30), 2958466.0),
+            DateSystem::Date1904 => (NaiveDate::from_ymd_opt(1904, 1, 1), 2957004.0),
+        };
+        (epoch.unwrap().and_time(NaiveTime::MIN), end)
+    }
+
+    /// Serial number of a date, to the millisecond.
+    pub fn date_to_serial(&self, dt: &NaiveDateTime) -> f64 {
+        let (epoch, _) = self.epoch();
+        let serial = (*dt - epoch).num_milliseconds() as f64 / 86_400_000.0;
+        // before 1900-03-01 there is no 1900-02-29 to skip
+        if *self == DateSystem::Date1900 && serial < 61.0 { serial - 1.0 } else { serial }
+    }
+
+    /// Date of a serial number rounded to the millisecond, `None` before
+    /// serial 0 or from 10000-01-01 on. Serial 60 of the 1900 system is 1900-02-28.
+    pub fn serial_to_date(&self, serial: f64) -> Option<NaiveDateTime> {
+        let (epoch, end) = self.epoch();
+        if !(0.0..end).contains(&serial) {
+            return None;
+        }
+        let mut milliseconds = (serial * 86_400_000.0).round() as i64;
+        if *self == DateSystem::Date1900 && milliseconds < 60 * 86_400_000 {
+            milliseconds += 86_400_000;
+        }
+        epoch.checked_add_signed(Duration::milliseconds(milliseconds))
+    }
+}
@@ -1,25 +1,33 @@
 //! OpenDocument ods read and write
 extern crate quick_xml;
-extern crate tempdir;
 extern crate zip;
 
 use self::quick_xml::events::{BytesStart, Event};
 use self::quick_xml::Reader;
-use self::tempdir::TempDir;
-use super::{Book, Cell, Sheet, Value};
+use self::zip::{ZipArchive, ZipWriter};
+use super::{Book, Cell, CellRange, Column, Comment, DateSystem, Error, ErrorKind, Hyperlink, LinkTarget, Pane, Row, Sheet, SheetView, Value};
 use crate::file_common::*;
+use crate::format::{Color, Item, Locale};
+use crate::style::{Alignment, Border, BorderLine, BorderStyle, Fill, Font, HorizontalAlignment, Rgb, Style, Underline, VerticalAlignment};
+use chrono::Duration;
+use std::collections::HashMap;
 use std::fs::File;
-use std::io;
-use std::io::BufReader;
+use std::io::{BufRead, Cursor, Read, Seek, Write};
 use std::path::Path;
 use std::result;
-use std::string::FromUtf8Error;
 
 mod read_content;
+mod read_settings;
 mod read_style;
 mod write_content;
+mod write_settings;
 mod write_style;
 
+// the column width and row height of LibreOffice, 10.86 characters and 12.8pt in xlsx
+const DEFAULT_COLUMN_WIDTH_MM: f64 = 22.58;
+const DEFAULT_ROW_HEIGHT_MM: f64 = 4.52;
+const POINTS_PER_CHARACTER: f64 = DEFAULT_COLUMN_WIDTH_MM * 72.0 / 25.4 / 10.86;
+
 const MANIFEST_XML_CONTENT: &'static str = r#"<?xml version="1.0" encoding="UTF-8"?>
 <manifest:manifest xmlns:manifest="urn:oasis:names:tc:opendocument:xmlns:manifest:1.0" manifest:version="1.2">
  <manifest:file-entry manifest:full-path="/" manifest:version="1.2" manifest:media-type="application/vnd.oasis.opendocument.spreadsheet"/>
@@ -32,76 +40,57 @@ const MANIFEST_XML_CONTENT: &'static str = r#"<?xml version="1.0" encoding="UTF-
  <manifest:file-entry manifest:full-path="meta.xml" manifest:media-type="text/xml"/>
 </manifest:manifest>"#;
 
-#[derive(Debug)]
-pub enum OdsError {
-    Io(io::Error),
-    Xml(quick_xml::Error),
-    Zip(zip::result::ZipError),
-    Uft8(FromUtf8Error),
-}
+type Result<T> = result::Result<T, Error>;
 
-impl From<io::Error> for OdsError {
-    fn from(err: io::Error) -> OdsError {
-        OdsError::Io(err)
-    }
+pub fn read(path: &Path) -> Result<Book> {
+    read_from(File::open(path)?)
 }
 
-impl From<quick_xml::Error> for OdsError {
-    fn from(err: quick_xml::Error) -> OdsError {
-        OdsError::Xml(err)
-    }
+/// Read a book from any seekable stream, e.g. an uploaded file.
+pub fn read_from<R: Read + Seek>(reader: R) -> Result<Book> {
+    read_archive(&mut ZipArchive::new(reader)?)
 }
 
-impl From<zip::result::ZipError> for OdsError {
-    fn from(err: zip::result::ZipError) -> OdsError {
-        OdsError::Zip(err)
-    }
+pub(crate) fn read_archive<R: Read + Seek>(zip: &mut ZipArchive<R>) -> Result<Book> {
+    let style_content = read_style::read(zip)?;
+    let mut book = read_content::read(zip, &style_content)?;
+    read_settings::read(zip, &mut book)?;
+    Ok(book)
 }
 
-impl From<FromUtf8Error> for OdsError {
-    fn from(err: FromUtf8Error) -> OdsError {
-        OdsError::Uft8(err)
-    }
+/// Read a book from an in-memory ods file.
+pub fn read_from_bytes(bytes: &[u8]) -> Result<Book> {
+    read_from(Cursor::new(bytes))
 }
 
-type Result<T> = result::Result<T, OdsError>;
+pub fn write(book: &Book, path: &Path) -> Result<()> {
+    write_to(book, File::create(path)?)
+}
 
-pub fn read(path: &Path) -> Result<Book> {
-    let file = File::open(path)?;
-    let dir = TempDir::new("shreadsheet")?;
-    match unzip(&file, &dir) {
-        Ok(_) => {}
-        Err(err) => {
-            dir.close()?;
-            return Err(OdsError::Zip(err));
-        }
-    }
-    let style_content = read_style::read(&dir).unwrap();
-    let book = read_content::read(&dir, &style_content);
-    dir.close()?;
-    book
-}
-
-pub fn write(book: &Book, path: &Path) -> result::Result<(), OdsError> {
-    let dir = TempDir::new("shreadsheet")?;
-    let _ = write_style::write(book, &dir);
-    let _ = write_content::write(book, &dir);
-    let _ = make_static_file(
-        &dir,
-        "META-INF/manifest.xml",
-        MANIFEST_XML_CONTENT,
-        Some("META-INF"),
-    )?;
-    write_to_file(path, &dir)?;
-    dir.close()?;
+/// Write a book to any seekable stream, e.g. a response body buffer.
+pub fn write_to<W: Write + Seek>(book: &Book, writer: W) -> Result<()> {
+    let mut zip = ZipWriter::new(writer);
+    make_mimetype_file(&mut zip, ODS_MIMETYPE)?;
+    write_style::write(book, &mut zip)?;
+    write_content::write(book, &mut zip)?;
+    write_settings::write(book, &mut zip)?;
+    make_static_file(&mut zip, "META-INF/manifest.xml", MANIFEST_XML_CONTENT)?;
+    zip.finish()?;
     Ok(())
 }
 
+/// Write a book into an in-memory ods file.
+pub fn write_to_bytes(book: &Book) -> Result<Vec<u8>> {
+    let mut cursor = Cursor::new(Vec::new());
+    write_to(book, &mut cursor)?;
+    Ok(cursor.into_inner())
+}
+
 fn read_number_format(
     e: &BytesStart,
     long_value: &str,
     short_value: &str,
-) -> result::Result<String, OdsError> {
+) -> result::Result<String, Error> {
     let mut number_style = String::from("");
     for a in e.attributes().with_checks(false) {
         match a {
@@ -119,7 +108,25 @@ fn read_number_format(
     }))
 }
 
-fn read_number_year(e: &BytesStart) -> result::Result<String, OdsError> {
+fn read_number_seconds(e: &BytesStart) -> result::Result<String, Error> {
+    let mut seconds = read_number_format(e, "SS", "S")?;
+    for a in e.attributes().with_checks(false) {
+        match a {
+            Ok(ref attr) if attr.key == b"number:decimal-places" => {
+                let places = get_attribute_value(attr)?.parse::<usize>().unwrap_or(0);
+                if places > 0 {
+                    seconds.push('.');
+                    seconds.push_str(&"0".repeat(places));
+                }
+            }
+            Ok(_) => {}
+            Err(_) => {}
+        }
+    }
+    Ok(seconds)
+}
+
+fn read_number_year(e: &BytesStart) -> result::Result<String, Error> {
     let mut number_style = String::from("");
     let mut number_calendar = String::from("");
     for a in e.attributes().with_checks(false) {
@@ -147,9 +154,14 @@ fn read_number_year(e: &BytesStart) -> result::Result<String, OdsError> {
     ))
 }
 
-fn read_number_date_style(
-    reader: &mut Reader<BufReader<File>>,
-) -> result::Result<String, OdsError> {
+// number:date-style or number:time-style as a format code, elapsed is set when
+// the time style has number:truncate-on-overflow="false", e.g. [HH]:MM
+fn read_number_date_style<B: BufRead>(
+    reader: &mut Reader<B>,
+    part: &str,
+    elapsed: bool,
+) -> result::Result<String, Error> {
+    let mut elapsed = elapsed;
     let mut buf = Vec::new();
     let mut style_format = String::from("");
     let mut text_empty_flag = true;
@@ -167,7 +179,7 @@ fn read_number_date_style(
                         style_format.push_str("\\ ");
                     }
                 }
-                b"number:date-style" => {
+                b"number:date-style" | b"number:time-style" => {
                     return Ok(style_format);
                 }
                 _ => (),
@@ -180,13 +192,29 @@ fn read_number_date_style(
                     b"number:day" => read_number_format(e, "DD", "D"),
                     b"number:hours" => read_number_format(e, "HH", "H"),
                     b"number:minutes" => read_number_format(e, "MM", "M"),
-                    b"number:seconds" => read_number_format(e, "SS", "S"),
+                    b"number:seconds" => read_number_seconds(e),
+                    b"number:am-pm" => Ok(String::from("AM/PM")),
                     _ => Ok(String::from("")),
-                };
-                style_format.push_str(added_string?.as_str());
+                }?;
+                // only the first unit of the time runs over
+                let time_unit = matches!(
+                    e.name(),
+                    b"number:hours" | b"number:minutes" | b"number:seconds");
+                if elapsed && time_unit {
+                    let end = added_string.find('.').unwrap_or(added_string.len());
+                    let (unit, fraction) = added_string.split_at(end);
+                    style_format.push_str(&format!("[{}]{}", unit, fraction));
+                    elapsed = false;
+                } else {
+                    style_format.push_str(added_string.as_str());
+                }
             }
             Ok(Event::Text(e)) => {
-                match e.unescape_and_decode(&reader).unwrap().as_str() {
+                let position = reader.buffer_position();
+                let text = e
+                    .unescape_and_decode(reader)
+                    .map_err(|err| Error::xml(part, position, err))?;
+                match text.as_str() {
                     "/" => style_format.push_str("/"),
                     ":" => style_format.push_str(":"),
                     other => {
@@ -203,7 +231,230 @@ fn read_number_date_style(
                 text_empty_flag = false;
             }
             Ok(Event::Eof) => break,
-            Err(e) => panic!("Error at position {}: {:?}", reader.buffer_position(), e),
+            Err(e) => return Err(Error::xml(part, reader.buffer_position(), e)),
+            _ => (),
+        }
+
+        // if we don't keep a borrow elsewhere, we can clear the buffer to keep memory usage low
+        buf.clear();
+    }
+    Ok(String::from(""))
+}
+
+// office:time-value such as PT13H30M00S, the hours run over a day: PT49H30M05S
+fn duration_to_iso(duration: &Duration) -> String {
+    let sign = if *duration < Duration::zero() { "-" } else { "" };
+    let milliseconds = duration.num_milliseconds().abs();
+    let seconds = milliseconds / 1000;
+    let fraction = match milliseconds % 1000 {
+        0 => String::new(),
+        millisecond => format!(".{:03}", millisecond).trim_end_matches('0').to_string(),
+    };
+    format!("{}PT{:02}H{:02}M{:02}{}S", sign, seconds / 3600, seconds / 60 % 60, seconds % 60, fraction)
+}
+
+// ISO 8601 duration with days, hours, minutes and seconds: -P1DT2H30M15.5S
+fn iso_to_duration(value: &str) -> Option<Duration> {
+    let (negative, value) = match value.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, value),
+    };
+    let mut rest = value.strip_prefix('P')?;
+    let mut milliseconds = 0.0;
+    let mut time = false;
+    while !rest.is_empty() {
+        if let Some(after) = rest.strip_prefix('T') {
+            time = true;
+            rest = after;
+            continue;
+        }
+        let end = rest.find(|c: char| !c.is_ascii_digit() && c != '.')?;
+        let number = rest[..end].parse::<f64>().ok()?;
+        let unit = match (rest[end..].chars().next()?, time) {
+            ('D', false) => 86_400_000.0,
+            ('H', true) => 3_600_000.0,
+            ('M', true) => 60_000.0,
+            ('S', true) => 1000.0,
+            _ => return None,
+        };
+        milliseconds += number * unit;
+        rest = &rest[end + 1..];
+    }
+    let duration = Duration::milliseconds(milliseconds.round() as i64);
+    Some(if negative { -duration } else { duration })
+}
+
+// number:number as digit placeholders, e.g. #,##0.00
+fn read_number_number(e: &BytesStart) -> result::Result<String, Error> {
+    let mut decimal_places = 0;
+    let mut integer_digits = 0;
+    let mut grouping = false;
+    let mut factor: u64 = 1;
+    for a in e.attributes().with_checks(false) {
+        match a {
+            Ok(ref attr) if attr.key == b"number:decimal-places" => {
+                decimal_places = get_attribute_value(attr)?.parse::<usize>().unwrap_or(0);
+            }
+            Ok(ref attr) if attr.key == b"number:min-integer-digits" => {
+                integer_digits = get_attribute_value(attr)?.parse::<usize>().unwrap_or(0);
+            }
+            Ok(ref attr) if attr.key == b"number:grouping" => {
+                grouping = get_attribute_value(attr)? == "true";
+            }
+            Ok(ref attr) if attr.key == b"number:display-factor" => {
+                factor = get_attribute_value(attr)?.parse::<u64>().unwrap_or(1);
+            }
+            Ok(_) => {}
+            Err(_) => {}
+        }
+    }
+    let mut code = if grouping {
+        let width = integer_digits.max(4);
+        let mut digits = "#".repeat(width - integer_digits) + &"0".repeat(integer_digits);
+        digits.insert(width - 3, ',');
+        digits
+    } else if integer_digits == 0 {
+        String::from("#")
+    } else {
+        "0".repeat(integer_digits)
+    };
+    if decimal_places > 0 {
+        code.push('.');
+        code.push_str(&"0".repeat(decimal_places));
+    }
+    while factor >= 1000 {
+        code.push(',');
+        factor /= 1000;
+    }
+    Ok(code)
+}
+
+// [Red] for fo:color="#FF0000", nothing for a color outside the palette
+fn read_color(value: &str) -> Option<String> {
+    let rgb = u32::from_str_radix(value.trim_start_matches('#'), 16).ok()?;
+    let named = [Color::Black, Color::Blue, Color::Cyan, Color::Green,
+        Color::Magenta, Color::Red, Color::White, Color::Yellow];
+    named.into_iter().chain((1..=56).map(Color::Indexed))
+        .find(|color| color.get_rgb() == rgb)
+        .map(|color| format!("[{}]", color))
+}
+
+// number:currency-style or number:percentage-style as a format code, the style:map
+// of the negative, zero or conditional sections apply styles found in sections
+fn read_number_style<B: BufRead>(
+    reader: &mut Reader<B>,
+    part: &str,
+    sections: &HashMap<String, String>,
+) -> result::Result<String, Error> {
+    let mut buf = Vec::new();
+    let mut style_format = String::from("");
+    let mut color = String::from("");
+    let mut maps: Vec<(String, String)> = vec![];
+    let mut element: Vec<u8> = vec![];
+    let mut text_empty_flag = true;
+    let mut symbol = String::from("");
+    let mut locale = String::from("");
+    loop {
+        let position = reader.buffer_position();
+        match reader.read_event(&mut buf) {
+            Ok(Event::Start(ref e)) => {
+                element = e.name().to_vec();
+                match e.name() {
+                    b"number:number" => style_format.push_str(&read_number_number(e)?),
+                    b"number:text" => text_empty_flag = true,
+                    b"number:currency-symbol" => {
+                        symbol = String::from("");
+                        let mut language = String::from("");
+                        let mut country = String::from("");
+                        for a in e.attributes().with_checks(false) {
+                            match a {
+                                Ok(ref attr) if attr.key == b"number:language" => {
+                                    language = get_attribute_value(attr)?;
+                                }
+                                Ok(ref attr) if attr.key == b"number:country" => {
+                                    country = get_attribute_value(attr)?;
+                                }
+                                Ok(_) => {}
+                                Err(_) => {}
+                            }
+                        }
+                        let name = format!("{}-{}", language, country);
+                        locale = match Locale::find(&name) {
+                            _ if language.is_empty() => String::from(""),
+                            Some(found) => format!("{:X}", found.get_lcid()),
+                            None => name,
+                        };
+                    }
+                    _ => (),
+                }
+            }
+            Ok(Event::End(ref e)) => {
+                match e.name() {
+                    b"number:text" if text_empty_flag => style_format.push(' '),
+                    b"number:currency-symbol" => {
+                        let locale = if locale.is_empty() { locale.clone() } else { format!("-{}", locale) };
+                        style_format.push_str(&format!("[${}{}]", symbol, locale));
+                    }
+                    b"number:currency-style" | b"number:percentage-style" => {
+                        return Ok(join_number_sections(format!("{}{}", color, style_format), maps, sections));
+                    }
+                    _ => (),
+                }
+                element.clear();
+            }
+            Ok(Event::Empty(ref e)) => {
+                match e.name() {
+                    b"number:number" => style_format.push_str(&read_number_number(e)?),
+                    b"style:text-properties" => {
+                        for a in e.attributes().with_checks(false) {
+                            match a {
+                                Ok(ref attr) if attr.key == b"fo:color" => {
+                                    color = read_color(&get_attribute_value(attr)?).unwrap_or_default();
+                                }
+                                Ok(_) => {}
+                                Err(_) => {}
+                            }
+                        }
+                    }
+                    b"style:map" => {
+                        let mut condition = String::from("");
+                        let mut style = String::from("");
+                        for a in e.attributes().with_checks(false) {
+                            match a {
+                                Ok(ref attr) if attr.key == b"style:condition" => {
+                                    condition = get_attribute_value(attr)?;
+                                }
+                                Ok(ref attr) if attr.key == b"style:apply-style-name" => {
+                                    style = get_attribute_value(attr)?;
+                                }
+                                Ok(_) => {}
+                                Err(_) => {}
+                            }
+                        }
+                        maps.push((condition, style));
+                    }
+                    _ => (),
+                }
+            }
+            Ok(Event::Text(e)) => {
+                let text = e
+                    .unescape_and_decode(reader)
+                    .map_err(|err| Error::xml(part, position, err))?;
+                match element.as_slice() {
+                    b"number:text" if text == "%" => {
+                        style_format.push('%');
+                        text_empty_flag = false;
+                    }
+                    b"number:text" => {
+                        style_format.push_str(&Item::Literal(text).to_string());
+                        text_empty_flag = false;
+                    }
+                    b"number:currency-symbol" => symbol = text,
+                    _ => (),
+                }
+            }
+            Ok(Event::Eof) => break,
+            Err(e) => return Err(Error::xml(part, reader.buffer_position(), e)),
             _ => (),
         }
 
@@ -212,3 +463,227 @@ fn read_number_date_style(
     }
     Ok(String::from(""))
 }
+
+// main;negative;zero, or the conditional sections followed by the main one
+fn join_number_sections(
+    main: String,
+    maps: Vec<(String, String)>,
+    sections: &HashMap<String, String>,
+) -> String {
+    let mut negative = None;
+    let mut zero = None;
+    let mut conditional = vec![];
+    for (condition, style) in maps {
+        let section = match sections.get(&style) {
+            Some(section) => section.clone(),
+            None => continue,
+        };
+        match condition.trim_start_matches("value()") {
+            "<0" => negative = Some(section),
+            "=0" => zero = Some(section),
+            other => conditional.push(format!("[{}]{}", other.replace("!=", "<>"), section)),
+        }
+    }
+    if !conditional.is_empty() {
+        conditional.push(main);
+        return conditional.join(";");
+    }
+    match (negative, zero) {
+        (None, None) => main,
+        (Some(negative), None) => format!("{};{}", main, negative),
+        (negative, Some(zero)) => format!("{};{};{}", main, negative.unwrap_or_else(|| main.clone()), zero),
+    }
+}
+
+// a length such as 0.74pt, 0.26mm or 0.1in in points
+fn length_to_points(value: &str) -> Option<f64> {
+    let unit = value.find(|c: char| c.is_ascii_alphabetic())?;
+    let number = value[..unit].parse::<f64>().ok()?;
+    match &value[unit..] {
+        "pt" => Some(number),
+        "mm" => Some(number * 72.0 / 25.4),
+        "cm" => Some(number * 72.0 / 2.54),
+        "in" => Some(number * 72.0),
+        "px" => Some(number * 0.75),
+        _ => None,
+    }
+}
+
+// fo:border such as "0.74pt solid #000000", the widths of ods back to the border styles of xlsx
+fn read_border_line(value: &str) -> Option<BorderLine> {
+    let mut width = 0.74;
+    let mut line = "none";
+    let mut color = Rgb(0, 0, 0);
+    for part in value.split_whitespace() {
+        if let Some(points) = length_to_points(part) {
+            width = points;
+        } else if let Some(rgb) = part.strip_prefix('#').and_then(Rgb::from_hex) {
+            color = rgb;
+        } else {
+            line = part;
+        }
+    }
+    let style = match line {
+        "solid" if width < 0.5 => BorderStyle::Hair,
+        "solid" if width < 1.25 => BorderStyle::Thin,
+        "solid" if width < 2.1 => BorderStyle::Medium,
+        "solid" => BorderStyle::Thick,
+        "dotted" => BorderStyle::Dotted,
+        "dashed" | "fine-dashed" if width < 1.25 => BorderStyle::Dashed,
+        "dashed" | "fine-dashed" => BorderStyle::MediumDashed,
+        "dash-dot" if width < 1.25 => BorderStyle::DashDot,
+        "dash-dot" => BorderStyle::MediumDashDot,
+        "dash-dot-dot" if width < 1.25 => BorderStyle::DashDotDot,
+        "dash-dot-dot" => BorderStyle::MediumDashDotDot,
+        "double" | "double-thin" => BorderStyle::Double,
+        _ => return None,
+    };
+    Some(BorderLine::new(style, color))
+}
+
+// the properties of a style:style up to its end tag as a cell style
+// the attributes of the properties of a style:style, up to its end
+fn read_style_properties<B: BufRead>(reader: &mut Reader<B>, part: &str) -> result::Result<HashMap<Vec<u8>, String>, Error> {
+    let mut buf = Vec::new();
+    let mut properties: HashMap<Vec<u8>, String> = HashMap::new();
+    loop {
+        match reader.read_event(&mut buf) {
+            Ok(Event::Start(ref e)) | Ok(Event::Empty(ref e))
+                if e.name().starts_with(b"style:") && e.name().ends_with(b"-properties") => {
+                for attr in e.attributes().with_checks(false).flatten() {
+                    properties.insert(attr.key.to_vec(), get_attribute_value(&attr)?);
+                }
+            },
+            Ok(Event::End(ref e)) if e.name() == b"style:style" => break,
+            Ok(Event::Eof) => break,
+            Err(e) => return Err(Error::xml(part, reader.buffer_position(), e)),
+            _ => (),
+        }
+        buf.clear();
+    }
+    Ok(properties)
+}
+
+fn read_cell_style(properties: &HashMap<Vec<u8>, String>) -> Style {
+    let property = |name: &str| properties.get(name.as_bytes()).map(|value| value.as_str());
+
+    let mut style = Style::new();
+    let mut font = Font::new();
+    if let Some(name) = property("style:font-name").or(property("fo:font-family")) {
+        font = font.with_name(name.trim_matches('\''));
+    }
+    if let Some(size) = property("fo:font-size").and_then(length_to_points) {
+        font = font.with_size(size);
+    }
+    if property("fo:font-weight") == Some("bold") {
+        font = font.with_bold(true);
+    }
+    if property("fo:font-style") == Some("italic") {
+        font = font.with_italic(true);
+    }
+    match property("style:text-underline-style") {
+        None | Some("none") => {},
+        Some(_) if property("style:text-underline-type") == Some("double") => font = font.with_underline(Underline::Double),
+        Some(_) => font = font.with_underline(Underline::Single),
+    }
+    if let Some(color) = property("fo:color").and_then(Rgb::from_hex) {
+        font = font.with_color(color);
+    }
+    if font != Font::default() {
+        style = style.with_font(font);
+    }
+    if let Some(color) = property("fo:background-color").and_then(Rgb::from_hex) {
+        style = style.with_fill(Fill::Solid(color));
+    }
+
+    let mut border = Border::new();
+    if let Some(line) = property("fo:border").and_then(read_border_line) {
+        border = Border::all(line);
+    }
+    if let Some(line) = property("fo:border-left").and_then(read_border_line) {
+        border = border.with_left(line);
+    }
+    if let Some(line) = property("fo:border-right").and_then(read_border_line) {
+        border = border.with_right(line);
+    }
+    if let Some(line) = property("fo:border-top").and_then(read_border_line) {
+        border = border.with_top(line);
+    }
+    if let Some(line) = property("fo:border-bottom").and_then(read_border_line) {
+        border = border.with_bottom(line);
+    }
+    style = style.with_border(border);
+
+    let mut alignment = Alignment::new();
+    let horizontal = match property("fo:text-align") {
+        _ if property("style:repeat-content") == Some("true") => Some(HorizontalAlignment::Fill),
+        Some("start") | Some("left") => Some(HorizontalAlignment::Left),
+        Some("center") => Some(HorizontalAlignment::Center),
+        Some("end") | Some("right") => Some(HorizontalAlignment::Right),
+        Some("justify") => Some(HorizontalAlignment::Justify),
+        _ => None,
+    };
+    if let Some(horizontal) = horizontal {
+        alignment = alignment.with_horizontal(horizontal);
+    }
+    let vertical = match property("style:vertical-align") {
+        Some("top") => Some(VerticalAlignment::Top),
+        Some("middle") => Some(VerticalAlignment::Center),
+        Some("bottom") => Some(VerticalAlignment::Bottom),
+        _ => None,
+    };
+    if let Some(vertical) = vertical {
+        alignment = alignment.with_vertical(vertical);
+    }
+    if property("fo:wrap-option") == Some("wrap") {
+        alignment = alignment.with_wrap(true);
+    }
+    // counterclockwise from 0 to 359, turns past 90 are upside down and become the nearest turn
+    if let Some(angle) = property("style:rotation-angle").and_then(|angle| angle.trim_end_matches("deg").parse::<f64>().ok()) {
+        let angle = (angle.round() as i16).rem_euclid(360);
+        alignment = alignment.with_rotation(if angle > 180 { angle - 360 } else { angle });
+    }
+    // an indent level is 10pt
+    if let Some(margin) = property("fo:margin-left").and_then(length_to_points) {
+        alignment = alignment.with_indent((margin / 10.0).round() as u8);
+    }
+    style.with_alignment(alignment)
+}
+
+// a column of the default width of LibreOffice has no width
+fn read_column_style(properties: &HashMap<Vec<u8>, String>) -> Column {
+    let property = |name: &str| properties.get(name.as_bytes()).map(|value| value.as_str());
+    let mut column = Column::new();
+    if let Some(width) = property("style:column-width").and_then(length_to_points) {
+        let width = round_hundredths(width / POINTS_PER_CHARACTER);
+        if width != round_hundredths(DEFAULT_COLUMN_WIDTH_MM * 72.0 / 25.4 / POINTS_PER_CHARACTER) {
+            column = column.with_width(width);
+        }
+    }
+    column.with_auto_fit(property("style:use-optimal-column-width") == Some("true"))
+}
+
+// an optimal row of the default height is a row of the default style
+fn read_row_style(properties: &HashMap<Vec<u8>, String>) -> Row {
+    let property = |name: &str| properties.get(name.as_bytes()).map(|value| value.as_str());
+    let auto_fit = property("style:use-optimal-row-height") != Some("false");
+    match property("style:row-height").and_then(length_to_points).map(round_hundredths) {
+        Some(height) if !auto_fit => Row::new().with_height(height),
+        Some(height) if height != round_hundredths(DEFAULT_ROW_HEIGHT_MM * 72.0 / 25.4) =>
+            Row::new().with_height(height).with_auto_fit(true),
+        _ => Row::new(),
+    }
+}
+
+// settings.xml counts split positions in pixels of 96 dpi
+fn points_to_pixels(points: f64) -> usize {
+    (points * 96.0 / 72.0).round() as usize
+}
+
+fn pixels_to_points(pixels: usize) -> f64 {
+    pixels as f64 * 72.0 / 96.0
+}
+
+fn round_hundredths(value: f64) -> f64 {
+    (value * 100.0).round() / 100.0
+}
@@ -1,20 +1,32 @@
-use super::{Book,Sheet,Cell};
+use super::{Book,Sheet,SheetView,Cell,CellRange,Column,Comment,Hyperlink,Row,Value,DateSystem,Error,ErrorKind};
+use crate::format::Format;
+use crate::style::Style;
 use crate::file_common::*;
-use super::quick_xml::Reader;
-use super::quick_xml::events::{Event};
-use super::tempdir::TempDir;
+use crate::formula;
+use super::quick_xml::events::{BytesStart, Event};
 use super::{Result};
 use super::read_style::StyleContent;
 use std::collections::HashMap;
+use chrono::prelude::*;
+use crate::index_to_column;
+use std::io::{Read, Seek};
+use super::zip::ZipArchive;
 
 const CONTENT_XML: &'static str = "content.xml";
+/// The fewest rows a sheet has; LibreOffice fills a sheet up to its last row
+/// (65536, 1048576 or more) with one repeated empty row.
+const SHEET_ROWS: usize = 65536;
 
-pub fn read(dir: &TempDir, style_content: &StyleContent) -> Result<Book> {
+pub fn read<R: Read + Seek>(zip: &mut ZipArchive<R>, style_content: &StyleContent) -> Result<Book> {
     let mut date_style_map = HashMap::new();
     let mut style_map_for_date: HashMap<String, String> = HashMap::new();
+    let mut cell_styles: HashMap<String, Style> = HashMap::new();
+    let mut column_styles: HashMap<String, Column> = HashMap::new();
+    let mut row_styles: HashMap<String, Row> = HashMap::new();
+    // table styles written from right to left
+    let mut right_to_left_styles: Vec<String> = Vec::new();
 
-    let path = dir.path().join(CONTENT_XML);
-    let mut reader = Reader::from_file(path)?;
+    let mut reader = open_part(zip, CONTENT_XML)?;
     reader.trim_text(true);
     let mut book = Book::new();
 
@@ -24,27 +36,114 @@ pub fn read(dir: &TempDir, style_content: &StyleContent) -> Result<Book> {
     let mut row: usize = 0;
     let mut column: usize = 0;
     let mut cell_type: String = String::from("");
+    let mut calc_type: String = String::from("");
+    let mut formula: Option<String> = None;
     let mut float_value: f64 = 0.0;
     let mut str_value: String = String::from("");
     let mut date_value: String = String::from("");
+    let mut bool_value: String = String::from("");
+    let mut currency_code: String = String::from("");
+    let mut time_value: String = String::from("");
     let mut table_style_name: String = String::from("");
+    // the next table:table-column, and the settings and repeat count of the row being read
+    let mut table_column: usize = 0;
+    let mut row_setting = Row::new();
+    let mut rows_repeated: usize = 1;
+    let mut row_has_cells = false;
+    let mut columns_repeated: usize = 1;
+    // the office:annotation of the cell being read, its author and lines are collected apart from the value
+    let mut comment: Option<Comment> = None;
+    let mut in_annotation = false;
+    let mut annotation_visible = false;
+    let mut in_creator = false;
+    let mut annotation_author = String::from("");
+    let mut annotation_lines: Vec<String> = Vec::new();
+    // paragraphs of a cell are lines of its text, read without trimming
+    let mut in_cell = false;
+    let mut in_paragraph = false;
+    let mut paragraphs: usize = 0;
+    let mut hyperlink: Option<Hyperlink> = None;
 
     loop {
+        let position = reader.buffer_position();
         match reader.read_event(&mut buf) {
+            Ok(Event::Start(ref e)) if in_annotation => {
+                match e.name() {
+                    b"dc:creator" => in_creator = true,
+                    b"text:p" => annotation_lines.push(String::from("")),
+                    _ => (),
+                }
+            },
+            Ok(Event::Empty(ref e)) if in_annotation && e.name() == b"text:p" => {
+                annotation_lines.push(String::from(""));
+            },
+            Ok(Event::Empty(_)) if in_annotation => (),
+            Ok(Event::Text(e)) if in_annotation => {
+                let text = e.unescape_and_decode(&reader)
+                    .map_err(|err| Error::xml(CONTENT_XML, position, err))?;
+                // dc:date and the like are left out
+                if in_creator {
+                    annotation_author.push_str(&text);
+                } else if let Some(line) = annotation_lines.last_mut() {
+                    line.push_str(&text);
+                }
+            },
+            Ok(Event::End(ref e)) if in_annotation => {
+                match e.name() {
+                    b"dc:creator" => in_creator = false,
+                    b"office:annotation" => {
+                        in_annotation = false;
+                        comment = Some(Comment::new(annotation_author.clone(), annotation_lines.join("\n"))
+                            .with_visible(annotation_visible));
+                    },
+                    _ => (),
+                }
+            },
             Ok(Event::Start(ref e)) => {
                 match e.name() {
+                    b"text:p" if in_cell => {
+                        if paragraphs > 0 {
+                            str_value.push('\n');
+                        }
+                        paragraphs += 1;
+                        in_paragraph = true;
+                        reader.trim_text(false);
+                    },
+                    b"text:a" if in_paragraph => {
+                        hyperlink = read_hyperlink(e)?;
+                    },
+                    b"office:annotation" => {
+                        in_annotation = true;
+                        annotation_author.clear();
+                        annotation_lines.clear();
+                        annotation_visible = read_attribute(e, b"office:display")?.is_some_and(|display| display == "true");
+                    },
                     b"table:table" => {
                         for a in e.attributes().with_checks(false) {
                             match a {
                                 Ok(ref attr) if attr.key == b"table:name" => {
                                     sheet.set_name(get_attribute_value(attr)?);
                                 },
+                                Ok(ref attr) if attr.key == b"table:style-name" => {
+                                    if right_to_left_styles.contains(&get_attribute_value(attr)?) {
+                                        sheet.set_view(SheetView::new().with_right_to_left(true));
+                                    }
+                                },
                                 Ok(_) => {},
                                 Err(_) => {},
                             }
                         }
                     },
-                    b"table:table-cell" => {
+                    // covered cells are hidden by a merged cell, and may have a value of their own
+                    b"table:table-cell" | b"table:covered-table-cell" => {
+                        in_cell = true;
+                        paragraphs = 0;
+                        str_value = String::from("");
+                        columns_repeated = read_repeat(e, b"table:number-columns-repeated", position)
+                            .map_err(|err| err.in_cell(sheet.get_name(), cell_ref(row, column)))?;
+                        if let Some(range) = read_merged_range(e, row, column, position)? {
+                            sheet.merge(range);
+                        }
                         for a in e.attributes().with_checks(false) {
                             match a {
                                 Ok(ref attr) if attr.key == b"table:style-name" => {
@@ -53,119 +152,295 @@ pub fn read(dir: &TempDir, style_content: &StyleContent) -> Result<Book> {
                                 Ok(ref attr) if attr.key == b"office:value-type" => {
                                     cell_type = get_attribute_value(attr)?;
                                 },
+                                Ok(ref attr) if attr.key == b"table:formula" => {
+                                    formula = Some(formula::from_open_formula(&get_attribute_value(attr)?));
+                                },
+                                Ok(ref attr) if attr.key == b"calcext:value-type" => {
+                                    calc_type = get_attribute_value(attr)?;
+                                },
                                 Ok(ref attr) if attr.key == b"office:value" => {
                                     let value = get_attribute_value(attr)?;
-                                    float_value = value.parse::<f64>().unwrap();
+                                    float_value = value.parse::<f64>().map_err(|_| {
+                                        Error::malformed(CONTENT_XML, position, format!("invalid number {}", value))
+                                            .in_cell(sheet.get_name(), cell_ref(row, column))
+                                    })?;
                                 },
                                 Ok(ref attr) if attr.key == b"office:date-value" => {
                                     date_value = get_attribute_value(attr)?;
                                 },
+                                Ok(ref attr) if attr.key == b"office:time-value" => {
+                                    time_value = get_attribute_value(attr)?;
+                                },
+                                Ok(ref attr) if attr.key == b"office:currency" => {
+                                    currency_code = get_attribute_value(attr)?;
+                                },
+                                Ok(ref attr) if attr.key == b"office:boolean-value" => {
+                                    bool_value = get_attribute_value(attr)?;
+                                },
                                 Ok(_) => {},
                                 Err(_) => {},
                             }
                         }
                     },
-                    b"number:date-style" => {
+                    b"number:date-style" | b"number:time-style" => {
+                        let mut name = String::from("");
+                        let mut elapsed = false;
                         for a in e.attributes().with_checks(false) {
                             match a {
                                 Ok(ref attr) if attr.key == b"style:name" => {
-                                    date_style_map.insert(
-                                        get_attribute_value(attr)?,
-                                        super::read_number_date_style(&mut reader)?);
+                                    name = get_attribute_value(attr)?;
+                                },
+                                Ok(ref attr) if attr.key == b"number:truncate-on-overflow" => {
+                                    elapsed = get_attribute_value(attr)? == "false";
                                 },
                                 Ok(_) => {},
                                 Err(_) => {},
                             }
                         }
+                        date_style_map.insert(name, super::read_number_date_style(&mut reader, CONTENT_XML, elapsed)?);
+                    },
+                    b"style:style" => {
+                        let name = read_data_style_name(e, &date_style_map, style_content, &mut style_map_for_date)?;
+                        let properties = super::read_style_properties(&mut reader, CONTENT_XML)?;
+                        match read_attribute(e, b"style:family")?.as_deref() {
+                            Some("table-column") => {
+                                column_styles.insert(name, super::read_column_style(&properties));
+                            },
+                            Some("table-row") => {
+                                row_styles.insert(name, super::read_row_style(&properties));
+                            },
+                            Some("table") => {
+                                if properties.get(&b"style:writing-mode"[..]).is_some_and(|mode| mode.starts_with("rl")) {
+                                    right_to_left_styles.push(name);
+                                }
+                            },
+                            _ => {
+                                let style = super::read_cell_style(&properties);
+                                if !style.is_default() {
+                                    cell_styles.insert(name, style);
+                                }
+                            },
+                        }
+                    },
+                    b"table:table-row" => {
+                        row_setting = match read_attribute(e, b"table:style-name")? {
+                            Some(name) => row_styles.get(&name).cloned().unwrap_or_default(),
+                            None => Row::new(),
+                        };
+                        if read_attribute(e, b"table:visibility")?.is_some_and(|visibility| visibility != "visible") {
+                            row_setting = row_setting.with_hidden(true);
+                        }
+                        rows_repeated = read_repeat(e, b"table:number-rows-repeated", position)?;
+                        row_has_cells = false;
+                    },
+                    b"table:table-column" => {
+                        table_column = read_table_column(e, &column_styles, &mut sheet, table_column, position)?;
+                    },
+                    // kept with the date styles, the cells find their format through the same map
+                    b"number:currency-style" | b"number:percentage-style" => {
+                        let mut name = String::from("");
+                        for a in e.attributes().with_checks(false) {
+                            match a {
+                                Ok(ref attr) if attr.key == b"style:name" => {
+                                    name = get_attribute_value(attr)?;
+                                },
+                                Ok(_) => {},
+                                Err(_) => {},
+                            }
+                        }
+                        let format = super::read_number_style(&mut reader, CONTENT_XML, &date_style_map)?;
+                        date_style_map.insert(name, format);
                     },
                     _ => (),
                 }
             },
             Ok(Event::End(ref e)) => {
                 match e.name() {
+                    b"text:p" if in_paragraph => {
+                        in_paragraph = false;
+                        reader.trim_text(true);
+                    },
                     b"table:table" => {
                         row = 0;
                         column = 0;
+                        table_column = 0;
                         book.add_sheet(sheet);
                         sheet = Sheet::new("");
                     },
                     b"table:table-row" => {
-                        row = row + 1;
+                        // the empty rows up to the end of a sheet are repeated by the million,
+                        // their height is the look of the application rather than of the sheet
+                        let fills_sheet = !row_has_cells && row + rows_repeated >= SHEET_ROWS;
+                        if row_setting != Row::new() && !fills_sheet {
+                            for index in row..row + rows_repeated {
+                                sheet.set_row(index, row_setting);
+                            }
+                        }
+                        row += rows_repeated;
                         column = 0;
                     },
-                    b"table:table-cell" => {
-                        match cell_type.as_str() {
+                    b"table:table-cell" | b"table:covered-table-cell" => {
+                        // error results are only marked by the LibreOffice extension attribute
+                        if calc_type == "error" {
+                            cell_type = calc_type.clone();
+                        }
+                        let cell = match cell_type.as_str() {
                             "string" => {
-                                let cell = Cell::str(str_value.clone(), String::from(""));
-                                sheet.add_cell(cell, row, column);
+                                Some(Cell::str(str_value.clone(), String::from("")))
                             },
                             "float" => {
-                                let cell = Cell::float(float_value, "");
-                                sheet.add_cell(cell, row, column);
+                                Some(Cell::float(float_value, ""))
+                            },
+                            "percentage" => {
+                                let format = match style_map_for_date.get(&table_style_name) {
+                                    Some(value) => value.clone(),
+                                    None => String::from(""),
+                                };
+                                Some(Cell::percentage(float_value, format))
+                            },
+                            // a time of day unless the style counts elapsed time or it runs over a day
+                            "time" => {
+                                let format = match style_map_for_date.get(&table_style_name) {
+                                    Some(value) => value.clone(),
+                                    None => String::from(""),
+                                };
+                                let duration = super::iso_to_duration(&time_value).ok_or_else(|| {
+                                    Error::malformed(CONTENT_XML, position, format!("invalid time {}", time_value))
+                                        .in_cell(sheet.get_name(), cell_ref(row, column))
+                                })?;
+                                let (value, default) = match NaiveTime::MIN.overflowing_add_signed(duration) {
+                                    (time, 0) if !Format::new(format.as_str()).is_duration_format() =>
+                                        (Value::Time(time), "HH:MM:SS"),
+                                    _ => (Value::Duration(duration), "[H]:MM:SS"),
+                                };
+                                Some(Cell::new(value, if format.is_empty() { String::from(default) } else { format }))
+                            },
+                            "currency" => {
+                                let format = match style_map_for_date.get(&table_style_name) {
+                                    Some(value) => value.clone(),
+                                    None => String::from(""),
+                                };
+                                Some(Cell::currency(float_value, currency_code.clone(), format))
+                            },
+                            "boolean" => {
+                                let value = match bool_value.as_str() {
+                                    "true" => true,
+                                    "false" => false,
+                                    _ => return Err(Error::malformed(
+                                        CONTENT_XML, position, format!("invalid boolean {}", bool_value))
+                                        .in_cell(sheet.get_name(), cell_ref(row, column))),
+                                };
+                                Some(Cell::bool(value, ""))
+                            },
+                            "error" => {
+                                let kind = ErrorKind::from_code(&str_value).ok_or_else(|| {
+                                    Error::malformed(CONTENT_XML, position, format!("unknown error code {}", str_value))
+                                        .in_cell(sheet.get_name(), cell_ref(row, column))
+                                })?;
+                                Some(Cell::error(kind, ""))
                             },
                             "date" => {
                                 let format = match style_map_for_date.get(&table_style_name) {
                                     Some(value) => value.clone(),
                                     None => String::from(""),
                                 };
-                                let cell = Cell::date(date_value.clone(), format);
-                                sheet.add_cell(cell, row, column);
+                                let dt = parse_date_value(&date_value).ok_or_else(|| {
+                                    Error::malformed(CONTENT_XML, position, format!("invalid date {}", date_value))
+                                        .in_cell(sheet.get_name(), cell_ref(row, column))
+                                })?;
+                                Some(Cell::new(Value::Date(dt), format))
+                            },
+                            _ => None,
+                        };
+                        // a comment on an empty cell is kept on an empty string
+                        let cell = match comment.take() {
+                            Some(comment) => {
+                                let mut cell = cell.unwrap_or_else(|| Cell::str("", ""));
+                                cell.set_comment(comment);
+                                Some(cell)
                             },
-                            _ => {},
+                            None => cell,
+                        };
+                        let cell = match (cell, hyperlink.take()) {
+                            (Some(mut cell), Some(hyperlink)) => {
+                                cell.set_hyperlink(hyperlink);
+                                Some(cell)
+                            },
+                            (cell, _) => cell,
+                        };
+                        if let Some(mut cell) = cell {
+                            if let Some(formula) = formula.take() {
+                                cell.set_formula(formula);
+                            }
+                            if let Some(style) = cell_styles.get(&table_style_name) {
+                                cell.set_style(style.clone());
+                            }
+                            for offset in 1..columns_repeated {
+                                sheet.add_cell(cell.clone(), row, column + offset);
+                            }
+                            sheet.add_cell(cell, row, column);
+                            row_has_cells = true;
                         }
+                        in_cell = false;
+                        formula = None;
                         cell_type = String::from("");
-                        column = column + 1;
+                        calc_type = String::from("");
+                        currency_code = String::from("");
+                        table_style_name = String::from("");
+                        column += columns_repeated;
                     },
                     _ => (),
                 }
             }
             Ok(Event::Empty(ref e)) => {
                 match e.name() {
-                    b"table:table-cell" => {
-                        if 0 == e.attributes().count() {
-                            column = column + 1;
-                        } else {
-                            for a in e.attributes().with_checks(false) {
-                                match a {
-                                    Ok(ref attr) if attr.key == b"table:number-columns-repeated" => {
-                                        let value = get_attribute_value(attr)?;
-                                        column = column + value.parse::<usize>().unwrap();
-                                    },
-                                    Ok(_) => {},
-                                    Err(_) => {},
-                                }
-                            }
+                    b"text:p" if in_cell => {
+                        if paragraphs > 0 {
+                            str_value.push('\n');
                         }
+                        paragraphs += 1;
                     },
-                    b"style:style" => {
-                        let mut style_name = String::from("");
-                        let mut data_style_name = String::from("");
+                    // runs of spaces
+                    b"text:s" if in_paragraph => {
+                        let count = read_repeat(e, b"text:c", position)?;
+                        str_value.push_str(&" ".repeat(count));
+                    },
+                    b"table:null-date" => {
                         for a in e.attributes().with_checks(false) {
                             match a {
-                                Ok(ref attr) if attr.key == b"style:name" => {
-                                   style_name = get_attribute_value(attr)?;
-                                },
-                                Ok(ref attr) if attr.key == b"style:data-style-name" => {
-                                   data_style_name = get_attribute_value(attr)?;
+                                Ok(ref attr) if attr.key == b"table:date-value" => {
+                                    if get_attribute_value(attr)? == "1904-01-01" {
+                                        book.set_date_system(DateSystem::Date1904);
+                                    }
                                 },
                                 Ok(_) => {},
                                 Err(_) => {},
                             }
                         }
-                        if data_style_name != "" {
-                            if let Some(format) = date_style_map.get(&data_style_name) {
-                                style_map_for_date.insert(style_name.clone(), format.clone());
-                            } else if let Some(format) = style_content.date_style_map.get(&data_style_name) {
-                                style_map_for_date.insert(style_name.clone(), format.clone());
-                            }
+                    },
+                    b"table:table-cell" | b"table:covered-table-cell" => {
+                        if let Some(range) = read_merged_range(e, row, column, position)? {
+                            sheet.merge(range);
                         }
+                        column += read_repeat(e, b"table:number-columns-repeated", position)
+                            .map_err(|err| err.in_cell(sheet.get_name(), cell_ref(row, column)))?;
+                    },
+                    b"style:style" => {
+                        read_data_style_name(e, &date_style_map, style_content, &mut style_map_for_date)?;
+                    },
+                    b"table:table-column" => {
+                        table_column = read_table_column(e, &column_styles, &mut sheet, table_column, position)?;
                     },
                     _ => (),
                 }
             }
-            Ok(Event::Text(e)) => str_value = e.unescape_and_decode(&reader).unwrap(),
+            // the text of the paragraphs of a cell, with the spaces around its links
+            Ok(Event::Text(e)) if in_paragraph => {
+                str_value.push_str(&e.unescape_and_decode(&reader)
+                    .map_err(|err| Error::xml(CONTENT_XML, position, err))?);
+            },
             Ok(Event::Eof) => break,
-            Err(e) => panic!("Error at position {}: {:?}", reader.buffer_position(), e),
+            Err(e) => return Err(Error::xml(CONTENT_XML, reader.buffer_position(), e)),
             _ => (),
         }
 
@@ -175,3 +450,111 @@ pub fn read(dir: &TempDir, style_content: &StyleContent) -> Result<Book> {
 
     Ok(book)
 }
+
+// the name of a style:style, remembering the format of its data style
+fn read_data_style_name(
+    e: &BytesStart,
+    date_style_map: &HashMap<String, String>,
+    style_content: &StyleContent,
+    style_map_for_date: &mut HashMap<String, String>,
+) -> Result<String> {
+    let mut style_name = String::from("");
+    let mut data_style_name = String::from("");
+    for a in e.attributes().with_checks(false) {
+        match a {
+            Ok(ref attr) if attr.key == b"style:name" => {
+               style_name = get_attribute_value(attr)?;
+            },
+            Ok(ref attr) if attr.key == b"style:data-style-name" => {
+               data_style_name = get_attribute_value(attr)?;
+            },
+            Ok(_) => {},
+            Err(_) => {},
+        }
+    }
+    if data_style_name != "" {
+        if let Some(format) = date_style_map.get(&data_style_name) {
+            style_map_for_date.insert(style_name.clone(), format.clone());
+        } else if let Some(format) = style_content.date_style_map.get(&data_style_name) {
+            style_map_for_date.insert(style_name.clone(), format.clone());
+        }
+    }
+    Ok(style_name)
+}
+
+fn read_attribute(e: &BytesStart, key: &[u8]) -> Result<Option<String>> {
+    for a in e.attributes().with_checks(false) {
+        match a {
+            Ok(ref attr) if attr.key == key => return Ok(Some(get_attribute_value(attr)?)),
+            Ok(_) => {},
+            Err(_) => {},
+        }
+    }
+    Ok(None)
+}
+
+fn read_repeat(e: &BytesStart, key: &[u8], position: usize) -> Result<usize> {
+    match read_attribute(e, key)? {
+        Some(value) => value.parse::<usize>().map_err(|_| {
+            Error::malformed(CONTENT_XML, position, format!("invalid count {}", value))
+        }),
+        None => Ok(1),
+    }
+}
+
+// the range of a cell which spans more than itself
+fn read_merged_range(e: &BytesStart, row: usize, column: usize, position: usize) -> Result<Option<CellRange>> {
+    let columns = read_repeat(e, b"table:number-columns-spanned", position)?;
+    let rows = read_repeat(e, b"table:number-rows-spanned", position)?;
+    if columns > 1 || rows > 1 {
+        Ok(Some(CellRange::new(row, column, row + rows.max(1) - 1, column + columns.max(1) - 1)))
+    } else {
+        Ok(None)
+    }
+}
+
+// the width and visibility of the columns of a table:table-column, returns the next column
+fn read_table_column(e: &BytesStart, column_styles: &HashMap<String, Column>, sheet: &mut Sheet, first: usize, position: usize) -> Result<usize> {
+    let mut setting = match read_attribute(e, b"table:style-name")? {
+        Some(name) => column_styles.get(&name).cloned().unwrap_or_default(),
+        None => Column::new(),
+    };
+    if read_attribute(e, b"table:visibility")?.is_some_and(|visibility| visibility != "visible") {
+        setting = setting.with_hidden(true);
+    }
+    let repeated = read_repeat(e, b"table:number-columns-repeated", position)?;
+    if setting != Column::new() {
+        for index in first..first + repeated {
+            sheet.set_column(index, setting);
+        }
+    }
+    Ok(first + repeated)
+}
+
+// #Sheet2.A1 goes to a cell of the book, any other reference is an url
+fn read_hyperlink(e: &BytesStart) -> Result<Option<Hyperlink>> {
+    let href = match read_attribute(e, b"xlink:href")? {
+        Some(href) => href,
+        None => return Ok(None),
+    };
+    let mut hyperlink = match href.strip_prefix('#').and_then(|reference| parse_sheet_reference(reference, '.')) {
+        Some((sheet, row, column)) => Hyperlink::cell(sheet, row, column),
+        None => Hyperlink::url(href),
+    };
+    if let Some(title) = read_attribute(e, b"office:title")? {
+        hyperlink = hyperlink.with_tooltip(title);
+    }
+    Ok(Some(hyperlink))
+}
+
+fn cell_ref(row: usize, column: usize) -> String {
+    format!("{}{}", index_to_column(column), row + 1)
+}
+
+// 2017-12-02 or 2017-12-02T13:30:00, the wall-clock time of a value with a zone
+fn parse_date_value(value: &str) -> Option<NaiveDateTime> {
+    value.parse::<NaiveDateTime>()
+        .or_else(|_| value.parse::<NaiveDate>().map(|date| date.and_time(NaiveTime::MIN)))
+        .or_else(|_| DateTime::parse_from_rfc3339(value).map(|dt| dt.naive_local()))
+        .ok()
+}
@@ -0,0 +1,128 @@
+use super::{Book, Pane, SheetView};
+use crate::file_common::*;
+use super::quick_xml::events::{Event};
+use super::Result;
+use std::collections::HashMap;
+use super::Error;
+use std::io::{Read, Seek};
+use super::zip::ZipArchive;
+use super::zip::result::ZipError;
+
+const SETTINGS_XML: &str = "settings.xml";
+
+// the view of a sheet from its config items, the items of the whole view fill in for missing ones
+fn make_view(items: &HashMap<String, String>, global: &HashMap<String, String>, right_to_left: bool) -> SheetView {
+    let item = |name: &str| items.get(name).or_else(|| global.get(name)).map(|value| value.as_str());
+    let number = |name: &str| item(name).and_then(|value| value.parse::<usize>().ok()).unwrap_or(0);
+    let mut view = SheetView::new()
+        .with_active_cell(number("CursorPositionY"), number("CursorPositionX"))
+        .with_grid_lines(item("ShowGrid") != Some("false"))
+        .with_headers(item("HasColumnRowHeaders") != Some("false"))
+        .with_right_to_left(right_to_left);
+    if let Some(zoom) = item("ZoomValue").and_then(|value| value.parse::<u16>().ok()) {
+        view = view.with_zoom(zoom);
+    }
+    let mode = |name: &str| item(name).unwrap_or("0");
+    let (horizontal, vertical) = (number("HorizontalSplitPosition"), number("VerticalSplitPosition"));
+    let columns = if mode("HorizontalSplitMode") == "0" { 0 } else { horizontal };
+    let rows = if mode("VerticalSplitMode") == "0" { 0 } else { vertical };
+    let pane = match (mode("HorizontalSplitMode"), mode("VerticalSplitMode")) {
+        ("2", _) | (_, "2") => Some(Pane::Frozen { rows, columns }),
+        ("1", _) | (_, "1") => Some(Pane::Split { x: super::pixels_to_points(columns), y: super::pixels_to_points(rows) }),
+        _ => None,
+    };
+    match pane {
+        Some(Pane::Frozen { rows, columns }) => view.with_frozen(rows, columns),
+        Some(Pane::Split { x, y }) => view.with_split(x, y),
+        None => view,
+    }
+}
+
+/// The views of the sheets and the active sheet, a file without settings.xml keeps the defaults.
+pub fn read<R: Read + Seek>(zip: &mut ZipArchive<R>, book: &mut Book) -> Result<()> {
+    let mut reader = match open_part(zip, SETTINGS_XML) {
+        Ok(reader) => reader,
+        Err(ZipError::FileNotFound) => return Ok(()),
+        Err(e) => return Err(Error::from(e)),
+    };
+    reader.trim_text(true);
+    let mut buf = Vec::new();
+    let mut in_view_settings = false;
+    let mut in_tables = false;
+    let mut table: Option<String> = None;
+    let mut item_name: Option<String> = None;
+    let mut global: HashMap<String, String> = HashMap::new();
+    let mut tables: HashMap<String, HashMap<String, String>> = HashMap::new();
+    loop {
+        match reader.read_event(&mut buf) {
+            Ok(Event::Start(ref e)) => {
+                let mut name = None;
+                for a in e.attributes().with_checks(false) {
+                    match a {
+                        Ok(ref attr) if attr.key == b"config:name" => {
+                            name = Some(get_attribute_value(attr)?);
+                        },
+                        Ok(_) => {},
+                        Err(_) => {},
+                    }
+                }
+                match e.name() {
+                    b"config:config-item-set" => {
+                        in_view_settings = name.as_ref().is_some_and(|name| name == "ooo:view-settings");
+                    },
+                    b"config:config-item-map-named" => {
+                        in_tables = in_view_settings && name.as_ref().is_some_and(|name| name == "Tables");
+                    },
+                    b"config:config-item-map-entry" if in_tables => {
+                        table = name;
+                    },
+                    b"config:config-item" if in_view_settings => {
+                        item_name = name;
+                    },
+                    _ => (),
+                }
+            },
+            Ok(Event::Text(e)) => {
+                if let Some(name) = item_name.take() {
+                    let value = e.unescape_and_decode(&reader)
+                        .map_err(|err| Error::xml(SETTINGS_XML, reader.buffer_position(), err))?;
+                    match table {
+                        Some(ref table) => {
+                            tables.entry(table.clone()).or_default().insert(name, value);
+                        },
+                        None => {
+                            global.insert(name, value);
+                        },
+                    }
+                }
+            },
+            Ok(Event::End(ref e)) => {
+                match e.name() {
+                    b"config:config-item" => item_name = None,
+                    b"config:config-item-map-entry" if in_tables => table = None,
+                    b"config:config-item-map-named" => in_tables = false,
+                    b"config:config-item-set" => in_view_settings = false,
+                    _ => (),
+                }
+            },
+            Ok(Event::Eof) => break,
+            Err(e) => return Err(Error::xml(SETTINGS_XML, reader.buffer_position(), e)),
+            _ => (),
+        }
+        buf.clear();
+    }
+
+    let empty = HashMap::new();
+    let mut active_sheet = 0;
+    for index in 0..book.get_sheet_size() {
+        let sheet = book.get_sheet_mut(index);
+        if global.get("ActiveTable") == Some(sheet.get_name()) {
+            active_sheet = index;
+        }
+        let items = tables.get(sheet.get_name()).unwrap_or(&empty);
+        let view = make_view(items, &global, sheet.get_view().is_right_to_left());
+        sheet.set_view(view);
+    }
+    book.set_active_sheet(active_sheet);
+    Ok(())
+}
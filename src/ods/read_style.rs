@@ -1,9 +1,9 @@
 use crate::file_common::*;
-use super::quick_xml::Reader;
 use super::quick_xml::events::{Event};
-use super::tempdir::TempDir;
 use std::collections::HashMap;
-use super::OdsError;
+use super::Error;
+use std::io::{Read, Seek};
+use super::zip::ZipArchive;
 
 const STYLES_XML: &'static str = "styles.xml";
 
@@ -12,11 +12,10 @@ pub struct StyleContent {
     pub date_style_map: HashMap<String, String>,
 }
 
-pub fn read(dir: &TempDir) -> Result<StyleContent, OdsError> {
+pub fn read<R: Read + Seek>(zip: &mut ZipArchive<R>) -> Result<StyleContent, Error> {
     let mut date_style_map = HashMap::new();
 
-    let path = dir.path().join(STYLES_XML);
-    let mut reader = Reader::from_file(path)?;
+    let mut reader = open_part(zip, STYLES_XML)?;
 
     reader.trim_text(true);
     let mut buf = Vec::new();
@@ -24,24 +23,43 @@ pub fn read(dir: &TempDir) -> Result<StyleContent, OdsError> {
         match reader.read_event(&mut buf) {
             Ok(Event::Start(ref e)) => {
                 match e.name() {
-                    b"number:date-style" => {
+                    b"number:date-style" | b"number:time-style" => {
+                        let mut name = String::from("");
+                        let mut elapsed = false;
                         for a in e.attributes().with_checks(false) {
                             match a {
                                 Ok(ref attr) if attr.key == b"style:name" => {
-                                    date_style_map.insert(
-                                        get_attribute_value(attr)?,
-                                        super::read_number_date_style(&mut reader)?);
+                                    name = get_attribute_value(attr)?;
+                                },
+                                Ok(ref attr) if attr.key == b"number:truncate-on-overflow" => {
+                                    elapsed = get_attribute_value(attr)? == "false";
+                                },
+                                Ok(_) => {},
+                                Err(_) => {},
+                            }
+                        }
+                        date_style_map.insert(name, super::read_number_date_style(&mut reader, STYLES_XML, elapsed)?);
+                    },
+                    // kept with the date styles, the cells find their format through the same map
+                    b"number:currency-style" | b"number:percentage-style" => {
+                        let mut name = String::from("");
+                        for a in e.attributes().with_checks(false) {
+                            match a {
+                                Ok(ref attr) if attr.key == b"style:name" => {
+                                    name = get_attribute_value(attr)?;
                                 },
                                 Ok(_) => {},
                                 Err(_) => {},
                             }
                         }
+                        let format = super::read_number_style(&mut reader, STYLES_XML, &date_style_map)?;
+                        date_style_map.insert(name, format);
                     },
                     _ => (),
                 }
             },
             Ok(Event::Eof) => break,
-            Err(e) => panic!("Error at position {}: {:?}", reader.buffer_position(), e),
+            Err(e) => return Err(Error::xml(STYLES_XML, reader.buffer_position(), e)),
             _ => (),
         }
 
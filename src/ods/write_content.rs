@@ -1,82 +1,269 @@
-use super::{Book,Sheet,Cell,Value};
-use super::tempdir::TempDir;
+use super::{Book,Sheet,Cell,CellRange,Column,Comment,LinkTarget,Row,Value,DateSystem};
+use crate::format::{Condition, Format, Item, Locale, Placeholder, Section};
+use crate::style::{BorderLine, BorderStyle, Fill, HorizontalAlignment, Rgb, Style, Underline, VerticalAlignment};
+use chrono::prelude::*;
+use chrono::Duration;
 use std::collections::HashMap;
 use std::result;
 use super::quick_xml::events::{Event, BytesDecl};
 use super::quick_xml::Writer;
 use std::io::Cursor;
-use std::io::Write;
-use std::fs::File;
 use crate::file_common::*;
-use super::OdsError;
+use crate::formula;
+use crate::index_to_column;
+use super::Error;
+use std::io::{Seek, Write};
+use super::zip::ZipWriter;
 
 const CONTENT_XML: &'static str = "content.xml";
+// declared in every document
+const FONT_FACES: [&str; 4] = ["Liberation Sans", "Arial Unicode MS", "Tahoma", "ヒラギノ明朝 ProN"];
 
-fn make_content_xml_none_table_cell(writer: &mut Writer<Cursor<Vec<u8>>>, none_count: i64) {
+fn make_content_xml_none_table_cell(writer: &mut Writer<Cursor<Vec<u8>>>, name: &str, none_count: i64) {
     if none_count > 0 {
         if none_count == 1 {
-            write_start_tag(writer, "table:table-cell", vec![], true);
+            write_start_tag(writer, name, vec![], true);
         } else {
-            write_start_tag(writer, "table:table-cell", vec![("table:number-columns-repeated", none_count.to_string().as_str())], true);
+            write_start_tag(writer, name, vec![("table:number-columns-repeated", none_count.to_string().as_str())], true);
         }
     }
 }
 
-fn make_content_xml_table_cell(writer: &mut Writer<Cursor<Vec<u8>>>, cell: &Cell, date_hash: &HashMap<String, String>) {
+// the place of a cell in the merged ranges of its sheet
+#[derive(Clone, Copy)]
+enum Span<'a> {
+    Single,
+    Merged(&'a CellRange),
+    Covered,
+}
+
+impl<'a> Span<'a> {
+    fn element(&self) -> &'static str {
+        match *self {
+            Span::Covered => "table:covered-table-cell",
+            _ => "table:table-cell",
+        }
+    }
+}
+
+// table:table-cell start tag, with the formula of the cell if it has one
+fn write_table_cell_start(writer: &mut Writer<Cursor<Vec<u8>>>, cell: &Cell, span: Span, attributes: Vec<(&str, &str)>) {
+    let formula = cell.get_formula().map(|formula| formula::to_open_formula(formula));
+    let mut all_attributes = vec![];
+    if let Some(ref formula) = formula {
+        all_attributes.push(("table:formula", formula.as_str()));
+    }
+    all_attributes.extend(attributes);
+    let spanned = match span {
+        Span::Merged(range) => Some((
+            (range.get_last_column() - range.get_first_column() + 1).to_string(),
+            (range.get_last_row() - range.get_first_row() + 1).to_string())),
+        _ => None,
+    };
+    if let Some((ref columns, ref rows)) = spanned {
+        all_attributes.push(("table:number-columns-spanned", columns.as_str()));
+        all_attributes.push(("table:number-rows-spanned", rows.as_str()));
+    }
+    write_start_tag(writer, span.element(), all_attributes, false);
+    if let Some(comment) = cell.get_comment() {
+        make_annotation(writer, comment);
+    }
+}
+
+// office:annotation comes before the text of the cell, a paragraph for each line
+fn make_annotation(writer: &mut Writer<Cursor<Vec<u8>>>, comment: &Comment) {
+    write_start_tag(writer, "office:annotation", vec![("office:display", if comment.is_visible() { "true" } else { "false" })], false);
+    write_start_tag(writer, "dc:creator", vec![], false);
+    write_text_node(writer, comment.get_author().as_str());
+    write_end_tag(writer, "dc:creator");
+    for line in comment.get_text().split('\n') {
+        write_start_tag(writer, "text:p", vec![], false);
+        write_text_node(writer, line);
+        write_end_tag(writer, "text:p");
+    }
+    write_end_tag(writer, "office:annotation");
+}
+
+// the text of a cell, inside a text:a when it has a link
+fn make_paragraph(writer: &mut Writer<Cursor<Vec<u8>>>, cell: &Cell, text: &str) {
+    write_start_tag(writer, "text:p", vec![], false);
+    match cell.get_hyperlink() {
+        Some(hyperlink) => {
+            let href = match hyperlink.get_target() {
+                LinkTarget::Url(url) => url.clone(),
+                LinkTarget::Cell { sheet, row, column } => format!("#{}.{}{}", quote_sheet_name(sheet), index_to_column(*column), row + 1),
+            };
+            let mut attributes = vec![("xlink:href", href.as_str()), ("xlink:type", "simple")];
+            if let Some(tooltip) = hyperlink.get_tooltip() {
+                attributes.push(("office:title", tooltip.as_str()));
+            }
+            write_start_tag(writer, "text:a", attributes, false);
+            write_text_node(writer, text);
+            write_end_tag(writer, "text:a");
+        },
+        None => write_text_node(writer, text),
+    }
+    write_end_tag(writer, "text:p");
+}
+
+// a time of day or a duration, both are durations in office:time-value
+// the text a cell shows, a serial number outside of the dates keeps its plain number
+fn formated_value(cell: &Cell, date_system: DateSystem) -> String {
+    cell.get_formated_value_in(date_system).unwrap_or_else(|| match cell.get_value() {
+        Value::Float(value) | Value::Currency(value, _) | Value::Percentage(value) => value.to_string(),
+        _ => String::new(),
+    })
+}
+
+fn make_time_cell(writer: &mut Writer<Cursor<Vec<u8>>>, cell: &Cell, span: Span, duration: Duration, style_hash: &HashMap<StyleKey<'_>, String>, date_system: DateSystem) {
+    let time_value = super::duration_to_iso(&duration);
+    let mut attributes = vec![];
+    if let Some(style_name) = style_name(cell, style_hash) {
+        attributes.push(("table:style-name", style_name.as_str()));
+    }
+    attributes.extend(vec![
+        ("office:value-type", "time"),
+        ("office:time-value", time_value.as_str()),
+        ("calcext:value-type", "time")]);
+    write_table_cell_start(writer, cell, span, attributes);
+    make_paragraph(writer, cell, &formated_value(cell, date_system));
+}
+
+fn make_content_xml_table_cell(writer: &mut Writer<Cursor<Vec<u8>>>, cell: &Cell, span: Span, style_hash: &HashMap<StyleKey<'_>, String>, date_system: DateSystem) {
     match cell.get_value() {
         &Value::Str(ref value) => {
-            write_start_tag(writer, "table:table-cell", vec![
+            let mut attributes = vec![];
+            if let Some(style_name) = style_name(cell, style_hash) {
+                attributes.push(("table:style-name", style_name.as_str()));
+            }
+            attributes.extend(vec![
                 ("office:value-type", "string"),
-                ("calcext:value-type", "string")], false);
-            write_start_tag(writer, "text:p", vec![], false);
-            write_text_node(writer, value.to_string());
+                ("calcext:value-type", "string")]);
+            write_table_cell_start(writer, cell, span, attributes);
+            make_paragraph(writer, cell, value);
         },
-        &Value::Float(ref value) => {
-            write_start_tag(writer, "table:table-cell", vec![
-                ("office:value-type", "float"),
-                ("office:value", value.to_string().as_str()),
-                ("calcext:value-type", "float")], false);
-            write_start_tag(writer, "text:p", vec![], false);
-            write_text_node(writer, value.to_string());
+        &Value::Float(ref value) | &Value::Percentage(ref value) => {
+            let value = value.to_string();
+            let value_type = if let &Value::Percentage(_) = cell.get_value() { "percentage" } else { "float" };
+            let mut attributes = vec![];
+            if let Some(style_name) = style_name(cell, style_hash) {
+                attributes.push(("table:style-name", style_name.as_str()));
+            }
+            attributes.extend(vec![
+                ("office:value-type", value_type),
+                ("office:value", value.as_str()),
+                ("calcext:value-type", value_type)]);
+            write_table_cell_start(writer, cell, span, attributes);
+            make_paragraph(writer, cell, &formated_value(cell, date_system));
+        },
+        &Value::Bool(ref value) => {
+            write_table_cell_start(writer, cell, span, vec![
+                ("table:style-name", style_name(cell, style_hash).unwrap().as_str()),
+                ("office:value-type", "boolean"),
+                ("office:boolean-value", if *value { "true" } else { "false" }),
+                ("calcext:value-type", "boolean")
+            ]);
+            make_paragraph(writer, cell, &formated_value(cell, date_system));
+        },
+        &Value::Error(ref kind) => {
+            let mut attributes = vec![];
+            if let Some(style_name) = style_name(cell, style_hash) {
+                attributes.push(("table:style-name", style_name.as_str()));
+            }
+            attributes.extend(vec![
+                ("office:value-type", "string"),
+                ("calcext:value-type", "error")]);
+            write_table_cell_start(writer, cell, span, attributes);
+            make_paragraph(writer, cell, kind.code());
         },
         &Value::Date(ref value) => {
-            let formater = cell.get_format().get_content();
-            write_start_tag(writer, "table:table-cell", vec![
-                ("table:style-name", date_hash.get(formater).unwrap().as_str()),
+            write_table_cell_start(writer, cell, span, vec![
+                ("table:style-name", style_name(cell, style_hash).unwrap().as_str()),
                 ("office:value-type", "date"),
-                ("office:date-value", value.format("%Y-%m-%dT%H:%M:%S").to_string().as_str()),
+                ("office:date-value", value.format("%Y-%m-%dT%H:%M:%S%.f").to_string().as_str()),
                 ("calcext:value-type", "date")
-            ], false);
-            write_start_tag(writer, "text:p", vec![], false);
-            write_text_node(writer, cell.get_formated_value().unwrap());
+            ]);
+            make_paragraph(writer, cell, &formated_value(cell, date_system));
         },
-        &Value::Currency(ref value) => {
-            let formater = cell.get_format().get_content();
-            write_start_tag(writer, "table:table-cell", vec![
-                ("table:style-name", date_hash.get(formater).unwrap().as_str()),
+        &Value::Time(time) => make_time_cell(writer, cell, span, time - NaiveTime::MIN, style_hash, date_system),
+        &Value::Duration(duration) => make_time_cell(writer, cell, span, duration, style_hash, date_system),
+        &Value::Currency(ref value, ref code) => {
+            let value = value.to_string();
+            let mut attributes = vec![];
+            if let Some(style_name) = style_name(cell, style_hash) {
+                attributes.push(("table:style-name", style_name.as_str()));
+            }
+            attributes.extend(vec![
                 ("office:value-type", "currency"),
-                ("office:date-value", value.to_string().as_str()),
-                ("calcext:value-type", "currency")
-            ], false);
-            write_start_tag(writer, "text:p", vec![], false);
-            write_text_node(writer, cell.get_formated_value().unwrap());
+                ("office:value", value.as_str()),
+                ("office:currency", code.as_str()),
+                ("calcext:value-type", "currency")]);
+            write_table_cell_start(writer, cell, span, attributes);
+            make_paragraph(writer, cell, &formated_value(cell, date_system));
         },
     }
-    write_end_tag(writer, "text:p");
-    write_end_tag(writer, "table:table-cell");
+    write_end_tag(writer, span.element());
+}
+
+// the width or height and auto fit of the automatic styles after co1 and ro1
+type SizeKey = (Option<f64>, bool);
+
+fn size_style_name(prefix: &str, key: SizeKey, styles: &[SizeKey]) -> String {
+    match styles.iter().position(|style| *style == key) {
+        Some(index) => format!("{}{}", prefix, index + 2),
+        None => format!("{}1", prefix),
+    }
+}
+
+fn column_key(column: &Column) -> SizeKey {
+    (column.get_width(), column.is_auto_fit())
 }
 
-fn make_content_xml_by_sheet(writer: &mut Writer<Cursor<Vec<u8>>>, sheet: &Sheet, date_hash: &HashMap<String, String>) {
-    write_start_tag(writer, "table:table", vec![("table:name", sheet.get_name().as_str()),("table:style-name", "ta1"),], false);
+// a row without a height follows its contents, as ro1 does
+fn row_key(row: &Row) -> SizeKey {
+    match row.get_height() {
+        Some(height) => (Some(height), row.is_auto_fit()),
+        None => (None, true),
+    }
+}
+
+fn visibility(hidden: bool) -> Option<(&'static str, &'static str)> {
+    if hidden { Some(("table:visibility", "collapse")) } else { None }
+}
+
+fn make_content_xml_by_sheet(writer: &mut Writer<Cursor<Vec<u8>>>, sheet: &Sheet, style_hash: &HashMap<StyleKey<'_>, String>, column_styles: &[SizeKey], row_styles: &[SizeKey], date_system: DateSystem) {
+    let table_style = if sheet.get_view().is_right_to_left() { "ta2" } else { "ta1" };
+    write_start_tag(writer, "table:table", vec![("table:name", sheet.get_name().as_str()),("table:style-name", table_style),], false);
 
     let indexes = sheet.get_max_index();
-    match indexes {
-        Some(indexes) => {
-            write_start_tag(writer, "table:table-column", vec![
-                ("table:style-name", "co1"),
-                ("table:number-columns-repeated", (indexes.1 + 1).to_string().as_str()),
-                ("table:default-cell-style-name", "Default")
-            ], true);
+    let last_column = indexes.map(|indexes| indexes.1).into_iter()
+        .chain(sheet.get_columns().keys().cloned())
+        .chain(sheet.merged_ranges().iter().map(|range| range.get_last_column())).max();
+    let last_row = indexes.map(|indexes| indexes.0).into_iter()
+        .chain(sheet.get_row_settings().keys().cloned())
+        .chain(sheet.merged_ranges().iter().map(|range| range.get_last_row())).max();
+    match last_column {
+        Some(last_column) => {
+            // columns of the same style and visibility are repeated
+            let default = Column::new();
+            let mut first = 0;
+            while first <= last_column {
+                let column = sheet.get_column(first).unwrap_or(&default);
+                let mut repeated = 1;
+                while first + repeated <= last_column && sheet.get_column(first + repeated).unwrap_or(&default) == column {
+                    repeated += 1;
+                }
+                let style_name = size_style_name("co", column_key(column), column_styles);
+                let repeated_str = repeated.to_string();
+                let mut attributes = vec![("table:style-name", style_name.as_str())];
+                attributes.extend(visibility(column.is_hidden()));
+                if repeated > 1 {
+                    attributes.push(("table:number-columns-repeated", repeated_str.as_str()));
+                }
+                attributes.push(("table:default-cell-style-name", "Default"));
+                write_start_tag(writer, "table:table-column", attributes, true);
+                first += repeated;
+            }
         },
         None => {
             write_start_tag(writer, "table:table-column", vec![
@@ -86,45 +273,88 @@ fn make_content_xml_by_sheet(writer: &mut Writer<Cursor<Vec<u8>>>, sheet: &Sheet
         }
     };
 
-    match indexes {
-        Some(indexes) => {
-            for row_index in 0..indexes.0 + 1 {
-                match sheet.get_rows().get(&row_index) {
-                    Some(columns) => {
-                        write_start_tag(writer, "table:table-row", vec![("table:style-name", "ro1"),], false);
-                        let mut max_column_index = 0;
-                        for (column_index, _) in columns.iter() {
-                            if *column_index > max_column_index {
-                                max_column_index = *column_index;
-                            }
-                        }
+    match last_row {
+        Some(last_row) => {
+            let column_count = last_column.map_or(1, |last_column| last_column + 1);
+            let no_cells = HashMap::new();
+            // a merged range may reach past the cells of the row
+            let last_cell_of = |row_index: usize| sheet.get_rows().get(&row_index).into_iter()
+                .flat_map(|columns| columns.keys().cloned())
+                .chain(sheet.merged_ranges().iter()
+                    .filter(|range| range.get_first_row() <= row_index && row_index <= range.get_last_row())
+                    .map(|range| range.get_last_column()))
+                .max();
+            let mut row_index = 0;
+            while row_index <= last_row {
+                let row = sheet.get_row(row_index).cloned().unwrap_or_default();
+                let last_cell = last_cell_of(row_index);
+                // empty rows of the same style and visibility are repeated
+                let mut repeated = 1;
+                if last_cell.is_none() {
+                    while row_index + repeated <= last_row
+                        && sheet.get_row(row_index + repeated).cloned().unwrap_or_default() == row
+                        && last_cell_of(row_index + repeated).is_none() {
+                        repeated += 1;
+                    }
+                }
+                let style_name = size_style_name("ro", row_key(&row), row_styles);
+                let repeated_str = repeated.to_string();
+                let mut attributes = vec![("table:style-name", style_name.as_str())];
+                if repeated > 1 {
+                    attributes.push(("table:number-rows-repeated", repeated_str.as_str()));
+                }
+                attributes.extend(visibility(row.is_hidden()));
+                write_start_tag(writer, "table:table-row", attributes, false);
+                let columns = sheet.get_rows().get(&row_index).unwrap_or(&no_cells);
+                match last_cell {
+                    Some(last_cell) => {
                         let mut none_count = 0;
-                        for column_index in 0..max_column_index+1 {
-                            match columns.get(&column_index) {
-                                Some(cell) => {
-                                    let _ = make_content_xml_none_table_cell(writer, none_count);
+                        let mut covered_count = 0;
+                        for column_index in 0..last_cell+1 {
+                            let span = match sheet.get_merged_range(row_index, column_index) {
+                                Some(range) if range.get_first_row() == row_index && range.get_first_column() == column_index => Span::Merged(range),
+                                Some(_) => Span::Covered,
+                                None => Span::Single,
+                            };
+                            match (columns.get(&column_index), span) {
+                                (None, Span::Single) => {
+                                    make_content_xml_none_table_cell(writer, "table:covered-table-cell", covered_count);
+                                    covered_count = 0;
+                                    none_count += 1;
+                                },
+                                (None, Span::Covered) => {
+                                    make_content_xml_none_table_cell(writer, "table:table-cell", none_count);
+                                    none_count = 0;
+                                    covered_count += 1;
+                                },
+                                (cell, span) => {
+                                    make_content_xml_none_table_cell(writer, "table:table-cell", none_count);
+                                    make_content_xml_none_table_cell(writer, "table:covered-table-cell", covered_count);
                                     none_count = 0;
-                                    let _ = make_content_xml_table_cell(writer, cell, date_hash);
+                                    covered_count = 0;
+                                    match cell {
+                                        Some(cell) => make_content_xml_table_cell(writer, cell, span, style_hash, date_system),
+                                        // the top left cell of an empty merged range
+                                        None => {
+                                            write_table_cell_start(writer, &Cell::str("", ""), span, vec![]);
+                                            write_end_tag(writer, span.element());
+                                        },
+                                    }
                                 },
-                                None => {
-                                    none_count = none_count + 1;
-                                }
                             }
                         }
-                        let _ = make_content_xml_none_table_cell(writer, none_count);
-                        write_end_tag(writer, "table:table-row");
+                        make_content_xml_none_table_cell(writer, "table:table-cell", none_count);
+                        make_content_xml_none_table_cell(writer, "table:covered-table-cell", covered_count);
                     },
                     None => {
                         // some row not found
-                        write_start_tag(writer, "table:table-row", vec![
-                            ("table:style-name", "ro1")
-                        ], false);
                         write_start_tag(writer, "table:table-cell", vec![
-                            ("table:number-columns-repeated", (indexes.1 + 1).to_string().as_str())
+                            ("table:number-columns-repeated", column_count.to_string().as_str())
                         ], true);
-                        write_end_tag(writer, "table:table-row");
                     }
                 }
+                write_end_tag(writer, "table:table-row");
+                row_index += repeated;
             }
         },
         None => {
@@ -139,130 +369,520 @@ fn make_content_xml_by_sheet(writer: &mut Writer<Cursor<Vec<u8>>>, sheet: &Sheet
     write_end_tag(writer, "table:table");
 }
 
-fn make_number_format(writer: &mut Writer<Cursor<Vec<u8>>>, formats: &Vec<&str>) {
-    for it in formats {
-        match *it {
-            "%Y" => {
-                 write_start_tag(writer, "number:year", vec![
-                    ("number:style", "long"),
-                ], true);
-            },
-            "%y" => {
-                 write_start_tag(writer, "number:year", vec![
-                ], true);
-            },
-            "%m" => {
-                 write_start_tag(writer, "number:month", vec![
-                    ("number:style", "long"),
-                ], true);
+// co2.. and ro2.. for the widths and heights which are not the default
+fn make_size_styles(writer: &mut Writer<Cursor<Vec<u8>>>, book: &Book) -> (Vec<SizeKey>, Vec<SizeKey>) {
+    let mut column_styles: Vec<SizeKey> = vec![];
+    let mut row_styles: Vec<SizeKey> = vec![];
+    for sheet in book.get_sheet_vec() {
+        let mut column_indexes: Vec<&usize> = sheet.get_columns().keys().collect();
+        column_indexes.sort();
+        for column_index in column_indexes {
+            let key = column_key(&sheet.get_columns()[column_index]);
+            if key != column_key(&Column::new()) && !column_styles.contains(&key) {
+                column_styles.push(key);
+            }
+        }
+        let mut row_indexes: Vec<&usize> = sheet.get_row_settings().keys().collect();
+        row_indexes.sort();
+        for row_index in row_indexes {
+            let key = row_key(&sheet.get_row_settings()[row_index]);
+            if key != row_key(&Row::new()) && !row_styles.contains(&key) {
+                row_styles.push(key);
+            }
+        }
+    }
+    for (index, &(width, auto_fit)) in column_styles.iter().enumerate() {
+        let name = format!("co{}", index + 2);
+        let width = format!("{:.3}mm", width.map_or(super::DEFAULT_COLUMN_WIDTH_MM, |width| width * super::POINTS_PER_CHARACTER * 25.4 / 72.0));
+        let mut properties = vec![("fo:break-before", "auto"),("style:column-width", width.as_str()),];
+        if auto_fit {
+            properties.push(("style:use-optimal-column-width", "true"));
+        }
+        write_start_tag(writer, "style:style", vec![("style:name", name.as_str()),("style:family", "table-column"),], false);
+        write_start_tag(writer, "style:table-column-properties", properties, true);
+        write_end_tag(writer, "style:style");
+    }
+    for (index, &(height, auto_fit)) in row_styles.iter().enumerate() {
+        let name = format!("ro{}", index + 2);
+        let height = format!("{}pt", height.unwrap_or(0.0));
+        write_start_tag(writer, "style:style", vec![("style:name", name.as_str()),("style:family", "table-row"),], false);
+        write_start_tag(writer, "style:table-row-properties", vec![
+            ("style:row-height", height.as_str()),
+            ("fo:break-before", "auto"),
+            ("style:use-optimal-row-height", if auto_fit { "true" } else { "false" }),
+        ], true);
+        write_end_tag(writer, "style:style");
+    }
+    (column_styles, row_styles)
+}
+
+fn long_style(long: bool) -> Vec<(&'static str, &'static str)> {
+    if long { vec![("number:style", "long")] } else { vec![] }
+}
+
+fn make_number_text(writer: &mut Writer<Cursor<Vec<u8>>>, text: &str) {
+    if text.is_empty() {
+        return;
+    }
+    write_start_tag(writer, "number:text", vec![], false);
+    write_text_node(writer, text);
+    write_end_tag(writer, "number:text");
+}
+
+// a time style when the section has only hours, minutes and seconds,
+// which may run over 24 hours with [h]
+fn make_date_style(writer: &mut Writer<Cursor<Vec<u8>>>, name: &str, section: &Section) {
+    let items = section.get_items();
+    let dates = || items.iter().filter(|item| item.is_date());
+    let element = if dates().all(Item::is_time) { "number:time-style" } else { "number:date-style" };
+    let mut attributes = vec![("style:name", name)];
+    if element == "number:date-style" {
+        attributes.push(("number:automatic-order", "true"));
+    }
+    if dates().any(|item| matches!(item, Item::ElapsedHour { .. } | Item::ElapsedMinute { .. } | Item::ElapsedSecond { .. })) {
+        attributes.push(("number:truncate-on-overflow", "false"));
+    }
+    write_start_tag(writer, element, attributes, false);
+    make_date_format(writer, section);
+    write_end_tag(writer, element);
+}
+
+// number:* elements of a date style
+fn make_date_format(writer: &mut Writer<Cursor<Vec<u8>>>, section: &Section) {
+    let items = section.get_items();
+    for (i, item) in items.iter().enumerate() {
+        match *item {
+            Item::Year { long } => {
+                write_start_tag(writer, "number:year", long_style(long), true);
             },
-            "%-m" => {
-                 write_start_tag(writer, "number:month", vec![
-                ], true);
+            Item::Month(count) => {
+                let mut attributes = long_style(count == 2 || count == 4);
+                if count > 2 {
+                    attributes.push(("number:textual", "true"));
+                }
+                write_start_tag(writer, "number:month", attributes, true);
             },
-            "%d" => {
-                 write_start_tag(writer, "number:day", vec![
-                    ("number:style", "long"),
-                ], true);
+            Item::Day(count) if count <= 2 => {
+                write_start_tag(writer, "number:day", long_style(count == 2), true);
             },
-            "%-d" => {
-                 write_start_tag(writer, "number:day", vec![
-                ], true);
+            Item::Day(count) => {
+                write_start_tag(writer, "number:day-of-week", long_style(count == 4), true);
             },
-            "%H" => {
-                 write_start_tag(writer, "number:hours", vec![
-                    ("number:style", "long"),
-                ], true);
+            Item::Weekday { long } => {
+                write_start_tag(writer, "number:day-of-week", long_style(long), true);
             },
-            "%-H" => {
-                 write_start_tag(writer, "number:hours", vec![
-                ], true);
+            Item::Hour { long } | Item::ElapsedHour { long } => {
+                write_start_tag(writer, "number:hours", long_style(long), true);
             },
-            "%M" => {
-                 write_start_tag(writer, "number:minutes", vec![
-                    ("number:style", "long"),
-                ], true);
+            Item::Minute { long } | Item::ElapsedMinute { long } => {
+                write_start_tag(writer, "number:minutes", long_style(long), true);
             },
-            "%-M" => {
-                 write_start_tag(writer, "number:minutes", vec![
-                ], true);
+            Item::Second { long } | Item::ElapsedSecond { long } => {
+                let mut attributes = long_style(long);
+                let places = match items.get(i + 1) {
+                    Some(Item::SecondFraction(places)) => places.to_string(),
+                    _ => String::new(),
+                };
+                if !places.is_empty() {
+                    attributes.push(("number:decimal-places", places.as_str()));
+                }
+                write_start_tag(writer, "number:seconds", attributes, true);
             },
-            "%S" => {
-                 write_start_tag(writer, "number:seconds", vec![
-                    ("number:style", "long"),
-                ], true);
+            // written with the seconds
+            Item::SecondFraction(_) => {},
+            Item::AmPm { .. } => {
+                write_start_tag(writer, "number:am-pm", vec![], true);
             },
-            "%-S" => {
-                 write_start_tag(writer, "number:seconds", vec![
-                ], true);
+            Item::EraYear { long } => {
+                let mut attributes = vec![("number:calendar", "gengou")];
+                attributes.extend(long_style(long));
+                write_start_tag(writer, "number:year", attributes, true);
             },
-            "{{era1}}" => {
-                 write_start_tag(writer, "number:year", vec![
-                     ("number:calendar", "gengou"),
-                ], true);
+            // gengou2 = GG unsupport libreoffice
+            Item::Era(count) => {
+                let mut attributes = vec![("number:calendar", "gengou")];
+                attributes.extend(long_style(count == 3));
+                write_start_tag(writer, "number:era", attributes, true);
             },
-            "{{era2}}" => {
-                 write_start_tag(writer, "number:year", vec![
-                     ("number:calendar", "gengou"),
-                     ("number:style", "long"),
-                ], true);
+            ref item => make_number_text(writer, &item.literal()),
+        }
+    }
+}
+
+fn count_digits(items: &[Item], placeholder: Option<Placeholder>) -> usize {
+    items.iter().filter(|item| match item {
+        Item::Digit(p) => placeholder.is_none_or(|placeholder| placeholder == *p),
+        _ => false,
+    }).count()
+}
+
+fn is_fixed_digit(item: &Item) -> bool {
+    matches!(item, Item::Literal(text) if text.len() == 1 && text.chars().all(|c| c.is_ascii_digit()))
+}
+
+// number:number, number:scientific-number or number:fraction for the digits of a section,
+// returns how many items it used
+fn make_number_element(writer: &mut Writer<Cursor<Vec<u8>>>, items: &[Item]) -> usize {
+    let last = items.iter().rposition(|item| matches!(item, Item::Digit(_))).unwrap();
+    if let Some(exponent) = items[..last].iter().position(|item| matches!(item, Item::Exponent { .. })) {
+        let point = items[..exponent].iter().position(|item| *item == Item::DecimalPoint).unwrap_or(exponent);
+        let decimal_places = count_digits(&items[point..exponent], None).to_string();
+        let integer_digits = count_digits(&items[..point], Some(Placeholder::Zero)).to_string();
+        let exponent_digits = count_digits(&items[exponent..=last], None).to_string();
+        write_start_tag(writer, "number:scientific-number", vec![
+            ("number:decimal-places", decimal_places.as_str()),
+            ("number:min-integer-digits", integer_digits.as_str()),
+            ("number:min-exponent-digits", exponent_digits.as_str()),
+        ], true);
+        return last + 1;
+    }
+    if let Some(slash) = items.iter().position(|item| *item == Item::Slash).filter(|slash| *slash < last || items.get(*slash + 1).is_some_and(is_fixed_digit)) {
+        let numerator_start = items[..slash].iter().rposition(|item| !matches!(item, Item::Digit(_))).map_or(0, |i| i + 1);
+        let end = items[slash + 1..].iter()
+            .position(|item| !matches!(item, Item::Digit(_)) && !is_fixed_digit(item))
+            .map_or(items.len(), |i| slash + 1 + i);
+        let integer_digits = count_digits(&items[..numerator_start], Some(Placeholder::Zero)).to_string();
+        let numerator_digits = (slash - numerator_start).to_string();
+        // the zeros of a fixed denominator such as ?/10 are no placeholders
+        let denominator: String = if items.get(slash + 1).is_some_and(is_fixed_digit) {
+            items[slash + 1..end].iter().map(|item| match item {
+                Item::Digit(Placeholder::Zero) => String::from("0"),
+                item => item.literal(),
+            }).collect()
+        } else {
+            String::new()
+        };
+        let denominator_digits = (end - slash - 1).to_string();
+        let mut attributes = vec![];
+        if items[..numerator_start].iter().any(|item| matches!(item, Item::Digit(_))) {
+            attributes.push(("number:min-integer-digits", integer_digits.as_str()));
+        }
+        attributes.push(("number:min-numerator-digits", numerator_digits.as_str()));
+        if denominator.is_empty() {
+            attributes.push(("number:min-denominator-digits", denominator_digits.as_str()));
+        } else {
+            attributes.push(("number:denominator-value", denominator.as_str()));
+        }
+        write_start_tag(writer, "number:fraction", attributes, true);
+        return end;
+    }
+    let point = items.iter().position(|item| *item == Item::DecimalPoint).unwrap_or(items.len());
+    let integer_end = items[..point].iter().rposition(|item| matches!(item, Item::Digit(_))).map_or(0, |i| i + 1);
+    // commas right after the integer digits divide the number by 1000 each
+    let mut end = if point < items.len() { last.max(point) + 1 } else { integer_end };
+    let mut factor: u64 = 1;
+    while items.get(end) == Some(&Item::Thousands) {
+        factor *= 1000;
+        end += 1;
+    }
+    let decimal_places = count_digits(&items[point.min(end)..end], None).to_string();
+    let integer_digits = count_digits(&items[..point], Some(Placeholder::Zero)).to_string();
+    let factor = factor.to_string();
+    let mut attributes = vec![
+        ("number:decimal-places", decimal_places.as_str()),
+        ("number:min-integer-digits", integer_digits.as_str()),
+    ];
+    if items[..integer_end].contains(&Item::Thousands) {
+        attributes.push(("number:grouping", "true"));
+    }
+    if factor != "1" {
+        attributes.push(("number:display-factor", factor.as_str()));
+    }
+    // text between the integer digits such as 000-0000
+    let embedded: Vec<(String, String)> = items[..integer_end].iter().enumerate()
+        .filter(|(_, item)| !matches!(item, Item::Digit(_) | Item::Thousands))
+        .map(|(i, item)| (count_digits(&items[i..integer_end], None).to_string(), item.literal()))
+        .collect();
+    if embedded.is_empty() {
+        write_start_tag(writer, "number:number", attributes, true);
+    } else {
+        write_start_tag(writer, "number:number", attributes, false);
+        for (position, text) in embedded.iter() {
+            write_start_tag(writer, "number:embedded-text", vec![("number:position", position.as_str())], false);
+            write_text_node(writer, text.as_str());
+            write_end_tag(writer, "number:embedded-text");
+        }
+        write_end_tag(writer, "number:number");
+    }
+    end
+}
+
+// number:currency-symbol of a tag such as [$€-407], with the language of its locale
+fn make_currency_symbol(writer: &mut Writer<Cursor<Vec<u8>>>, currency: &str, locale: &str) {
+    let name = Locale::find(locale).map(|locale| locale.get_name().clone()).unwrap_or_default();
+    let mut parts = name.splitn(2, '-');
+    let mut attributes = vec![];
+    if let (Some(language), Some(country)) = (parts.next(), parts.next()) {
+        attributes.push(("number:language", language));
+        attributes.push(("number:country", country));
+    }
+    write_start_tag(writer, "number:currency-symbol", attributes, false);
+    write_text_node(writer, currency);
+    write_end_tag(writer, "number:currency-symbol");
+}
+
+// one section as a number, percentage or currency style, maps are (condition, style name)
+fn make_number_section(writer: &mut Writer<Cursor<Vec<u8>>>, name: &str, section: &Section, maps: Vec<(String, String)>, currency: bool) {
+    let items = section.get_items();
+    let element = if currency {
+        "number:currency-style"
+    } else if items.contains(&Item::Percent) {
+        "number:percentage-style"
+    } else {
+        "number:number-style"
+    };
+    write_start_tag(writer, element, vec![("style:name", name)], false);
+    if let Some(color) = section.get_color() {
+        write_start_tag(writer, "style:text-properties", vec![
+            ("fo:color", format!("#{:06X}", color.get_rgb()).as_str()),
+        ], true);
+    }
+    let mut i = 0;
+    while i < items.len() {
+        match items[i] {
+            Item::Digit(_) if i == items.iter().position(|item| matches!(item, Item::Digit(_))).unwrap() => {
+                i += make_number_element(writer, &items[i..]);
+                continue;
             },
-            // gengou2 = GG unsupport libreoffice
-            "{{gengou1}}" | "{{gengou2}}" => {
-                 write_start_tag(writer, "number:era", vec![
-                     ("number:calendar", "gengou"),
-                ], true);
+            Item::General => {
+                write_start_tag(writer, "number:number", vec![("number:min-integer-digits", "1")], true);
             },
-            "{{gengou3}}" => {
-                 write_start_tag(writer, "number:era", vec![
-                     ("number:calendar", "gengou"),
-                     ("number:style", "long"),
-                ], true);
+            Item::Locale(ref tag) if currency && !tag.get_currency().is_empty() => {
+                make_currency_symbol(writer, tag.get_currency(), tag.get_locale());
             },
-            _ => {
-                write_start_tag(writer, "number:text", vec![], false);
-                write_text_node(writer, String::from(*it));
-                write_end_tag(writer, "number:text");
+            ref item => make_number_text(writer, &item.literal()),
+        }
+        i += 1;
+    }
+    for (condition, style) in maps {
+        write_start_tag(writer, "style:map", vec![
+            ("style:condition", condition.as_str()),
+            ("style:apply-style-name", style.as_str()),
+        ], true);
+    }
+    write_end_tag(writer, element);
+}
+
+fn condition_expression(condition: Condition) -> String {
+    match condition {
+        Condition::Eq(value) => format!("value()={}", value),
+        Condition::Ne(value) => format!("value()!={}", value),
+        Condition::Lt(value) => format!("value()<{}", value),
+        Condition::Le(value) => format!("value()<={}", value),
+        Condition::Gt(value) => format!("value()>{}", value),
+        Condition::Ge(value) => format!("value()>={}", value),
+    }
+}
+
+// sections other than the main one become styles of their own, applied with style:map:
+// the negative and zero sections, or the sections with a condition
+fn make_number_style(writer: &mut Writer<Cursor<Vec<u8>>>, name: &str, format: &Format, currency: bool) {
+    let sections = format.get_sections();
+    let sections = &sections[..sections.len().min(3)];
+    let conditional = sections.iter().any(|section| section.get_condition().is_some());
+    let main = if conditional {
+        sections.iter().position(|section| section.get_condition().is_none()).unwrap_or(sections.len() - 1)
+    } else {
+        0
+    };
+    let mut maps = vec![];
+    for (i, section) in sections.iter().enumerate() {
+        let condition = match section.get_condition() {
+            _ if i == main => continue,
+            Some(condition) => condition_expression(condition),
+            None if conditional => continue,
+            None => String::from(if i == 1 { "value()<0" } else { "value()=0" }),
+        };
+        let section_name = format!("{}P{}", name, i);
+        make_number_section(writer, &section_name, section, vec![], currency);
+        maps.push((condition, section_name));
+    }
+    make_number_section(writer, name, &sections[main], maps, currency);
+}
+
+// Cells with the same value type, format and style share one automatic style.
+type StyleKey<'a> = (&'static str, &'a str, &'a Style);
+
+// the value type of the data style, empty for a cell with only a style
+fn style_key(cell: &Cell) -> Option<StyleKey<'_>> {
+    let format = cell.get_format();
+    let value_type = match cell.get_value() {
+        &Value::Date(_) => "date",
+        &Value::Bool(_) => "boolean",
+        &Value::Float(_) | &Value::Percentage(_) | &Value::Time(_) | &Value::Duration(_)
+            if format.is_date_format() => "date",
+        &Value::Float(_) | &Value::Percentage(_) | &Value::Time(_) | &Value::Duration(_)
+            if !format.get_content().is_empty() => "float",
+        &Value::Currency(..) if !format.get_content().is_empty() => "currency",
+        _ if !cell.get_style().is_default() => "",
+        _ => return None,
+    };
+    let content = if value_type.is_empty() { "" } else { format.get_content().as_str() };
+    Some((value_type, content, cell.get_style()))
+}
+
+fn style_name<'a, 'b>(cell: &'b Cell, style_hash: &'a HashMap<StyleKey<'b>, String>) -> Option<&'a String> {
+    style_key(cell).and_then(|key| style_hash.get(&key))
+}
+
+fn ods_color(color: Rgb) -> String {
+    format!("#{}", color.to_hex())
+}
+
+// width and line style of ods for the border styles of xlsx
+fn border_line(line: BorderLine) -> String {
+    let (width, style) = match line.get_style() {
+        BorderStyle::Hair => ("0.06pt", "solid"),
+        BorderStyle::Thin => ("0.74pt", "solid"),
+        BorderStyle::Medium => ("1.76pt", "solid"),
+        BorderStyle::Thick => ("2.49pt", "solid"),
+        BorderStyle::Dotted => ("0.74pt", "dotted"),
+        BorderStyle::Dashed => ("0.74pt", "dashed"),
+        BorderStyle::MediumDashed => ("1.76pt", "dashed"),
+        BorderStyle::DashDot => ("0.74pt", "dash-dot"),
+        BorderStyle::MediumDashDot | BorderStyle::SlantDashDot => ("1.76pt", "dash-dot"),
+        BorderStyle::DashDotDot => ("0.74pt", "dash-dot-dot"),
+        BorderStyle::MediumDashDotDot => ("1.76pt", "dash-dot-dot"),
+        BorderStyle::Double => ("2.27pt", "double"),
+    };
+    format!("{} {} {}", width, style, ods_color(line.get_color()))
+}
+
+// style:table-cell-properties, style:paragraph-properties and style:text-properties of a style
+fn make_style_properties(writer: &mut Writer<Cursor<Vec<u8>>>, style: &Style) {
+    let alignment = style.get_alignment();
+    let border = style.get_border();
+    let mut cell_properties: Vec<(&str, String)> = vec![];
+    match style.get_fill() {
+        Some(&Fill::Solid(color)) | Some(&Fill::Pattern { background: color, .. }) => {
+            cell_properties.push(("fo:background-color", ods_color(color)));
+        },
+        None => {},
+    }
+    for (name, line) in [("fo:border-left", border.get_left()), ("fo:border-right", border.get_right()),
+                         ("fo:border-top", border.get_top()), ("fo:border-bottom", border.get_bottom())] {
+        if let Some(line) = line {
+            cell_properties.push((name, border_line(line)));
+        }
+    }
+    if alignment.is_wrap() {
+        cell_properties.push(("fo:wrap-option", String::from("wrap")));
+    }
+    if let Some(vertical) = alignment.get_vertical() {
+        cell_properties.push(("style:vertical-align", String::from(match vertical {
+            VerticalAlignment::Top => "top",
+            VerticalAlignment::Center => "middle",
+            VerticalAlignment::Bottom => "bottom",
+        })));
+    }
+    if alignment.get_rotation() != 0 {
+        cell_properties.push(("style:rotation-angle", (alignment.get_rotation() as i32).rem_euclid(360).to_string()));
+    }
+    if let Some(horizontal) = alignment.get_horizontal() {
+        cell_properties.push(("style:text-align-source", String::from("fix")));
+        if horizontal == HorizontalAlignment::Fill {
+            cell_properties.push(("style:repeat-content", String::from("true")));
+        }
+    }
+    if !cell_properties.is_empty() {
+        write_start_tag(writer, "style:table-cell-properties", cell_properties.iter().map(|(k, v)| (*k, v.as_str())).collect(), true);
+    }
+
+    let mut paragraph_properties: Vec<(&str, String)> = vec![];
+    match alignment.get_horizontal() {
+        Some(HorizontalAlignment::Left) => paragraph_properties.push(("fo:text-align", String::from("start"))),
+        Some(HorizontalAlignment::Center) => paragraph_properties.push(("fo:text-align", String::from("center"))),
+        Some(HorizontalAlignment::Right) => paragraph_properties.push(("fo:text-align", String::from("end"))),
+        Some(HorizontalAlignment::Justify) => paragraph_properties.push(("fo:text-align", String::from("justify"))),
+        Some(HorizontalAlignment::Fill) | None => {},
+    }
+    // an indent level is 10pt
+    if alignment.get_indent() != 0 {
+        paragraph_properties.push(("fo:margin-left", format!("{}pt", alignment.get_indent() as u32 * 10)));
+    }
+    if !paragraph_properties.is_empty() {
+        write_start_tag(writer, "style:paragraph-properties", paragraph_properties.iter().map(|(k, v)| (*k, v.as_str())).collect(), true);
+    }
+
+    if let Some(font) = style.get_font() {
+        let mut text_properties: Vec<(&str, String)> = vec![];
+        if let Some(name) = font.get_name() {
+            text_properties.push(("style:font-name", name.clone()));
+        }
+        if let Some(size) = font.get_size() {
+            text_properties.push(("fo:font-size", format!("{}pt", size)));
+        }
+        if font.is_bold() {
+            text_properties.push(("fo:font-weight", String::from("bold")));
+        }
+        if font.is_italic() {
+            text_properties.push(("fo:font-style", String::from("italic")));
+        }
+        if font.get_underline() != Underline::None {
+            text_properties.push(("style:text-underline-style", String::from("solid")));
+            text_properties.push(("style:text-underline-width", String::from("auto")));
+            text_properties.push(("style:text-underline-color", String::from("font-color")));
+            if font.get_underline() == Underline::Double {
+                text_properties.push(("style:text-underline-type", String::from("double")));
             }
         }
+        if let Some(color) = font.get_color() {
+            text_properties.push(("fo:color", ods_color(color)));
+        }
+        write_start_tag(writer, "style:text-properties", text_properties.iter().map(|(k, v)| (*k, v.as_str())).collect(), true);
     }
 }
 
-fn make_num_styles(writer: &mut Writer<Cursor<Vec<u8>>>, book: &Book) -> HashMap<String, String> {
+fn make_cell_styles<'a>(writer: &mut Writer<Cursor<Vec<u8>>>, book: &'a Book) -> HashMap<StyleKey<'a>, String> {
     let mut result = HashMap::new();
     let mut count: usize = 0;
     for sheet in book.get_sheet_vec() {
         sheet.walk_through(|_, _, cell| {
-            match cell.get_value() {
-                &Value::Date(_) => {
-                    let format = cell.get_format().get_content();
-                    if !result.contains_key(format) {
-                        count = count + 1;
-                        let n_name = format!("N{}", count);
-                        let s_name = format!("ce{}", count);
-                        result.insert(format.clone(), s_name.clone());
-                         write_start_tag(writer, "number:date-style", vec![
-                            ("style:name", n_name.as_str()),
-                            ("number:automatic-order", "true"),
-                            ], false);
-                        make_number_format(writer, &cell.get_format().get_date_formats().unwrap());
-                        write_end_tag(writer, "number:date-style");
-                        write_start_tag(writer, "style:style", vec![
-                            ("style:name", s_name.as_str()),
-                            ("style:family", "table-cell"),
-                            ("style:parent-style-name", "Default"),
-                            ("style:data-style-name", n_name.as_str()),
-                            ], true);
-                    }
-                },
-                _ => {}
+            let key = match style_key(cell) {
+                Some(key) => key,
+                None => return,
+            };
+            if result.contains_key(&key) {
+                return;
+            }
+            let (value_type, _, style) = key;
+            let format = cell.get_format();
+            count += 1;
+            let n_name = format!("N{}", count);
+            let s_name = format!("ce{}", count);
+            if value_type == "date" {
+                make_date_style(writer, &n_name, &format.get_sections()[0]);
+            } else if value_type == "float" || value_type == "currency" {
+                make_number_style(writer, &n_name, format, value_type == "currency");
+            } else if value_type == "boolean" {
+                write_start_tag(writer, "number:boolean-style", vec![
+                    ("style:name", n_name.as_str()),
+                    ], false);
+                write_start_tag(writer, "number:boolean", vec![], true);
+                write_end_tag(writer, "number:boolean-style");
+            }
+            let mut attributes = vec![
+                ("style:name", s_name.as_str()),
+                ("style:family", "table-cell"),
+                ("style:parent-style-name", "Default"),
+            ];
+            if !value_type.is_empty() {
+                attributes.push(("style:data-style-name", n_name.as_str()));
             }
+            if style.is_default() {
+                write_start_tag(writer, "style:style", attributes, true);
+            } else {
+                write_start_tag(writer, "style:style", attributes, false);
+                make_style_properties(writer, style);
+                write_end_tag(writer, "style:style");
+            }
+            result.insert(key, s_name);
         });
     }
     result
 }
 
-pub fn write(book: &Book, dir: &TempDir) -> result::Result<(), OdsError> {
+pub fn write<W: Write + Seek>(book: &Book, zip: &mut ZipWriter<W>) -> result::Result<(), Error> {
     let mut writer = Writer::new(Cursor::new(Vec::new()));
     let _ = writer.write_event(Event::Decl(BytesDecl::new(b"1.0", Some(b"UTF-8"), None)));
     write_text_node(&mut writer, "\n");
@@ -278,6 +898,19 @@ pub fn write(book: &Book, dir: &TempDir) -> result::Result<(), OdsError> {
     write_end_tag(&mut writer, "style:font-face");
     write_start_tag(&mut writer, "style:font-face", vec![("style:name", "ヒラギノ明朝 ProN"),("svg:font-family", "&apos;ヒラギノ明朝 ProN&apos;"),("style:font-family-generic", "system"),("style:font-pitch", "variable"),], false);
     write_end_tag(&mut writer, "style:font-face");
+    let mut font_names: Vec<&String> = vec![];
+    for sheet in book.get_sheet_vec() {
+        sheet.walk_through(|_, _, cell| {
+            if let Some(name) = cell.get_style().get_font().and_then(|font| font.get_name()) {
+                if !font_names.contains(&name) && !FONT_FACES.contains(&name.as_str()) {
+                    font_names.push(name);
+                }
+            }
+        });
+    }
+    for name in font_names {
+        write_start_tag(&mut writer, "style:font-face", vec![("style:name", name.as_str()),("svg:font-family", name.as_str()),], true);
+    }
     write_end_tag(&mut writer, "office:font-face-decls");
     write_start_tag(&mut writer, "office:automatic-styles", vec![], false);
     write_start_tag(&mut writer, "style:style", vec![("style:name", "co1"),("style:family", "table-column"),], false);
@@ -288,21 +921,30 @@ pub fn write(book: &Book, dir: &TempDir) -> result::Result<(), OdsError> {
     write_start_tag(&mut writer, "style:table-row-properties", vec![("style:row-height", "4.52mm"),("fo:break-before", "auto"),("style:use-optimal-row-height", "true"),], false);
     write_end_tag(&mut writer, "style:table-row-properties");
     write_end_tag(&mut writer, "style:style");
+    let (column_styles, row_styles) = make_size_styles(&mut writer, book);
     write_start_tag(&mut writer, "style:style", vec![("style:name", "ta1"),("style:family", "table"),("style:master-page-name", "Default"),], false);
     write_start_tag(&mut writer, "style:table-properties", vec![("table:display", "true"),("style:writing-mode", "lr-tb"),], false);
     write_end_tag(&mut writer, "style:table-properties");
     write_end_tag(&mut writer, "style:style");
+    if book.get_sheet_vec().iter().any(|sheet| sheet.get_view().is_right_to_left()) {
+        write_start_tag(&mut writer, "style:style", vec![("style:name", "ta2"),("style:family", "table"),("style:master-page-name", "Default"),], false);
+        write_start_tag(&mut writer, "style:table-properties", vec![("table:display", "true"),("style:writing-mode", "rl-tb"),], true);
+        write_end_tag(&mut writer, "style:style");
+    }
 
-    let date_hash = make_num_styles(&mut writer, book);
+    let style_hash = make_cell_styles(&mut writer, book);
 
     write_end_tag(&mut writer, "office:automatic-styles");
     write_start_tag(&mut writer, "office:body", vec![], false);
     write_start_tag(&mut writer, "office:spreadsheet", vec![], false);
     write_start_tag(&mut writer, "table:calculation-settings", vec![("table:automatic-find-labels", "false"),("table:use-regular-expressions", "false"),("table:use-wildcards", "true"),], false);
+    if book.get_date_system() == DateSystem::Date1904 {
+        write_start_tag(&mut writer, "table:null-date", vec![("table:date-value", "1904-01-01"),], true);
+    }
     write_end_tag(&mut writer, "table:calculation-settings");
 
     for sheet in book.get_sheet_vec() {
-        let _ = make_content_xml_by_sheet(&mut writer, &sheet, &date_hash);
+        make_content_xml_by_sheet(&mut writer, sheet, &style_hash, &column_styles, &row_styles, book.get_date_system());
     }
 
     write_start_tag(&mut writer, "table:named-expressions", vec![], false);
@@ -311,10 +953,7 @@ pub fn write(book: &Book, dir: &TempDir) -> result::Result<(), OdsError> {
     write_end_tag(&mut writer, "office:body");
     write_end_tag(&mut writer, "office:document-content");
 
-    let file_path = dir.path().join(CONTENT_XML);
-    let mut f = File::create(file_path)?;
-    f.write_all(writer.into_inner().get_ref())?;
-    f.sync_all()?;
+    make_file_from_writer(CONTENT_XML, zip, writer)?;
 
     Ok(())
 }
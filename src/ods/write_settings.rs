@@ -0,0 +1,82 @@
+use super::{Book, Pane, SheetView};
+use std::io::Cursor;
+use std::result;
+use super::quick_xml::events::{Event, BytesDecl};
+use super::quick_xml::Writer;
+use crate::file_common::*;
+use super::Error;
+use std::io::{Seek, Write};
+use super::zip::ZipWriter;
+
+const SETTINGS_XML: &str = "settings.xml";
+
+fn make_config_item(writer: &mut Writer<Cursor<Vec<u8>>>, name: &str, config_type: &str, value: &str) {
+    write_start_tag(writer, "config:config-item", vec![("config:name", name),("config:type", config_type),], false);
+    write_text_node(writer, value);
+    write_end_tag(writer, "config:config-item");
+}
+
+fn bool_str(value: bool) -> &'static str {
+    if value { "true" } else { "false" }
+}
+
+// a frozen pane is split at a column and row, a split one at a position in pixels
+fn make_table_settings(writer: &mut Writer<Cursor<Vec<u8>>>, name: &str, view: &SheetView) {
+    let (mode, horizontal, vertical) = match view.get_pane() {
+        Some(Pane::Frozen { rows, columns }) => ("2", columns, rows),
+        Some(Pane::Split { x, y }) => ("1", super::points_to_pixels(x), super::points_to_pixels(y)),
+        None => ("0", 0, 0),
+    };
+    let (row_index, column_index) = view.get_active_cell();
+    write_start_tag(writer, "config:config-item-map-entry", vec![("config:name", name),], false);
+    make_config_item(writer, "CursorPositionX", "int", &column_index.to_string());
+    make_config_item(writer, "CursorPositionY", "int", &row_index.to_string());
+    make_config_item(writer, "HorizontalSplitMode", "short", if horizontal > 0 { mode } else { "0" });
+    make_config_item(writer, "VerticalSplitMode", "short", if vertical > 0 { mode } else { "0" });
+    make_config_item(writer, "HorizontalSplitPosition", "int", &horizontal.to_string());
+    make_config_item(writer, "VerticalSplitPosition", "int", &vertical.to_string());
+    // the cursor is in the bottom right pane when the columns are split, else in the bottom left one
+    make_config_item(writer, "ActiveSplitRange", "short", if horizontal > 0 { "3" } else { "2" });
+    make_config_item(writer, "PositionLeft", "int", "0");
+    make_config_item(writer, "PositionRight", "int", if mode == "2" { horizontal.to_string() } else { String::from("0") }.as_str());
+    make_config_item(writer, "PositionTop", "int", "0");
+    make_config_item(writer, "PositionBottom", "int", if mode == "2" { vertical.to_string() } else { String::from("0") }.as_str());
+    make_config_item(writer, "ZoomType", "short", "0");
+    make_config_item(writer, "ZoomValue", "int", &view.get_zoom().to_string());
+    make_config_item(writer, "PageViewZoomValue", "int", "60");
+    make_config_item(writer, "ShowGrid", "boolean", bool_str(view.shows_grid_lines()));
+    make_config_item(writer, "HasColumnRowHeaders", "boolean", bool_str(view.shows_headers()));
+    write_end_tag(writer, "config:config-item-map-entry");
+}
+
+pub fn write<W: Write + Seek>(book: &Book, zip: &mut ZipWriter<W>) -> result::Result<(), Error> {
+    let mut writer = Writer::new(Cursor::new(Vec::new()));
+    let _ = writer.write_event(Event::Decl(BytesDecl::new(b"1.0", Some(b"UTF-8"), None)));
+    write_text_node(&mut writer, "\n");
+    write_start_tag(&mut writer, "office:document-settings", vec![("xmlns:office", "urn:oasis:names:tc:opendocument:xmlns:office:1.0"),("xmlns:xlink", "http://www.w3.org/1999/xlink"),("xmlns:config", "urn:oasis:names:tc:opendocument:xmlns:config:1.0"),("xmlns:ooo", "http://openoffice.org/2004/office"),("office:version", "1.2"),], false);
+    write_start_tag(&mut writer, "office:settings", vec![], false);
+    write_start_tag(&mut writer, "config:config-item-set", vec![("config:name", "ooo:view-settings"),], false);
+    write_start_tag(&mut writer, "config:config-item-map-indexed", vec![("config:name", "Views"),], false);
+    write_start_tag(&mut writer, "config:config-item-map-entry", vec![], false);
+    make_config_item(&mut writer, "ViewId", "string", "view1");
+    write_start_tag(&mut writer, "config:config-item-map-named", vec![("config:name", "Tables"),], false);
+    for sheet in book.get_sheet_vec() {
+        make_table_settings(&mut writer, sheet.get_name(), sheet.get_view());
+    }
+    write_end_tag(&mut writer, "config:config-item-map-named");
+    // older versions of LibreOffice only know the settings of the whole view
+    let active = book.get_sheet_vec().get(book.get_active_sheet());
+    if let Some(sheet) = active {
+        make_config_item(&mut writer, "ActiveTable", "string", sheet.get_name());
+    }
+    let view = active.map(|sheet| sheet.get_view().clone()).unwrap_or_default();
+    make_config_item(&mut writer, "HasColumnRowHeaders", "boolean", bool_str(view.shows_headers()));
+    make_config_item(&mut writer, "ShowGrid", "boolean", bool_str(view.shows_grid_lines()));
+    write_end_tag(&mut writer, "config:config-item-map-entry");
+    write_end_tag(&mut writer, "config:config-item-map-indexed");
+    write_end_tag(&mut writer, "config:config-item-set");
+    write_end_tag(&mut writer, "office:settings");
+    write_end_tag(&mut writer, "office:document-settings");
+    make_file_from_writer(SETTINGS_XML, zip, writer)?;
+    Ok(())
+}
@@ -0,0 +1,468 @@
+//! Cell Style
+//!
+//! A [`Style`] is how a cell looks apart from its number format: the font,
+//! the fill, the border of each edge and the alignment of the text. Cells
+//! which look the same share one entry of `cellXfs` in xlsx and one
+//! automatic `style:style` in ods.
+//!
+//! ```
+//! use spsheet::Cell;
+//! use spsheet::style::{Alignment, Border, BorderLine, BorderStyle, Fill, Font, HorizontalAlignment, Rgb, Style};
+//! let style = Style::new()
+//!     .with_font(Font::new().with_name("Arial").with_size(12.0).with_bold(true))
+//!     .with_fill(Fill::Solid(Rgb(0xFF, 0xFF, 0xCC)))
+//!     .with_border(Border::new().with_bottom(BorderLine::new(BorderStyle::Thin, Rgb(0, 0, 0))))
+//!     .with_alignment(Alignment::new().with_horizontal(HorizontalAlignment::Center));
+//! let mut cell = Cell::str("Total", "");
+//! cell.set_style(style);
+//! assert_eq!(Some(12.0), cell.get_style().get_font().and_then(|font| font.get_size()));
+//! ```
+use super::format::Color;
+
+/// 24 bit color
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Rgb(pub u8, pub u8, pub u8);
+
+impl Rgb {
+    /// `#RRGGBB`, `RRGGBB`, or `AARRGGBB` as xlsx writes it with the alpha first
+    ///
+    /// ```
+    /// use spsheet::style::Rgb;
+    /// assert_eq!(Some(Rgb(0xFF, 0, 0x80)), Rgb::from_hex("#ff0080"));
+    /// assert_eq!(Some(Rgb(0xFF, 0, 0x80)), Rgb::from_hex("FFFF0080"));
+    /// assert_eq!("FF0080", Rgb(0xFF, 0, 0x80).to_hex());
+    /// ```
+    pub fn from_hex(hex: &str) -> Option<Rgb> {
+        let hex = hex.strip_prefix('#').unwrap_or(hex);
+        let hex = match hex.len() {
+            6 => hex,
+            8 => &hex[2..],
+            _ => return None,
+        };
+        let value = u32::from_str_radix(hex, 16).ok()?;
+        Some(Rgb((value >> 16) as u8, (value >> 8) as u8, value as u8))
+    }
+
+    /// `RRGGBB` in upper case
+    pub fn to_hex(&self) -> String {
+        format!("{:02X}{:02X}{:02X}", self.0, self.1, self.2)
+    }
+}
+
+impl From<Color> for Rgb {
+    fn from(color: Color) -> Rgb {
+        let rgb = color.get_rgb();
+        Rgb((rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Underline {
+    #[default]
+    None,
+    Single,
+    Double,
+}
+
+/// Font of a cell, what is not set is the default font of the document
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub struct Font {
+    name: Option<String>,
+    // in hundredths of a point, which keeps the style hashable
+    size: Option<u32>,
+    bold: bool,
+    italic: bool,
+    underline: Underline,
+    color: Option<Rgb>,
+}
+
+impl Font {
+    pub fn new() -> Font {
+        Font::default()
+    }
+
+    pub fn with_name<S: Into<String>>(mut self, name: S) -> Font {
+        self.name = Some(name.into());
+        self
+    }
+
+    /// Size in points, rounded to a hundredth
+    pub fn with_size(mut self, size: f64) -> Font {
+        self.size = Some((size * 100.0).round() as u32);
+        self
+    }
+
+    pub fn with_bold(mut self, bold: bool) -> Font {
+        self.bold = bold;
+        self
+    }
+
+    pub fn with_italic(mut self, italic: bool) -> Font {
+        self.italic = italic;
+        self
+    }
+
+    pub fn with_underline(mut self, underline: Underline) -> Font {
+        self.underline = underline;
+        self
+    }
+
+    pub fn with_color(mut self, color: Rgb) -> Font {
+        self.color = Some(color);
+        self
+    }
+
+    pub fn get_name(&self) -> Option<&String> {
+        self.name.as_ref()
+    }
+
+    pub fn get_size(&self) -> Option<f64> {
+        self.size.map(|size| size as f64 / 100.0)
+    }
+
+    pub fn is_bold(&self) -> bool {
+        self.bold
+    }
+
+    pub fn is_italic(&self) -> bool {
+        self.italic
+    }
+
+    pub fn get_underline(&self) -> Underline {
+        self.underline
+    }
+
+    pub fn get_color(&self) -> Option<Rgb> {
+        self.color
+    }
+}
+
+/// Hatching of a pattern fill, named as in xlsx
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Pattern {
+    DarkGray,
+    MediumGray,
+    LightGray,
+    Gray125,
+    Gray0625,
+    DarkHorizontal,
+    DarkVertical,
+    DarkDown,
+    DarkUp,
+    DarkGrid,
+    DarkTrellis,
+    LightHorizontal,
+    LightVertical,
+    LightDown,
+    LightUp,
+    LightGrid,
+    LightTrellis,
+}
+
+const PATTERNS: [(Pattern, &str); 17] = [
+    (Pattern::DarkGray, "darkGray"),
+    (Pattern::MediumGray, "mediumGray"),
+    (Pattern::LightGray, "lightGray"),
+    (Pattern::Gray125, "gray125"),
+    (Pattern::Gray0625, "gray0625"),
+    (Pattern::DarkHorizontal, "darkHorizontal"),
+    (Pattern::DarkVertical, "darkVertical"),
+    (Pattern::DarkDown, "darkDown"),
+    (Pattern::DarkUp, "darkUp"),
+    (Pattern::DarkGrid, "darkGrid"),
+    (Pattern::DarkTrellis, "darkTrellis"),
+    (Pattern::LightHorizontal, "lightHorizontal"),
+    (Pattern::LightVertical, "lightVertical"),
+    (Pattern::LightDown, "lightDown"),
+    (Pattern::LightUp, "lightUp"),
+    (Pattern::LightGrid, "lightGrid"),
+    (Pattern::LightTrellis, "lightTrellis"),
+];
+
+impl Pattern {
+    pub fn get_name(&self) -> &'static str {
+        PATTERNS.iter().find(|(pattern, _)| pattern == self).unwrap().1
+    }
+
+    pub fn from_name(name: &str) -> Option<Pattern> {
+        PATTERNS.iter().find(|(_, n)| *n == name).map(|(pattern, _)| *pattern)
+    }
+}
+
+/// Background of a cell. ods has no patterns, a pattern fill is written
+/// there as its background color and read back as a solid fill.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Fill {
+    Solid(Rgb),
+    Pattern {
+        pattern: Pattern,
+        color: Rgb,
+        background: Rgb,
+    },
+}
+
+/// Line style of a border edge, named as in xlsx
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BorderStyle {
+    Hair,
+    Thin,
+    Medium,
+    Thick,
+    Dotted,
+    Dashed,
+    MediumDashed,
+    DashDot,
+    MediumDashDot,
+    DashDotDot,
+    MediumDashDotDot,
+    SlantDashDot,
+    Double,
+}
+
+const BORDER_STYLES: [(BorderStyle, &str); 13] = [
+    (BorderStyle::Hair, "hair"),
+    (BorderStyle::Thin, "thin"),
+    (BorderStyle::Medium, "medium"),
+    (BorderStyle::Thick, "thick"),
+    (BorderStyle::Dotted, "dotted"),
+    (BorderStyle::Dashed, "dashed"),
+    (BorderStyle::MediumDashed, "mediumDashed"),
+    (BorderStyle::DashDot, "dashDot"),
+    (BorderStyle::MediumDashDot, "mediumDashDot"),
+    (BorderStyle::DashDotDot, "dashDotDot"),
+    (BorderStyle::MediumDashDotDot, "mediumDashDotDot"),
+    (BorderStyle::SlantDashDot, "slantDashDot"),
+    (BorderStyle::Double, "double"),
+];
+
+impl BorderStyle {
+    pub fn get_name(&self) -> &'static str {
+        BORDER_STYLES.iter().find(|(style, _)| style == self).unwrap().1
+    }
+
+    pub fn from_name(name: &str) -> Option<BorderStyle> {
+        BORDER_STYLES.iter().find(|(_, n)| *n == name).map(|(style, _)| *style)
+    }
+}
+
+/// One edge of a border
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct BorderLine {
+    style: BorderStyle,
+    color: Rgb,
+}
+
+impl BorderLine {
+    pub fn new(style: BorderStyle, color: Rgb) -> BorderLine {
+        BorderLine { style, color }
+    }
+
+    pub fn get_style(&self) -> BorderStyle {
+        self.style
+    }
+
+    pub fn get_color(&self) -> Rgb {
+        self.color
+    }
+}
+
+/// Lines around a cell, an edge without a line is `None`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Border {
+    left: Option<BorderLine>,
+    right: Option<BorderLine>,
+    top: Option<BorderLine>,
+    bottom: Option<BorderLine>,
+}
+
+impl Border {
+    pub fn new() -> Border {
+        Border::default()
+    }
+
+    /// The same line on all four edges
+    pub fn all(line: BorderLine) -> Border {
+        Border { left: Some(line), right: Some(line), top: Some(line), bottom: Some(line) }
+    }
+
+    pub fn with_left(mut self, line: BorderLine) -> Border {
+        self.left = Some(line);
+        self
+    }
+
+    pub fn with_right(mut self, line: BorderLine) -> Border {
+        self.right = Some(line);
+        self
+    }
+
+    pub fn with_top(mut self, line: BorderLine) -> Border {
+        self.top = Some(line);
+        self
+    }
+
+    pub fn with_bottom(mut self, line: BorderLine) -> Border {
+        self.bottom = Some(line);
+        self
+    }
+
+    pub fn get_left(&self) -> Option<BorderLine> {
+        self.left
+    }
+
+    pub fn get_right(&self) -> Option<BorderLine> {
+        self.right
+    }
+
+    pub fn get_top(&self) -> Option<BorderLine> {
+        self.top
+    }
+
+    pub fn get_bottom(&self) -> Option<BorderLine> {
+        self.bottom
+    }
+
+    pub fn is_empty(&self) -> bool {
+        *self == Border::default()
+    }
+}
+
+/// Horizontal alignment, `None` in [`Alignment`] aligns by the value type
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HorizontalAlignment {
+    Left,
+    Center,
+    Right,
+    Justify,
+    /// the text is repeated to fill the cell
+    Fill,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum VerticalAlignment {
+    Top,
+    Center,
+    Bottom,
+}
+
+/// Position of the text in a cell
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Alignment {
+    horizontal: Option<HorizontalAlignment>,
+    vertical: Option<VerticalAlignment>,
+    wrap: bool,
+    rotation: i16,
+    indent: u8,
+}
+
+impl Alignment {
+    pub fn new() -> Alignment {
+        Alignment::default()
+    }
+
+    pub fn with_horizontal(mut self, horizontal: HorizontalAlignment) -> Alignment {
+        self.horizontal = Some(horizontal);
+        self
+    }
+
+    pub fn with_vertical(mut self, vertical: VerticalAlignment) -> Alignment {
+        self.vertical = Some(vertical);
+        self
+    }
+
+    pub fn with_wrap(mut self, wrap: bool) -> Alignment {
+        self.wrap = wrap;
+        self
+    }
+
+    /// Degrees counterclockwise from -90 to 90
+    pub fn with_rotation(mut self, rotation: i16) -> Alignment {
+        self.rotation = rotation.clamp(-90, 90);
+        self
+    }
+
+    /// Indent level, one level is about the width of three characters
+    pub fn with_indent(mut self, indent: u8) -> Alignment {
+        self.indent = indent;
+        self
+    }
+
+    pub fn get_horizontal(&self) -> Option<HorizontalAlignment> {
+        self.horizontal
+    }
+
+    pub fn get_vertical(&self) -> Option<VerticalAlignment> {
+        self.vertical
+    }
+
+    pub fn is_wrap(&self) -> bool {
+        self.wrap
+    }
+
+    pub fn get_rotation(&self) -> i16 {
+        self.rotation
+    }
+
+    pub fn get_indent(&self) -> u8 {
+        self.indent
+    }
+
+    pub fn is_default(&self) -> bool {
+        *self == Alignment::default()
+    }
+}
+
+/// Font, fill, border and alignment of a cell
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub struct Style {
+    font: Option<Font>,
+    fill: Option<Fill>,
+    border: Border,
+    alignment: Alignment,
+}
+
+impl Style {
+    pub fn new() -> Style {
+        Style::default()
+    }
+
+    pub fn with_font(mut self, font: Font) -> Style {
+        self.font = Some(font);
+        self
+    }
+
+    pub fn with_fill(mut self, fill: Fill) -> Style {
+        self.fill = Some(fill);
+        self
+    }
+
+    pub fn with_border(mut self, border: Border) -> Style {
+        self.border = border;
+        self
+    }
+
+    pub fn with_alignment(mut self, alignment: Alignment) -> Style {
+        self.alignment = alignment;
+        self
+    }
+
+    pub fn get_font(&self) -> Option<&Font> {
+        self.font.as_ref()
+    }
+
+    pub fn get_fill(&self) -> Option<&Fill> {
+        self.fill.as_ref()
+    }
+
+    pub fn get_border(&self) -> &Border {
+        &self.border
+    }
+
+    pub fn get_alignment(&self) -> &Alignment {
+        &self.alignment
+    }
+
+    /// Nothing set, the cell looks as the document default
+    pub fn is_default(&self) -> bool {
+        *self == Style::default()
+    }
+}
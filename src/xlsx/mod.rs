@@ -1,32 +1,32 @@
 //! Office Open XML xlsx read and write
 extern crate chrono;
 extern crate quick_xml;
-extern crate time;
-extern crate tempdir;
-extern crate walkdir;
 extern crate zip;
 
 use crate::file_common::*;
 use std::collections::HashMap;
-use std::io;
+use std::io::{Cursor, Read, Seek, Write};
 use std::path::Path;
 use std::result;
 use std::fs::File;
-use std::string::FromUtf8Error;
 use self::chrono::prelude::*;
-use self::tempdir::TempDir;
-use super::{Book,Sheet,Cell,Value,column_and_row_to_index,index_to_column};
+use self::zip::{ZipArchive, ZipWriter};
+use super::{Book,Sheet,SheetView,Pane,Cell,CellRange,Column,Comment,Hyperlink,LinkTarget,Row,Value,DateSystem,Error,ErrorKind,column_and_row_to_index,index_to_column};
 
 mod read_sheet;
 mod read_shared_strings;
 mod read_styles;
 mod read_workbook_xml_rels;
 mod read_workbook;
+mod read_comments;
+mod read_sheet_xml_rels;
 mod write_sheet;
 mod write_shared_strings;
 mod write_styles;
 mod write_workbook;
 mod write_workbook_xml_rels;
+mod write_comments;
+mod write_sheet_xml_rels;
 
 const CONTENT_TYPE_XML: &'static str = r#"<?xml version="1.0" encoding="UTF-8"?>
 <Types xmlns="http://schemas.openxmlformats.org/package/2006/content-types"><Override PartName="/_rels/.rels" ContentType="application/vnd.openxmlformats-package.relationships+xml"/><Override PartName="/docProps/app.xml" ContentType="application/vnd.openxmlformats-officedocument.extended-properties+xml"/><Override PartName="/docProps/core.xml" ContentType="application/vnd.openxmlformats-package.core-properties+xml"/><Override PartName="/xl/sharedStrings.xml" ContentType="application/vnd.openxmlformats-officedocument.spreadsheetml.sharedStrings+xml"/><Override PartName="/xl/_rels/workbook.xml.rels" ContentType="application/vnd.openxmlformats-package.relationships+xml"/><Override PartName="/xl/worksheets/sheet4.xml" ContentType="application/vnd.openxmlformats-officedocument.spreadsheetml.worksheet+xml"/><Override PartName="/xl/worksheets/sheet3.xml" ContentType="application/vnd.openxmlformats-officedocument.spreadsheetml.worksheet+xml"/><Override PartName="/xl/worksheets/sheet2.xml" ContentType="application/vnd.openxmlformats-officedocument.spreadsheetml.worksheet+xml"/><Override PartName="/xl/worksheets/sheet1.xml" ContentType="application/vnd.openxmlformats-officedocument.spreadsheetml.worksheet+xml"/><Override PartName="/xl/styles.xml" ContentType="application/vnd.openxmlformats-officedocument.spreadsheetml.styles+xml"/><Override PartName="/xl/workbook.xml" ContentType="application/vnd.openxmlformats-officedocument.spreadsheetml.sheet.main+xml"/>
@@ -40,104 +40,89 @@ const RELS_CONTENT: &'static str = r#"<?xml version="1.0" encoding="UTF-8"?>
 <Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships"><Relationship Id="rId1" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/officeDocument" Target="xl/workbook.xml"/><Relationship Id="rId2" Type="http://schemas.openxmlformats.org/package/2006/relationships/metadata/core-properties" Target="docProps/core.xml"/><Relationship Id="rId3" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/extended-properties" Target="docProps/app.xml"/>
 </Relationships>"#;
 
-#[derive(Debug)]
-pub enum XlsxError {
-    Io(io::Error),
-    Xml(quick_xml::Error),
-    Zip(zip::result::ZipError),
-    Uft8(FromUtf8Error),
-}
+type Result<T> = result::Result<T, Error>;
 
-impl From<io::Error> for XlsxError {
-    fn from(err: io::Error) -> XlsxError {
-        XlsxError::Io(err)
+// the static content types with the comments and drawings of the sheets which have comments
+fn make_content_types(book: &Book) -> String {
+    let mut types = String::new();
+    for (i, sheet) in book.get_sheet_vec().iter().enumerate() {
+        if !write_comments::sheet_comments(sheet).is_empty() {
+            types.push_str(format!(r#"<Override PartName="/xl/comments{}.xml" ContentType="application/vnd.openxmlformats-officedocument.spreadsheetml.comments+xml"/>"#, i + 1).as_str());
+        }
     }
-}
-
-impl From<quick_xml::Error> for XlsxError {
-    fn from(err: quick_xml::Error) -> XlsxError {
-        XlsxError::Xml(err)
+    if !types.is_empty() {
+        types.insert_str(0, r#"<Default Extension="vml" ContentType="application/vnd.openxmlformats-officedocument.vmlDrawing"/>"#);
     }
+    CONTENT_TYPE_XML.replace("\n</Types>", format!("{}\n</Types>", types).as_str())
 }
 
-impl From<zip::result::ZipError> for XlsxError {
-    fn from(err: zip::result::ZipError) -> XlsxError {
-        XlsxError::Zip(err)
-    }
+pub fn read(path: &Path) -> Result<Book> {
+    read_from(File::open(path)?)
 }
 
-impl From<FromUtf8Error> for XlsxError {
-    fn from(err: FromUtf8Error) -> XlsxError {
-        XlsxError::Uft8(err)
-    }
+/// Read a book from any seekable stream, e.g. an uploaded file.
+pub fn read_from<R: Read + Seek>(reader: R) -> Result<Book> {
+    read_archive(&mut ZipArchive::new(reader)?)
 }
 
-type Result<T> = result::Result<T, XlsxError>;
-
-pub fn read(path: &Path) -> Result<Book> {
-    let file = File::open(path)?;
-    let dir = TempDir::new("shreadsheet")?;
-    match unzip(&file, &dir) {
-        Ok(_) => {},
-        Err(err) => {
-            dir.close()?;
-            return Err(XlsxError::Zip(err));
-        }
-    }
+pub(crate) fn read_archive<R: Read + Seek>(zip: &mut ZipArchive<R>) -> Result<Book> {
     let mut book = Book::new();
-    {
-        let styles = read_styles::read(&dir)?;
-        let rels = read_workbook_xml_rels::read(&dir)?;
-        let mut rels_map = HashMap::new();
-        for r in &rels {
-            rels_map.insert(r.get("id").unwrap(), r.get("target").unwrap());
-        }
-        let sheets = read_workbook::read(&dir)?;
-        let shared_strings = read_shared_strings::read(&dir)?;
-        for s in &sheets {
-            let sheet_target = rels_map.get(s.get("rid").unwrap()).unwrap();
-            book.add_sheet(
-                read_sheet::read(
-                    &dir, s.get("name").unwrap(),
-                    sheet_target,
-                    &shared_strings,
-                    &styles)?);
-        }
+    let styles = read_styles::read(zip)?;
+    let rels = read_workbook_xml_rels::read(zip)?;
+    let mut rels_map = HashMap::new();
+    for r in rels {
+        rels_map.insert(r["id"].clone(), r["target"].clone());
+    }
+    let (sheets, date_system, active_sheet) = read_workbook::read(zip, &rels_map)?;
+    book.set_date_system(date_system);
+    book.set_active_sheet(active_sheet);
+    let shared_strings = read_shared_strings::read(zip)?;
+    for s in &sheets {
+        book.add_sheet(
+            read_sheet::read(
+                zip, &s["name"],
+                &s["target"],
+                &shared_strings,
+                &styles,
+                date_system)?);
     }
-    dir.close()?;
     Ok(book)
 }
 
-pub fn write(book: &Book, path: &Path) -> result::Result<(), XlsxError> {
-    let dir = TempDir::new("shreadsheet")?;
+/// Read a book from an in-memory xlsx file.
+pub fn read_from_bytes(bytes: &[u8]) -> Result<Book> {
+    read_from(Cursor::new(bytes))
+}
+
+pub fn write(book: &Book, path: &Path) -> Result<()> {
+    write_to(book, File::create(path)?)
+}
+
+/// Write a book to any seekable stream, e.g. a response body buffer.
+pub fn write_to<W: Write + Seek>(book: &Book, writer: W) -> Result<()> {
+    let mut zip = ZipWriter::new(writer);
     let now = Utc::now();
     let now_str = now.format("%Y-%m-%dT%H:%M:%SZ").to_string();
-    let _ = make_static_file(
-        &dir, RELS,
-        RELS_CONTENT,
-        Some("_rels"))?;
-    let _ = make_static_file(
-        &dir, "[Content_Types].xml",
-        CONTENT_TYPE_XML,
-        None)?;
-    let _ = make_static_file(
-        &dir, "docProps/app.xml",
-        APP_XML,
-        Some("docProps"))?;
-    let _ = make_static_file(
-        &dir, "docProps/core.xml",
-        CORE_XML.replace("XXXXXXXXXX", now_str.as_str()).as_str(),
-        Some("docProps"))?;
-    let format_map = write_styles::write(book, &dir)?;
-    let shared_strings = write_shared_strings::write(book, &dir)?;
-    let _ = write_workbook_xml_rels::write(book, &dir)?;
-    let _ = write_workbook::write(book, &dir)?;
-    let mut index = 1;
-    for sheet in book.get_sheet_vec() {
-        let _ = write_sheet::write(sheet, &dir, &shared_strings, index, &format_map)?;
-        index = index + 1;
+    make_static_file(&mut zip, RELS, RELS_CONTENT)?;
+    make_static_file(&mut zip, "[Content_Types].xml", make_content_types(book).as_str())?;
+    make_static_file(&mut zip, "docProps/app.xml", APP_XML)?;
+    make_static_file(
+        &mut zip, "docProps/core.xml",
+        CORE_XML.replace("XXXXXXXXXX", now_str.as_str()).as_str())?;
+    let format_map = write_styles::write(book, &mut zip)?;
+    let shared_strings = write_shared_strings::write(book, &mut zip)?;
+    write_workbook_xml_rels::write(book, &mut zip)?;
+    write_workbook::write(book, &mut zip)?;
+    for (index, sheet) in book.get_sheet_vec().iter().enumerate() {
+        write_sheet::write(sheet, &mut zip, &shared_strings, index + 1, index == book.get_active_sheet(), &format_map, book.get_date_system())?;
     }
-    write_to_file(path, &dir)?;
-    dir.close()?;
+    zip.finish()?;
     Ok(())
 }
+
+/// Write a book into an in-memory xlsx file.
+pub fn write_to_bytes(book: &Book) -> Result<Vec<u8>> {
+    let mut cursor = Cursor::new(Vec::new());
+    write_to(book, &mut cursor)?;
+    Ok(cursor.into_inner())
+}
@@ -0,0 +1,155 @@
+use crate::file_common::*;
+use std::result;
+use super::quick_xml::events::{Event};
+use super::Error;
+use super::{Comment, column_and_row_to_index};
+use std::io::{Read, Seek};
+use super::zip::ZipArchive;
+
+/// The comments of a commentsN.xml part as (row, column, comment), rich text runs are joined.
+pub fn read<R: Read + Seek>(zip: &mut ZipArchive<R>, part: &str) -> result::Result<Vec<(usize, usize, Comment)>, Error> {
+    let mut reader = open_part(zip, part)?;
+    reader.trim_text(true);
+    let mut buf = Vec::new();
+    let mut authors: Vec<String> = Vec::new();
+    let mut res: Vec<(usize, usize, Comment)> = Vec::new();
+    let mut in_author = false;
+    let mut in_text = false;
+    let mut in_phonetic = false;
+    // the cell, author and text of the comment being read
+    let mut cell: Option<(usize, usize)> = None;
+    let mut author_id: usize = 0;
+    let mut text = String::from("");
+    loop {
+        let position = reader.buffer_position();
+        match reader.read_event(&mut buf) {
+            Ok(Event::Start(ref e)) => {
+                match e.name() {
+                    b"author" => {
+                        in_author = true;
+                        authors.push(String::from(""));
+                    },
+                    b"comment" => {
+                        cell = None;
+                        author_id = 0;
+                        text = String::from("");
+                        for a in e.attributes().with_checks(false) {
+                            match a {
+                                Ok(ref attr) if attr.key == b"ref" => {
+                                    let value = get_attribute_value(attr)?;
+                                    cell = match column_and_row_to_index(value.as_str()) {
+                                        Some((column, row)) => Some((row, column)),
+                                        None => return Err(Error::malformed(
+                                            part, position, format!("invalid cell reference {}", value))),
+                                    };
+                                },
+                                Ok(ref attr) if attr.key == b"authorId" => {
+                                    let value = get_attribute_value(attr)?;
+                                    author_id = value.parse::<usize>().map_err(|_| {
+                                        Error::malformed(part, position, format!("invalid author id {}", value))
+                                    })?;
+                                },
+                                Ok(_) => {},
+                                Err(_) => {},
+                            }
+                        }
+                    },
+                    // Excel starts the text with an `Author:` run and a "\n.." run
+                    b"text" => {
+                        reader.trim_text(false);
+                    },
+                    b"t" => in_text = true,
+                    b"rPh" => in_phonetic = true,
+                    _ => (),
+                }
+            },
+            Ok(Event::End(ref e)) => {
+                match e.name() {
+                    b"author" => in_author = false,
+                    b"text" => {
+                        reader.trim_text(true);
+                    },
+                    b"t" => in_text = false,
+                    b"rPh" => in_phonetic = false,
+                    b"comment" => {
+                        let (row, column) = cell.take().ok_or_else(|| {
+                            Error::malformed(part, position, "comment without ref")
+                        })?;
+                        let author = authors.get(author_id).cloned().unwrap_or_default();
+                        res.push((row, column, Comment::new(author, text.clone())));
+                    },
+                    _ => (),
+                }
+            },
+            Ok(Event::Text(e)) => {
+                let value = e.unescape_and_decode(&reader)
+                    .map_err(|err| Error::xml(part, position, err))?;
+                if in_author {
+                    if let Some(author) = authors.last_mut() {
+                        author.push_str(&value);
+                    }
+                } else if in_text && !in_phonetic {
+                    text.push_str(&value);
+                }
+            },
+            Ok(Event::Eof) => break,
+            Err(e) => return Err(Error::xml(part, reader.buffer_position(), e)),
+            _ => (),
+        }
+        buf.clear();
+    }
+    Ok(res)
+}
+
+/// The cells of the notes in a vmlDrawingN.vml part which are shown all the time.
+pub fn read_visible<R: Read + Seek>(zip: &mut ZipArchive<R>, part: &str) -> result::Result<Vec<(usize, usize)>, Error> {
+    let mut reader = open_part(zip, part)?;
+    reader.trim_text(true);
+    // VML from older versions of Excel leaves tags such as <br> open
+    reader.check_end_names(false);
+    let mut buf = Vec::new();
+    let mut res: Vec<(usize, usize)> = Vec::new();
+    let mut element: Vec<u8> = Vec::new();
+    let mut row: Option<usize> = None;
+    let mut column: Option<usize> = None;
+    let mut visible = false;
+    loop {
+        let position = reader.buffer_position();
+        match reader.read_event(&mut buf) {
+            Ok(Event::Start(ref e)) => {
+                if e.name() == b"x:ClientData" {
+                    row = None;
+                    column = None;
+                    visible = false;
+                }
+                visible = visible || e.name() == b"x:Visible";
+                element = e.name().to_vec();
+            },
+            Ok(Event::Empty(ref e)) => {
+                visible = visible || e.name() == b"x:Visible";
+            },
+            Ok(Event::Text(e)) => {
+                let value = e.unescape_and_decode(&reader)
+                    .map_err(|err| Error::xml(part, position, err))?;
+                match &element[..] {
+                    b"x:Row" => row = value.parse::<usize>().ok(),
+                    b"x:Column" => column = value.parse::<usize>().ok(),
+                    _ => (),
+                }
+            },
+            Ok(Event::End(ref e)) => {
+                if e.name() == b"x:ClientData" && visible {
+                    if let (Some(row), Some(column)) = (row, column) {
+                        res.push((row, column));
+                    }
+                }
+                element.clear();
+            },
+            Ok(Event::Eof) => break,
+            Err(e) => return Err(Error::xml(part, reader.buffer_position(), e)),
+            _ => (),
+        }
+        buf.clear();
+    }
+    Ok(res)
+}
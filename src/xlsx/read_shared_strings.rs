@@ -1,22 +1,61 @@
+use crate::file_common::*;
 use std::result;
-use super::quick_xml::Reader;
 use super::quick_xml::events::{Event};
-use super::tempdir::TempDir;
-use super::XlsxError;
+use super::Error;
+use std::io::{Read, Seek};
+use super::zip::ZipArchive;
+use super::zip::result::ZipError;
 
 const SHARED_STRINGS: &'static str = "xl/sharedStrings.xml";
 
-pub fn read(dir: &TempDir) -> result::Result<Vec<String>, XlsxError> {
-    let path = dir.path().join(SHARED_STRINGS);
-    let mut reader = Reader::from_file(path)?;
+pub fn read<R: Read + Seek>(zip: &mut ZipArchive<R>) -> result::Result<Vec<String>, Error> {
+    // a book with no text, or with inline strings only, has no shared strings
+    let mut reader = match open_part(zip, SHARED_STRINGS) {
+        Ok(reader) => reader,
+        Err(ZipError::FileNotFound) => return Ok(vec![]),
+        Err(e) => return Err(e.into()),
+    };
     reader.trim_text(true);
     let mut buf = Vec::new();
     let mut res: Vec<String> = Vec::new();
+    // an si may be empty or made of rich text runs, the phonetic guide is left out
+    let mut in_text = false;
+    let mut in_phonetic = false;
     loop {
         match reader.read_event(&mut buf) {
-            Ok(Event::Text(e)) => res.push(e.unescape_and_decode(&reader).unwrap()),
+            Ok(Event::Start(ref e)) => {
+                match e.name() {
+                    b"si" => {
+                        res.push(String::from(""));
+                        // spaces at the ends of runs are part of the text
+                        reader.trim_text(false);
+                    },
+                    b"t" => in_text = !in_phonetic,
+                    b"rPh" => in_phonetic = true,
+                    _ => (),
+                }
+            },
+            Ok(Event::Empty(ref e)) if e.name() == b"si" => res.push(String::from("")),
+            Ok(Event::End(ref e)) => {
+                match e.name() {
+                    b"si" => {
+                        reader.trim_text(true);
+                    },
+                    b"t" => in_text = false,
+                    b"rPh" => in_phonetic = false,
+                    _ => (),
+                }
+            },
+            Ok(Event::Text(e)) => {
+                let position = reader.buffer_position();
+                let text = e.unescape_and_decode(&reader)
+                    .map_err(|err| Error::xml(SHARED_STRINGS, position, err))?;
+                if let Some(value) = res.last_mut().filter(|_| in_text) {
+                    value.push_str(&text);
+                }
+            },
             Ok(Event::Eof) => break,
-            Err(e) => panic!("Error at position {}: {:?}", reader.buffer_position(), e),
+            Err(e) => return Err(Error::xml(SHARED_STRINGS, reader.buffer_position(), e)),
             _ => (),
         }
         buf.clear();
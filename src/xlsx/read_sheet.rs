@@ -1,55 +1,123 @@
 use crate::file_common::*;
+use crate::format::{Format, Item};
+use crate::formula;
 use std::collections::HashMap;
 use std::result;
-// use super::time::Duration;
-use super::chrono::{ Duration, prelude::* };
-use super::quick_xml::Reader;
-use super::quick_xml::events::{Event};
-use super::tempdir::TempDir;
-use super::XlsxError;
-use super::{Sheet,Cell,Value,column_and_row_to_index};
-
-pub fn read(dir: &TempDir, name: &String, target: &String, shared_strings: &Vec<String>, styles: &Vec<HashMap<String, String>>) -> result::Result<Sheet, XlsxError> {
-    let mut sheet = Sheet::new(name.as_str());
-
-    let path = dir.path().join("xl/".to_string() + target);
-    let mut reader = Reader::from_file(path)?;
+use super::quick_xml::events::{BytesStart, Event};
+use super::Error;
+use super::read_styles::CellXf;
+use super::read_comments;
+use super::read_sheet_xml_rels;
+use super::{Sheet,SheetView,Cell,CellRange,Column,Hyperlink,Row,Value,ErrorKind,DateSystem,column_and_row_to_index,index_to_column};
+use std::io::{Read, Seek};
+use super::zip::ZipArchive;
+
+// XFD, the last column of xlsx
+const LAST_COLUMN: usize = 16384;
+
+pub fn read<R: Read + Seek>(zip: &mut ZipArchive<R>, name: &str, target: &str, shared_strings: &[String], styles: &[CellXf], date_system: DateSystem) -> result::Result<Sheet, Error> {
+    let mut sheet = Sheet::new(name);
+
+    let part = "xl/".to_string() + target;
+    let mut reader = open_part(zip, &part)?;
     reader.trim_text(true);
     let mut buf = Vec::new();
 
     let mut row_index: usize = 0;
     let mut column_index: usize = 0;
+    // rows and cells without an r attribute follow the previous one
+    let mut next_row: usize = 0;
+    let mut next_column: usize = 0;
     let mut string_value: String = String::from("");
     let mut type_value: String = String::from("");
     let mut style_index: usize = 0;
+    // inline strings: <is><t>..</t></is> or rich text runs <is><r><t>..</t></r>..</is>
+    let mut inline_value: String = String::from("");
+    let mut in_inline_text = false;
+    let mut in_phonetic = false;
+    let mut cell: Option<Cell> = None;
+    let mut formula: Option<String> = None;
+    let mut in_formula = false;
+    let mut shared_index: Option<String> = None;
+    // si -> (formula, row, column) of the cell which holds the shared formula text
+    let mut shared_formulas: HashMap<String, (String, usize, usize)> = HashMap::new();
+
+    let mut view = SheetView::new();
+    // the selection of the pane with the cursor is the one kept
+    let mut active_pane = String::from("topLeft");
+    // set once the relationships of the sheet are known
+    let mut hyperlinks: Vec<(HashMap<String, String>, usize)> = Vec::new();
 
     loop {
+        let position = reader.buffer_position();
         match reader.read_event(&mut buf) {
+            Ok(Event::Start(ref e)) | Ok(Event::Empty(ref e)) if e.name() == b"sheetView" => {
+                view = read_sheet_view(e)?;
+            },
+            Ok(Event::Empty(ref e)) if e.name() == b"pane" => {
+                let attributes = read_attributes(e)?;
+                let split = |key: &str| attributes.get(key).and_then(|value| value.parse::<f64>().ok()).unwrap_or(0.0);
+                view = match attributes.get("state").map(|state| state.as_str()) {
+                    Some("frozen") | Some("frozenSplit") => view.with_frozen(split("ySplit") as usize, split("xSplit") as usize),
+                    _ => view.with_split(split("xSplit") / 20.0, split("ySplit") / 20.0),
+                };
+                if let Some(pane) = attributes.get("activePane") {
+                    active_pane = pane.clone();
+                }
+            },
+            Ok(Event::Empty(ref e)) if e.name() == b"hyperlink" => {
+                hyperlinks.push((read_attributes(e)?, position));
+            },
+            Ok(Event::Empty(ref e)) if e.name() == b"selection" => {
+                let attributes = read_attributes(e)?;
+                if attributes.get("pane").map_or("topLeft", |pane| pane.as_str()) == active_pane {
+                    if let Some((column, row)) = attributes.get("activeCell").and_then(|cell| column_and_row_to_index(cell.as_str())) {
+                        view = view.with_active_cell(row, column);
+                    }
+                    // the first range, when it is more than the active cell
+                    let range = attributes.get("sqref")
+                        .and_then(|sqref| sqref.split_whitespace().next().and_then(CellRange::parse));
+                    let (row, column) = view.get_active_cell();
+                    if let Some(range) = range.filter(|range| *range != CellRange::new(row, column, row, column)) {
+                        view = view.with_selection(range);
+                    }
+                }
+            },
             Ok(Event::Start(ref e)) => {
                 match e.name() {
                     b"row" => {
-                        for a in e.attributes().with_checks(false) {
-                            match a {
-                                Ok(ref attr) if attr.key == b"r" => {
-                                    let value = get_attribute_value(attr)?;
-                                    row_index = value.parse::<usize>().unwrap() - 1;
-                                },
-                                Ok(_) => {},
-                                Err(_) => {},
-                            }
-                        }
+                        let (index, row) = read_row(e, next_row, &part, name, position)?;
+                        row_index = index;
+                        next_row = index + 1;
+                        next_column = 0;
+                        sheet.set_row(row_index, row);
                     },
                     b"c" => {
+                        type_value = String::from("");
+                        style_index = 0;
+                        cell = None;
+                        formula = None;
+                        column_index = next_column;
                         for a in e.attributes().with_checks(false) {
                             match a {
                                 Ok(ref attr) if attr.key == b"r" => {
                                     let value = get_attribute_value(attr)?;
                                     // A3のような値からcolumn_indexを計算する
-                                    column_index = column_and_row_to_index(value).unwrap().0;
+                                    column_index = match column_and_row_to_index(value.as_str()) {
+                                        Some((column, _)) => column,
+                                        None => return Err(Error::malformed(
+                                            &part, position, format!("invalid cell reference {}", value))
+                                            .in_cell(name, value)),
+                                    };
                                 },
                                 Ok(ref attr) if attr.key == b"s" => {
                                     let value = get_attribute_value(attr)?;
-                                    style_index = value.parse::<usize>().unwrap();
+                                    style_index = match value.parse::<usize>() {
+                                        Ok(index) if index < styles.len() => index,
+                                        _ => return Err(Error::malformed(
+                                            &part, position, format!("unknown style index {}", value))
+                                            .in_cell(name, cell_ref(row_index, column_index))),
+                                    };
                                 },
                                 Ok(ref attr) if attr.key == b"t" => {
                                     type_value = get_attribute_value(attr)?;
@@ -58,57 +126,394 @@ pub fn read(dir: &TempDir, name: &String, target: &String, shared_strings: &Vec<
                                 Err(_) => {},
                             }
                         }
+                        next_column = column_index + 1;
+                    },
+                    b"v" => string_value = String::from(""),
+                    b"f" => {
+                        in_formula = true;
+                        formula = Some(String::from(""));
+                        shared_index = read_shared_index(e)?;
                     },
+                    b"is" => {
+                        inline_value = String::from("");
+                        // spaces between runs are part of the text
+                        reader.trim_text(false);
+                    },
+                    b"t" => in_inline_text = !in_phonetic,
+                    b"rPh" => in_phonetic = true,
                     _ => (),
                 }
             },
             Ok(Event::End(ref e)) => {
                 match e.name() {
+                    b"t" => in_inline_text = false,
+                    b"rPh" => in_phonetic = false,
+                    b"is" => {
+                        reader.trim_text(true);
+                        cell = Some(Cell::str(inline_value.clone(), String::from("")));
+                    },
                     b"v" => {
-                        let cell = if type_value == "s" {
-                            let index = string_value.parse::<usize>().unwrap();
-                            let val = shared_strings.get(index).unwrap();
-                            Cell::str((*val).clone(), String::from(""))
-                        } else {
-                            let hash = &styles[style_index];
-                            match hash.get("formatCode") {
-                                Some(format_code) => {
-                                    Cell::new(
-                                        Value::Date(number_to_date(&string_value)),
-                                        format_code.to_string()
-                                    )
-                                },
-                                None => {
-                                    Cell::float(string_value.parse::<f64>().unwrap(), "")
-                                }
-                            }
+                        cell = Some(make_cell(&string_value, &type_value, style_index, shared_strings, styles, date_system)
+                            .map_err(|message| Error::malformed(&part, position, message)
+                                .in_cell(name, cell_ref(row_index, column_index)))?);
+                    },
+                    b"f" => {
+                        in_formula = false;
+                        if let (Some(si), Some(text)) = (shared_index.take(), formula.as_ref()) {
+                            shared_formulas.insert(si, (text.clone(), row_index, column_index));
+                        }
+                        formula = formula.map(|text| format!("={}", text));
+                    },
+                    b"c" => {
+                        let cell = match (cell.take(), formula.take()) {
+                            (Some(mut cell), Some(formula)) => {
+                                cell.set_formula(formula);
+                                Some(cell)
+                            },
+                            (Some(cell), None) => Some(cell),
+                            // a formula which has never been calculated
+                            (None, Some(formula)) => Some(Cell::formula(formula, Value::Str(String::from("")))),
+                            (None, None) => None,
                         };
-                        sheet.add_cell(cell, row_index, column_index);
+                        if let Some(mut cell) = cell {
+                            if let Some(xf) = styles.get(style_index) {
+                                cell.set_style(xf.style.clone());
+                            }
+                            sheet.add_cell(cell, row_index, column_index);
+                        }
                     },
                     _ => (),
                 }
             },
-            Ok(Event::Text(e)) => string_value = e.unescape_and_decode(&reader).unwrap(),
+            Ok(Event::Empty(ref e)) if e.name() == b"row" => {
+                let (index, row) = read_row(e, next_row, &part, name, position)?;
+                next_row = index + 1;
+                sheet.set_row(index, row);
+            },
+            Ok(Event::Empty(ref e)) if e.name() == b"c" => {
+                // a cell without a value still takes its column
+                next_column = read_attributes(e)?.get("r")
+                    .and_then(|value| column_and_row_to_index(value.as_str()))
+                    .map_or(next_column, |(column, _)| column) + 1;
+            },
+            Ok(Event::Empty(ref e)) if e.name() == b"col" => {
+                let (first, last, column) = read_col(e, &part, position)?;
+                for column_index in first..last + 1 {
+                    sheet.set_column(column_index, column);
+                }
+            },
+            Ok(Event::Empty(ref e)) if e.name() == b"mergeCell" => {
+                for a in e.attributes().with_checks(false) {
+                    match a {
+                        Ok(ref attr) if attr.key == b"ref" => {
+                            let value = get_attribute_value(attr)?;
+                            let range = CellRange::parse(value.as_str()).ok_or_else(|| Error::malformed(
+                                &part, position, format!("invalid merged range {}", value)))?;
+                            sheet.merge(range);
+                        },
+                        Ok(_) => {},
+                        Err(_) => {},
+                    }
+                }
+            },
+            // <f t="shared" si="0"/> reuses the formula of another cell
+            Ok(Event::Empty(ref e)) if e.name() == b"f" => {
+                if let Some(si) = read_shared_index(e)? {
+                    let (text, row, column) = shared_formulas.get(&si).ok_or_else(|| {
+                        Error::malformed(&part, position, format!("unknown shared formula {}", si))
+                            .in_cell(name, cell_ref(row_index, column_index))
+                    })?;
+                    let shifted = formula::shift(text,
+                        row_index as isize - *row as isize,
+                        column_index as isize - *column as isize);
+                    formula = Some(format!("={}", shifted));
+                }
+            },
+            Ok(Event::Text(e)) => {
+                string_value = e.unescape_and_decode(&reader)
+                    .map_err(|err| Error::xml(&part, position, err))?;
+                if in_inline_text {
+                    inline_value.push_str(&string_value);
+                }
+                if in_formula {
+                    if let Some(ref mut text) = formula {
+                        text.push_str(&string_value);
+                    }
+                }
+            },
             Ok(Event::Eof) => break,
-            Err(e) => panic!("Error at position {}: {:?}", reader.buffer_position(), e),
+            Err(e) => return Err(Error::xml(&part, reader.buffer_position(), e)),
             _ => (),
         }
         buf.clear();
     }
 
+    sheet.set_view(view);
+    let relationships = read_sheet_xml_rels::read(zip, &part)?;
+    for (attributes, position) in hyperlinks {
+        let hyperlink = read_hyperlink(&attributes, &relationships)
+            .ok_or_else(|| Error::malformed(&part, position, "hyperlink without a target"))?;
+        let range = attributes.get("ref").and_then(|reference| CellRange::parse(reference.as_str()))
+            .ok_or_else(|| Error::malformed(&part, position, "hyperlink without a cell reference"))?;
+        set_hyperlink(&mut sheet, range, hyperlink);
+    }
+    read_sheet_comments(zip, &mut sheet, &relationships)?;
     Ok(sheet)
 }
 
-// 1900年からのepoch
-// 43071.5625 -> 2017-12-02T13:30:00
-fn number_to_date(src: &String) -> DateTime<Utc> {
-    let num = src.parse::<f64>().unwrap();
-    let timestamp = ((((num - num.floor()) * 86400.0) as f64).round()) as i64;
-    let hms = DateTime::from_timestamp(timestamp, 0).expect("unable to get datetime");
-    let spreadsheet_epoch = Utc.with_ymd_and_hms(1900, 1, 1, 0, 0, 0).unwrap();
-    let duration = Duration::days(num.floor() as i64 -2) + Duration::hours(hms.hour().into()) + Duration::minutes(hms.minute().into()) + Duration::seconds(hms.second().into());
+// an url of the relationships or a location in the book, which is kept as an url when it is a defined name
+fn read_hyperlink(attributes: &HashMap<String, String>, relationships: &[HashMap<&'static str, String>]) -> Option<Hyperlink> {
+    let url = attributes.get("r:id")
+        .and_then(|id| relationships.iter().find(|relationship| relationship.get("id") == Some(id)))
+        .map(|relationship| relationship["target"].clone());
+    let mut hyperlink = match (url, attributes.get("location")) {
+        (Some(url), Some(location)) => Hyperlink::url(format!("{}#{}", url, location)),
+        (Some(url), None) => Hyperlink::url(url),
+        (None, Some(location)) => match parse_sheet_reference(location, '!') {
+            Some((sheet, row, column)) => Hyperlink::cell(sheet, row, column),
+            None => Hyperlink::url(format!("#{}", location)),
+        },
+        (None, None) => return None,
+    };
+    if let Some(tooltip) = attributes.get("tooltip") {
+        hyperlink = hyperlink.with_tooltip(tooltip.as_str());
+    }
+    Some(hyperlink)
+}
 
+// the link of a range goes to each cell with a value, and to the top left one in any case
+fn set_hyperlink(sheet: &mut Sheet, range: CellRange, hyperlink: Hyperlink) {
+    for row in range.get_first_row()..range.get_last_row() + 1 {
+        for column in range.get_first_column()..range.get_last_column() + 1 {
+            if let Some(cell) = sheet.get_cell_mut(row, column) {
+                cell.set_hyperlink(hyperlink.clone());
+            }
+        }
+    }
+    let (row, column) = (range.get_first_row(), range.get_first_column());
+    if sheet.get_cell(row, column).is_none() {
+        let mut cell = Cell::str("", "");
+        cell.set_hyperlink(hyperlink);
+        sheet.add_cell(cell, row, column);
+    }
+}
 
-    let dt = (spreadsheet_epoch + duration);
-    dt
+// the comments of the related commentsN.xml, shown all the time when their note in the legacy drawing is
+fn read_sheet_comments<R: Read + Seek>(zip: &mut ZipArchive<R>, sheet: &mut Sheet, relationships: &[HashMap<&'static str, String>]) -> result::Result<(), Error> {
+    let mut comments = Vec::new();
+    let mut visible = Vec::new();
+    for relationship in relationships {
+        match relationship.get("type").map(|relationship_type| relationship_type.as_str()) {
+            Some(relationship_type) if relationship_type.ends_with("/comments") => {
+                comments.extend(read_comments::read(zip, &relationship["target"])?);
+            },
+            Some(relationship_type) if relationship_type.ends_with("/vmlDrawing") => {
+                visible.extend(read_comments::read_visible(zip, &relationship["target"])?);
+            },
+            _ => (),
+        }
+    }
+    for (row, column, comment) in comments {
+        let comment = comment.with_visible(visible.contains(&(row, column)));
+        match sheet.get_cell_mut(row, column) {
+            Some(cell) => cell.set_comment(comment),
+            // a comment on an empty cell is kept on an empty string
+            None => {
+                let mut cell = Cell::str("", "");
+                cell.set_comment(comment);
+                sheet.add_cell(cell, row, column);
+            },
+        }
+    }
+    Ok(())
+}
+
+fn read_attributes(e: &BytesStart) -> result::Result<HashMap<String, String>, Error> {
+    let mut map = HashMap::new();
+    for attr in e.attributes().with_checks(false).flatten() {
+        map.insert(String::from_utf8_lossy(attr.key).into_owned(), get_attribute_value(&attr)?);
+    }
+    Ok(map)
+}
+
+// the flags and zoom of <sheetView>, without its pane and selection
+fn read_sheet_view(e: &BytesStart) -> result::Result<SheetView, Error> {
+    let attributes = read_attributes(e)?;
+    let flag = |key: &str, default: bool| attributes.get(key).map_or(default, |value| is_true(value));
+    let mut view = SheetView::new()
+        .with_grid_lines(flag("showGridLines", true))
+        .with_headers(flag("showRowColHeaders", true))
+        .with_right_to_left(flag("rightToLeft", false));
+    if let Some(zoom) = attributes.get("zoomScale").and_then(|zoom| zoom.parse::<u16>().ok()) {
+        // 0 is the default zoom
+        if zoom > 0 {
+            view = view.with_zoom(zoom);
+        }
+    }
+    Ok(view)
+}
+
+fn is_true(value: &str) -> bool {
+    value == "1" || value == "true"
+}
+
+// the index of <row r=".." ht=".." customHeight=".." hidden="..">, `next_index` without r;
+// a height without customHeight was fitted to the contents
+fn read_row(e: &BytesStart, next_index: usize, part: &str, name: &str, position: usize) -> result::Result<(usize, Row), Error> {
+    let mut row_index = next_index;
+    let mut height = None;
+    let mut custom_height = false;
+    let mut hidden = false;
+    for a in e.attributes().with_checks(false) {
+        match a {
+            Ok(ref attr) if attr.key == b"r" => {
+                let value = get_attribute_value(attr)?;
+                row_index = match value.parse::<usize>() {
+                    Ok(row) if row > 0 => row - 1,
+                    _ => return Err(Error::malformed(
+                        part, position, format!("invalid row number {}", value))
+                        .in_cell(name, value)),
+                };
+            },
+            Ok(ref attr) if attr.key == b"ht" => {
+                let value = get_attribute_value(attr)?;
+                height = Some(value.parse::<f64>().map_err(|_| Error::malformed(
+                    part, position, format!("invalid row height {}", value)))?);
+            },
+            Ok(ref attr) if attr.key == b"customHeight" => {
+                custom_height = is_true(&get_attribute_value(attr)?);
+            },
+            Ok(ref attr) if attr.key == b"hidden" => {
+                hidden = is_true(&get_attribute_value(attr)?);
+            },
+            Ok(_) => {},
+            Err(_) => {},
+        }
+    }
+    let mut row = Row::new().with_hidden(hidden);
+    if let Some(height) = height {
+        row = row.with_height(height).with_auto_fit(!custom_height);
+    }
+    Ok((row_index, row))
+}
+
+// the columns of <col min=".." max=".." width=".." customWidth=".." hidden=".." bestFit="..">,
+// the width of a column without customWidth is the default one
+fn read_col(e: &BytesStart, part: &str, position: usize) -> result::Result<(usize, usize, Column), Error> {
+    let mut min = 0;
+    let mut max = 0;
+    let mut width = None;
+    let mut custom_width = false;
+    let mut column = Column::new();
+    for a in e.attributes().with_checks(false) {
+        match a {
+            Ok(ref attr) if attr.key == b"min" || attr.key == b"max" => {
+                let value = get_attribute_value(attr)?;
+                let index = match value.parse::<usize>() {
+                    Ok(index) if index > 0 && index <= LAST_COLUMN => index - 1,
+                    _ => return Err(Error::malformed(
+                        part, position, format!("invalid column number {}", value))),
+                };
+                if attr.key == b"min" { min = index } else { max = index }
+            },
+            Ok(ref attr) if attr.key == b"width" => {
+                let value = get_attribute_value(attr)?;
+                width = Some(value.parse::<f64>().map_err(|_| Error::malformed(
+                    part, position, format!("invalid column width {}", value)))?);
+            },
+            Ok(ref attr) if attr.key == b"customWidth" => {
+                custom_width = is_true(&get_attribute_value(attr)?);
+            },
+            Ok(ref attr) if attr.key == b"hidden" => {
+                column = column.with_hidden(is_true(&get_attribute_value(attr)?));
+            },
+            Ok(ref attr) if attr.key == b"bestFit" => {
+                column = column.with_auto_fit(is_true(&get_attribute_value(attr)?));
+            },
+            Ok(_) => {},
+            Err(_) => {},
+        }
+    }
+    if let (Some(width), true) = (width, custom_width) {
+        column = column.with_width(width);
+    }
+    Ok((min, max, column))
+}
+
+// si of <f t="shared" si="..">
+fn read_shared_index(e: &BytesStart) -> result::Result<Option<String>, Error> {
+    let mut shared = false;
+    let mut si = None;
+    for a in e.attributes().with_checks(false) {
+        match a {
+            Ok(ref attr) if attr.key == b"t" => {
+                shared = get_attribute_value(attr)? == "shared";
+            },
+            Ok(ref attr) if attr.key == b"si" => {
+                si = Some(get_attribute_value(attr)?);
+            },
+            Ok(_) => {},
+            Err(_) => {},
+        }
+    }
+    Ok(if shared { si } else { None })
+}
+
+fn cell_ref(row_index: usize, column_index: usize) -> String {
+    format!("{}{}", index_to_column(column_index), row_index + 1)
+}
+
+fn make_cell(value: &str, type_value: &str, style_index: usize, shared_strings: &[String], styles: &[CellXf], date_system: DateSystem) -> result::Result<Cell, String> {
+    if type_value == "s" {
+        let val = value.parse::<usize>().ok()
+            .and_then(|index| shared_strings.get(index))
+            .ok_or_else(|| format!("unknown shared string index {}", value))?;
+        return Ok(Cell::str((*val).clone(), String::from("")));
+    }
+    if type_value == "str" || type_value == "inlineStr" {
+        return Ok(Cell::str(value, ""));
+    }
+    if type_value == "b" {
+        return match value {
+            "1" => Ok(Cell::bool(true, "")),
+            "0" => Ok(Cell::bool(false, "")),
+            _ => Err(format!("invalid boolean {}", value)),
+        };
+    }
+    if type_value == "e" {
+        return ErrorKind::from_code(value)
+            .map(|kind| Cell::error(kind, ""))
+            .ok_or_else(|| format!("unknown error code {}", value));
+    }
+    let num = value.parse::<f64>()
+        .map_err(|_| format!("invalid number {}", value))?;
+    let format_code = match styles.get(style_index).and_then(|xf| xf.format_code.as_ref()) {
+        Some(format_code) => format_code.as_str(),
+        None => return Ok(Cell::float(num, "")),
+    };
+    let format = Format::new(format_code);
+    // [h]:mm counts elapsed time, hh:mm below one day is a time of day
+    if format.is_duration_format() {
+        return Ok(Cell::new(Value::Duration(formula::serial_to_duration(num)), format_code));
+    }
+    // a serial no date can have stays a number, Excel shows it as ####
+    if format.is_time_format() && (0.0..1.0).contains(&num) {
+        return Ok(match formula::serial_to_time(num) {
+            Some(time) => Cell::new(Value::Time(time), format_code),
+            None => Cell::float(num, format_code),
+        });
+    }
+    if format.is_date_format() {
+        return Ok(match date_system.serial_to_date(num) {
+            Some(dt) => Cell::new(Value::Date(dt), format_code),
+            None => Cell::float(num, format_code),
+        });
+    }
+    if format.get_sections()[0].get_items().contains(&Item::Percent) {
+        return Ok(Cell::percentage(num, format_code));
+    }
+    match format.get_currency_code() {
+        Some(code) => Ok(Cell::currency(num, code.as_str(), format_code)),
+        None => Ok(Cell::float(num, format_code)),
+    }
 }
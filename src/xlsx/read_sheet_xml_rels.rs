@@ -0,0 +1,44 @@
+use std::collections::HashMap;
+use std::result;
+use super::Error;
+use super::read_workbook_xml_rels;
+use std::io::{Read, Seek};
+use super::zip::ZipArchive;
+use super::zip::result::ZipError;
+
+// a target relative to the directory of the sheet, or from the root of the package
+fn resolve_target(directory: &str, target: &str) -> String {
+    if let Some(target) = target.strip_prefix('/') {
+        return target.to_string();
+    }
+    let mut parts: Vec<&str> = directory.split('/').filter(|part| !part.is_empty()).collect();
+    for part in target.split('/') {
+        match part {
+            ".." => {
+                parts.pop();
+            },
+            "." | "" => (),
+            _ => parts.push(part),
+        }
+    }
+    parts.join("/")
+}
+
+/// The relationships of a sheet part such as xl/worksheets/sheet1.xml, with the targets inside
+/// the package made into part names. A sheet without a .rels part has none.
+pub fn read<R: Read + Seek>(zip: &mut ZipArchive<R>, sheet_part: &str) -> result::Result<Vec<HashMap<&'static str, String>>, Error> {
+    let (directory, file) = sheet_part.rsplit_once('/').unwrap_or(("", sheet_part));
+    let part = format!("{}/_rels/{}.rels", directory, file);
+    let mut relationships = match read_workbook_xml_rels::read_relationships(zip, &part) {
+        Ok(relationships) => relationships,
+        Err(Error::Zip(ZipError::FileNotFound)) => return Ok(vec![]),
+        Err(e) => return Err(e),
+    };
+    for relationship in relationships.iter_mut() {
+        if relationship.get("mode").map(|mode| mode.as_str()) != Some("External") {
+            let target = resolve_target(directory, &relationship["target"]);
+            relationship.insert("target", target);
+        }
+    }
+    Ok(relationships)
+}
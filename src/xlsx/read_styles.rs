@@ -1,79 +1,292 @@
 use crate::file_common::*;
+use crate::style::{Alignment, Border, BorderLine, BorderStyle, Fill, Font, HorizontalAlignment, Pattern, Rgb, Style, Underline, VerticalAlignment};
 use std::collections::HashMap;
 use std::result;
-use super::quick_xml::Reader;
-use super::quick_xml::events::{Event};
-use super::tempdir::TempDir;
-use super::XlsxError;
+use super::quick_xml::events::{BytesStart, Event};
+use super::Error;
+use std::io::{Read, Seek};
+use super::zip::ZipArchive;
 
 const STYLE_XML: &'static str = "xl/styles.xml";
 
-pub fn read(dir: &TempDir) -> result::Result<Vec<HashMap<String, String>>, XlsxError> {
-    let path = dir.path().join(STYLE_XML);
-    let mut reader = Reader::from_file(path)?;
+/// One entry of cellXfs: the custom number format and the look of the cells using it
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct CellXf {
+    pub format_code: Option<String>,
+    pub style: Style,
+}
+
+// the attributes of an element by name
+fn attributes(e: &BytesStart) -> result::Result<HashMap<Vec<u8>, String>, Error> {
+    let mut map = HashMap::new();
+    for attr in e.attributes().with_checks(false).flatten() {
+        map.insert(attr.key.to_vec(), get_attribute_value(&attr)?);
+    }
+    Ok(map)
+}
+
+// only rgb colors are kept, theme and indexed colors need the palette of the document
+fn read_color(attributes: &HashMap<Vec<u8>, String>) -> Option<Rgb> {
+    attributes.get(&b"rgb"[..]).and_then(|rgb| Rgb::from_hex(rgb))
+}
+
+fn read_flag(attributes: &HashMap<Vec<u8>, String>) -> bool {
+    attributes.get(&b"val"[..]).is_none_or(|val| val != "0" && val != "false")
+}
+
+fn read_alignment(attributes: &HashMap<Vec<u8>, String>) -> Alignment {
+    let mut alignment = Alignment::new();
+    let horizontal = match attributes.get(&b"horizontal"[..]).map(|value| value.as_str()) {
+        Some("left") => Some(HorizontalAlignment::Left),
+        Some("center") | Some("centerContinuous") => Some(HorizontalAlignment::Center),
+        Some("right") => Some(HorizontalAlignment::Right),
+        Some("justify") | Some("distributed") => Some(HorizontalAlignment::Justify),
+        Some("fill") => Some(HorizontalAlignment::Fill),
+        _ => None,
+    };
+    if let Some(horizontal) = horizontal {
+        alignment = alignment.with_horizontal(horizontal);
+    }
+    let vertical = match attributes.get(&b"vertical"[..]).map(|value| value.as_str()) {
+        Some("top") => Some(VerticalAlignment::Top),
+        Some("center") => Some(VerticalAlignment::Center),
+        Some("bottom") => Some(VerticalAlignment::Bottom),
+        _ => None,
+    };
+    if let Some(vertical) = vertical {
+        alignment = alignment.with_vertical(vertical);
+    }
+    if attributes.get(&b"wrapText"[..]).is_some_and(|value| value == "1" || value == "true") {
+        alignment = alignment.with_wrap(true);
+    }
+    // 91 to 180 turn clockwise, 255 stacks the letters which is not supported
+    match attributes.get(&b"textRotation"[..]).and_then(|value| value.parse::<i16>().ok()) {
+        Some(rotation @ 1..=90) => alignment = alignment.with_rotation(rotation),
+        Some(rotation @ 91..=180) => alignment = alignment.with_rotation(90 - rotation),
+        _ => {},
+    }
+    if let Some(indent) = attributes.get(&b"indent"[..]).and_then(|value| value.parse::<u8>().ok()) {
+        alignment = alignment.with_indent(indent);
+    }
+    alignment
+}
+
+fn make_style(xf: &HashMap<Vec<u8>, String>, alignment: Alignment, fonts: &[Font], fills: &[Option<Fill>], borders: &[Border]) -> Style {
+    let index = |key: &[u8]| xf.get(key).and_then(|value| value.parse::<usize>().ok()).unwrap_or(0);
+    let mut style = Style::new().with_alignment(alignment);
+    // font 0 is the default font of the document
+    if let Some(font) = fonts.get(index(b"fontId")).filter(|font| index(b"fontId") > 0 && **font != Font::default()) {
+        style = style.with_font(font.clone());
+    }
+    if let Some(Some(fill)) = fills.get(index(b"fillId")) {
+        style = style.with_fill(*fill);
+    }
+    if let Some(border) = borders.get(index(b"borderId")) {
+        style = style.with_border(*border);
+    }
+    style
+}
+
+pub fn read<R: Read + Seek>(zip: &mut ZipArchive<R>) -> result::Result<Vec<CellXf>, Error> {
+    let mut reader = open_part(zip, STYLE_XML)?;
     reader.trim_text(true);
     let mut buf = Vec::new();
     let mut cell_xfs_flag = false;
-    let mut cell_xfs: Vec<HashMap<String, String>> = Vec::new();
+    let mut cell_xfs: Vec<CellXf> = Vec::new();
     let mut num_fmts: HashMap<String, String> = HashMap::new();
-    let mut num_fmt_id = String::from("");
-    let mut format_code = String::from("");
+    let mut fonts: Vec<Font> = Vec::new();
+    let mut fills: Vec<Option<Fill>> = Vec::new();
+    let mut borders: Vec<Border> = Vec::new();
+    let mut font = Font::new();
+    // patternType, fgColor and bgColor of the fill being read
+    let mut pattern = String::from("none");
+    let mut fg_color: Option<Rgb> = None;
+    let mut bg_color: Option<Rgb> = None;
+    let mut border = Border::new();
+    // the edge being read and its style
+    let mut edge: Option<(Vec<u8>, BorderStyle)> = None;
+    let mut edge_color = Rgb(0, 0, 0);
+    let mut xf: HashMap<Vec<u8>, String> = HashMap::new();
+    let mut alignment = Alignment::new();
     loop {
-        match reader.read_event(&mut buf) {
-            Ok(Event::Start(ref e)) => {
+        let event = reader.read_event(&mut buf);
+        match event {
+            Ok(Event::Start(ref e)) | Ok(Event::Empty(ref e)) => {
+                let empty = matches!(event, Ok(Event::Empty(_)));
                 match e.name() {
                     b"cellXfs" => {
                         cell_xfs_flag = true;
                     },
-                    b"xf" => {
-                        let mut map: HashMap<String, String> = HashMap::new();
-                        for a in e.attributes().with_checks(false) {
-                            match a {
-                                Ok(ref attr) if attr.key == b"numFmtId" => {
-                                    num_fmt_id = get_attribute_value(attr)?;
-                                    match num_fmts.get(&num_fmt_id) {
-                                        Some(val) => {
-                                            map.insert(String::from("formatCode"), val.clone());
-                                        },
-                                        None => {},
-                                    }
-                                },
-                                Ok(_) => {},
-                                Err(_) => {},
+                    b"numFmt" => {
+                        let map = attributes(e)?;
+                        if let (Some(id), Some(code)) = (map.get(&b"numFmtId"[..]), map.get(&b"formatCode"[..])) {
+                            num_fmts.insert(id.clone(), code.clone());
+                        }
+                    },
+                    b"font" => {
+                        font = Font::new();
+                        if empty {
+                            fonts.push(font.clone());
+                        }
+                    },
+                    b"b" => font = font.clone().with_bold(read_flag(&attributes(e)?)),
+                    b"i" => font = font.clone().with_italic(read_flag(&attributes(e)?)),
+                    b"u" => {
+                        let underline = match attributes(e)?.get(&b"val"[..]).map(|value| value.as_str()) {
+                            Some("double") | Some("doubleAccounting") => Underline::Double,
+                            Some("none") => Underline::None,
+                            _ => Underline::Single,
+                        };
+                        font = font.clone().with_underline(underline);
+                    },
+                    b"sz" => {
+                        if let Some(size) = attributes(e)?.get(&b"val"[..]).and_then(|value| value.parse::<f64>().ok()) {
+                            font = font.clone().with_size(size);
+                        }
+                    },
+                    b"name" => {
+                        if let Some(name) = attributes(e)?.get(&b"val"[..]) {
+                            font = font.clone().with_name(name.as_str());
+                        }
+                    },
+                    b"color" => {
+                        if let Some(color) = read_color(&attributes(e)?) {
+                            if edge.is_some() {
+                                edge_color = color;
+                            } else {
+                                font = font.clone().with_color(color);
                             }
                         }
-                        if cell_xfs_flag {
-                            cell_xfs.push(map);
+                    },
+                    b"fill" => {
+                        pattern = String::from("none");
+                        fg_color = None;
+                        bg_color = None;
+                        if empty {
+                            fills.push(None);
+                        }
+                    },
+                    b"patternFill" => {
+                        pattern = attributes(e)?.get(&b"patternType"[..]).cloned().unwrap_or_else(|| String::from("none"));
+                    },
+                    b"fgColor" => fg_color = read_color(&attributes(e)?),
+                    b"bgColor" => bg_color = read_color(&attributes(e)?),
+                    b"border" => {
+                        border = Border::new();
+                        if empty {
+                            borders.push(border);
+                        }
+                    },
+                    b"left" | b"right" | b"top" | b"bottom" => {
+                        let style = attributes(e)?.get(&b"style"[..]).and_then(|style| BorderStyle::from_name(style));
+                        edge_color = Rgb(0, 0, 0);
+                        edge = style.map(|style| (e.name().to_vec(), style));
+                        if empty {
+                            border = add_edge(border, edge.take(), edge_color);
                         }
                     },
+                    b"xf" => {
+                        xf = attributes(e)?;
+                        alignment = Alignment::new();
+                        if empty && cell_xfs_flag {
+                            cell_xfs.push(make_cell_xf(&xf, alignment, &num_fmts, &fonts, &fills, &borders));
+                        }
+                    },
+                    b"alignment" => alignment = read_alignment(&attributes(e)?),
                     _ => (),
                 }
             },
-            Ok(Event::Empty(ref e)) => {
+            Ok(Event::End(ref e)) => {
                 match e.name() {
-                    b"numFmt" => {
-                        for a in e.attributes().with_checks(false) {
-                            match a {
-                                Ok(ref attr) if attr.key == b"numFmtId" => {
-                                    num_fmt_id = get_attribute_value(attr)?;
-                                },
-                                Ok(ref attr) if attr.key == b"formatCode" => {
-                                    format_code = get_attribute_value(attr)?;
-                                },
-                                Ok(_) => {},
-                                Err(_) => {},
-                            }
-                        }
-                        num_fmts.insert(num_fmt_id.clone(), condvert_character_reference(&format_code));
+                    b"cellXfs" => cell_xfs_flag = false,
+                    b"font" => fonts.push(font.clone()),
+                    b"fill" => {
+                        let fill = match pattern.as_str() {
+                            "none" => None,
+                            "solid" => Some(Fill::Solid(fg_color.unwrap_or(Rgb(0, 0, 0)))),
+                            name => Pattern::from_name(name).map(|pattern| Fill::Pattern {
+                                pattern,
+                                color: fg_color.unwrap_or(Rgb(0, 0, 0)),
+                                background: bg_color.unwrap_or(Rgb(0xFF, 0xFF, 0xFF)),
+                            }),
+                        };
+                        // the second fill is reserved for gray125
+                        fills.push(if fills.len() == 1 { None } else { fill });
+                    },
+                    b"left" | b"right" | b"top" | b"bottom" => {
+                        border = add_edge(border, edge.take(), edge_color);
+                    },
+                    b"border" => borders.push(border),
+                    b"xf" if cell_xfs_flag => {
+                        cell_xfs.push(make_cell_xf(&xf, alignment, &num_fmts, &fonts, &fills, &borders));
                     },
                     _ => (),
                 }
-            }
+            },
             Ok(Event::Eof) => break,
-            Err(e) => panic!("Error at position {}: {:?}", reader.buffer_position(), e),
+            Err(e) => return Err(Error::xml(STYLE_XML, reader.buffer_position(), e)),
             _ => (),
         }
         buf.clear();
     }
     Ok(cell_xfs)
 }
+
+fn add_edge(border: Border, edge: Option<(Vec<u8>, BorderStyle)>, color: Rgb) -> Border {
+    match edge {
+        Some((name, style)) => {
+            let line = BorderLine::new(style, color);
+            match name.as_slice() {
+                b"left" => border.with_left(line),
+                b"right" => border.with_right(line),
+                b"top" => border.with_top(line),
+                _ => border.with_bottom(line),
+            }
+        },
+        None => border,
+    }
+}
+
+// the formats every reader knows without a numFmt element (ECMA-376 18.8.30),
+// 0 is General which is left out
+fn builtin_format(id: &str) -> Option<&'static str> {
+    let code = match id {
+        "1" => "0",
+        "2" => "0.00",
+        "3" => "#,##0",
+        "4" => "#,##0.00",
+        "9" => "0%",
+        "10" => "0.00%",
+        "11" => "0.00E+00",
+        "12" => "# ?/?",
+        "13" => "# ??/??",
+        "14" => "mm-dd-yy",
+        "15" => "d-mmm-yy",
+        "16" => "d-mmm",
+        "17" => "mmm-yy",
+        "18" => "h:mm AM/PM",
+        "19" => "h:mm:ss AM/PM",
+        "20" => "h:mm",
+        "21" => "h:mm:ss",
+        "22" => "m/d/yy h:mm",
+        "37" => "#,##0 ;(#,##0)",
+        "38" => "#,##0 ;[Red](#,##0)",
+        "39" => "#,##0.00;(#,##0.00)",
+        "40" => "#,##0.00;[Red](#,##0.00)",
+        "45" => "mm:ss",
+        "46" => "[h]:mm:ss",
+        "47" => "mm:ss.0",
+        "48" => "##0.0E+0",
+        "49" => "@",
+        _ => return None,
+    };
+    Some(code)
+}
+
+fn make_cell_xf(xf: &HashMap<Vec<u8>, String>, alignment: Alignment, num_fmts: &HashMap<String, String>, fonts: &[Font], fills: &[Option<Fill>], borders: &[Border]) -> CellXf {
+    CellXf {
+        format_code: xf.get(&b"numFmtId"[..])
+            .and_then(|id| num_fmts.get(id).cloned().or_else(|| builtin_format(id).map(String::from))),
+        style: make_style(xf, alignment, fonts, fills, borders),
+    }
+}
@@ -1,25 +1,55 @@
 use crate::file_common::*;
 use std::collections::HashMap;
 use std::result;
-use super::quick_xml::Reader;
 use super::quick_xml::events::{Event};
-use super::tempdir::TempDir;
-use super::XlsxError;
+use super::{DateSystem, Error};
+use std::io::{Read, Seek};
+use super::zip::ZipArchive;
 
 const WORKBOOK_XML: &'static str = "xl/workbook.xml";
 
-pub fn read(dir: &TempDir) -> result::Result<Vec<HashMap<&str, String>>, XlsxError> {
-    let path = dir.path().join(WORKBOOK_XML);
-    let mut reader = Reader::from_file(path)?;
+type SheetEntries = Vec<HashMap<&'static str, String>>;
+
+// the sheets in order, the date system of workbookPr and the activeTab of workbookView
+pub fn read<R: Read + Seek>(zip: &mut ZipArchive<R>, rels: &HashMap<String, String>) -> result::Result<(SheetEntries, DateSystem, usize), Error> {
+    let mut reader = open_part(zip, WORKBOOK_XML)?;
     reader.trim_text(true);
     let mut buf = Vec::new();
-    let mut res: Vec<HashMap<&str, String>> = Vec::new();
+    let mut res: Vec<HashMap<&'static str, String>> = Vec::new();
+    let mut date_system = DateSystem::Date1900;
+    let mut active_sheet = 0;
     loop {
+        let position = reader.buffer_position();
         match reader.read_event(&mut buf) {
             Ok(Event::Empty(ref e)) => {
                 match e.name() {
+                    b"workbookPr" => {
+                        for a in e.attributes().with_checks(false) {
+                            match a {
+                                Ok(ref attr) if attr.key == b"date1904" => {
+                                    let value = get_attribute_value(attr)?;
+                                    if value == "1" || value == "true" {
+                                        date_system = DateSystem::Date1904;
+                                    }
+                                },
+                                Ok(_) => {},
+                                Err(_) => {},
+                            }
+                        }
+                    },
+                    b"workbookView" => {
+                        for a in e.attributes().with_checks(false) {
+                            match a {
+                                Ok(ref attr) if attr.key == b"activeTab" => {
+                                    active_sheet = get_attribute_value(attr)?.parse::<usize>().unwrap_or(0);
+                                },
+                                Ok(_) => {},
+                                Err(_) => {},
+                            }
+                        }
+                    },
                     b"sheet" => {
-                        let mut map: HashMap<&str, String> = HashMap::new();
+                        let mut map: HashMap<&'static str, String> = HashMap::new();
                         for a in e.attributes().with_checks(false) {
                             match a {
                                 Ok(ref attr) if attr.key == b"name" => {
@@ -38,16 +68,25 @@ pub fn read(dir: &TempDir) -> result::Result<Vec<HashMap<&str, String>>, XlsxErr
                                 Err(_) => {},
                             }
                         }
+                        if !map.contains_key("name") {
+                            return Err(Error::malformed(WORKBOOK_XML, position, "sheet without name"));
+                        }
+                        let target = match map.get("rid").and_then(|rid| rels.get(rid)) {
+                            Some(target) => target.clone(),
+                            None => return Err(Error::malformed(
+                                WORKBOOK_XML, position, "sheet without relationship")),
+                        };
+                        map.insert("target", target);
                         res.push(map);
                     },
                     _ => (),
                 }
             }
             Ok(Event::Eof) => break,
-            Err(e) => panic!("Error at position {}: {:?}", reader.buffer_position(), e),
+            Err(e) => return Err(Error::xml(WORKBOOK_XML, reader.buffer_position(), e)),
             _ => (),
         }
         buf.clear();
     }
-    Ok(res)
+    Ok((res, date_system, active_sheet))
 }
@@ -1,25 +1,30 @@
 use crate::file_common::*;
 use std::collections::HashMap;
 use std::result;
-use super::quick_xml::Reader;
 use super::quick_xml::events::{Event};
-use super::tempdir::TempDir;
-use super::XlsxError;
+use super::Error;
+use std::io::{Read, Seek};
+use super::zip::ZipArchive;
 
 const WORKBOOK_XML_RELS: &'static str = "xl/_rels/workbook.xml.rels";
 
-pub fn read(dir: &TempDir) -> result::Result<Vec<HashMap<&str, String>>, XlsxError> {
-    let path = dir.path().join(WORKBOOK_XML_RELS);
-    let mut reader = Reader::from_file(path)?;
+pub fn read<R: Read + Seek>(zip: &mut ZipArchive<R>) -> result::Result<Vec<HashMap<&'static str, String>>, Error> {
+    read_relationships(zip, WORKBOOK_XML_RELS)
+}
+
+/// id, type, target and mode of each Relationship of a .rels part
+pub fn read_relationships<R: Read + Seek>(zip: &mut ZipArchive<R>, part: &str) -> result::Result<Vec<HashMap<&'static str, String>>, Error> {
+    let mut reader = open_part(zip, part)?;
     reader.trim_text(true);
     let mut buf = Vec::new();
-    let mut res: Vec<HashMap<&str, String>> = Vec::new();
+    let mut res: Vec<HashMap<&'static str, String>> = Vec::new();
     loop {
+        let position = reader.buffer_position();
         match reader.read_event(&mut buf) {
             Ok(Event::Empty(ref e)) => {
                 match e.name() {
                     b"Relationship" => {
-                        let mut map: HashMap<&str, String> = HashMap::new();
+                        let mut map: HashMap<&'static str, String> = HashMap::new();
                         for a in e.attributes().with_checks(false) {
                             match a {
                                 Ok(ref attr) if attr.key == b"Id" => {
@@ -31,17 +36,24 @@ pub fn read(dir: &TempDir) -> result::Result<Vec<HashMap<&str, String>>, XlsxErr
                                 Ok(ref attr) if attr.key == b"Target" => {
                                     map.insert("target", get_attribute_value(attr)?);
                                 },
+                                Ok(ref attr) if attr.key == b"TargetMode" => {
+                                    map.insert("mode", get_attribute_value(attr)?);
+                                },
                                 Ok(_) => {},
                                 Err(_) => {},
                             }
                         }
+                        if !map.contains_key("id") || !map.contains_key("target") {
+                            return Err(Error::malformed(
+                                part, position, "relationship without Id or Target"));
+                        }
                         res.push(map);
                     },
                     _ => (),
                 }
             }
             Ok(Event::Eof) => break,
-            Err(e) => panic!("Error at position {}: {:?}", reader.buffer_position(), e),
+            Err(e) => return Err(Error::xml(part, reader.buffer_position(), e)),
             _ => (),
         }
         buf.clear();
@@ -0,0 +1,131 @@
+use crate::file_common::*;
+use std::io::Cursor;
+use std::result;
+use super::quick_xml::events::{Event, BytesDecl};
+use super::quick_xml::Writer;
+use super::{Sheet, Comment, index_to_column};
+use super::Error;
+use std::io::{Seek, Write};
+use super::zip::ZipWriter;
+
+/// The comments of a sheet in row and column order.
+pub fn sheet_comments(sheet: &Sheet) -> Vec<(usize, usize, &Comment)> {
+    let mut comments: Vec<(usize, usize, &Comment)> = sheet.get_rows().iter()
+        .flat_map(|(row_index, columns)| columns.iter().filter_map(move |(column_index, cell)| {
+            cell.get_comment().map(|comment| (*row_index, *column_index, comment))
+        }))
+        .collect();
+    comments.sort_by_key(|&(row_index, column_index, _)| (row_index, column_index));
+    comments
+}
+
+fn make_comments_xml<W: Write + Seek>(comments: &[(usize, usize, &Comment)], zip: &mut ZipWriter<W>, index: usize) -> result::Result<(), Error> {
+    let mut authors: Vec<&String> = comments.iter().map(|&(_, _, comment)| comment.get_author()).collect();
+    authors.sort();
+    authors.dedup();
+    let mut writer = Writer::new(Cursor::new(Vec::new()));
+    let _ = writer.write_event(Event::Decl(
+        BytesDecl::new(b"1.0", Some(b"UTF-8"), Some(b"yes"))));
+    write_text_node(&mut writer, "\n");
+    write_start_tag(&mut writer, "comments", vec![
+        ("xmlns", "http://schemas.openxmlformats.org/spreadsheetml/2006/main")
+    ], false);
+    write_start_tag(&mut writer, "authors", vec![], false);
+    for author in &authors {
+        write_start_tag(&mut writer, "author", vec![], false);
+        write_text_node(&mut writer, author.as_str());
+        write_end_tag(&mut writer, "author");
+    }
+    write_end_tag(&mut writer, "authors");
+    write_start_tag(&mut writer, "commentList", vec![], false);
+    for &(row_index, column_index, comment) in comments {
+        let author_id = authors.iter().position(|author| *author == comment.get_author()).unwrap_or(0);
+        write_start_tag(&mut writer, "comment", vec![
+            ("ref", format!("{}{}", index_to_column(column_index), row_index + 1).as_str()),
+            ("authorId", author_id.to_string().as_str()),
+        ], false);
+        write_start_tag(&mut writer, "text", vec![], false);
+        write_start_tag(&mut writer, "t", vec![("xml:space", "preserve")], false);
+        write_text_node(&mut writer, comment.get_text().as_str());
+        write_end_tag(&mut writer, "t");
+        write_end_tag(&mut writer, "text");
+        write_end_tag(&mut writer, "comment");
+    }
+    write_end_tag(&mut writer, "commentList");
+    write_end_tag(&mut writer, "comments");
+    make_file_from_writer(format!("xl/comments{}.xml", index).as_str(), zip, writer)?;
+    Ok(())
+}
+
+// the legacy drawing holds the note boxes, and whether they are shown all the time
+fn make_vml_drawing<W: Write + Seek>(comments: &[(usize, usize, &Comment)], zip: &mut ZipWriter<W>, index: usize) -> result::Result<(), Error> {
+    let mut writer = Writer::new(Cursor::new(Vec::new()));
+    write_start_tag(&mut writer, "xml", vec![
+        ("xmlns:v", "urn:schemas-microsoft-com:vml"),
+        ("xmlns:o", "urn:schemas-microsoft-com:office:office"),
+        ("xmlns:x", "urn:schemas-microsoft-com:office:excel"),
+    ], false);
+    write_start_tag(&mut writer, "o:shapelayout", vec![("v:ext", "edit")], false);
+    write_start_tag(&mut writer, "o:idmap", vec![("v:ext", "edit"), ("data", index.to_string().as_str())], true);
+    write_end_tag(&mut writer, "o:shapelayout");
+    // the text box shape Excel draws every note with
+    write_start_tag(&mut writer, "v:shapetype", vec![
+        ("id", "_x0000_t202"),
+        ("coordsize", "21600,21600"),
+        ("o:spt", "202"),
+        ("path", "m,l,21600r21600,l21600,xe"),
+    ], false);
+    write_start_tag(&mut writer, "v:stroke", vec![("joinstyle", "miter")], true);
+    write_start_tag(&mut writer, "v:path", vec![("gradientshapeok", "t"), ("o:connecttype", "rect")], true);
+    write_end_tag(&mut writer, "v:shapetype");
+    // shape ids are numbered from 1024 times the id of the map
+    let mut shape_id = index * 1024;
+    for &(row_index, column_index, comment) in comments {
+        shape_id += 1;
+        let visibility = if comment.is_visible() { "visible" } else { "hidden" };
+        write_start_tag(&mut writer, "v:shape", vec![
+            ("id", format!("_x0000_s{}", shape_id).as_str()),
+            ("type", "#_x0000_t202"),
+            ("style", format!("position:absolute;margin-left:59.25pt;margin-top:1.5pt;width:108pt;height:59.25pt;z-index:1;visibility:{}", visibility).as_str()),
+            ("fillcolor", "#ffffe1"),
+            ("o:insetmode", "auto"),
+        ], false);
+        write_start_tag(&mut writer, "v:fill", vec![("color2", "#ffffe1")], true);
+        write_start_tag(&mut writer, "v:shadow", vec![("on", "t"), ("color", "black"), ("obscured", "t")], true);
+        write_start_tag(&mut writer, "v:path", vec![("o:connecttype", "none")], true);
+        write_start_tag(&mut writer, "v:textbox", vec![("style", "mso-direction-alt:auto")], false);
+        write_start_tag(&mut writer, "div", vec![("style", "text-align:left")], true);
+        write_end_tag(&mut writer, "v:textbox");
+        write_start_tag(&mut writer, "x:ClientData", vec![("ObjectType", "Note")], false);
+        write_start_tag(&mut writer, "x:MoveWithCells", vec![], true);
+        write_start_tag(&mut writer, "x:SizeWithCells", vec![], true);
+        // the box sits right of the cell, two columns wide and four rows high
+        let anchor = format!("{}, 15, {}, 2, {}, 15, {}, 16", column_index + 1, row_index, column_index + 3, row_index + 4);
+        write_start_tag(&mut writer, "x:Anchor", vec![], false);
+        write_text_node(&mut writer, anchor.as_str());
+        write_end_tag(&mut writer, "x:Anchor");
+        write_start_tag(&mut writer, "x:AutoFill", vec![], false);
+        write_text_node(&mut writer, "False");
+        write_end_tag(&mut writer, "x:AutoFill");
+        write_start_tag(&mut writer, "x:Row", vec![], false);
+        write_text_node(&mut writer, row_index.to_string().as_str());
+        write_end_tag(&mut writer, "x:Row");
+        write_start_tag(&mut writer, "x:Column", vec![], false);
+        write_text_node(&mut writer, column_index.to_string().as_str());
+        write_end_tag(&mut writer, "x:Column");
+        if comment.is_visible() {
+            write_start_tag(&mut writer, "x:Visible", vec![], true);
+        }
+        write_end_tag(&mut writer, "x:ClientData");
+        write_end_tag(&mut writer, "v:shape");
+    }
+    write_end_tag(&mut writer, "xml");
+    make_file_from_writer(format!("xl/drawings/vmlDrawing{}.vml", index).as_str(), zip, writer)?;
+    Ok(())
+}
+
+/// commentsN.xml and the vmlDrawingN.vml of the sheet with the index N.
+pub fn write<W: Write + Seek>(comments: &[(usize, usize, &Comment)], zip: &mut ZipWriter<W>, index: usize) -> result::Result<(), Error> {
+    make_comments_xml(comments, zip, index)?;
+    make_vml_drawing(comments, zip, index)
+}
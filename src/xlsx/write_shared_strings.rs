@@ -4,19 +4,20 @@ use std::io::Cursor;
 use std::result;
 use super::quick_xml::events::{Event, BytesDecl};
 use super::quick_xml::Writer;
-use super::tempdir::TempDir;
 use super::{Book,Value};
-use super::XlsxError;
+use super::Error;
+use std::io::{Seek, Write};
+use super::zip::ZipWriter;
 
 const SHARED_STRINGS: &'static str = "xl/sharedStrings.xml";
 
-pub fn write(book: &Book, dir: &TempDir) -> result::Result<HashMap<String, usize>, XlsxError> {
+pub fn write<W: Write + Seek>(book: &Book, zip: &mut ZipWriter<W>) -> result::Result<HashMap<String, usize>, Error> {
     let mut shared_strings: Vec<String> = Vec::new();
     let mut count: usize = 0;
     for sheet in book.get_sheet_vec() {
         sheet.sorted_access(|_, _, cell| {
             match cell.get_value() {
-                &Value::Str(ref val) => {
+                &Value::Str(ref val) if cell.get_formula().is_none() => {
                     count = count + 1;
                     if !shared_strings.contains(val) {
                         shared_strings.push(val.clone());
@@ -46,6 +47,6 @@ pub fn write(book: &Book, dir: &TempDir) -> result::Result<HashMap<String, usize
          index = index + 1;
     }
     write_end_tag(&mut writer, "sst");
-    let _ = make_file_from_writer(SHARED_STRINGS, dir, writer, Some("xl"))?;
+    make_file_from_writer(SHARED_STRINGS, zip, writer)?;
     Ok(map)
 }
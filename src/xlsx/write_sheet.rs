@@ -1,15 +1,110 @@
 use crate::file_common::*;
 use std::io::Cursor;
 use std::result;
-use super::chrono::prelude::*;
 use super::quick_xml::events::{Event, BytesDecl};
 use super::quick_xml::Writer;
-use super::tempdir::TempDir;
-use super::{Sheet, Value, index_to_column};
-use super::XlsxError;
+use super::{Sheet, SheetView, Pane, Value, DateSystem, LinkTarget, index_to_column};
+use crate::formula;
+use super::Error;
+use super::write_styles;
+use super::write_comments;
+use super::write_sheet_xml_rels;
 use std::collections::HashMap;
+use std::io::{Seek, Write};
+use super::zip::ZipWriter;
 
-pub fn write(sheet: &Sheet, dir: &TempDir, shared_strings: &HashMap<String, usize>, index: usize, format_map: &HashMap<String, usize>) -> result::Result<(), XlsxError> {
+// columns written to <cols>, as many as LibreOffice has
+const MAX_COLUMNS: usize = 1025;
+const DEFAULT_COLUMN_WIDTH: f64 = 10.86;
+const COMMENTS_TYPE: &str = "http://schemas.openxmlformats.org/officeDocument/2006/relationships/comments";
+const HYPERLINK_TYPE: &str = "http://schemas.openxmlformats.org/officeDocument/2006/relationships/hyperlink";
+const VML_DRAWING_TYPE: &str = "http://schemas.openxmlformats.org/officeDocument/2006/relationships/vmlDrawing";
+
+fn bool_str(value: bool) -> &'static str {
+    if value { "true" } else { "false" }
+}
+
+fn make_default_col(writer: &mut Writer<Cursor<Vec<u8>>>, first: usize, last: usize) {
+    write_start_tag(writer, "col", vec![
+        ("collapsed", "false"),
+        ("customWidth", "false"),
+        ("hidden", "false"),
+        ("outlineLevel", "0"),
+        ("max", (last + 1).to_string().as_str()),
+        ("min", (first + 1).to_string().as_str()),
+        ("style", "0"),
+        ("width", DEFAULT_COLUMN_WIDTH.to_string().as_str()),
+    ], true);
+}
+
+fn cell_ref(row_index: usize, column_index: usize) -> String {
+    format!("{}{}", index_to_column(column_index), row_index + 1)
+}
+
+// the pane and selection of a sheetView, split positions are in twips
+fn make_sheet_view(writer: &mut Writer<Cursor<Vec<u8>>>, view: &SheetView, selected: bool) {
+    let zoom = view.get_zoom().to_string();
+    write_start_tag(writer, "sheetView", vec![
+        ("showFormulas", "false"),
+        ("showGridLines", bool_str(view.shows_grid_lines())),
+        ("showRowColHeaders", bool_str(view.shows_headers())),
+        ("showZeros", "true"),
+        ("rightToLeft", bool_str(view.is_right_to_left())),
+        ("tabSelected", bool_str(selected)),
+        ("showOutlineSymbols", "true"),
+        ("defaultGridColor", "true"),
+        ("view", "normal"),
+        ("topLeftCell", "A1"),
+        ("colorId", "64"),
+        ("zoomScale", zoom.as_str()),
+        ("zoomScaleNormal", zoom.as_str()),
+        ("zoomScalePageLayoutView", "100"),
+        ("workbookViewId", "0"),
+    ], false);
+    let (x_split, y_split, state) = match view.get_pane() {
+        Some(Pane::Frozen { rows, columns }) => (columns.to_string(), rows.to_string(), "frozen"),
+        Some(Pane::Split { x, y }) => ((x * 20.0).round().to_string(), (y * 20.0).round().to_string(), "split"),
+        None => (String::from("0"), String::from("0"), ""),
+    };
+    // the cursor is in the pane at the bottom right of the split
+    let active_pane = match (x_split != "0", y_split != "0") {
+        (true, true) => "bottomRight",
+        (false, true) => "bottomLeft",
+        (true, false) => "topRight",
+        (false, false) => "topLeft",
+    };
+    if view.get_pane().is_some() {
+        let mut attributes = vec![];
+        if x_split != "0" {
+            attributes.push(("xSplit", x_split.as_str()));
+        }
+        if y_split != "0" {
+            attributes.push(("ySplit", y_split.as_str()));
+        }
+        let top_left = match view.get_pane() {
+            Some(Pane::Frozen { rows, columns }) => Some(cell_ref(rows, columns)),
+            _ => None,
+        };
+        if let Some(ref top_left) = top_left {
+            attributes.push(("topLeftCell", top_left.as_str()));
+        }
+        attributes.push(("activePane", active_pane));
+        attributes.push(("state", state));
+        write_start_tag(writer, "pane", attributes, true);
+    }
+    let (row_index, column_index) = view.get_active_cell();
+    let active_cell = cell_ref(row_index, column_index);
+    let sqref = view.get_selection().map_or(active_cell.clone(), |range| range.to_string());
+    write_start_tag(writer, "selection", vec![
+        ("pane", active_pane),
+        ("activeCell", active_cell.as_str()),
+        ("activeCellId", "0"),
+        ("sqref", sqref.as_str()),
+    ], true);
+    write_end_tag(writer, "sheetView");
+}
+
+pub fn write<W: Write + Seek>(sheet: &Sheet, zip: &mut ZipWriter<W>, shared_strings: &HashMap<String, usize>, index: usize, selected: bool, format_map: &HashMap<write_styles::XfKey, usize>, date_system: DateSystem) -> result::Result<(), Error> {
     let dimension = match sheet.get_max_index() {
         Some((max_row_index, max_column_index)) => {
             if max_row_index == 0 && max_column_index == 0 {
@@ -33,85 +128,148 @@ pub fn write(sheet: &Sheet, dir: &TempDir, shared_strings: &HashMap<String, usiz
     write_end_tag(&mut writer, "sheetPr");
     write_start_tag(&mut writer, "dimension", vec![("ref", dimension.as_str())], true);
     write_start_tag(&mut writer, "sheetViews", vec![], false);
-    write_start_tag(&mut writer, "sheetView", vec![("showFormulas", "false"),("showGridLines", "true"),("showRowColHeaders", "true"),("showZeros", "true"),("rightToLeft", "false"),("tabSelected", "true"),("showOutlineSymbols", "true"),("defaultGridColor", "true"),("view", "normal"),("topLeftCell", "A1"),("colorId", "64"),("zoomScale", "100"),("zoomScaleNormal", "100"),("zoomScalePageLayoutView", "100"),("workbookViewId", "0")], false);
-    write_start_tag(&mut writer, "selection", vec![("pane", "topLeft"),("activeCell", "A1"),("activeCellId", "0"),("sqref", "A1")], true);
-    write_end_tag(&mut writer, "sheetView");
+    make_sheet_view(&mut writer, sheet.get_view(), selected);
     write_end_tag(&mut writer, "sheetViews");
     write_start_tag(&mut writer, "sheetFormatPr", vec![("defaultRowHeight", "12.8"),("zeroHeight", "false"),("outlineLevelRow", "0"),("outlineLevelCol", "0")], true);
     write_start_tag(&mut writer, "cols", vec![], false);
-    write_start_tag(&mut writer, "col", vec![("collapsed", "false"),("customWidth", "true"),("hidden", "false"),("outlineLevel", "0"),("max", "1025"),("min", "1"),("style", "0"),("width", "10.86")], true);
+    let mut column_indexes: Vec<&usize> = sheet.get_columns().keys().collect();
+    column_indexes.sort();
+    // the columns in between and after keep the default width
+    let mut next_column = 0;
+    for &column_index in column_indexes.into_iter().filter(|&&index| index < MAX_COLUMNS) {
+        if next_column < column_index {
+            make_default_col(&mut writer, next_column, column_index - 1);
+        }
+        let column = &sheet.get_columns()[&column_index];
+        let index_str = (column_index + 1).to_string();
+        let width = column.get_width().unwrap_or(DEFAULT_COLUMN_WIDTH).to_string();
+        write_start_tag(&mut writer, "col", vec![
+            ("collapsed", "false"),
+            ("customWidth", bool_str(column.get_width().is_some())),
+            ("hidden", bool_str(column.is_hidden())),
+            ("bestFit", bool_str(column.is_auto_fit())),
+            ("outlineLevel", "0"),
+            ("max", &index_str),
+            ("min", &index_str),
+            ("style", "0"),
+            ("width", &width),
+        ], true);
+        next_column = column_index + 1;
+    }
+    if next_column < MAX_COLUMNS {
+        make_default_col(&mut writer, next_column, MAX_COLUMNS - 1);
+    }
     write_end_tag(&mut writer, "cols");
-    if sheet.get_rows().len() == 0 {
+    // rows with cells and rows with a height or flags
+    let mut row_indexes: Vec<usize> = sheet.get_rows().keys().chain(sheet.get_row_settings().keys()).cloned().collect();
+    row_indexes.sort();
+    row_indexes.dedup();
+    if row_indexes.is_empty() {
         write_start_tag(&mut writer, "sheetData", vec![], true);
     } else {
         write_start_tag(&mut writer, "sheetData", vec![], false);
-        let mut current_row_index = ::std::usize::MAX;
-        sheet.sorted_access(|row_index, column_index, cell| {
+        for row_index in row_indexes {
             let row_str = (row_index + 1).to_string();
-            if current_row_index != row_index {
-                if current_row_index != ::std::usize::MAX {
-                    write_end_tag(&mut writer, "row");
-                }
-                current_row_index = row_index;
-                write_start_tag(&mut writer, "row", vec![
-                    ("r", &row_str),
-                    ("customFormat", "false"),
-                    ("hidden", "false"),
-                    ("customHeight", "false"),
-                    ("outlineLevel", "0"),
-                    ("collapsed", "false"),
-                ], false);
+            let row = sheet.get_row(row_index).cloned().unwrap_or_default();
+            let height = row.get_height().map(|height| height.to_string());
+            let mut attributes = vec![
+                ("r", row_str.as_str()),
+                ("customFormat", "false"),
+            ];
+            if let Some(ref height) = height {
+                attributes.push(("ht", height.as_str()));
             }
-            let col_str = format!(
-                "{}{}", index_to_column(column_index), row_str);
-            let format = cell.get_format().get_content();
-            match cell.get_value() {
-                &Value::Str(ref val) => {
-                    write_start_tag(&mut writer, "c", vec![
-                        ("r", &col_str),
-                        ("s", "0"),
-                        ("t", "s"),
-                    ], false);
-                    write_start_tag(&mut writer, "v", vec![], false);
-                    let val_index = shared_strings.get(val).unwrap().to_string();
-                    write_text_node(&mut writer, val_index.as_str());
-                },
-                &Value::Float(ref val) => {
-                    write_start_tag(&mut writer, "c", vec![
-                        ("r", &col_str),
-                        ("s", "0"),
-                        ("t", "n"),
-                    ], false);
-                    write_start_tag(&mut writer, "v", vec![], false);
-                    write_text_node(&mut writer, val.to_string().as_str());
-                },
-                &Value::Date(ref val) => {
-                    let s_value = format_map.get(format).unwrap();
-                    write_start_tag(&mut writer, "c", vec![
-                        ("r", &col_str),
-                        ("s", s_value.to_string().as_str()),
-                        ("t", "n"),
-                    ], false);
-                    write_start_tag(&mut writer, "v", vec![], false);
-                    write_text_node(&mut writer, datetime_to_serail(val).to_string().as_str());
-                },
-                &Value::Currency(ref val) => {
-                    let s_value = format_map.get(format).unwrap();
-                    write_start_tag(&mut writer, "c", vec![
-                        ("r", &col_str),
-                        ("s", s_value.to_string().as_str()),
-                        ("t", "n"),
-                    ], false);
-                    write_start_tag(&mut writer, "v", vec![], false);
-                    write_text_node(&mut writer, val.to_string().as_str());
+            attributes.extend(vec![
+                ("hidden", bool_str(row.is_hidden())),
+                ("customHeight", bool_str(height.is_some() && !row.is_auto_fit())),
+                ("outlineLevel", "0"),
+                ("collapsed", "false"),
+            ]);
+            let columns = match sheet.get_rows().get(&row_index) {
+                Some(columns) => columns,
+                None => {
+                    write_start_tag(&mut writer, "row", attributes, true);
+                    continue;
                 },
+            };
+            write_start_tag(&mut writer, "row", attributes, false);
+            let mut column_indexes: Vec<&usize> = columns.keys().collect();
+            column_indexes.sort();
+            for column_index in column_indexes {
+                let cell = &columns[column_index];
+                let col_str = format!(
+                    "{}{}", index_to_column(*column_index), row_str);
+                let style = format_map.get(&(write_styles::number_format(cell), cell.get_style()))
+                    .map_or(0, |style| *style)
+                    .to_string();
+                let (type_value, value) = match cell.get_value() {
+                    // formula results are stored in the cell, not in the shared strings
+                    &Value::Str(ref val) if cell.get_formula().is_some() => ("str", val.clone()),
+                    &Value::Str(ref val) => ("s", shared_strings.get(val).unwrap().to_string()),
+                    // xlsx has no currency type, the format tells the reader about the currency
+                    &Value::Float(ref val) | &Value::Currency(ref val, _) | &Value::Percentage(ref val) => ("n", val.to_string()),
+                    &Value::Time(ref val) => ("n", formula::time_to_serial(val).to_string()),
+                    &Value::Duration(ref val) => ("n", formula::duration_to_serial(val).to_string()),
+                    &Value::Bool(ref val) => ("b", String::from(if *val { "1" } else { "0" })),
+                    &Value::Error(ref kind) => ("e", kind.code().to_string()),
+                    &Value::Date(ref val) => ("n", date_system.date_to_serial(val).to_string()),
+                };
+                write_start_tag(&mut writer, "c", vec![
+                    ("r", &col_str),
+                    ("s", &style),
+                    ("t", type_value),
+                ], false);
+                if let Some(formula) = cell.get_formula() {
+                    write_start_tag(&mut writer, "f", vec![], false);
+                    write_text_node(&mut writer, formula.trim_start_matches('='));
+                    write_end_tag(&mut writer, "f");
+                }
+                write_start_tag(&mut writer, "v", vec![], false);
+                write_text_node(&mut writer, value);
+                write_end_tag(&mut writer, "v");
+                write_end_tag(&mut writer, "c");
             }
-            write_end_tag(&mut writer, "v");
-            write_end_tag(&mut writer, "c");
-        });
-        write_end_tag(&mut writer, "row");
+            write_end_tag(&mut writer, "row");
+        }
         write_end_tag(&mut writer, "sheetData");
     }
+    if !sheet.merged_ranges().is_empty() {
+        write_start_tag(&mut writer, "mergeCells", vec![("count", sheet.merged_ranges().len().to_string().as_str())], false);
+        for range in sheet.merged_ranges() {
+            write_start_tag(&mut writer, "mergeCell", vec![("ref", range.to_string().as_str())], true);
+        }
+        write_end_tag(&mut writer, "mergeCells");
+    }
+    // urls are relationships of the sheet, cells of the book are locations
+    let mut relationships = vec![];
+    let mut hyperlinks = vec![];
+    for (row_index, columns) in sheet.get_rows() {
+        for (column_index, cell) in columns {
+            if let Some(hyperlink) = cell.get_hyperlink() {
+                hyperlinks.push((*row_index, *column_index, hyperlink));
+            }
+        }
+    }
+    hyperlinks.sort_by_key(|&(row_index, column_index, _)| (row_index, column_index));
+    if !hyperlinks.is_empty() {
+        write_start_tag(&mut writer, "hyperlinks", vec![], false);
+        for (row_index, column_index, hyperlink) in hyperlinks {
+            let cell = cell_ref(row_index, column_index);
+            let (key, value) = match hyperlink.get_target() {
+                LinkTarget::Url(url) => {
+                    relationships.push((HYPERLINK_TYPE, url.clone(), true));
+                    ("r:id", format!("rId{}", relationships.len()))
+                },
+                LinkTarget::Cell { sheet, row, column } => ("location", format!("{}!{}", quote_sheet_name(sheet), cell_ref(*row, *column))),
+            };
+            let mut attributes = vec![("ref", cell.as_str()), (key, value.as_str())];
+            if let Some(tooltip) = hyperlink.get_tooltip() {
+                attributes.push(("tooltip", tooltip.as_str()));
+            }
+            write_start_tag(&mut writer, "hyperlink", attributes, true);
+        }
+        write_end_tag(&mut writer, "hyperlinks");
+    }
     write_start_tag(&mut writer, "printOptions", vec![("headings", "false"),("gridLines", "false"),("gridLinesSet", "true"),("horizontalCentered", "false"),("verticalCentered", "false")], true);
     write_start_tag(&mut writer, "pageMargins", vec![("left", "0.7875"),("right", "0.7875"),("top", "1.025"),("bottom", "1.025"),("header", "0.7875"),("footer", "0.7875")], true);
     write_start_tag(&mut writer, "pageSetup", vec![("paperSize", "9"),("scale", "100"),("firstPageNumber", "1"),("fitToWidth", "1"),("fitToHeight", "1"),("pageOrder", "downThenOver"),("orientation", "portrait"),("blackAndWhite", "false"),("draft", "false"),("cellComments", "none"),("useFirstPageNumber", "true"),("horizontalDpi", "300"),("verticalDpi", "300"),("copies", "1")], true);
@@ -123,14 +281,17 @@ pub fn write(sheet: &Sheet, dir: &TempDir, shared_strings: &HashMap<String, usiz
     write_text_node(&mut writer, "&amp;C&amp;&quot;Arial,標準&quot;ページ &amp;P");
     write_end_tag(&mut writer, "oddFooter");
     write_end_tag(&mut writer, "headerFooter");
+    let comments = write_comments::sheet_comments(sheet);
+    if !comments.is_empty() {
+        write_comments::write(&comments, zip, index)?;
+        relationships.push((COMMENTS_TYPE, format!("../comments{}.xml", index), false));
+        relationships.push((VML_DRAWING_TYPE, format!("../drawings/vmlDrawing{}.vml", index), false));
+        write_start_tag(&mut writer, "legacyDrawing", vec![("r:id", format!("rId{}", relationships.len()).as_str())], true);
+    }
     write_end_tag(&mut writer, "worksheet");
-    let _ = make_file_from_writer(format!("xl/worksheets/sheet{}.xml", index).as_str(), dir, writer, Some("xl/worksheets"))?;
+    make_file_from_writer(format!("xl/worksheets/sheet{}.xml", index).as_str(), zip, writer)?;
+    if !relationships.is_empty() {
+        write_sheet_xml_rels::write(&relationships, zip, index)?;
+    }
     Ok(())
 }
-
-fn datetime_to_serail(src: &DateTime<Utc>) -> f64 {
-    let seconds = src.hour() * 3600 + src.minute() * 60 + src.second();
-    let base = Utc.ymd(1900, 1, 1).and_hms(0, 0, 0);
-    let days = src.num_days_from_ce() - base.num_days_from_ce() + 2;
-    days as f64 + seconds as f64 / 86400.0
-}
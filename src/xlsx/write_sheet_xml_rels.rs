@@ -0,0 +1,35 @@
+use crate::file_common::*;
+use std::io::Cursor;
+use std::result;
+use super::quick_xml::events::{Event, BytesDecl};
+use super::quick_xml::Writer;
+use super::Error;
+use std::io::{Seek, Write};
+use super::zip::ZipWriter;
+
+/// The relationships of the sheet with the index N as (type, target, external), their ids are rId1, rId2, ..
+pub fn write<W: Write + Seek>(relationships: &[(&str, String, bool)], zip: &mut ZipWriter<W>, index: usize) -> result::Result<(), Error> {
+    let mut writer = Writer::new(Cursor::new(Vec::new()));
+    let _ = writer.write_event(Event::Decl(
+        BytesDecl::new(b"1.0", Some(b"UTF-8"), None)));
+    write_text_node(&mut writer, "\n");
+    write_start_tag(&mut writer, "Relationships", vec![
+        ("xmlns", "http://schemas.openxmlformats.org/package/2006/relationships")
+    ], false);
+    for (i, &(relationship_type, ref target, external)) in relationships.iter().enumerate() {
+        let id = format!("rId{}", i + 1);
+        let mut attributes = vec![
+            ("Id", id.as_str()),
+            ("Type", relationship_type),
+            ("Target", target.as_str()),
+        ];
+        // links out of the package
+        if external {
+            attributes.push(("TargetMode", "External"));
+        }
+        write_start_tag(&mut writer, "Relationship", attributes, true);
+    }
+    write_end_tag(&mut writer, "Relationships");
+    make_file_from_writer(format!("xl/worksheets/_rels/sheet{}.xml.rels", index).as_str(), zip, writer)?;
+    Ok(())
+}
@@ -4,31 +4,45 @@ use std::io::Cursor;
 use std::result;
 use super::quick_xml::events::{Event, BytesDecl};
 use super::quick_xml::Writer;
-use super::tempdir::TempDir;
-use super::{Book, Value};
-use super::XlsxError;
+use super::{Book, Cell, Value};
+use crate::style::{Alignment, Border, BorderLine, Fill, Font, HorizontalAlignment, Rgb, Style, Underline, VerticalAlignment};
+use super::Error;
+use std::io::{Seek, Write};
+use super::zip::ZipWriter;
 
 const STYLE_XML: &'static str = "xl/styles.xml";
 
+// the format a cell is written with as a numFmt, if any
+pub(crate) fn number_format(cell: &Cell) -> Option<&String> {
+    let format = cell.get_format().get_content();
+    match cell.get_value() {
+        &Value::Date(_) => Some(format),
+        &Value::Float(_) | &Value::Currency(..) | &Value::Percentage(_) | &Value::Time(_) | &Value::Duration(_)
+            if !format.is_empty() => Some(format),
+        _ => None,
+    }
+}
+
+// Cells with the same numFmt and style share one xf of cellXfs.
+pub(crate) type XfKey<'a> = (Option<&'a String>, &'a Style);
+
 fn make_num_fmts(writer: &mut Writer<Cursor<Vec<u8>>>, book: &Book) -> Vec<HashMap<String, String>> {
     let mut result = vec![];
     let mut num_fmot_id = 164;
     let mut key_map = HashMap::new();
     for sheet in book.get_sheet_vec() {
         sheet.walk_through(|_, _, cell| {
-            match cell.get_value() {
-                &Value::Date(_) => {
-                    let format = cell.get_format().get_content();
-                    if !key_map.contains_key(format) {
-                        let mut map = HashMap::new();
-                        map.insert(String::from("numFmtId"), num_fmot_id.to_string());
-                        map.insert(String::from("format"), format.clone());
-                        result.push(map);
-                        num_fmot_id = num_fmot_id + 1;
-                        key_map.insert(format.clone(), ());
-                    }
-                },
-                _ => {}
+            let format = match number_format(cell) {
+                Some(format) => format,
+                None => return,
+            };
+            if !key_map.contains_key(format) {
+                let mut map = HashMap::new();
+                map.insert(String::from("numFmtId"), num_fmot_id.to_string());
+                map.insert(String::from("format"), format.clone());
+                result.push(map);
+                num_fmot_id = num_fmot_id + 1;
+                key_map.insert(format.clone(), ());
             }
         });
     }
@@ -50,9 +64,152 @@ fn make_num_fmts(writer: &mut Writer<Cursor<Vec<u8>>>, book: &Book) -> Vec<HashM
     result
 }
 
-fn make_cell_xfs(writer: &mut Writer<Cursor<Vec<u8>>>, num_fmts: &Vec<HashMap<String, String>>) -> HashMap<String, usize> {
-    let mut result: HashMap<String, usize> = HashMap::new();
-    let count = num_fmts.len() + 1;
+fn argb(color: Rgb) -> String {
+    format!("FF{}", color.to_hex())
+}
+
+// the default font and the font of number formats come first
+fn make_fonts(writer: &mut Writer<Cursor<Vec<u8>>>, fonts: &[&Font]) {
+    write_start_tag(writer, "fonts", vec![("count", (fonts.len() + 2).to_string().as_str()),], false);
+    write_start_tag(writer, "font", vec![], false);
+    write_start_tag(writer, "sz", vec![("val", "10.0"),], false);
+    write_end_tag(writer, "sz");
+    write_start_tag(writer, "color", vec![("rgb", "FF000000"),], false);
+    write_end_tag(writer, "color");
+    write_start_tag(writer, "name", vec![("val", "Arial"),], false);
+    write_end_tag(writer, "name");
+    write_end_tag(writer, "font");
+    write_start_tag(writer, "font", vec![], false);
+    write_end_tag(writer, "font");
+    for font in fonts {
+        write_start_tag(writer, "font", vec![], false);
+        if font.is_bold() {
+            write_start_tag(writer, "b", vec![], true);
+        }
+        if font.is_italic() {
+            write_start_tag(writer, "i", vec![], true);
+        }
+        match font.get_underline() {
+            Underline::None => {},
+            Underline::Single => write_start_tag(writer, "u", vec![], true),
+            Underline::Double => write_start_tag(writer, "u", vec![("val", "double")], true),
+        }
+        if let Some(size) = font.get_size() {
+            write_start_tag(writer, "sz", vec![("val", size.to_string().as_str())], true);
+        }
+        if let Some(color) = font.get_color() {
+            write_start_tag(writer, "color", vec![("rgb", argb(color).as_str())], true);
+        }
+        if let Some(name) = font.get_name() {
+            write_start_tag(writer, "name", vec![("val", name.as_str())], true);
+        }
+        write_end_tag(writer, "font");
+    }
+    write_end_tag(writer, "fonts");
+}
+
+// none and gray125 are the two fills xlsx reserves
+fn make_fills(writer: &mut Writer<Cursor<Vec<u8>>>, fills: &[&Fill]) {
+    write_start_tag(writer, "fills", vec![("count", (fills.len() + 2).to_string().as_str()),], false);
+    write_start_tag(writer, "fill", vec![], false);
+    write_start_tag(writer, "patternFill", vec![("patternType", "none"),], false);
+    write_end_tag(writer, "patternFill");
+    write_end_tag(writer, "fill");
+    write_start_tag(writer, "fill", vec![], false);
+    write_start_tag(writer, "patternFill", vec![("patternType", "lightGray"),], false);
+    write_end_tag(writer, "patternFill");
+    write_end_tag(writer, "fill");
+    for fill in fills {
+        let (pattern, color, background) = match **fill {
+            Fill::Solid(color) => ("solid", color, None),
+            Fill::Pattern { pattern, color, background } => (pattern.get_name(), color, Some(background)),
+        };
+        write_start_tag(writer, "fill", vec![], false);
+        write_start_tag(writer, "patternFill", vec![("patternType", pattern),], false);
+        write_start_tag(writer, "fgColor", vec![("rgb", argb(color).as_str())], true);
+        match background {
+            Some(background) => write_start_tag(writer, "bgColor", vec![("rgb", argb(background).as_str())], true),
+            None => write_start_tag(writer, "bgColor", vec![("indexed", "64")], true),
+        }
+        write_end_tag(writer, "patternFill");
+        write_end_tag(writer, "fill");
+    }
+    write_end_tag(writer, "fills");
+}
+
+fn make_border_line(writer: &mut Writer<Cursor<Vec<u8>>>, edge: &str, line: Option<BorderLine>) {
+    match line {
+        Some(line) => {
+            write_start_tag(writer, edge, vec![("style", line.get_style().get_name())], false);
+            write_start_tag(writer, "color", vec![("rgb", argb(line.get_color()).as_str())], true);
+            write_end_tag(writer, edge);
+        },
+        None => write_start_tag(writer, edge, vec![], true),
+    }
+}
+
+fn make_borders(writer: &mut Writer<Cursor<Vec<u8>>>, borders: &[&Border]) {
+    write_start_tag(writer, "borders", vec![("count", (borders.len() + 1).to_string().as_str()),], false);
+    write_start_tag(writer, "border", vec![], false);
+    write_end_tag(writer, "border");
+    for border in borders {
+        write_start_tag(writer, "border", vec![], false);
+        make_border_line(writer, "left", border.get_left());
+        make_border_line(writer, "right", border.get_right());
+        make_border_line(writer, "top", border.get_top());
+        make_border_line(writer, "bottom", border.get_bottom());
+        write_start_tag(writer, "diagonal", vec![], true);
+        write_end_tag(writer, "border");
+    }
+    write_end_tag(writer, "borders");
+}
+
+fn make_alignment(writer: &mut Writer<Cursor<Vec<u8>>>, alignment: &Alignment) {
+    let horizontal = alignment.get_horizontal().map(|horizontal| match horizontal {
+        HorizontalAlignment::Left => "left",
+        HorizontalAlignment::Center => "center",
+        HorizontalAlignment::Right => "right",
+        HorizontalAlignment::Justify => "justify",
+        HorizontalAlignment::Fill => "fill",
+    });
+    let vertical = alignment.get_vertical().map(|vertical| match vertical {
+        VerticalAlignment::Top => "top",
+        VerticalAlignment::Center => "center",
+        VerticalAlignment::Bottom => "bottom",
+    });
+    // 1 to 90 turn counterclockwise, 91 to 180 clockwise
+    let rotation = match alignment.get_rotation() {
+        rotation if rotation < 0 => (90 - rotation).to_string(),
+        rotation => rotation.to_string(),
+    };
+    let indent = alignment.get_indent().to_string();
+    let mut attributes = vec![("readingOrder", "0")];
+    if let Some(horizontal) = horizontal {
+        attributes.push(("horizontal", horizontal));
+    }
+    if let Some(vertical) = vertical {
+        attributes.push(("vertical", vertical));
+    }
+    if alignment.is_wrap() {
+        attributes.push(("wrapText", "1"));
+    }
+    if alignment.get_rotation() != 0 {
+        attributes.push(("textRotation", rotation.as_str()));
+    }
+    if alignment.get_indent() != 0 {
+        attributes.push(("indent", indent.as_str()));
+    }
+    write_start_tag(writer, "alignment", attributes, true);
+}
+
+// index of an item in a table which starts after `reserved` fixed entries
+fn table_index<T: PartialEq>(table: &[&T], item: Option<&T>, reserved: usize) -> usize {
+    item.and_then(|item| table.iter().position(|entry| *entry == item)).map_or(0, |index| index + reserved)
+}
+
+fn make_cell_xfs<'a>(writer: &mut Writer<Cursor<Vec<u8>>>, num_fmts: &[HashMap<String, String>], keys: &[XfKey<'a>], fonts: &[&Font], fills: &[&Fill], borders: &[&Border]) -> HashMap<XfKey<'a>, usize> {
+    let mut result: HashMap<XfKey<'a>, usize> = HashMap::new();
+    let count = keys.len() + 1;
     write_start_tag(writer, "cellXfs", vec![("count", count.to_string().as_str()),], false);
     write_start_tag(writer, "xf", vec![
         ("borderId", "0"),
@@ -66,29 +223,45 @@ fn make_cell_xfs(writer: &mut Writer<Cursor<Vec<u8>>>, num_fmts: &Vec<HashMap<St
     write_start_tag(writer, "alignment", vec![
         ("readingOrder", "0"),
         ("shrinkToFit", "0"),
-        ("vertical", "bottom"),
         ("wrapText", "0"),
     ], true);
     write_end_tag(writer, "xf");
 
-    let mut count = 0;
-    for map in num_fmts.iter() {
-        count = count + 1;
-        let num_fmt_id = map.get(&String::from("numFmtId")).unwrap();
-        let format_code = map.get(&String::from("format")).unwrap();
-        result.insert(format_code.clone(), count as usize);
-        write_start_tag(writer, "xf", vec![
-            ("borderId", "0"),
-            ("fillId", "0"),
-            ("fontId", "1"),
-            ("numFmtId", num_fmt_id.to_string().as_str()),
+    for (index, key) in keys.iter().enumerate() {
+        let (format, style) = *key;
+        result.insert(*key, index + 1);
+        let num_fmt_id = match format {
+            Some(format) => num_fmts.iter()
+                .find(|map| map.get("format") == Some(format))
+                .map(|map| map["numFmtId"].clone())
+                .unwrap(),
+            None => String::from("0"),
+        };
+        // number formats without a font of their own keep the font they always had
+        let font_id = match style.get_font() {
+            Some(font) => table_index(fonts, Some(font), 2),
+            None if format.is_some() => 1,
+            None => 0,
+        }.to_string();
+        let fill_id = table_index(fills, style.get_fill(), 2).to_string();
+        let border_id = if style.get_border().is_empty() { 0 } else { table_index(borders, Some(style.get_border()), 1) }.to_string();
+        let mut attributes = vec![
+            ("borderId", border_id.as_str()),
+            ("fillId", fill_id.as_str()),
+            ("fontId", font_id.as_str()),
+            ("numFmtId", num_fmt_id.as_str()),
             ("xfId", "0"),
             ("applyAlignment", "1"),
             ("applyFont", "1"),
-        ], false);
-        write_start_tag(writer, "alignment", vec![
-            ("readingOrder", "0"),
-        ], true);
+        ];
+        if fill_id != "0" {
+            attributes.push(("applyFill", "1"));
+        }
+        if border_id != "0" {
+            attributes.push(("applyBorder", "1"));
+        }
+        write_start_tag(writer, "xf", attributes, false);
+        make_alignment(writer, style.get_alignment());
         write_end_tag(writer, "xf");
     }
 
@@ -96,7 +269,7 @@ fn make_cell_xfs(writer: &mut Writer<Cursor<Vec<u8>>>, num_fmts: &Vec<HashMap<St
     result
 }
 
-pub fn write(book: &Book, dir: &TempDir) -> result::Result<HashMap<String, usize>, XlsxError> {
+pub fn write<'a, W: Write + Seek>(book: &'a Book, zip: &mut ZipWriter<W>) -> result::Result<HashMap<XfKey<'a>, usize>, Error> {
     let mut writer = Writer::new(Cursor::new(Vec::new()));
     let _ = writer.write_event(Event::Decl(
         BytesDecl::new(b"1.0", Some(b"UTF-8"), Some(b"yes"))));
@@ -105,38 +278,39 @@ pub fn write(book: &Book, dir: &TempDir) -> result::Result<HashMap<String, usize
 
     let num_fmts = make_num_fmts(&mut writer, book);
 
-    write_start_tag(&mut writer, "fonts", vec![("count", "2"),], false);
-    write_start_tag(&mut writer, "font", vec![], false);
-    write_start_tag(&mut writer, "sz", vec![("val", "10.0"),], false);
-    write_end_tag(&mut writer, "sz");
-    write_start_tag(&mut writer, "color", vec![("rgb", "FF000000"),], false);
-    write_end_tag(&mut writer, "color");
-    write_start_tag(&mut writer, "name", vec![("val", "Arial"),], false);
-    write_end_tag(&mut writer, "name");
-    write_end_tag(&mut writer, "font");
-    write_start_tag(&mut writer, "font", vec![], false);
-    write_end_tag(&mut writer, "font");
-    write_end_tag(&mut writer, "fonts");
-    write_start_tag(&mut writer, "fills", vec![("count", "2"),], false);
-    write_start_tag(&mut writer, "fill", vec![], false);
-    write_start_tag(&mut writer, "patternFill", vec![("patternType", "none"),], false);
-    write_end_tag(&mut writer, "patternFill");
-    write_end_tag(&mut writer, "fill");
-    write_start_tag(&mut writer, "fill", vec![], false);
-    write_start_tag(&mut writer, "patternFill", vec![("patternType", "lightGray"),], false);
-    write_end_tag(&mut writer, "patternFill");
-    write_end_tag(&mut writer, "fill");
-    write_end_tag(&mut writer, "fills");
-    write_start_tag(&mut writer, "borders", vec![("count", "1"),], false);
-    write_start_tag(&mut writer, "border", vec![], false);
-    write_end_tag(&mut writer, "border");
-    write_end_tag(&mut writer, "borders");
+    // every look in the book once, in the order the cells use them
+    let mut keys: Vec<XfKey> = vec![];
+    let mut fonts: Vec<&Font> = vec![];
+    let mut fills: Vec<&Fill> = vec![];
+    let mut borders: Vec<&Border> = vec![];
+    for sheet in book.get_sheet_vec() {
+        sheet.walk_through(|_, _, cell| {
+            let style = cell.get_style();
+            let key = (number_format(cell), style);
+            if (key.0.is_none() && style.is_default()) || keys.contains(&key) {
+                return;
+            }
+            keys.push(key);
+            if let Some(font) = style.get_font().filter(|font| !fonts.contains(font)) {
+                fonts.push(font);
+            }
+            if let Some(fill) = style.get_fill().filter(|fill| !fills.contains(fill)) {
+                fills.push(fill);
+            }
+            if !style.get_border().is_empty() && !borders.contains(&style.get_border()) {
+                borders.push(style.get_border());
+            }
+        });
+    }
+    make_fonts(&mut writer, &fonts);
+    make_fills(&mut writer, &fills);
+    make_borders(&mut writer, &borders);
     write_start_tag(&mut writer, "cellStyleXfs", vec![("count", "1"),], false);
     write_start_tag(&mut writer, "xf", vec![("borderId", "0"),("fillId", "0"),("fontId", "0"),("numFmtId", "0"),("applyAlignment", "1"),("applyFont", "1"),], false);
     write_end_tag(&mut writer, "xf");
     write_end_tag(&mut writer, "cellStyleXfs");
 
-    let result = make_cell_xfs(&mut writer, &num_fmts);
+    let result = make_cell_xfs(&mut writer, &num_fmts, &keys, &fonts, &fills, &borders);
 
     write_start_tag(&mut writer, "cellStyles", vec![("count", "1"),], false);
     write_start_tag(&mut writer, "cellStyle", vec![("xfId", "0"),("name", "Normal"),("builtinId", "0"),], false);
@@ -146,6 +320,6 @@ pub fn write(book: &Book, dir: &TempDir) -> result::Result<HashMap<String, usize
     write_end_tag(&mut writer, "dxfs");
     write_end_tag(&mut writer, "styleSheet");
 
-    let _ = make_file_from_writer(STYLE_XML, dir, writer, Some("xl"))?;
+    make_file_from_writer(STYLE_XML, zip, writer)?;
     Ok(result)
 }
@@ -3,13 +3,14 @@ use std::io::Cursor;
 use std::result;
 use super::quick_xml::events::{Event, BytesDecl};
 use super::quick_xml::Writer;
-use super::tempdir::TempDir;
-use super::{Book};
-use super::XlsxError;
+use super::{Book, DateSystem};
+use super::Error;
+use std::io::{Seek, Write};
+use super::zip::ZipWriter;
 
 const WORKBOOK_XML: &'static str = "xl/workbook.xml";
 
-pub fn write(book: &Book, dir: &TempDir) -> result::Result<(), XlsxError> {
+pub fn write<W: Write + Seek>(book: &Book, zip: &mut ZipWriter<W>) -> result::Result<(), Error> {
     let mut writer = Writer::new(Cursor::new(Vec::new()));
     let _ = writer.write_event(Event::Decl(
         BytesDecl::new(b"1.0", Some(b"UTF-8"), Some(b"yes"))));
@@ -24,7 +25,7 @@ pub fn write(book: &Book, dir: &TempDir) -> result::Result<(), XlsxError> {
     write_start_tag(&mut writer, "workbookPr", vec![
         ("backupFile", "false"),
         ("showObjects", "all"),
-        ("date1904", "false")
+        ("date1904", if book.get_date_system() == DateSystem::Date1904 { "true" } else { "false" })
     ], true);
     write_start_tag(&mut writer, "workbookProtection", vec![
     ], true);
@@ -40,7 +41,7 @@ pub fn write(book: &Book, dir: &TempDir) -> result::Result<(), XlsxError> {
         ("windowHeight", "8192"),
         ("tabRatio", "500"),
         ("firstSheet", "0"),
-        ("activeTab", "0")
+        ("activeTab", book.get_active_sheet().to_string().as_str())
     ], true);
     write_end_tag(&mut writer, "bookViews");
     write_start_tag(&mut writer, "sheets", vec![], false);
@@ -73,6 +74,6 @@ pub fn write(book: &Book, dir: &TempDir) -> result::Result<(), XlsxError> {
     write_end_tag(&mut writer, "ext");
     write_end_tag(&mut writer, "extLst");
     write_end_tag(&mut writer, "workbook");
-    let _ = make_file_from_writer(WORKBOOK_XML, dir, writer, Some("xl"))?;
+    make_file_from_writer(WORKBOOK_XML, zip, writer)?;
     Ok(())
 }
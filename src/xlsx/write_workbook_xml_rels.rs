@@ -3,13 +3,14 @@ use std::io::Cursor;
 use std::result;
 use super::quick_xml::events::{Event, BytesDecl};
 use super::quick_xml::Writer;
-use super::tempdir::TempDir;
 use super::{Book};
-use super::XlsxError;
+use super::Error;
+use std::io::{Seek, Write};
+use super::zip::ZipWriter;
 
 const WORKBOOK_XML_RELS: &'static str = "xl/_rels/workbook.xml.rels";
 
-pub fn write(book: &Book, dir: &TempDir) -> result::Result<(), XlsxError> {
+pub fn write<W: Write + Seek>(book: &Book, zip: &mut ZipWriter<W>) -> result::Result<(), Error> {
     let mut writer = Writer::new(Cursor::new(Vec::new()));
     let _ = writer.write_event(Event::Decl(
         BytesDecl::new(b"1.0", Some(b"UTF-8"), None)));
@@ -37,6 +38,6 @@ pub fn write(book: &Book, dir: &TempDir) -> result::Result<(), XlsxError> {
         ("Target", "sharedStrings.xml")
     ], true);
     write_end_tag(&mut writer, "Relationships");
-    let _ = make_file_from_writer(WORKBOOK_XML_RELS, dir, writer, Some("xl/_rels"))?;
+    make_file_from_writer(WORKBOOK_XML_RELS, zip, writer)?;
     Ok(())
 }
@@ -1,9 +1,13 @@
 // cargo test --all-features -- --nocapture
 
 extern crate spsheet;
-use spsheet::{Book,Sheet,Cell,column_to_index,index_to_column,column_and_row_to_index};
-use spsheet::format::Format;
+use spsheet::{Cell,ErrorKind,column_to_index,index_to_column,column_and_row_to_index};
 
+use spsheet::{Book,CellRange,Column,Comment,DateSystem,Hyperlink,LinkTarget,Pane,Row,Sheet,SheetView,Error,Value};
+use spsheet::format::Color;
+use spsheet::style::{Alignment, Border, BorderLine, BorderStyle, Fill, Font, HorizontalAlignment, Rgb, Style, Underline, VerticalAlignment};
+use chrono::{Duration, NaiveDateTime, NaiveTime};
+#[cfg(any(feature = "ods", feature = "xlsx"))]
 use std::path::Path;
 use pretty_assertions::assert_eq;
 
@@ -13,27 +17,42 @@ use spsheet::ods;
 #[cfg(feature = "xlsx")]
 use spsheet::xlsx;
 
+#[cfg(any(feature = "ods", feature = "xlsx"))]
+use std::io::{Cursor, Read, Write};
+
+#[cfg(any(feature = "ods", feature = "xlsx"))]
 fn make_sheet1() -> Sheet {
     let mut sheet = Sheet::new("シート1");
     sheet.add_cell(Cell::str("a", ""), 0, 0);
     sheet.add_cell(Cell::str("b", ""), 0, 1);
     sheet.add_cell(Cell::float(1.0, ""), 1, 0);
     sheet.add_cell(Cell::float(2.0, ""), 1, 1);
-    sheet.add_cell(Cell::date("2017-12-02", "MM\\月DD\"日也\""), 2, 0);
-    sheet.add_cell(Cell::date("2017-12-02T13:30:00", "YYYY/MM/DD\\ HH:MM:SS"), 2, 1);
-    sheet.add_cell(Cell::date("2017-12-02", "GGGEE"), 2, 2);
-    sheet.add_cell(Cell::date("2017-12-02", "GE"), 2, 3);
+    sheet.add_cell(Cell::date("2017-12-02", "MM\\月DD\"日也\"").unwrap(), 2, 0);
+    sheet.add_cell(Cell::date("2017-12-02T13:30:00", "YYYY/MM/DD\\ HH:MM:SS").unwrap(), 2, 1);
+    sheet.add_cell(Cell::date("2017-12-02", "GGGEE").unwrap(), 2, 2);
+    sheet.add_cell(Cell::date("2017-12-02", "GE").unwrap(), 2, 3);
+    sheet.add_cell(Cell::bool(true, ""), 3, 0);
+    sheet.add_cell(Cell::bool(false, ""), 3, 1);
+    sheet.add_cell(Cell::error(ErrorKind::Div0, ""), 4, 0);
+    sheet.add_cell(Cell::error(ErrorKind::NA, ""), 4, 1);
+    sheet.add_cell(Cell::error(ErrorKind::Name, ""), 4, 2);
+    sheet.add_cell(Cell::formula("=SUM(A2:B2)", Value::Float(3.0)), 5, 0);
+    sheet.add_cell(Cell::formula("=IF(A4,\"yes\",\"no\")", Value::Str(String::from("yes"))), 5, 1);
+    sheet.add_cell(Cell::formula("=シート2!A1&'シート 3'!$A$1", Value::Str(String::from("予定表～①ﾊﾝｶｸだa"))), 5, 2);
+    sheet.add_cell(Cell::formula("=1/0", Value::Error(ErrorKind::Div0)), 5, 3);
     sheet
 }
 
+#[cfg(any(feature = "ods", feature = "xlsx"))]
 fn make_sheet2() -> Sheet {
     let mut sheet = Sheet::new("シート2");
     sheet.add_cell(Cell::str("予定表～①ﾊﾝｶｸだ", ""), 0, 0);
     sheet
 }
 
+#[cfg(any(feature = "ods", feature = "xlsx"))]
 fn make_sheet3() -> Sheet {
-    let mut sheet = Sheet::new("シート3");
+    let mut sheet = Sheet::new("シート 3");
     sheet.add_cell(Cell::str("a", ""), 0, 0);
     sheet.add_cell(Cell::str("b", ""), 0, 2);
     sheet.add_cell(Cell::str("c", ""), 2, 0);
@@ -44,10 +63,12 @@ fn make_sheet3() -> Sheet {
     sheet
 }
 
+#[cfg(any(feature = "ods", feature = "xlsx"))]
 fn make_sheet4() -> Sheet {
     Sheet::new("シート4")
 }
 
+#[cfg(any(feature = "ods", feature = "xlsx"))]
 fn make_book() -> Book {
     let mut book = Book::new();
     book.add_sheet(make_sheet1());
@@ -57,12 +78,49 @@ fn make_book() -> Book {
     book
 }
 
+// Rewrite one part of a zip package, keeping the others as they are.
+#[cfg(any(feature = "ods", feature = "xlsx"))]
+fn replace_part<F>(bytes: &[u8], name: &str, replace: F) -> Vec<u8>
+    where F: Fn(String) -> String
+{
+    let mut src = zip::ZipArchive::new(Cursor::new(bytes)).unwrap();
+    let mut dst = zip::ZipWriter::new(Cursor::new(Vec::new()));
+    for i in 0..src.len() {
+        let mut file = src.by_index(i).unwrap();
+        let mut content = String::new();
+        file.read_to_string(&mut content).unwrap();
+        if file.name() == name {
+            content = replace(content);
+        }
+        dst.start_file(file.name(), zip::write::FileOptions::default()).unwrap();
+        dst.write_all(content.as_bytes()).unwrap();
+    }
+    dst.finish().unwrap().into_inner()
+}
+
+// Leave one part out of a zip package.
+#[cfg(feature = "xlsx")]
+fn remove_part(bytes: &[u8], name: &str) -> Vec<u8> {
+    let mut src = zip::ZipArchive::new(Cursor::new(bytes)).unwrap();
+    let mut dst = zip::ZipWriter::new(Cursor::new(Vec::new()));
+    for i in 0..src.len() {
+        let mut file = src.by_index(i).unwrap();
+        if file.name() != name {
+            let mut content = Vec::new();
+            file.read_to_end(&mut content).unwrap();
+            dst.start_file(file.name(), zip::write::FileOptions::default()).unwrap();
+            dst.write_all(&content).unwrap();
+        }
+    }
+    dst.finish().unwrap().into_inner()
+}
+
 #[test]
 fn it_works() {
-    for i in vec![0,1,26,27,28,100,101,102] {
+    for i in [0,1,26,27,28,100,101,102] {
         assert_eq!(i, column_to_index(index_to_column(i)));
     }
-    for i in vec!["A", "B", "Z", "AA", "AB", "ZZ", "AAA", "AAB", "ABC"] {
+    for i in ["A", "B", "Z", "AA", "AB", "ZZ", "AAA", "AAB", "ABC"] {
         assert_eq!(i, index_to_column(column_to_index(i)));
     }
     assert_eq!(Some((701,11)), column_and_row_to_index("ZZ12"));
@@ -86,6 +144,1347 @@ fn xlsx_test() {
     assert_eq!(book, res);
 }
 
+#[test]
+#[cfg(feature = "ods")]
+fn ods_bytes_test() {
+    let book = make_book();
+    let bytes = ods::write_to_bytes(&book).unwrap();
+    let res = ods::read_from_bytes(&bytes).unwrap();
+    assert_eq!(book, res);
+}
+
+#[test]
+#[cfg(feature = "xlsx")]
+fn xlsx_bytes_test() {
+    let book = make_book();
+    let bytes = xlsx::write_to_bytes(&book).unwrap();
+    let res = xlsx::read_from_bytes(&bytes).unwrap();
+    assert_eq!(book, res);
+}
+
+#[test]
+#[cfg(feature = "ods")]
+fn ods_malformed_test() {
+    let bytes = ods::write_to_bytes(&make_book()).unwrap();
+    let malformed = replace_part(&bytes, "content.xml", |content| {
+        content.replacen("office:value=\"2\"", "office:value=\"two\"", 1)
+    });
+    match ods::read_from_bytes(&malformed) {
+        Err(Error::Malformed { part, sheet, cell, .. }) => {
+            assert_eq!("content.xml", part);
+            assert_eq!(Some(String::from("シート1")), sheet);
+            assert_eq!(Some(String::from("B2")), cell);
+        },
+        other => panic!("unexpected result {:?}", other),
+    }
+
+    let malformed = replace_part(&bytes, "content.xml", |content| content.replace("</table:table>", ""));
+    assert!(matches!(ods::read_from_bytes(&malformed), Err(Error::Xml { .. })));
+}
+
+#[test]
+#[cfg(feature = "xlsx")]
+fn xlsx_malformed_test() {
+    let bytes = xlsx::write_to_bytes(&make_book()).unwrap();
+    let malformed = replace_part(&bytes, "xl/worksheets/sheet1.xml", |content| {
+        content.replacen("<v>2</v>", "<v>#N/A</v>", 1)
+    });
+    match xlsx::read_from_bytes(&malformed) {
+        Err(Error::Malformed { part, position, sheet, cell, .. }) => {
+            assert_eq!("xl/worksheets/sheet1.xml", part);
+            assert!(position > 0);
+            assert_eq!(Some(String::from("シート1")), sheet);
+            assert_eq!(Some(String::from("B2")), cell);
+        },
+        other => panic!("unexpected result {:?}", other),
+    }
+
+    let malformed = replace_part(&bytes, "xl/worksheets/sheet3.xml", |content| {
+        content.replace("<v>0</v>", "<v>99</v>")
+    });
+    assert!(matches!(xlsx::read_from_bytes(&malformed), Err(Error::Malformed { .. })));
+
+    let malformed = replace_part(&bytes, "xl/workbook.xml", |content| content.replace("</sheets>", ""));
+    assert!(matches!(xlsx::read_from_bytes(&malformed), Err(Error::Xml { .. })));
+}
+
+#[test]
+#[cfg(feature = "xlsx")]
+fn error_test() {
+    fn read_boxed(bytes: &[u8]) -> Result<spsheet::Book, Box<dyn std::error::Error>> {
+        Ok(xlsx::read_from_bytes(bytes)?)
+    }
+
+    let bytes = xlsx::write_to_bytes(&make_book()).unwrap();
+    let malformed = replace_part(&bytes, "xl/worksheets/sheet1.xml", |content| {
+        content.replacen("<v>2</v>", "<v>#N/A</v>", 1)
+    });
+    let err = read_boxed(&malformed).unwrap_err();
+    assert!(err.to_string().starts_with("malformed xl/worksheets/sheet1.xml at byte "));
+    assert!(err.to_string().ends_with(", sheet シート1, cell B2: invalid number #N/A"));
+
+    let err = read_boxed(b"not a zip file").unwrap_err();
+    assert!(err.source().is_some());
+}
+
+#[test]
+#[cfg(all(feature = "ods", feature = "xlsx"))]
+fn open_test() {
+    let book = make_book();
+    spsheet::save(&book, Path::new("./tests/open.xlsx")).unwrap();
+    spsheet::save(&book, Path::new("./tests/open.ods")).unwrap();
+    assert_eq!(book, spsheet::open(Path::new("./tests/open.xlsx")).unwrap());
+    assert_eq!(book, spsheet::open(Path::new("./tests/open.ods")).unwrap());
+
+    let bytes = xlsx::write_to_bytes(&book).unwrap();
+    assert_eq!(book, spsheet::open_from(Cursor::new(&bytes)).unwrap());
+    let bytes = ods::write_to_bytes(&book).unwrap();
+    assert_eq!(&bytes[30..38], b"mimetype");
+    assert_eq!(book, spsheet::open_from(Cursor::new(&bytes)).unwrap());
+
+    let not_spreadsheet = replace_part(&bytes, "mimetype", |_| String::from("application/vnd.oasis.opendocument.text"));
+    assert!(matches!(spsheet::open_from(Cursor::new(&not_spreadsheet)), Err(Error::Unsupported(_))));
+    assert!(matches!(spsheet::open_from(Cursor::new(b"a,b,c")), Err(Error::Unsupported(_))));
+    assert!(matches!(spsheet::save(&book, Path::new("./tests/open.csv")), Err(Error::Unsupported(_))));
+}
+
+#[test]
+fn bool_test() {
+    assert_eq!(Some(String::from("TRUE")), Cell::bool(true, "").get_formated_value());
+    assert_eq!(Some(String::from("FALSE")), Cell::bool(false, "").get_formated_value());
+}
+
+#[test]
+#[cfg(feature = "xlsx")]
+fn xlsx_inline_string_test() {
+    let bytes = xlsx::write_to_bytes(&make_book()).unwrap();
+    let inline = replace_part(&bytes, "xl/worksheets/sheet4.xml", |content| {
+        content.replace("<sheetData/>", concat!(
+            "<sheetData><row r=\"1\">",
+            "<c r=\"A1\" t=\"inlineStr\"><is><t>plain</t></is></c>",
+            "<c r=\"B1\" t=\"inlineStr\"><is><r><rPr><b/></rPr><t>bold</t></r>",
+            "<r><t xml:space=\"preserve\"> and normal</t></r></is></c>",
+            "<c r=\"C1\" t=\"inlineStr\"><is><t>漢字</t><rPh sb=\"0\" eb=\"2\"><t>カンジ</t></rPh></is></c>",
+            "<c r=\"D1\" t=\"str\"><f>A1&amp;\"!\"</f><v>plain!</v></c>",
+            "</row></sheetData>"))
+    });
+    let book = xlsx::read_from_bytes(&inline).unwrap();
+    let sheet = book.get_sheet(3);
+    assert_eq!(&Cell::str("plain", ""), sheet.get_cell(0, 0).unwrap());
+    assert_eq!(&Cell::str("bold and normal", ""), sheet.get_cell(0, 1).unwrap());
+    assert_eq!(&Cell::str("漢字", ""), sheet.get_cell(0, 2).unwrap());
+    assert_eq!(&Cell::formula("=A1&\"!\"", Value::Str(String::from("plain!"))), sheet.get_cell(0, 3).unwrap());
+}
+
+#[test]
+#[cfg(feature = "xlsx")]
+fn xlsx_without_shared_strings_test() {
+    let mut sheet = Sheet::new("sheet1");
+    sheet.add_cell(Cell::float(1.0, ""), 0, 0);
+    let mut book = Book::new();
+    book.add_sheet(sheet);
+    let bytes = xlsx::write_to_bytes(&book).unwrap();
+    // a package with inline strings only has no xl/sharedStrings.xml
+    let bytes = remove_part(&bytes, "xl/sharedStrings.xml");
+    let bytes = replace_part(&bytes, "xl/worksheets/sheet1.xml", |content| {
+        content.replace("</row>", "<c r=\"B1\" t=\"inlineStr\"><is><t>inline</t></is></c></row>")
+    });
+    let res = xlsx::read_from_bytes(&bytes).unwrap();
+    assert_eq!(&Cell::float(1.0, ""), res.get_sheet(0).get_cell(0, 0).unwrap());
+    assert_eq!(&Cell::str("inline", ""), res.get_sheet(0).get_cell(0, 1).unwrap());
+}
+
+#[test]
+#[cfg(feature = "xlsx")]
+fn xlsx_cell_without_reference_test() {
+    let bytes = xlsx::write_to_bytes(&make_book()).unwrap();
+    // cells and rows without r follow the one before them
+    let implicit = replace_part(&bytes, "xl/worksheets/sheet4.xml", |content| {
+        content.replace("<sheetData/>", concat!(
+            "<sheetData><row r=\"2\">",
+            "<c t=\"inlineStr\"><is><t>a</t></is></c>",
+            "<c/>",
+            "<c t=\"inlineStr\"><is><t>c</t></is></c>",
+            "<c r=\"F2\" t=\"inlineStr\"><is><t>f</t></is></c>",
+            "<c t=\"inlineStr\"><is><t>g</t></is></c>",
+            "</row><row>",
+            "<c t=\"inlineStr\"><is><t>next</t></is></c>",
+            "</row></sheetData>"))
+    });
+    let book = xlsx::read_from_bytes(&implicit).unwrap();
+    let sheet = book.get_sheet(3);
+    assert_eq!(&Cell::str("a", ""), sheet.get_cell(1, 0).unwrap());
+    assert_eq!(None, sheet.get_cell(1, 1));
+    assert_eq!(&Cell::str("c", ""), sheet.get_cell(1, 2).unwrap());
+    assert_eq!(&Cell::str("f", ""), sheet.get_cell(1, 5).unwrap());
+    assert_eq!(&Cell::str("g", ""), sheet.get_cell(1, 6).unwrap());
+    assert_eq!(&Cell::str("next", ""), sheet.get_cell(2, 0).unwrap());
+}
+
+// Text of one part of a zip package.
+#[cfg(any(feature = "ods", feature = "xlsx"))]
+fn read_part(bytes: &[u8], name: &str) -> String {
+    let mut zip = zip::ZipArchive::new(Cursor::new(bytes)).unwrap();
+    let mut content = String::new();
+    zip.by_name(name).unwrap().read_to_string(&mut content).unwrap();
+    content
+}
+
+#[test]
+#[cfg(feature = "ods")]
+fn ods_formula_test() {
+    let bytes = ods::write_to_bytes(&make_book()).unwrap();
+    let content = read_part(&bytes, "content.xml");
+    assert!(content.contains("table:formula=\"of:=SUM([.A2:.B2])\""));
+    assert!(content.contains("table:formula=\"of:=IF([.A4];&quot;yes&quot;;&quot;no&quot;)\""));
+    assert!(content.contains("table:formula=\"of:=[$シート2.A1]&amp;[$&apos;シート 3&apos;.$A$1]\""));
+    // the cached values of the fixture are what the formulas give
+    let mut book = make_book();
+    book.recalculate().unwrap();
+    assert_eq!(make_book(), book);
+}
+
+#[test]
+#[cfg(feature = "ods")]
+fn ods_number_format_test() {
+    let mut sheet = Sheet::new("sheet1");
+    sheet.add_cell(Cell::float(-1234.5, "#,##0.00;[Red]\\(#,##0.00\\);\"-\""), 0, 0);
+    sheet.add_cell(Cell::float(0.125, "0.0%"), 0, 1);
+    sheet.add_cell(Cell::float(12345.0, "0.00E+00"), 0, 2);
+    sheet.add_cell(Cell::float(1.25, "# ?/4"), 0, 3);
+    sheet.add_cell(Cell::float(5550123.0, "000-0000"), 0, 4);
+    sheet.add_cell(Cell::float(43029.0, "yyyy/mm/dd"), 0, 5);
+    sheet.add_cell(Cell::float(2500.0, "[Blue][>=1000]#,##0,\"K\";0"), 0, 6);
+    sheet.add_cell(Cell::float(0.35, "?/100"), 0, 7);
+    sheet.add_cell(Cell::float(-5.0, "yyyy-mm-dd"), 0, 8);
+    let mut book = Book::new();
+    book.add_sheet(sheet);
+    let content = read_part(&ods::write_to_bytes(&book).unwrap(), "content.xml");
+    assert!(content.contains("P1\"><style:text-properties fo:color=\"#FF0000\"/><number:text>(</number:text><number:number number:decimal-places=\"2\" number:min-integer-digits=\"1\" number:grouping=\"true\"/><number:text>)</number:text></number:number-style>"));
+    assert!(content.contains("P2\"><number:text>-</number:text></number:number-style>"));
+    assert!(content.contains("<style:map style:condition=\"value()&lt;0\" style:apply-style-name=\"N"));
+    assert!(content.contains("<style:map style:condition=\"value()=0\" style:apply-style-name=\"N"));
+    assert!(content.contains("\"><number:number number:decimal-places=\"1\" number:min-integer-digits=\"1\"/><number:text>%</number:text></number:percentage-style>"));
+    assert!(content.contains("<number:scientific-number number:decimal-places=\"2\" number:min-integer-digits=\"1\" number:min-exponent-digits=\"2\"/>"));
+    assert!(content.contains("<number:fraction number:min-integer-digits=\"0\" number:min-numerator-digits=\"1\" number:denominator-value=\"4\"/>"));
+    assert!(content.contains("<number:embedded-text number:position=\"4\">-</number:embedded-text>"));
+    assert!(content.contains("<number:date-style"));
+    assert!(content.contains("<text:p>(1,234.50)</text:p>"));
+    assert!(content.contains("<text:p>2017/10/21</text:p>"));
+    assert!(content.contains("P0\"><style:text-properties fo:color=\"#0000FF\"/><number:number number:decimal-places=\"0\" number:min-integer-digits=\"1\" number:grouping=\"true\" number:display-factor=\"1000\"/><number:text>K</number:text>"));
+    assert!(content.contains("<style:map style:condition=\"value()&gt;=1000\" style:apply-style-name=\"N"));
+    assert!(content.contains("<text:p>3K</text:p>"));
+    assert!(content.contains("number:min-numerator-digits=\"1\" number:denominator-value=\"100\"/>"));
+    assert!(content.contains("<text:p>35/100</text:p>"));
+    // no date has a negative serial number
+    assert!(content.contains("<text:p>-5</text:p>"));
+}
+
+#[test]
+#[cfg(feature = "xlsx")]
+fn xlsx_number_format_test() {
+    let mut sheet = Sheet::new("sheet1");
+    sheet.add_cell(Cell::float(1234.5, "#,##0.00"), 0, 0);
+    sheet.add_cell(Cell::date("2017-12-02", "yyyy/mm/dd").unwrap(), 0, 1);
+    // no date has this serial, it is read back as the number
+    sheet.add_cell(Cell::float(-1.0, "yyyy-mm-dd"), 0, 2);
+    let mut book = Book::new();
+    book.add_sheet(sheet);
+    let res = xlsx::read_from_bytes(&xlsx::write_to_bytes(&book).unwrap()).unwrap();
+    assert_eq!(book, res);
+    assert_eq!(Some(String::from("1,234.50")), res.get_sheet(0).get_cell(0, 0).unwrap().get_formated_value());
+}
+
+#[test]
+#[cfg(feature = "xlsx")]
+fn xlsx_builtin_number_format_test() {
+    // point the xf at a built-in format, which has no numFmt element
+    let read = |cell: Cell, id: &str| {
+        let mut sheet = Sheet::new("sheet1");
+        sheet.add_cell(cell, 0, 0);
+        let mut book = Book::new();
+        book.add_sheet(sheet);
+        let bytes = replace_part(&xlsx::write_to_bytes(&book).unwrap(), "xl/styles.xml", |content| {
+            content.replace("numFmtId=\"164\" xfId", &format!("numFmtId=\"{}\" xfId", id))
+        });
+        xlsx::read_from_bytes(&bytes).unwrap().get_sheet(0).get_cell(0, 0).unwrap().clone()
+    };
+    let percentage = read(Cell::float(0.125, "0.0%"), "10");
+    assert_eq!(&Value::Percentage(0.125), percentage.get_value());
+    assert_eq!(Some(String::from("12.50%")), percentage.get_formated_value());
+    let duration = read(Cell::float(1.5, "[HH]:MM"), "46");
+    assert!(matches!(duration.get_value(), Value::Duration(_)));
+    assert_eq!(Some(String::from("36:00:00")), duration.get_formated_value());
+}
+
+#[test]
+#[cfg(feature = "ods")]
+fn ods_currency_test() {
+    let mut sheet = Sheet::new("sheet1");
+    sheet.add_cell(Cell::currency(-1234.5, "EUR", "#,##0.00 [$€-407];[Red]-#,##0.00 [$€-407]"), 0, 0);
+    sheet.add_cell(Cell::currency(0.0, "USD", "[$$-409]#,##0.00;(#,##0.00);-"), 0, 1);
+    sheet.add_cell(Cell::currency(12.0, "GBP", ""), 0, 2);
+    let mut book = Book::new();
+    book.add_sheet(sheet);
+    let bytes = ods::write_to_bytes(&book).unwrap();
+    let content = read_part(&bytes, "content.xml");
+    assert!(content.contains("office:value-type=\"currency\" office:value=\"-1234.5\" office:currency=\"EUR\""));
+    assert!(content.contains("<number:currency-symbol number:language=\"de\" number:country=\"DE\">€</number:currency-symbol>"));
+    assert!(content.contains("<number:currency-style style:name=\"N"));
+    assert!(content.contains("<text:p>-1.234,50 €</text:p>"));
+    assert!(content.contains("<text:p>-</text:p>"));
+    let res = ods::read_from_bytes(&bytes).unwrap();
+    assert_eq!(book, res);
+}
+
+#[test]
+#[cfg(feature = "xlsx")]
+fn xlsx_currency_test() {
+    let mut sheet = Sheet::new("sheet1");
+    sheet.add_cell(Cell::currency(1234.5, "EUR", "#,##0.00 [$€-407]"), 0, 0);
+    sheet.add_cell(Cell::currency(-5.0, "USD", "$#,##0.00_);[Red]($#,##0.00)"), 0, 1);
+    sheet.add_cell(Cell::currency(7.0, "CHF", "[$CHF] 0.00"), 0, 2);
+    let mut book = Book::new();
+    book.add_sheet(sheet);
+    let bytes = xlsx::write_to_bytes(&book).unwrap();
+    assert!(read_part(&bytes, "xl/styles.xml").contains("formatCode=\"#,##0.00 [$€-407]\""));
+    let res = xlsx::read_from_bytes(&bytes).unwrap();
+    assert_eq!(book, res);
+    assert_eq!(Some(String::from("($5.00)")), res.get_sheet(0).get_cell(0, 1).unwrap().get_formated_value());
+}
+
+fn make_time_book() -> Book {
+    let mut sheet = Sheet::new("sheet1");
+    sheet.add_cell(Cell::percentage(0.125, "0.0%"), 0, 0);
+    sheet.add_cell(Cell::percentage(0.5, ""), 0, 1);
+    sheet.add_cell(Cell::time("13:30:05", "HH:MM:SS").unwrap(), 1, 0);
+    sheet.add_cell(Cell::time("08:15:00", "").unwrap(), 1, 1);
+    sheet.add_cell(Cell::duration(Duration::seconds(49 * 3600 + 30 * 60 + 5), "[HH]:MM:SS"), 2, 0);
+    sheet.add_cell(Cell::duration(Duration::minutes(90), ""), 2, 1);
+    let mut book = Book::new();
+    book.add_sheet(sheet);
+    book
+}
+
+#[test]
+fn time_value_test() {
+    let book = make_time_book();
+    let sheet = book.get_sheet(0);
+    let formated = |row: usize, column: usize| sheet.get_cell(row, column).unwrap().get_formated_value().unwrap();
+    assert_eq!("12.5%", formated(0, 0));
+    assert_eq!("50%", formated(0, 1));
+    assert_eq!("13:30:05", formated(1, 0));
+    assert_eq!("08:15:00", formated(1, 1));
+    assert_eq!("49:30:05", formated(2, 0));
+    assert_eq!("1:30:00", formated(2, 1));
+    assert_eq!(&Value::Time(NaiveTime::from_hms_opt(8, 15, 0).unwrap()), sheet.get_cell(1, 1).unwrap().get_value());
+    assert_eq!("0.0625", Cell::duration(Duration::minutes(90), "0.0000").get_formated_value().unwrap());
+}
+
+#[test]
+#[cfg(feature = "ods")]
+fn ods_time_value_test() {
+    let book = make_time_book();
+    let bytes = ods::write_to_bytes(&book).unwrap();
+    let content = read_part(&bytes, "content.xml");
+    assert!(content.contains("office:value-type=\"percentage\" office:value=\"0.125\""));
+    assert!(content.contains("office:value-type=\"time\" office:time-value=\"PT13H30M05S\""));
+    assert!(content.contains("office:time-value=\"PT49H30M05S\""));
+    assert!(content.contains("<text:p>12.5%</text:p>"));
+    assert_eq!(book, ods::read_from_bytes(&bytes).unwrap());
+}
+
+#[test]
+#[cfg(feature = "xlsx")]
+fn xlsx_time_value_test() {
+    let book = make_time_book();
+    let bytes = xlsx::write_to_bytes(&book).unwrap();
+    let styles = read_part(&bytes, "xl/styles.xml");
+    assert!(styles.contains("formatCode=\"[HH]:MM:SS\""));
+    assert!(styles.contains("formatCode=\"[H]:MM:SS\""));
+    assert!(styles.contains("formatCode=\"0.0%\""));
+    assert!(read_part(&bytes, "xl/worksheets/sheet1.xml").contains("<v>0.0625</v>"));
+    assert_eq!(book, xlsx::read_from_bytes(&bytes).unwrap());
+}
+
+#[test]
+fn date_system_test() {
+    let date = |s: &str| s.parse::<NaiveDateTime>().unwrap();
+    let system = DateSystem::Date1900;
+    assert_eq!(0.0, system.date_to_serial(&date("1899-12-31T00:00:00")));
+    assert_eq!(1.0, system.date_to_serial(&date("1900-01-01T00:00:00")));
+    assert_eq!(59.5, system.date_to_serial(&date("1900-02-28T12:00:00")));
+    assert_eq!(61.0, system.date_to_serial(&date("1900-03-01T00:00:00")));
+    assert_eq!(Some(date("1900-01-01T06:00:00")), system.serial_to_date(1.25));
+    assert_eq!(Some(date("1900-02-28T00:00:00")), system.serial_to_date(60.0));
+    assert_eq!(Some(date("1900-03-01T00:00:00")), system.serial_to_date(61.0));
+    assert_eq!(Some(date("9999-12-31T00:00:00")), system.serial_to_date(2958465.0));
+    assert_eq!(None, system.serial_to_date(-0.5));
+    assert_eq!(None, system.serial_to_date(2958466.0));
+
+    let system = DateSystem::Date1904;
+    assert_eq!(0.0, system.date_to_serial(&date("1904-01-01T00:00:00")));
+    assert_eq!(Some(date("1904-01-01T18:00:00")), system.serial_to_date(0.75));
+    assert_eq!(Some(date("9999-12-31T00:00:00")), system.serial_to_date(2957003.0));
+    assert_eq!(None, system.serial_to_date(-0.25));
+
+    for system in [DateSystem::Date1900, DateSystem::Date1904] {
+        for s in ["1904-01-01T00:00:00.001", "2017-12-02T13:30:00.123", "9999-12-31T23:59:59.999"] {
+            let dt = date(s);
+            assert_eq!(Some(dt), system.serial_to_date(system.date_to_serial(&dt)), "{:?} {}", system, s);
+        }
+    }
+
+    // serials and elapsed time are shown in the system of the book
+    let serial = Cell::float(1.5, "yyyy-mm-dd hh:mm");
+    assert_eq!(Some(String::from("1900-01-01 12:00")), serial.get_formated_value());
+    assert_eq!(Some(String::from("1904-01-02 12:00")), serial.get_formated_value_in(DateSystem::Date1904));
+    let elapsed = Cell::date("1904-01-02T12:00:00", "[h]").unwrap();
+    assert_eq!(Some(String::from("36")), elapsed.get_formated_value_in(DateSystem::Date1904));
+    let number = Cell::date("1904-01-02T12:00:00", "0.0").unwrap();
+    assert_eq!(Some(String::from("1.5")), number.get_formated_value_in(DateSystem::Date1904));
+}
+
+#[test]
+#[cfg(feature = "ods")]
+fn ods_date1904_test() {
+    let mut book = make_book();
+    book.set_date_system(DateSystem::Date1904);
+    let bytes = ods::write_to_bytes(&book).unwrap();
+    assert!(read_part(&bytes, "content.xml").contains("<table:null-date table:date-value=\"1904-01-01\"/>"));
+    let mut sheet = Sheet::new("sheet1");
+    sheet.add_cell(Cell::float(0.0, "yyyy-mm-dd"), 0, 0);
+    let mut serial = Book::new();
+    serial.set_date_system(DateSystem::Date1904);
+    serial.add_sheet(sheet);
+    let content = read_part(&ods::write_to_bytes(&serial).unwrap(), "content.xml");
+    assert!(content.contains("<text:p>1904-01-01</text:p>"));
+    assert_eq!(book, ods::read_from_bytes(&bytes).unwrap());
+}
+
+#[test]
+#[cfg(feature = "xlsx")]
+fn xlsx_date1904_test() {
+    let mut book = Book::new();
+    let mut sheet = Sheet::new("sheet1");
+    sheet.add_cell(Cell::date("2017-12-02T13:30:00", "YYYY/MM/DD\\ HH:MM:SS").unwrap(), 0, 0);
+    sheet.add_cell(Cell::date("1904-01-01T00:00:00.250", "YYYY/MM/DD\\ HH:MM:SS").unwrap(), 1, 0);
+    book.add_sheet(sheet);
+    book.set_date_system(DateSystem::Date1904);
+    let bytes = xlsx::write_to_bytes(&book).unwrap();
+    assert!(read_part(&bytes, "xl/workbook.xml").contains("date1904=\"true\""));
+    assert!(read_part(&bytes, "xl/worksheets/sheet1.xml").contains("<v>41609.5625</v>"));
+    assert_eq!(book, xlsx::read_from_bytes(&bytes).unwrap());
+
+    // Excel writes the flag as 1
+    let excel = replace_part(&bytes, "xl/workbook.xml", |content| {
+        content.replace("date1904=\"true\"", "date1904=\"1\"")
+    });
+    assert_eq!(book, xlsx::read_from_bytes(&excel).unwrap());
+
+    book.set_date_system(DateSystem::Date1900);
+    let bytes = xlsx::write_to_bytes(&book).unwrap();
+    assert!(read_part(&bytes, "xl/worksheets/sheet1.xml").contains("<v>43071.5625</v>"));
+    assert_eq!(book, xlsx::read_from_bytes(&bytes).unwrap());
+}
+
+#[test]
+#[cfg(feature = "xlsx")]
+fn xlsx_formula_test() {
+    let bytes = xlsx::write_to_bytes(&make_book()).unwrap();
+    assert!(read_part(&bytes, "xl/worksheets/sheet1.xml").contains("<f>SUM(A2:B2)</f><v>3</v>"));
+
+    let shared = replace_part(&bytes, "xl/worksheets/sheet4.xml", |content| {
+        content.replace("<sheetData/>", concat!(
+            "<sheetData>",
+            "<row r=\"1\"><c r=\"B1\"><f t=\"shared\" ref=\"B1:B3\" si=\"0\">A1*2+$A$1</f><v>2</v></c></row>",
+            "<row r=\"2\"><c r=\"B2\"><f t=\"shared\" si=\"0\"/><v>4</v></c></row>",
+            "<row r=\"3\"><c r=\"B3\"><f>NOW()</f></c></row>",
+            "</sheetData>"))
+    });
+    let book = xlsx::read_from_bytes(&shared).unwrap();
+    let sheet = book.get_sheet(3);
+    assert_eq!(&Cell::formula("=A1*2+$A$1", Value::Float(2.0)), sheet.get_cell(0, 1).unwrap());
+    assert_eq!(&Cell::formula("=A2*2+$A$1", Value::Float(4.0)), sheet.get_cell(1, 1).unwrap());
+    assert_eq!(Some(&String::from("=NOW()")), sheet.get_cell(2, 1).unwrap().get_formula());
+}
+
+#[test]
+fn error_value_test() {
+    for kind in [ErrorKind::Null, ErrorKind::Div0, ErrorKind::Value, ErrorKind::Ref,
+                 ErrorKind::Name, ErrorKind::Num, ErrorKind::NA, ErrorKind::GettingData] {
+        assert_eq!(Some(kind), ErrorKind::from_code(kind.code()));
+    }
+    assert_eq!(None, ErrorKind::from_code("#FOO!"));
+    assert_eq!(Some(String::from("#REF!")), Cell::error(ErrorKind::Ref, "").get_formated_value());
+}
+
+#[test]
+#[cfg(feature = "ods")]
+fn ods_error_value_test() {
+    // LibreOffice stores error results as a zero float marked by calcext
+    let bytes = ods::write_to_bytes(&make_book()).unwrap();
+    let libreoffice = replace_part(&bytes, "content.xml", |content| {
+        content.replace("office:value-type=\"string\" calcext:value-type=\"error\"",
+                        "office:value-type=\"float\" office:value=\"0\" calcext:value-type=\"error\"")
+    });
+    assert_eq!(make_book(), ods::read_from_bytes(&libreoffice).unwrap());
+
+    let unknown = replace_part(&bytes, "content.xml", |content| content.replace("#N/A", "#FOO!"));
+    assert!(matches!(ods::read_from_bytes(&unknown), Err(Error::Malformed { .. })));
+}
+
+#[test]
+fn recalculate_test() {
+    let mut items = Sheet::new("Items");
+    for (row, (name, price)) in [("apple", 120.0), ("banana", 80.0), ("cherry", 300.0)].iter().enumerate() {
+        items.add_cell(Cell::str(*name, ""), row, 0);
+        items.add_cell(Cell::float(*price, ""), row, 1);
+    }
+    let mut sheet = Sheet::new("Main");
+    // B1 is calculated before A1 although it comes later
+    sheet.add_cell(Cell::formula("=SUM(B1:B3)*2", Value::Float(0.0)), 0, 0);
+    sheet.add_cell(Cell::formula("=Items!B1+Items!B2", Value::Float(0.0)), 0, 1);
+    sheet.add_cell(Cell::float(10.0, ""), 1, 1);
+    sheet.add_cell(Cell::formula("=-2^2+10/4-3%", Value::Float(0.0)), 2, 0);
+    sheet.add_cell(Cell::formula("=\"n=\"&B2&IF(B2>5,\" big\",\" small\")", Value::Float(0.0)), 2, 1);
+    sheet.add_cell(Cell::formula("=VLOOKUP(\"BANANA\",Items!A1:B3,2,FALSE)", Value::Float(0.0)), 3, 0);
+    sheet.add_cell(Cell::formula("=INDEX(Items!A1:A3,MATCH(300,Items!B1:B3,0))", Value::Float(0.0)), 3, 1);
+    sheet.add_cell(Cell::formula("=ROUND(2.675,2)", Value::Float(0.0)), 4, 0);
+    sheet.add_cell(Cell::formula("=IF(B2=0,1/0,AVERAGE(Items!B1:B3))", Value::Float(0.0)), 4, 1);
+    sheet.add_cell(Cell::formula("=1/0", Value::Float(0.0)), 5, 0);
+    sheet.add_cell(Cell::formula("=NOSUCHFUNCTION(1)", Value::Float(0.0)), 5, 1);
+    sheet.add_cell(Cell::formula("=TEXT(1234.5,\"#,##0.00\")", Value::Float(0.0)), 6, 0);
+    sheet.add_cell(Cell::formula("=COUNTIF(Items!B1:B3,\">100\")", Value::Float(0.0)), 6, 1);
+    let mut date = Cell::date("2000-01-01", "YYYY/MM/DD").unwrap();
+    date.set_formula("=DATE(2017,13,2)");
+    sheet.add_cell(date, 7, 0);
+    sheet.add_cell(Cell::formula("=YEAR(A8)&\"-\"&MONTH(A8)", Value::Float(0.0)), 7, 1);
+
+    let mut book = Book::new();
+    book.add_sheet(sheet);
+    book.add_sheet(items);
+    book.recalculate().unwrap();
+
+    let sheet = book.get_sheet(0);
+    let value = |row, column| sheet.get_cell(row, column).unwrap().get_value().clone();
+    assert_eq!(Value::Float(420.0), value(0, 0));
+    assert_eq!(Value::Float(200.0), value(0, 1));
+    assert_eq!(Value::Float(4.0 + 2.5 - 0.03), value(2, 0));
+    assert_eq!(Value::Str(String::from("n=10 big")), value(2, 1));
+    assert_eq!(Value::Float(80.0), value(3, 0));
+    assert_eq!(Value::Str(String::from("cherry")), value(3, 1));
+    assert_eq!(Value::Float(2.68), value(4, 0));
+    assert_eq!(Value::Float(500.0 / 3.0), value(4, 1));
+    assert_eq!(Value::Error(ErrorKind::Div0), value(5, 0));
+    assert_eq!(Value::Error(ErrorKind::Name), value(5, 1));
+    assert_eq!(Value::Str(String::from("1,234.50")), value(6, 0));
+    assert_eq!(Value::Float(2.0), value(6, 1));
+    assert_eq!(Some(String::from("2018/01/02")), sheet.get_cell(7, 0).unwrap().get_formated_value());
+    assert_eq!(Value::Str(String::from("2018-1")), value(7, 1));
+}
+
+#[test]
+fn recalculate_date1904_test() {
+    let mut sheet = Sheet::new("sheet1");
+    sheet.add_cell(Cell::date("2017-12-02", "yyyy/mm/dd").unwrap(), 0, 0);
+    sheet.add_cell(Cell::formula("=A1", Value::Float(0.0)), 0, 1);
+    sheet.add_cell(Cell::formula("=DATE(1904,1,2)", Value::Float(0.0)), 1, 0);
+    sheet.add_cell(Cell::formula("=YEAR(0)&TEXT(1,\"-mm-dd\")", Value::Float(0.0)), 1, 1);
+    let mut date = Cell::date("2000-01-01", "yyyy/mm/dd").unwrap();
+    date.set_formula("=A1+31");
+    sheet.add_cell(date, 2, 0);
+    let mut book = Book::new();
+    book.set_date_system(DateSystem::Date1904);
+    book.add_sheet(sheet);
+    book.recalculate().unwrap();
+    let sheet = book.get_sheet(0);
+    let value = |row, column| sheet.get_cell(row, column).unwrap().get_value().clone();
+    assert_eq!(Value::Float(41609.0), value(0, 1));
+    assert_eq!(Value::Float(1.0), value(1, 0));
+    assert_eq!(Value::Str(String::from("1904-01-02")), value(1, 1));
+    assert_eq!(Value::Date("2018-01-02T00:00:00".parse().unwrap()), value(2, 0));
+}
+
+#[test]
+fn circular_reference_test() {
+    let mut sheet = Sheet::new("sheet1");
+    sheet.add_cell(Cell::formula("=C1", Value::Float(1.0)), 0, 0);
+    sheet.add_cell(Cell::formula("=A1+1", Value::Float(2.0)), 0, 1);
+    sheet.add_cell(Cell::formula("=SUM(A1:B1)", Value::Float(3.0)), 0, 2);
+    sheet.add_cell(Cell::formula("=1+", Value::Float(4.0)), 1, 0);
+    let mut book = Book::new();
+    book.add_sheet(sheet);
+    let original = book.clone();
+    match book.recalculate() {
+        Err(Error::Formula { sheet, cell, .. }) => {
+            assert_eq!("sheet1", sheet);
+            assert_eq!("A2", cell);
+        },
+        other => panic!("unexpected result {:?}", other),
+    }
+
+    let mut sheet = book.get_sheet(0).clone();
+    sheet.add_cell(Cell::float(4.0, ""), 1, 0);
+    let mut book = Book::new();
+    book.add_sheet(sheet);
+    match book.recalculate() {
+        Err(Error::CircularReference { sheet, cell }) => {
+            assert_eq!("sheet1", sheet);
+            assert_eq!("A1", cell);
+        },
+        other => panic!("unexpected result {:?}", other),
+    }
+    assert_eq!(original.get_sheet(0).get_cell(0, 2), book.get_sheet(0).get_cell(0, 2));
+}
+
+#[test]
+fn whole_column_test() {
+    let mut sheet = Sheet::new("sheet1");
+    for row in 0..3 {
+        sheet.add_cell(Cell::float(row as f64 + 1.0, ""), row, 0);
+        sheet.add_cell(Cell::float(10.0, ""), row, 1);
+    }
+    sheet.add_cell(Cell::formula("=SUM(A:A)", Value::Float(0.0)), 0, 3);
+    sheet.add_cell(Cell::formula("=SUM($2:$2)", Value::Float(0.0)), 4, 3);
+    sheet.add_cell(Cell::formula("=COUNT(sheet1!A:B)", Value::Float(0.0)), 2, 3);
+    let mut book = Book::new();
+    book.add_sheet(sheet);
+    book.recalculate().unwrap();
+    let sheet = book.get_sheet(0);
+    let value = |row, column| sheet.get_cell(row, column).unwrap().get_value().clone();
+    assert_eq!(Value::Float(6.0), value(0, 3));
+    assert_eq!(Value::Float(12.0), value(4, 3));
+    assert_eq!(Value::Float(6.0), value(2, 3));
+}
+
+#[test]
+#[cfg(feature = "ods")]
+fn ods_whole_column_test() {
+    let mut sheet = Sheet::new("sheet1");
+    sheet.add_cell(Cell::formula("=SUM(A:A)+SUM('シート 2'!$1:2)", Value::Float(0.0)), 0, 1);
+    let mut book = Book::new();
+    book.add_sheet(sheet);
+    let bytes = ods::write_to_bytes(&book).unwrap();
+    let content = read_part(&bytes, "content.xml");
+    assert!(content.contains("table:formula=\"of:=SUM([.A:.A])+SUM([$&apos;シート 2&apos;.$1:.2])\""));
+    let book = ods::read_from_bytes(&bytes).unwrap();
+    assert_eq!(Some("=SUM(A:A)+SUM('シート 2'!$1:2)"), book.get_sheet(0).get_cell(0, 1).unwrap().get_formula().map(String::as_str));
+}
+
+#[test]
+fn formula_limits_test() {
+    let mut sheet = Sheet::new("sheet1");
+    sheet.add_cell(Cell::formula("=DATE(1E300,1,1)", Value::Float(0.0)), 0, 0);
+    sheet.add_cell(Cell::formula("=DATE(2017,1,-1E300)", Value::Float(0.0)), 0, 1);
+    sheet.add_cell(Cell::formula("=TEXT(\"inf\",\"0\")", Value::Float(0.0)), 1, 0);
+    sheet.add_cell(Cell::formula("=\"NaN\"+1", Value::Float(0.0)), 1, 1);
+    sheet.add_cell(Cell::formula(format!("={}1{}", "(".repeat(60), ")".repeat(60)), Value::Float(0.0)), 2, 0);
+    let mut book = Book::new();
+    book.add_sheet(sheet);
+    book.recalculate().unwrap();
+    let sheet = book.get_sheet(0);
+    let value = |row, column| sheet.get_cell(row, column).unwrap().get_value().clone();
+    assert_eq!(Value::Error(ErrorKind::Num), value(0, 0));
+    assert_eq!(Value::Error(ErrorKind::Num), value(0, 1));
+    assert_eq!(Value::Error(ErrorKind::Value), value(1, 0));
+    assert_eq!(Value::Error(ErrorKind::Value), value(1, 1));
+    assert_eq!(Value::Float(1.0), value(2, 0));
+
+    let nested = format!("={}1{}", "(".repeat(20000), ")".repeat(20000));
+    let chained = format!("=1{}", "+1".repeat(100000));
+    for formula in [nested, chained, format!("={}1", "-".repeat(20000))].iter() {
+        let mut sheet = Sheet::new("sheet1");
+        sheet.add_cell(Cell::formula(formula, Value::Float(0.0)), 0, 0);
+        let mut book = Book::new();
+        book.add_sheet(sheet);
+        assert!(matches!(book.recalculate(), Err(Error::Formula { .. })));
+    }
+}
+
+#[test]
+fn number_format_test() {
+    let formated = |value: f64, format: &str| Cell::float(value, format).get_formated_value().unwrap();
+    assert_eq!("1234.5", formated(1234.5, ""));
+    assert_eq!("0.333333333", formated(1.0 / 3.0, "General"));
+    assert_eq!("1.23457E+11", formated(123456789012.0, "General"));
+    assert_eq!("1E-10", formated(0.0000000001, "General"));
+    assert_eq!("1,235", formated(1234.5, "#,##0"));
+    assert_eq!("-1,234.50", formated(-1234.5, "#,##0.00"));
+    assert_eq!("2.68", formated(2.675, "0.00"));
+    assert_eq!("007", formated(7.0, "000"));
+    assert_eq!(".5", formated(0.5, "#.##"));
+    assert_eq!("3.1 ", formated(3.1, "0.0?"));
+    assert_eq!("1,235K", formated(1234567.0, "#,##0,\"K\""));
+    assert_eq!("12.5%", formated(0.125, "0.0%"));
+    assert_eq!("1.23E+04", formated(12345.0, "0.00E+00"));
+    assert_eq!("12.3E+3", formated(12345.0, "##0.0E+0"));
+    assert_eq!("1.2E-3", formated(0.0012, "0.0E-0"));
+    assert_eq!("1 1/4", formated(1.25, "# ?/?"));
+    assert_eq!("5/4", formated(1.25, "?/?"));
+    assert_eq!("3 1/8", formated(3.125, "# ?/8"));
+    assert_eq!(" 1/3", formated(1.0 / 3.0, "# ?/?"));
+    assert_eq!("0  7/20", formated(0.35, "0 ??/??"));
+    assert_eq!("3 14/99", formated(std::f64::consts::PI, "# ??/??"));
+    assert_eq!(" 4/10", formated(0.35, "# ?/10"));
+    assert_eq!("35/100", formated(0.35, "?/100"));
+    assert_eq!("1 25/100", formated(1.25, "# ??/100"));
+    assert_eq!("3      14093/99532     ", formated(std::f64::consts::PI, "# ??????????/??????????"));
+    assert_eq!("#NUM!", formated(f64::INFINITY, "0.00"));
+    assert_eq!("#NUM!", formated(f64::NAN, "General"));
+    assert_eq!("$(1,234.00)", formated(-1234.0, "$#,##0.00_);$\\(#,##0.00\\)"));
+    assert_eq!("(1,234.00)", formated(-1234.0, "#,##0.00_);[Red](#,##0.00)"));
+    assert_eq!("1,234.00 ", formated(1234.0, "#,##0.00_);[Red](#,##0.00)"));
+    assert_eq!("zero", formated(0.0, "0;-0;\"zero\""));
+    assert_eq!("￥1,000", formated(1000.0, "[$￥-411]#,##0"));
+    assert_eq!("555-0123", formated(5550123.0, "000-0000"));
+    assert_eq!(Some(String::from("text: abc")), Cell::str("abc", "0;-0;0;\"text: \"@").get_formated_value());
+    assert_eq!(Some(String::from("abc")), Cell::str("abc", "0.00").get_formated_value());
+    assert_eq!(Some(String::from("2017/10/21")), Cell::float(43029.0, "yyyy/mm/dd").get_formated_value());
+}
+
+#[test]
+fn format_sections_test() {
+    use spsheet::format::{Color, Condition, Format, Item, Placeholder};
+    let format = Format::new("[Blue][>=1000]#,##0;[color 3]0.0;\"-\"");
+    let sections = format.get_sections();
+    assert_eq!(3, sections.len());
+    assert_eq!(Some(Condition::Ge(1000.0)), sections[0].get_condition());
+    assert_eq!(Some(Color::Blue), sections[0].get_color());
+    assert_eq!(None, sections[1].get_color());
+    assert_eq!(&vec![Item::Literal(String::from("-"))], sections[2].get_items());
+    assert_eq!("[Color3]0.0", Format::new("[color3]0.0").to_string());
+    assert_eq!(&vec![Item::Digit(Placeholder::Zero), Item::DecimalPoint, Item::Digit(Placeholder::Zero)],
+        Format::new("[赤]0.0").get_sections()[0].get_items());
+
+    let format = Format::new("[$￥-411]#,##0");
+    let locale = format.get_sections()[0].get_locale().unwrap();
+    assert_eq!("￥", locale.get_currency());
+    assert_eq!("411", locale.get_locale());
+    assert_eq!("[$￥-411]#,##0", format.to_string());
+
+    let format = Format::new("YYYY/M/D\\ H:MM:SS \"(\"aaa\")\"");
+    assert!(format.is_date_format());
+    assert_eq!(&vec![Item::Year { long: true }, Item::Slash, Item::Month(1), Item::Slash, Item::Day(1),
+        Item::Literal(String::from(" ")), Item::Hour { long: false }, Item::Literal(String::from(":")),
+        Item::Minute { long: true }, Item::Literal(String::from(":")), Item::Second { long: true },
+        Item::Literal(String::from(" ")), Item::Literal(String::from("(")), Item::Weekday { long: false },
+        Item::Literal(String::from(")"))], format.get_sections()[0].get_items());
+    assert_eq!("yyyy/m/d h:mm:ss (aaa)", format.to_string());
+    assert_eq!(Some(String::from("2017/12/2 13:05:09 (土)")),
+        Cell::date("2017-12-02T13:05:09", "YYYY/M/D\\ H:MM:SS \"(\"aaa\")\"").unwrap().get_formated_value());
+    assert_eq!(Some(String::from("17-D-Dec")), Cell::date("2017-12-02", "yy-mmmmm-mmm").unwrap().get_formated_value());
+    assert_eq!("0_);(0);# ?/8;\"kg\"", Format::new("0_);\\(0\\);# ?/8;\"kg\"").to_string());
+    // escaped digits and placeholders stay text when the code is read again
+    for code in ["0\\0\\#\\?", "\\1\\.0\\E\\%", "# ?/10", "\\@0\\/\\,"].iter() {
+        let format = Format::new(*code);
+        assert_eq!(*code, format.to_string());
+        assert_eq!(format, Format::new(format.to_string()));
+    }
+    assert_eq!(Some(String::from("5#")), Cell::float(5.0, "0\\#").get_formated_value());
+}
+
+#[test]
+fn format_condition_test() {
+    use spsheet::format::{Color, Format};
+    let format = Format::new("[<100]0.0;[>=1000]#,##0,\"K\";0");
+    assert_eq!("50.0", format.get_formated_number(50.0));
+    assert_eq!("-3.0", format.get_formated_number(-3.0));
+    assert_eq!("1,500K", format.get_formated_number(1500000.0));
+    assert_eq!("500", format.get_formated_number(500.0));
+
+    let format = Format::new("[Red][<=-10]0;[Blue][>=10]0;[Green]0");
+    assert_eq!((String::from("12"), Some(Color::Red)), format.get_formated_number_with_color(-12.0));
+    assert_eq!((String::from("12"), Some(Color::Blue)), format.get_formated_number_with_color(12.0));
+    assert_eq!((String::from("-5"), Some(Color::Green)), format.get_formated_number_with_color(-5.0));
+
+    assert_eq!((String::from("1"), Some(Color::Indexed(10))), Format::new("[Color10]0").get_formated_number_with_color(1.0));
+    assert_eq!(0x008000, Color::Indexed(10).get_rgb());
+    assert_eq!(0xFF0000, Color::Red.get_rgb());
+    assert_eq!((String::from("a"), Some(Color::Magenta)), Format::new("0;0;0;[Magenta]@").get_formated_text_with_color("a"));
+    assert_eq!((String::from("a"), None), Format::new("[Red]0").get_formated_text_with_color("a"));
+    assert_eq!(Some((String::from("TRUE"), None)), Cell::bool(true, "[Red]0").get_formated_value_with_color());
+}
+
+#[test]
+fn format_time_test() {
+    use spsheet::format::{Format, Item};
+    let formated = |value: &str, format: &str| Cell::date(value, format).unwrap().get_formated_value().unwrap();
+    assert_eq!("49:30:05", formated("1900-01-02T01:30:05", "[h]:mm:ss"));
+    assert_eq!("1530", formated("1900-01-01T01:30:00", "[mm]"));
+    assert_eq!("1:30 PM", formated("2017-12-02T13:30:00", "h:mm AM/PM"));
+    assert_eq!("12:05 am", formated("2017-12-02T00:05:00", "h:mm am/pm"));
+    assert_eq!("9 a", formated("2017-12-02T09:00:00", "h A/P").to_lowercase());
+    assert_eq!("13:05:09.250", formated("2017-12-02T13:05:09.25", "hh:mm:ss.000"));
+    assert_eq!("05:09.3", formated("2017-12-02T13:05:09.25", "mm:ss.0"));
+
+    let format = Format::new("[h]:mm:ss.00");
+    assert_eq!(&vec![Item::ElapsedHour { long: false }, Item::Literal(String::from(":")),
+        Item::Minute { long: true }, Item::Literal(String::from(":")), Item::Second { long: true },
+        Item::SecondFraction(2)], format.get_sections()[0].get_items());
+    assert!(format.is_date_format());
+    assert_eq!("[h]:mm:ss.00", format.to_string());
+    assert_eq!("h:mm AM/PM", Format::new("h:mm AM/PM").to_string());
+}
+
+#[test]
+#[cfg(feature = "ods")]
+fn ods_time_format_test() {
+    let mut sheet = Sheet::new("sheet1");
+    sheet.add_cell(Cell::date("1900-01-02T01:30:05", "[HH]:MM:SS").unwrap(), 0, 0);
+    sheet.add_cell(Cell::date("2017-12-02T13:30:00", "H:MM\\ AM/PM").unwrap(), 0, 1);
+    sheet.add_cell(Cell::date("2017-12-02T13:05:09.25", "HH:MM:SS.000").unwrap(), 0, 2);
+    let mut book = Book::new();
+    book.add_sheet(sheet);
+    let bytes = ods::write_to_bytes(&book).unwrap();
+    let content = read_part(&bytes, "content.xml");
+    assert!(content.contains("<number:time-style"));
+    assert!(content.contains("number:truncate-on-overflow=\"false\""));
+    assert!(content.contains("<number:am-pm/>"));
+    assert!(content.contains("<number:seconds number:style=\"long\" number:decimal-places=\"3\"/>"));
+    assert!(content.contains("<text:p>49:30:05</text:p>"));
+    assert!(content.contains("<text:p>1:30 PM</text:p>"));
+    let res = ods::read_from_bytes(&bytes).unwrap();
+    assert_eq!(book, res);
+}
+
+fn styled(mut cell: Cell, style: Style) -> Cell {
+    cell.set_style(style);
+    cell
+}
+
+// patterns, slanted lines and number formats are left out, ods keeps none of them
+fn make_style_book() -> Book {
+    let black = Rgb(0, 0, 0);
+    let mut sheet = Sheet::new("sheet1");
+    sheet.add_cell(styled(Cell::str("Title", ""), Style::new()
+        .with_font(Font::new().with_name("Arial Black").with_size(14.5).with_bold(true).with_italic(true)
+            .with_underline(Underline::Double).with_color(Rgb(0x1F, 0x4E, 0x79)))
+        .with_alignment(Alignment::new().with_horizontal(HorizontalAlignment::Center).with_vertical(VerticalAlignment::Center))), 0, 0);
+    sheet.add_cell(styled(Cell::float(-1234.5, ""), Style::new()
+        .with_fill(Fill::Solid(Rgb(0xFF, 0xFF, 0xCC)))
+        .with_border(Border::new()
+            .with_top(BorderLine::new(BorderStyle::Thin, black))
+            .with_bottom(BorderLine::new(BorderStyle::Double, Rgb(0xFF, 0, 0)))
+            .with_left(BorderLine::new(BorderStyle::MediumDashed, black))
+            .with_right(BorderLine::new(BorderStyle::Hair, black)))), 1, 0);
+    sheet.add_cell(styled(Cell::str("long text which wraps", ""), Style::new()
+        .with_alignment(Alignment::new().with_wrap(true).with_horizontal(HorizontalAlignment::Left).with_indent(2))), 1, 1);
+    sheet.add_cell(styled(Cell::str("up", ""), Style::new()
+        .with_font(Font::new().with_underline(Underline::Single))
+        .with_alignment(Alignment::new().with_rotation(45).with_vertical(VerticalAlignment::Top))), 2, 0);
+    sheet.add_cell(styled(Cell::str("down", ""), Style::new()
+        .with_border(Border::all(BorderLine::new(BorderStyle::Thick, Rgb(0, 0x80, 0))))
+        .with_alignment(Alignment::new().with_rotation(-30).with_horizontal(HorizontalAlignment::Right))), 2, 1);
+    sheet.add_cell(styled(Cell::bool(true, ""), Style::new()
+        .with_alignment(Alignment::new().with_horizontal(HorizontalAlignment::Fill))), 3, 0);
+    sheet.add_cell(Cell::str("plain", ""), 3, 1);
+    let mut book = Book::new();
+    book.add_sheet(sheet);
+    book
+}
+
+#[test]
+fn style_test() {
+    let book = make_style_book();
+    let sheet = book.get_sheet(0);
+    assert!(sheet.get_cell(3, 1).unwrap().get_style().is_default());
+    let font = sheet.get_cell(0, 0).unwrap().get_style().get_font().unwrap();
+    assert_eq!((Some(14.5), true, true), (font.get_size(), font.is_bold(), font.is_italic()));
+    assert_eq!(-30, sheet.get_cell(2, 1).unwrap().get_style().get_alignment().get_rotation());
+    assert_eq!(90, Alignment::new().with_rotation(120).get_rotation());
+    assert_eq!(Rgb(0xFF, 0, 0), Rgb::from(Color::Red));
+}
+
+#[test]
+#[cfg(feature = "xlsx")]
+fn xlsx_style_test() {
+    let mut book = make_style_book();
+    let mut sheet = Sheet::new("sheet2");
+    sheet.add_cell(styled(Cell::float(1.5, "0.00"), Style::new()
+        .with_fill(Fill::Pattern { pattern: spsheet::style::Pattern::DarkTrellis, color: Rgb(0xFF, 0, 0), background: Rgb(0, 0, 0xFF) })
+        .with_border(Border::new().with_bottom(BorderLine::new(BorderStyle::SlantDashDot, Rgb(0, 0, 0))))), 0, 0);
+    book.add_sheet(sheet);
+    let bytes = xlsx::write_to_bytes(&book).unwrap();
+    let styles = read_part(&bytes, "xl/styles.xml");
+    assert!(styles.contains("<font><b/><i/><u val=\"double\"/><sz val=\"14.5\"/><color rgb=\"FF1F4E79\"/><name val=\"Arial Black\"/></font>"));
+    assert!(styles.contains("<patternFill patternType=\"solid\"><fgColor rgb=\"FFFFFFCC\"/>"));
+    assert!(styles.contains("<patternFill patternType=\"darkTrellis\"><fgColor rgb=\"FFFF0000\"/><bgColor rgb=\"FF0000FF\"/>"));
+    assert!(styles.contains("<bottom style=\"double\"><color rgb=\"FFFF0000\"/></bottom>"));
+    assert!(styles.contains("textRotation=\"120\""));
+    assert_eq!(book, xlsx::read_from_bytes(&bytes).unwrap());
+}
+
+#[test]
+#[cfg(feature = "ods")]
+fn ods_style_test() {
+    let book = make_style_book();
+    let bytes = ods::write_to_bytes(&book).unwrap();
+    let content = read_part(&bytes, "content.xml");
+    assert!(content.contains("<style:font-face style:name=\"Arial Black\" svg:font-family=\"Arial Black\"/>"));
+    assert!(content.contains("fo:font-size=\"14.5pt\" fo:font-weight=\"bold\" fo:font-style=\"italic\""));
+    assert!(content.contains("fo:background-color=\"#FFFFCC\" fo:border-left=\"1.76pt dashed #000000\""));
+    assert!(content.contains("style:rotation-angle=\"330\""));
+    assert!(content.contains("<style:paragraph-properties fo:text-align=\"start\" fo:margin-left=\"20pt\"/>"));
+    assert_eq!(book, ods::read_from_bytes(&bytes).unwrap());
+
+    // a pattern is shown as its background in ods
+    let mut book = Book::new();
+    let mut sheet = Sheet::new("sheet1");
+    sheet.add_cell(styled(Cell::str("x", ""), Style::new()
+        .with_fill(Fill::Pattern { pattern: spsheet::style::Pattern::LightGrid, color: Rgb(0, 0, 0), background: Rgb(0xCC, 0xCC, 0xCC) })), 0, 0);
+    book.add_sheet(sheet);
+    let res = ods::read_from_bytes(&ods::write_to_bytes(&book).unwrap()).unwrap();
+    assert_eq!(Some(&Fill::Solid(Rgb(0xCC, 0xCC, 0xCC))), res.get_sheet(0).get_cell(0, 0).unwrap().get_style().get_fill());
+}
+
+fn make_size_book() -> Book {
+    let mut sheet = Sheet::new("sheet1");
+    for row in 0..4 {
+        for column in 0..5 {
+            sheet.add_cell(Cell::float((row * 5 + column) as f64, ""), row, column);
+        }
+    }
+    sheet.set_column(0, Column::new().with_width(20.0));
+    sheet.set_column(1, Column::new().with_hidden(true));
+    sheet.set_column(2, Column::new().with_width(15.5).with_auto_fit(true));
+    sheet.set_column(3, Column::new().with_width(20.0));
+    sheet.set_column(6, Column::new().with_width(8.43).with_hidden(true));
+    sheet.set_row(0, Row::new().with_height(30.0));
+    sheet.set_row(2, Row::new().with_hidden(true));
+    sheet.set_row(3, Row::new().with_height(18.75).with_auto_fit(true));
+    sheet.set_row(10, Row::new().with_height(45.0));
+    let mut book = Book::new();
+    book.add_sheet(sheet);
+    book.add_sheet(Sheet::new("sheet2"));
+    book
+}
+
+#[test]
+fn size_test() {
+    let mut sheet = make_size_book().get_sheet(0).clone();
+    assert_eq!(5, sheet.get_columns().len());
+    assert_eq!(None, sheet.get_column(4));
+    assert_eq!((Some(18.75), true), (sheet.get_row(3).unwrap().get_height(), sheet.get_row(3).unwrap().is_auto_fit()));
+    sheet.set_row(3, Row::new());
+    assert_eq!(None, sheet.get_row(3));
+    assert_eq!(Some((3, 4)), sheet.get_max_index());
+}
+
+#[test]
+#[cfg(feature = "xlsx")]
+fn xlsx_size_test() {
+    let book = make_size_book();
+    let bytes = xlsx::write_to_bytes(&book).unwrap();
+    let sheet = read_part(&bytes, "xl/worksheets/sheet1.xml");
+    assert!(sheet.contains("<col collapsed=\"false\" customWidth=\"true\" hidden=\"false\" bestFit=\"false\" outlineLevel=\"0\" max=\"1\" min=\"1\" style=\"0\" width=\"20\"/>"));
+    assert!(sheet.contains("<col collapsed=\"false\" customWidth=\"false\" hidden=\"true\" bestFit=\"false\" outlineLevel=\"0\" max=\"2\" min=\"2\" style=\"0\" width=\"10.86\"/>"));
+    assert!(sheet.contains("max=\"1025\" min=\"8\""));
+    assert!(sheet.contains("<row r=\"1\" customFormat=\"false\" ht=\"30\" hidden=\"false\" customHeight=\"true\""));
+    assert!(sheet.contains("<row r=\"11\" customFormat=\"false\" ht=\"45\" hidden=\"false\" customHeight=\"true\" outlineLevel=\"0\" collapsed=\"false\"/>"));
+    assert_eq!(book, xlsx::read_from_bytes(&bytes).unwrap());
+
+    // a range of columns and a height which was fitted to the contents
+    let bytes = replace_part(&bytes, "xl/worksheets/sheet1.xml", |sheet| sheet.replace("<row r=\"1\" customFormat=\"false\" ht=\"30\" hidden=\"false\" customHeight=\"true\"", "<row r=\"1\" ht=\"30\"")
+        .replace("max=\"1025\" min=\"8\"", "max=\"9\" min=\"8\" hidden=\"1\""));
+    let res = xlsx::read_from_bytes(&bytes).unwrap();
+    let res = res.get_sheet(0);
+    assert!(res.get_row(0).unwrap().is_auto_fit());
+    assert_eq!(Some(&Column::new().with_hidden(true)), res.get_column(8));
+}
+
+#[test]
+#[cfg(feature = "ods")]
+fn ods_size_test() {
+    let book = make_size_book();
+    let bytes = ods::write_to_bytes(&book).unwrap();
+    let content = read_part(&bytes, "content.xml");
+    assert!(content.contains("<style:style style:name=\"co2\" style:family=\"table-column\"><style:table-column-properties fo:break-before=\"auto\" style:column-width=\"41.584mm\"/></style:style>"));
+    assert!(content.contains("<style:table-row-properties style:row-height=\"18.75pt\" fo:break-before=\"auto\" style:use-optimal-row-height=\"true\"/>"));
+    assert!(content.contains("<table:table-column table:style-name=\"co1\" table:visibility=\"collapse\" table:default-cell-style-name=\"Default\"/>"));
+    assert!(content.contains("<table:table-column table:style-name=\"co1\" table:number-columns-repeated=\"2\" table:default-cell-style-name=\"Default\"/>"));
+    assert!(content.contains("<table:table-row table:style-name=\"ro1\" table:visibility=\"collapse\">"));
+    assert_eq!(book, ods::read_from_bytes(&bytes).unwrap());
+
+    // empty rows of the same style are written once
+    let empty_rows = "<table:table-row table:style-name=\"ro1\" table:number-rows-repeated=\"6\"><table:table-cell table:number-columns-repeated=\"7\"/></table:table-row>";
+    assert!(content.contains(empty_rows));
+
+    // repeated rows share their style
+    let bytes = replace_part(&bytes, "content.xml", |content| content.replacen(empty_rows,
+        "<table:table-row table:style-name=\"ro2\" table:number-rows-repeated=\"2\"><table:table-cell table:number-columns-repeated=\"7\"/></table:table-row>", 1));
+    let res = ods::read_from_bytes(&bytes).unwrap();
+    let res = res.get_sheet(0);
+    assert_eq!(Some(30.0), res.get_row(4).unwrap().get_height());
+    assert_eq!(Some(30.0), res.get_row(5).unwrap().get_height());
+    assert_eq!(Some(45.0), res.get_row(6).unwrap().get_height());
+    assert_eq!(6, res.get_row_settings().len());
+
+    // LibreOffice styles the empty rows up to the end of the sheet, they are not kept
+    let bytes = replace_part(&bytes, "content.xml", |content| content.replacen("</table:table-row></table:table>",
+        "</table:table-row><table:table-row table:style-name=\"ro2\" table:number-rows-repeated=\"1048568\"><table:table-cell table:number-columns-repeated=\"7\"/></table:table-row></table:table>", 1));
+    let res = ods::read_from_bytes(&bytes).unwrap();
+    assert_eq!(6, res.get_sheet(0).get_row_settings().len());
+}
+
+fn make_merge_book() -> Book {
+    let mut sheet = Sheet::new("sheet1");
+    sheet.add_cell(Cell::str("Invoice", ""), 0, 0);
+    sheet.add_cell(Cell::str("No. 42", ""), 0, 4);
+    sheet.add_cell(Cell::str("Item", ""), 2, 1);
+    // hidden by the merged cell
+    sheet.add_cell(Cell::float(3.0, ""), 2, 2);
+    sheet.add_cell(Cell::float(12.5, ""), 4, 1);
+    sheet.merge(CellRange::parse("A1:D1").unwrap());
+    sheet.merge(CellRange::parse("B3:C4").unwrap());
+    sheet.merge(CellRange::parse("E6:F7").unwrap());
+    let mut book = Book::new();
+    book.add_sheet(sheet);
+    book
+}
+
+#[test]
+fn merge_test() {
+    let mut sheet = make_merge_book().get_sheet(0).clone();
+    assert_eq!(Some(&CellRange::new(2, 1, 3, 2)), sheet.get_merged_range(3, 1));
+    assert_eq!(None, sheet.get_merged_range(4, 1));
+    sheet.merge(CellRange::parse("D1:E1").unwrap());
+    assert_eq!(vec!["D1:E1", "B3:C4", "E6:F7"], sheet.merged_ranges().iter().map(|range| range.to_string()).collect::<Vec<_>>());
+    sheet.unmerge(CellRange::parse("A1:Z100").unwrap());
+    assert!(sheet.merged_ranges().is_empty());
+    sheet.merge(CellRange::parse("B2").unwrap());
+    assert!(sheet.merged_ranges().is_empty());
+}
+
+#[test]
+#[cfg(feature = "xlsx")]
+fn xlsx_merge_test() {
+    let book = make_merge_book();
+    let bytes = xlsx::write_to_bytes(&book).unwrap();
+    let sheet = read_part(&bytes, "xl/worksheets/sheet1.xml");
+    assert!(sheet.contains("</sheetData><mergeCells count=\"3\"><mergeCell ref=\"A1:D1\"/><mergeCell ref=\"B3:C4\"/><mergeCell ref=\"E6:F7\"/></mergeCells>"));
+    assert_eq!(book, xlsx::read_from_bytes(&bytes).unwrap());
+}
+
+#[test]
+#[cfg(feature = "ods")]
+fn ods_merge_test() {
+    let book = make_merge_book();
+    let bytes = ods::write_to_bytes(&book).unwrap();
+    let content = read_part(&bytes, "content.xml");
+    assert!(content.contains("table:number-columns-spanned=\"4\" table:number-rows-spanned=\"1\"><text:p>Invoice</text:p></table:table-cell><table:covered-table-cell table:number-columns-repeated=\"3\"/><table:table-cell"));
+    assert!(content.contains("<table:covered-table-cell office:value-type=\"float\" office:value=\"3\""));
+    assert!(content.contains("<table:table-cell table:number-columns-spanned=\"2\" table:number-rows-spanned=\"2\"></table:table-cell>"));
+    assert_eq!(book, ods::read_from_bytes(&bytes).unwrap());
+
+    // cells repeated with their value, and styled empty cells take their column
+    let mut bytes = replace_part(&bytes, "content.xml", |content| content.replace(
+        "<table:covered-table-cell table:number-columns-repeated=\"3\"/>",
+        "<table:covered-table-cell table:number-columns-repeated=\"2\"/><table:covered-table-cell table:style-name=\"Default\"/>"));
+    let mut sheet = ods::read_from_bytes(&bytes).unwrap().get_sheet(0).clone();
+    assert_eq!(Some(&Cell::str("No. 42", "")), sheet.get_cell(0, 4));
+    bytes = replace_part(&bytes, "content.xml", |content| content.replace(
+        "<table:table-cell office:value-type=\"float\" office:value=\"12.5\"",
+        "<table:table-cell table:number-columns-repeated=\"2\" office:value-type=\"float\" office:value=\"12.5\""));
+    sheet = ods::read_from_bytes(&bytes).unwrap().get_sheet(0).clone();
+    assert_eq!(Some(&Cell::float(12.5, "")), sheet.get_cell(4, 2));
+}
+
+fn make_view_book() -> Book {
+    let mut book = Book::new();
+    let mut sheet = Sheet::new("frozen");
+    sheet.add_cell(Cell::str("header", ""), 0, 0);
+    sheet.set_view(SheetView::new().with_frozen(1, 2).with_zoom(150).with_active_cell(3, 4)
+        .with_selection(CellRange::parse("D4:F6").unwrap()).with_grid_lines(false));
+    book.add_sheet(sheet);
+    let mut sheet = Sheet::new("split");
+    sheet.set_view(SheetView::new().with_split(72.0, 36.0).with_headers(false).with_right_to_left(true));
+    book.add_sheet(sheet);
+    let mut sheet = Sheet::new("rows");
+    sheet.set_view(SheetView::new().with_frozen(3, 0).with_active_cell(5, 0));
+    book.add_sheet(sheet);
+    book.add_sheet(Sheet::new("plain"));
+    book.set_active_sheet(1);
+    book
+}
+
+#[test]
+fn view_test() {
+    let view = SheetView::new().with_frozen(2, 1).with_zoom(1000);
+    assert_eq!(Some(Pane::Frozen { rows: 2, columns: 1 }), view.get_pane());
+    assert_eq!(400, view.get_zoom());
+    assert_eq!(None, view.with_frozen(0, 0).get_pane());
+    assert!(SheetView::new().shows_grid_lines() && SheetView::new().shows_headers());
+    assert_eq!("B2", CellRange::parse("B2:B2").unwrap().to_string());
+    let book = make_view_book();
+    assert_eq!(1, book.get_active_sheet());
+    assert_eq!(Some(Pane::Split { x: 72.0, y: 36.0 }), book.get_sheet(1).get_view().get_pane());
+    assert_eq!((3, 4), book.get_sheet(0).get_view().get_active_cell());
+}
+
+#[test]
+#[cfg(feature = "xlsx")]
+fn xlsx_view_test() {
+    let book = make_view_book();
+    let bytes = xlsx::write_to_bytes(&book).unwrap();
+    let sheet = read_part(&bytes, "xl/worksheets/sheet1.xml");
+    assert!(sheet.contains("showGridLines=\"false\" showRowColHeaders=\"true\" showZeros=\"true\" rightToLeft=\"false\" tabSelected=\"false\""));
+    assert!(sheet.contains("zoomScale=\"150\""));
+    assert!(sheet.contains("<pane xSplit=\"2\" ySplit=\"1\" topLeftCell=\"C2\" activePane=\"bottomRight\" state=\"frozen\"/><selection pane=\"bottomRight\" activeCell=\"E4\" activeCellId=\"0\" sqref=\"D4:F6\"/>"));
+    let sheet = read_part(&bytes, "xl/worksheets/sheet2.xml");
+    assert!(sheet.contains("tabSelected=\"true\""));
+    assert!(sheet.contains("<pane xSplit=\"1440\" ySplit=\"720\" activePane=\"bottomRight\" state=\"split\"/>"));
+    assert!(read_part(&bytes, "xl/worksheets/sheet3.xml").contains("<pane ySplit=\"3\" topLeftCell=\"A4\" activePane=\"bottomLeft\" state=\"frozen\"/>"));
+    assert!(read_part(&bytes, "xl/workbook.xml").contains("activeTab=\"1\""));
+    assert_eq!(book, xlsx::read_from_bytes(&bytes).unwrap());
+}
+
+#[test]
+#[cfg(feature = "ods")]
+fn ods_view_test() {
+    let mut book = make_view_book();
+    let bytes = ods::write_to_bytes(&book).unwrap();
+    let settings = read_part(&bytes, "settings.xml");
+    assert!(settings.contains("<config:config-item-map-entry config:name=\"frozen\"><config:config-item config:name=\"CursorPositionX\" config:type=\"int\">4</config:config-item><config:config-item config:name=\"CursorPositionY\" config:type=\"int\">3</config:config-item><config:config-item config:name=\"HorizontalSplitMode\" config:type=\"short\">2</config:config-item><config:config-item config:name=\"VerticalSplitMode\" config:type=\"short\">2</config:config-item><config:config-item config:name=\"HorizontalSplitPosition\" config:type=\"int\">2</config:config-item><config:config-item config:name=\"VerticalSplitPosition\" config:type=\"int\">1</config:config-item>"));
+    assert!(settings.contains("<config:config-item config:name=\"HorizontalSplitPosition\" config:type=\"int\">96</config:config-item><config:config-item config:name=\"VerticalSplitPosition\" config:type=\"int\">48</config:config-item>"));
+    assert!(settings.contains("<config:config-item config:name=\"ActiveTable\" config:type=\"string\">split</config:config-item>"));
+    assert!(read_part(&bytes, "content.xml").contains("<table:table table:name=\"split\" table:style-name=\"ta2\">"));
+    // ods keeps the cursor without the selection
+    let view = book.get_sheet(0).get_view().clone();
+    book.get_sheet_mut(0).set_view(SheetView::new().with_frozen(1, 2).with_zoom(view.get_zoom()).with_active_cell(3, 4).with_grid_lines(false));
+    assert_eq!(book, ods::read_from_bytes(&bytes).unwrap());
+}
+
+fn make_comment_book() -> Book {
+    let mut sheet = Sheet::new("review");
+    let mut cell = Cell::str("Total", "");
+    cell.set_comment(Comment::new("Reviewer", "check this\nsecond line").with_visible(true));
+    sheet.add_cell(cell, 0, 0);
+    let mut cell = Cell::float(12.5, "");
+    cell.set_comment(Comment::new("Author", "from the <old> sheet & more"));
+    sheet.add_cell(cell, 2, 2);
+    // a note on a cell without a value
+    let mut cell = Cell::str("", "");
+    cell.set_comment(Comment::new("Reviewer", "fill in"));
+    sheet.add_cell(cell, 4, 1);
+    let mut book = Book::new();
+    book.add_sheet(sheet);
+    book.add_sheet(Sheet::new("plain"));
+    let mut sheet = Sheet::new("notes");
+    let mut cell = Cell::bool(true, "");
+    cell.set_comment(Comment::new("Author", "done"));
+    sheet.add_cell(cell, 1, 0);
+    book.add_sheet(sheet);
+    book
+}
+
+#[test]
+fn comment_test() {
+    let mut cell = make_comment_book().get_sheet(0).get_cell(0, 0).unwrap().clone();
+    assert_eq!("check this\nsecond line", cell.get_comment().unwrap().get_text());
+    assert!(cell.get_comment().unwrap().is_visible());
+    assert_eq!(Some(Comment::new("Reviewer", "check this\nsecond line").with_visible(true)), cell.remove_comment());
+    assert_eq!(None, cell.get_comment());
+    assert_eq!(Cell::str("Total", ""), cell);
+}
+
+#[test]
+#[cfg(feature = "xlsx")]
+fn xlsx_comment_test() {
+    let book = make_comment_book();
+    let bytes = xlsx::write_to_bytes(&book).unwrap();
+    let comments = read_part(&bytes, "xl/comments1.xml");
+    assert!(comments.contains("<authors><author>Author</author><author>Reviewer</author></authors>"));
+    assert!(comments.contains("<comment ref=\"A1\" authorId=\"1\"><text><t xml:space=\"preserve\">check this\nsecond line</t></text></comment>"));
+    assert!(comments.contains("<comment ref=\"C3\" authorId=\"0\"><text><t xml:space=\"preserve\">from the &lt;old&gt; sheet &amp; more</t></text></comment>"));
+    let drawing = read_part(&bytes, "xl/drawings/vmlDrawing1.vml");
+    assert!(drawing.contains("<x:Row>0</x:Row><x:Column>0</x:Column><x:Visible/></x:ClientData>"));
+    assert!(drawing.contains("<x:Row>2</x:Row><x:Column>2</x:Column></x:ClientData>"));
+    assert!(read_part(&bytes, "xl/worksheets/sheet1.xml").contains("<legacyDrawing r:id=\"rId2\"/></worksheet>"));
+    assert!(read_part(&bytes, "xl/worksheets/_rels/sheet3.xml.rels").contains("Target=\"../comments3.xml\""));
+    let content_types = read_part(&bytes, "[Content_Types].xml");
+    assert!(content_types.contains("<Override PartName=\"/xl/comments3.xml\""));
+    assert!(!content_types.contains("/xl/comments2.xml"));
+    assert_eq!(book, xlsx::read_from_bytes(&bytes).unwrap());
+
+    // rich text runs are joined and the phonetic guide is left out
+    let bytes = replace_part(&bytes, "xl/comments1.xml", |content| content.replace(
+        "<t xml:space=\"preserve\">fill in</t>",
+        "<r><rPr><b/></rPr><t>fill </t></r><r><t xml:space=\"preserve\">in</t></r><rPh sb=\"0\" eb=\"1\"><t>ふ</t></rPh>"));
+    let sheet = xlsx::read_from_bytes(&bytes).unwrap().get_sheet(0).clone();
+    assert_eq!("fill in", sheet.get_cell(4, 1).unwrap().get_comment().unwrap().get_text());
+
+    // Excel puts the author in a bold run before the text
+    let bytes = replace_part(&bytes, "xl/comments1.xml", |content| content.replace(
+        "<t xml:space=\"preserve\">check this\nsecond line</t>",
+        "<r><rPr><b/></rPr><t>Reviewer:</t></r>\n<r><rPr/><t xml:space=\"preserve\">\ncheck this</t></r>"));
+    let sheet = xlsx::read_from_bytes(&bytes).unwrap().get_sheet(0).clone();
+    assert_eq!("Reviewer:\ncheck this", sheet.get_cell(0, 0).unwrap().get_comment().unwrap().get_text());
+}
+
+#[test]
+#[cfg(feature = "xlsx")]
+fn xlsx_shared_string_runs_test() {
+    let mut sheet = Sheet::new("sheet1");
+    sheet.add_cell(Cell::str("x", ""), 0, 0);
+    let mut book = Book::new();
+    book.add_sheet(sheet);
+    // the spaces at the ends of runs are kept, the ones between the elements are not text
+    let bytes = replace_part(&xlsx::write_to_bytes(&book).unwrap(), "xl/sharedStrings.xml", |_| String::from(concat!(
+        "<sst xmlns=\"http://schemas.openxmlformats.org/spreadsheetml/2006/main\" count=\"1\" uniqueCount=\"1\">\n",
+        "<si>\n  <r><rPr><b/></rPr><t>Hello</t></r>\n  <r><t xml:space=\"preserve\"> world</t></r>\n",
+        "  <rPh sb=\"0\" eb=\"1\"><t>ハ</t></rPh>\n</si>\n</sst>")));
+    let book = xlsx::read_from_bytes(&bytes).unwrap();
+    assert_eq!(&Value::Str(String::from("Hello world")), book.get_sheet(0).get_cell(0, 0).unwrap().get_value());
+}
+
+#[test]
+#[cfg(feature = "ods")]
+fn ods_comment_test() {
+    let book = make_comment_book();
+    let bytes = ods::write_to_bytes(&book).unwrap();
+    let content = read_part(&bytes, "content.xml");
+    assert!(content.contains("<office:annotation office:display=\"true\"><dc:creator>Reviewer</dc:creator><text:p>check this</text:p><text:p>second line</text:p></office:annotation><text:p>Total</text:p>"));
+    assert!(content.contains("<office:annotation office:display=\"false\"><dc:creator>Author</dc:creator><text:p>from the &lt;old&gt; sheet &amp; more</text:p></office:annotation>"));
+    assert_eq!(book, ods::read_from_bytes(&bytes).unwrap());
+
+    // the date of an annotation is not part of its text
+    let bytes = replace_part(&bytes, "content.xml", |content| content.replace(
+        "<dc:creator>Author</dc:creator><text:p>done",
+        "<dc:creator>Author</dc:creator><dc:date>2026-10-18T10:00:00</dc:date><text:p>done"));
+    let sheet = ods::read_from_bytes(&bytes).unwrap().get_sheet(2).clone();
+    assert_eq!(&Comment::new("Author", "done"), sheet.get_cell(1, 0).unwrap().get_comment().unwrap());
+}
+
+fn make_link_book() -> Book {
+    let mut sheet = Sheet::new("Tickets");
+    let mut cell = Cell::str("See ticket 42", "");
+    cell.set_hyperlink(Hyperlink::url("https://example.com/tickets/42?view=full&tab=1").with_tooltip("open the ticket"));
+    sheet.add_cell(cell, 0, 0);
+    let mut cell = Cell::float(43.0, "");
+    cell.set_hyperlink(Hyperlink::url("https://example.com/tickets/43"));
+    sheet.add_cell(cell, 1, 0);
+    let mut cell = Cell::str("Draft", "");
+    cell.set_hyperlink(Hyperlink::cell("Q3 'draft'", 4, 2).with_tooltip("the draft"));
+    cell.set_comment(Comment::new("Reviewer", "still open"));
+    sheet.add_cell(cell, 0, 1);
+    let mut book = Book::new();
+    book.add_sheet(sheet);
+    let mut sheet = Sheet::new("Q3 'draft'");
+    let mut cell = Cell::str("Back", "");
+    cell.set_hyperlink(Hyperlink::cell("Tickets", 0, 0));
+    sheet.add_cell(cell, 4, 2);
+    book.add_sheet(sheet);
+    book
+}
+
+#[test]
+fn hyperlink_test() {
+    let mut cell = make_link_book().get_sheet(0).get_cell(0, 0).unwrap().clone();
+    assert_eq!(&LinkTarget::Url(String::from("https://example.com/tickets/42?view=full&tab=1")), cell.get_hyperlink().unwrap().get_target());
+    assert_eq!(Some(&String::from("open the ticket")), cell.get_hyperlink().unwrap().get_tooltip());
+    assert!(cell.remove_hyperlink().is_some());
+    assert_eq!(Cell::str("See ticket 42", ""), cell);
+}
+
+#[test]
+#[cfg(feature = "xlsx")]
+fn xlsx_hyperlink_test() {
+    let book = make_link_book();
+    let bytes = xlsx::write_to_bytes(&book).unwrap();
+    let sheet = read_part(&bytes, "xl/worksheets/sheet1.xml");
+    assert!(sheet.contains("<hyperlinks><hyperlink ref=\"A1\" r:id=\"rId1\" tooltip=\"open the ticket\"/><hyperlink ref=\"B1\" location=\"&apos;Q3 &apos;&apos;draft&apos;&apos;&apos;!C5\" tooltip=\"the draft\"/><hyperlink ref=\"A2\" r:id=\"rId2\"/></hyperlinks>"));
+    assert!(sheet.contains("<legacyDrawing r:id=\"rId4\"/>"));
+    let rels = read_part(&bytes, "xl/worksheets/_rels/sheet1.xml.rels");
+    assert!(rels.contains("<Relationship Id=\"rId1\" Type=\"http://schemas.openxmlformats.org/officeDocument/2006/relationships/hyperlink\" Target=\"https://example.com/tickets/42?view=full&amp;tab=1\" TargetMode=\"External\"/>"));
+    assert!(read_part(&bytes, "xl/worksheets/sheet2.xml").contains("<hyperlink ref=\"C5\" location=\"Tickets!A1\"/>"));
+    assert_eq!(book, xlsx::read_from_bytes(&bytes).unwrap());
+
+    // a range links each of its cells with a value, a defined name is kept as a reference
+    let bytes = replace_part(&bytes, "xl/worksheets/sheet2.xml", |content| content.replace(
+        "<hyperlink ref=\"C5\" location=\"Tickets!A1\"/>",
+        "<hyperlink ref=\"C5:D6\" location=\"Totals\"/>"));
+    let sheet = xlsx::read_from_bytes(&bytes).unwrap().get_sheet(1).clone();
+    assert_eq!(&LinkTarget::Url(String::from("#Totals")), sheet.get_cell(4, 2).unwrap().get_hyperlink().unwrap().get_target());
+    assert_eq!(None, sheet.get_cell(5, 3));
+}
+
+#[test]
+#[cfg(feature = "ods")]
+fn ods_hyperlink_test() {
+    let book = make_link_book();
+    let bytes = ods::write_to_bytes(&book).unwrap();
+    let content = read_part(&bytes, "content.xml");
+    assert!(content.contains("<text:p><text:a xlink:href=\"https://example.com/tickets/42?view=full&amp;tab=1\" xlink:type=\"simple\" office:title=\"open the ticket\">See ticket 42</text:a></text:p>"));
+    assert!(content.contains("</office:annotation><text:p><text:a xlink:href=\"#&apos;Q3 &apos;&apos;draft&apos;&apos;&apos;.C5\" xlink:type=\"simple\" office:title=\"the draft\">Draft</text:a></text:p>"));
+    assert!(content.contains("<text:a xlink:href=\"#Tickets.A1\" xlink:type=\"simple\">Back</text:a>"));
+    assert_eq!(book, ods::read_from_bytes(&bytes).unwrap());
+
+    // the text around a link and runs of spaces are part of the value
+    let bytes = replace_part(&bytes, "content.xml", |content| content.replace(
+        "<text:a xlink:href=\"https://example.com/tickets/42?view=full&amp;tab=1\" xlink:type=\"simple\" office:title=\"open the ticket\">See ticket 42</text:a>",
+        "See <text:a xlink:href=\"https://example.com/tickets/42\" xlink:type=\"simple\">ticket<text:s text:c=\"2\"/>42</text:a> now"));
+    let mut cell = Cell::str("See ticket  42 now", "");
+    cell.set_hyperlink(Hyperlink::url("https://example.com/tickets/42"));
+    assert_eq!(Some(&cell), ods::read_from_bytes(&bytes).unwrap().get_sheet(0).get_cell(0, 0));
+}
+
+#[test]
+fn date_value_test() {
+    use chrono::{FixedOffset, TimeZone};
+    assert!(Cell::date("2017-02-30", "").is_err());
+    assert!(Cell::date("2017-12-02T25:00:00", "").is_err());
+    assert!(Cell::time("13:61:00", "").is_err());
+
+    let cell = Cell::date("2017-12-02T13:30:00", "").unwrap();
+    let tokyo = FixedOffset::east_opt(9 * 3600).unwrap();
+    assert_eq!(tokyo.with_ymd_and_hms(2017, 12, 2, 13, 30, 0).single(), cell.get_value().to_datetime(&tokyo));
+    assert_eq!(None, Value::Float(1.0).to_datetime(&tokyo));
+}
+
+#[test]
+#[cfg(feature = "ods")]
+fn ods_zoned_date_test() {
+    let mut sheet = Sheet::new("sheet1");
+    sheet.add_cell(Cell::date("2017-12-02T13:30:00", "YYYY/MM/DD\\ HH:MM:SS").unwrap(), 0, 0);
+    let mut book = Book::new();
+    book.add_sheet(sheet);
+    let bytes = ods::write_to_bytes(&book).unwrap();
+    assert!(read_part(&bytes, "content.xml").contains("office:date-value=\"2017-12-02T13:30:00\""));
+    // the wall-clock time of a value with a zone is kept
+    let zoned = replace_part(&bytes, "content.xml", |content| {
+        content.replace("office:date-value=\"2017-12-02T13:30:00\"", "office:date-value=\"2017-12-02T13:30:00+09:00\"")
+    });
+    assert_eq!(book, ods::read_from_bytes(&zoned).unwrap());
+}
+
+#[test]
+fn format_locale_test() {
+    use spsheet::format::{Format, Locale};
+    let formated = |value: &str, format: &str| Cell::date(value, format).unwrap().get_formated_value().unwrap();
+    assert_eq!("Samstag, 2. Dezember 2017", formated("2017-12-02", "[$-407]dddd, d. mmmm yyyy"));
+    assert_eq!("sam. 2 déc.", formated("2017-12-02", "[$-fr-FR]ddd d mmm"));
+    assert_eq!("2017年12月2日(土)", formated("2017-12-02", "yyyy\\年m\\月d\\日(aaa)"));
+    assert_eq!("土曜日", formated("2017-12-02", "[$-411]aaaa"));
+    assert_eq!("Sat", formated("2017-12-02", "[$-409]aaa"));
+    assert_eq!("December", formated("2017-12-02", "[$-1010409]mmmm"));
+    assert_eq!("十二月", formated("2017-12-02", "[$-zh-CN]mmmm"));
+
+    assert_eq!("1.234,50 €", Format::new("#,##0.00 [$€-407]").get_formated_number(1234.5));
+    assert_eq!("1\u{a0}234,5", Format::new("[$-40C]#,##0.0").get_formated_number(1234.5));
+    assert_eq!("1234,5", Format::new("[$-de-DE]General").get_formated_number(1234.5));
+    assert_eq!("$1,234.50", Format::new("[$$-409]#,##0.00").get_formated_number(1234.5));
+    assert_eq!("1,23E+03", Format::new("[$-407]0.00E+00").get_formated_number(1234.5));
+
+    let locale = Locale::find("0407").unwrap();
+    assert_eq!("de-DE", locale.get_name());
+    assert_eq!(',', locale.get_decimal_separator());
+    assert_eq!(Some(0x411), Locale::find("ja-JP-x-gannen").map(|locale| locale.get_lcid()));
+    assert_eq!(None, Locale::find("sv-SE"));
+    Locale::new("sv-SE", 0x41D).with_separators(',', ' ').register();
+    assert_eq!("1 234,50", Format::new("[$-41D]#,##0.00").get_formated_number(1234.5));
+}
+
 #[test]
 fn format_test() {
 }
\ No newline at end of file